@@ -1,4 +1,6 @@
 use std::sync::{Arc, Mutex};
+use std::net::{SocketAddrV4, UdpSocket};
+use std::time::{Duration, Instant};
 use std;
 
 use opcua_core::prelude::*;
@@ -41,6 +43,55 @@ impl Client {
         }
     }
 
+    /// Finds the servers on the local network that announce themselves through multicast DNS
+    /// (mDNS). A query for the OPC UA service is multicast and announcements are collected until
+    /// the timeout elapses. Each endpoint url is returned once.
+    pub fn discover_servers_on_network(timeout_ms: u32) -> Result<Vec<ServerOnNetwork>, StatusCode> {
+        Client::discover_servers_on_network_at(&mdns_multicast_address(), timeout_ms)
+    }
+
+    /// Finds the servers whose mDNS responders listen at the multicast address and port
+    pub fn discover_servers_on_network_at(multicast_address: &SocketAddrV4, timeout_ms: u32) -> Result<Vec<ServerOnNetwork>, StatusCode> {
+        // Responders answer a query from a port other than the mDNS port directly to that port
+        let socket = UdpSocket::bind(("0.0.0.0", 0)).map_err(|err| {
+            error!("Cannot bind socket for mDNS query, error = {}", err);
+            BAD_COMMUNICATION_ERROR
+        })?;
+        socket.send_to(&encode_query(0), multicast_address).map_err(|err| {
+            error!("Cannot send mDNS query, error = {}", err);
+            BAD_COMMUNICATION_ERROR
+        })?;
+
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms as u64);
+        let mut announcements: Vec<ServiceAnnouncement> = Vec::new();
+        let mut buf = [0u8; 9000];
+        loop {
+            let now = Instant::now();
+            if now >= deadline || socket.set_read_timeout(Some(deadline - now)).is_err() {
+                break;
+            }
+            if let Ok((len, _)) = socket.recv_from(&mut buf) {
+                if let Ok(received) = decode_announcements(&buf[..len]) {
+                    for announcement in received {
+                        if !announcements.iter().any(|a| a.endpoint_url() == announcement.endpoint_url()) {
+                            announcements.push(announcement);
+                        }
+                    }
+                }
+            }
+        }
+
+        let servers = announcements.iter().enumerate().map(|(idx, announcement)| {
+            ServerOnNetwork {
+                record_id: idx as UInt32,
+                server_name: UAString::from_str(&announcement.instance_name),
+                discovery_url: UAString::from_str(&announcement.endpoint_url()),
+                server_capabilities: Some(announcement.capabilities.iter().map(|c| UAString::from_str(c)).collect()),
+            }
+        }).collect();
+        Ok(servers)
+    }
+
     /// Creates a new session from this client.
     pub fn new_session(&mut self, endpoint_url: &str, security_policy: SecurityPolicy) -> Result<Arc<Mutex<Session>>, String> {
         if !is_opc_ua_binary_url(endpoint_url) {
@@ -1,7 +1,7 @@
 use std::net::TcpStream;
 use std::result::Result;
 use std::sync::{Arc, Mutex};
-use std::io::{Read, ErrorKind};
+use std::io::{Read, Write, ErrorKind};
 
 use chrono::*;
 
@@ -143,6 +143,9 @@ impl TcpTransport {
             }
         }
 
+        // Decrypt / verify chunk if necessary
+        let chunk = self.secure_channel.verify_and_remove_security(&chunk.data)?;
+
         // TODO test chunk message type and either push to queue, turn to message or clear
         let in_chunks = vec![chunk];
         let message = self.turn_received_chunks_into_message(&in_chunks)?;
@@ -254,11 +257,23 @@ impl TcpTransport {
         // Sequence number monotonically increases per chunk
         self.last_sent_sequence_number += chunks.len() as UInt32;
 
-        // Send chunks
-        let stream = self.stream();
+        // Sign / encrypt chunks if necessary. The buffer leaves room for the padding, signature
+        // and the growth of asymmetrically encrypted blocks
+        let mut out_chunks = Vec::with_capacity(chunks.len());
         for chunk in chunks {
             trace!("Sending chunk of type {:?}", chunk.message_header()?.message_type);
-            let _ = chunk.encode(stream)?;
+            let mut data = vec![0u8; chunk.data.len() * 2 + 1024];
+            let size = self.secure_channel.apply_security(&chunk, &mut data)?;
+            data.truncate(size);
+            out_chunks.push(data);
+        }
+
+        // Send chunks
+        let stream = self.stream();
+        for data in out_chunks {
+            if stream.write_all(&data).is_err() {
+                return Err(BAD_COMMUNICATION_ERROR);
+            }
         }
 
         trace!("Request sent");
@@ -4,7 +4,7 @@ use std::sync::{Arc, Mutex};
 use chrono;
 
 use opcua_types::*;
-use opcua_core::crypto::{SecurityPolicy, CertificateStore};
+use opcua_core::crypto::{SecurityPolicy, CertificateStore, X509};
 
 use comms::tcp_transport::*;
 
@@ -78,6 +78,13 @@ impl Session {
         }
     }
 
+    /// Sets the endpoint the session connects to. A secure channel with a security policy other
+    /// than None is signed or encrypted with the endpoint's server certificate.
+    pub fn set_endpoint(&mut self, endpoint: EndpointDescription) {
+        let mut session_state = self.session_state.lock().unwrap();
+        session_state.endpoint = Some(endpoint);
+    }
+
     /// Connects to the server (if possible) using the configured session arguments
     pub fn connect(&mut self) -> Result<(), StatusCode> {
         let _ = self.transport.connect(&self.endpoint_url)?;
@@ -244,6 +251,59 @@ impl Session {
         }
     }
 
+    /// Sends a FindServers request to the server and returns the descriptions of the servers it
+    /// knows of, limited to those with the server uris if any are supplied
+    pub fn find_servers(&mut self, server_uris: &[&str]) -> Result<Vec<ApplicationDescription>, StatusCode> {
+        let request = FindServersRequest {
+            request_header: self.make_request_header(),
+            endpoint_url: UAString::from_str(&self.endpoint_url),
+            locale_ids: None,
+            server_uris: Some(server_uris.iter().map(|server_uri| UAString::from_str(server_uri)).collect()),
+        };
+        let response = self.send_request(SupportedMessage::FindServersRequest(request))?;
+        if let SupportedMessage::FindServersResponse(response) = response {
+            Self::process_service_result(&response.response_header)?;
+            Ok(response.servers.unwrap_or(Vec::new()))
+        } else {
+            Err(BAD_UNKNOWN_RESPONSE)
+        }
+    }
+
+    /// Sends a FindServersOnNetwork request to a local discovery server and returns the records of
+    /// the servers it knows of that have all of the capabilities, starting from the record id.
+    /// A max_records_to_return of 0 returns every record.
+    pub fn find_servers_on_network(&mut self, starting_record_id: UInt32, max_records_to_return: UInt32, server_capability_filter: &[&str]) -> Result<Vec<ServerOnNetwork>, StatusCode> {
+        let request = FindServersOnNetworkRequest {
+            request_header: self.make_request_header(),
+            starting_record_id,
+            max_records_to_return,
+            server_capability_filter: Some(server_capability_filter.iter().map(|capability| UAString::from_str(capability)).collect()),
+        };
+        let response = self.send_request(SupportedMessage::FindServersOnNetworkRequest(request))?;
+        if let SupportedMessage::FindServersOnNetworkResponse(response) = response {
+            Self::process_service_result(&response.response_header)?;
+            Ok(response.servers.unwrap_or(Vec::new()))
+        } else {
+            Err(BAD_UNKNOWN_RESPONSE)
+        }
+    }
+
+    /// Sends a RegisterServer request to a discovery server. The server registers again to renew
+    /// its registration and registers with is_online set to false when it is going away.
+    pub fn register_server(&mut self, server: RegisteredServer) -> Result<(), StatusCode> {
+        let request = RegisterServerRequest {
+            request_header: self.make_request_header(),
+            server,
+        };
+        let response = self.send_request(SupportedMessage::RegisterServerRequest(request))?;
+        if let SupportedMessage::RegisterServerResponse(response) = response {
+            Self::process_service_result(&response.response_header)?;
+            Ok(())
+        } else {
+            Err(BAD_UNKNOWN_RESPONSE)
+        }
+    }
+
     /// Find matching endpoint
     pub fn find_matching_endpoint(endpoints: &[EndpointDescription], endpoint_url: &str, security_policy: SecurityPolicy) -> Option<EndpointDescription> {
         if security_policy == SecurityPolicy::Unknown {
@@ -281,6 +341,37 @@ impl Session {
         }
     }
 
+    /// Sends a RegisterNodes request to the server and returns the ids the client should use for
+    /// the nodes in later requests
+    pub fn register_nodes(&mut self, nodes_to_register: &[NodeId]) -> Result<Vec<NodeId>, StatusCode> {
+        let request = RegisterNodesRequest {
+            request_header: self.make_request_header(),
+            nodes_to_register: Some(nodes_to_register.to_vec()),
+        };
+        let response = self.send_request(SupportedMessage::RegisterNodesRequest(request))?;
+        if let SupportedMessage::RegisterNodesResponse(response) = response {
+            Self::process_service_result(&response.response_header)?;
+            Ok(response.registered_node_ids.unwrap_or(Vec::new()))
+        } else {
+            Err(BAD_UNKNOWN_RESPONSE)
+        }
+    }
+
+    /// Sends an UnregisterNodes request to the server
+    pub fn unregister_nodes(&mut self, nodes_to_unregister: &[NodeId]) -> Result<(), StatusCode> {
+        let request = UnregisterNodesRequest {
+            request_header: self.make_request_header(),
+            nodes_to_unregister: Some(nodes_to_unregister.to_vec()),
+        };
+        let response = self.send_request(SupportedMessage::UnregisterNodesRequest(request))?;
+        if let SupportedMessage::UnregisterNodesResponse(response) = response {
+            Self::process_service_result(&response.response_header)?;
+            Ok(())
+        } else {
+            Err(BAD_UNKNOWN_RESPONSE)
+        }
+    }
+
     /// Sends a Read request to the server
     pub fn read_nodes(&mut self, nodes_to_read: &[ReadValueId]) -> Result<Option<Vec<DataValue>>, StatusCode> {
         debug!("read_nodes requested to read nodes {:?}", nodes_to_read);
@@ -316,6 +407,22 @@ impl Session {
         }
     }
 
+    /// Sends a Cancel request to the server for the outstanding requests with the request handle
+    /// and returns how many were cancelled
+    pub fn cancel(&mut self, request_handle: UInt32) -> Result<UInt32, StatusCode> {
+        let request = CancelRequest {
+            request_header: self.make_request_header(),
+            request_handle,
+        };
+        let response = self.send_request(SupportedMessage::CancelRequest(request))?;
+        if let SupportedMessage::CancelResponse(response) = response {
+            Self::process_service_result(&response.response_header)?;
+            Ok(response.cancel_count)
+        } else {
+            Err(BAD_UNKNOWN_RESPONSE)
+        }
+    }
+
     /// Checks if secure channel token needs to be renewed and renews it
     fn ensure_secure_channel_token(&mut self) -> Result<(), StatusCode> {
         let renew_token = {
@@ -374,22 +481,32 @@ impl Session {
         // TODO
         let requested_lifetime = 60000;
 
+        let security_mode = self.prepare_secure_channel()?;
+        let client_nonce = ByteString::from_bytes(&self.transport.secure_channel.nonce);
+
         let request = OpenSecureChannelRequest {
             request_header: self.make_request_header(),
             client_protocol_version: 0,
             request_type: request_type,
-            security_mode: MessageSecurityMode::None,
-            client_nonce: ByteString::from_bytes(&[0]),
+            security_mode,
+            client_nonce,
             requested_lifetime: requested_lifetime,
         };
         let response = self.send_request(SupportedMessage::OpenSecureChannelRequest(request))?;
         if let SupportedMessage::OpenSecureChannelResponse(response) = response {
+            {
+                let secure_channel = &mut self.transport.secure_channel;
+                secure_channel.set_their_nonce(&response.server_nonce)?;
+                secure_channel.secure_channel_id = response.security_token.channel_id;
+                secure_channel.token_id = response.security_token.token_id;
+                if secure_channel.security_policy != SecurityPolicy::None {
+                    secure_channel.derive_keys();
+                }
+            }
             {
                 let session_state = self.session_state.clone();
                 let mut session_state = session_state.lock().unwrap();
                 session_state.channel_token = Some(response.security_token);
-                // TODO tell TCP channel about the channel token info so it can sign, sign+encrypt
-                // messages using the token
             }
             Ok(())
         } else {
@@ -397,6 +514,32 @@ impl Session {
         }
     }
 
+    /// Sets up the secure channel with the security policy of the session and the security mode
+    /// and server certificate of its endpoint, and returns the security mode
+    fn prepare_secure_channel(&mut self) -> Result<MessageSecurityMode, StatusCode> {
+        let secure_channel = &mut self.transport.secure_channel;
+        let security_mode = if self.security_policy == SecurityPolicy::None || self.security_policy == SecurityPolicy::Unknown {
+            MessageSecurityMode::None
+        } else {
+            let session_state = self.session_state.lock().unwrap();
+            if session_state.endpoint.is_none() {
+                error!("Cannot open a secure channel with security policy {:?} because no endpoint has been set", self.security_policy);
+                return Err(BAD_TCP_ENDPOINT_URL_INVALID);
+            }
+            if secure_channel.cert.is_none() || secure_channel.private_key.is_none() {
+                error!("Cannot open a secure channel with security policy {:?} without a certificate and private key", self.security_policy);
+                return Err(BAD_CERTIFICATE_INVALID);
+            }
+            let endpoint = session_state.endpoint.as_ref().unwrap();
+            secure_channel.their_cert = Some(X509::from_byte_string(&endpoint.server_certificate)?);
+            endpoint.security_mode
+        };
+        secure_channel.security_policy = if security_mode == MessageSecurityMode::None { SecurityPolicy::None } else { self.security_policy };
+        secure_channel.security_mode = security_mode;
+        secure_channel.create_random_nonce();
+        Ok(security_mode)
+    }
+
     pub fn send_request(&mut self, request: SupportedMessage) -> Result<SupportedMessage, StatusCode> {
         // Make sure secure channel token hasn't expired
         let _ = self.ensure_secure_channel_token();
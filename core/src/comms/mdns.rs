@@ -0,0 +1,426 @@
+//! Contains the multicast DNS (mDNS) and DNS service discovery (DNS-SD) messages through which a
+//! server announces its endpoints on the local network and clients find them. A server endpoint
+//! is announced as an instance of the `_opcua-tcp._tcp` service with PTR, SRV, TXT and A records.
+
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+use opcua_types::*;
+
+/// The multicast address that mDNS queries and announcements are sent to
+pub const MDNS_MULTICAST_ADDRESS: [u8; 4] = [224, 0, 0, 251];
+/// The port that mDNS responders listen on
+pub const MDNS_PORT: u16 = 5353;
+/// The labels of the DNS-SD service that servers using the binary TCP transport are instances of
+pub const OPCUA_TCP_SERVICE: [&'static str; 3] = ["_opcua-tcp", "_tcp", "local"];
+/// The longest time to live of the records in an answer to a legacy unicast query
+pub const LEGACY_UNICAST_MAX_TTL: u32 = 10;
+
+/// The port of an opc.tcp url that does not say which
+const DEFAULT_OPC_TCP_PORT: u16 = 4840;
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+/// Set on the class of a record that replaces whatever caches hold for its name
+const CLASS_CACHE_FLUSH: u16 = 0x8000;
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_AUTHORITATIVE: u16 = 0x0400;
+const MAX_LABEL_LEN: usize = 63;
+/// Limits how many compression pointers are followed in a name so a malicious message cannot loop
+const MAX_NAME_POINTERS: usize = 16;
+
+/// Returns the address that mDNS queries and announcements are sent to
+pub fn mdns_multicast_address() -> SocketAddrV4 {
+    let address = MDNS_MULTICAST_ADDRESS;
+    SocketAddrV4::new(Ipv4Addr::new(address[0], address[1], address[2], address[3]), MDNS_PORT)
+}
+
+/// A server endpoint as it is announced through DNS-SD
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceAnnouncement {
+    /// The name of the service instance, usually the application name of the server
+    pub instance_name: String,
+    /// The host of the endpoint
+    pub host: String,
+    /// The port of the endpoint
+    pub port: u16,
+    /// The path of the endpoint url
+    pub path: String,
+    /// The capabilities of the server, e.g. "LDS" for a local discovery server
+    pub capabilities: Vec<String>,
+}
+
+impl ServiceAnnouncement {
+    /// Makes the announcement of an endpoint from its url. Returns None if the url is not an
+    /// opc.tcp url.
+    pub fn from_endpoint_url(instance_name: &str, endpoint_url: &str, capabilities: &[String]) -> Option<ServiceAnnouncement> {
+        const SCHEME: &'static str = "opc.tcp://";
+        if !endpoint_url.starts_with(SCHEME) {
+            return None;
+        }
+        let url = &endpoint_url[SCHEME.len()..];
+        let (authority, path) = if let Some(idx) = url.find('/') {
+            (&url[..idx], &url[idx..])
+        } else {
+            (url, "/")
+        };
+        let (host, port) = if let Some(idx) = authority.rfind(':') {
+            if let Ok(port) = authority[idx + 1..].parse::<u16>() {
+                (&authority[..idx], port)
+            } else {
+                return None;
+            }
+        } else {
+            (authority, DEFAULT_OPC_TCP_PORT)
+        };
+        if host.is_empty() {
+            None
+        } else {
+            Some(ServiceAnnouncement {
+                instance_name: instance_name.to_string(),
+                host: host.to_string(),
+                port,
+                path: path.to_string(),
+                capabilities: capabilities.to_vec(),
+            })
+        }
+    }
+
+    /// Returns the url of the announced endpoint
+    pub fn endpoint_url(&self) -> String {
+        format!("opc.tcp://{}:{}{}", self.host, self.port, self.path)
+    }
+
+    /// The instance name is a single label of the service instance's name, so it may hold dots
+    fn instance_labels(&self) -> Vec<String> {
+        let mut instance_name = self.instance_name.clone();
+        while instance_name.len() > MAX_LABEL_LEN {
+            instance_name.pop();
+        }
+        let mut labels = vec![instance_name];
+        labels.extend(OPCUA_TCP_SERVICE.iter().map(|label| label.to_string()));
+        labels
+    }
+
+    /// The SRV record names the host. An IPv4 address is given a name of its own in the local
+    /// domain that an A record resolves and a name without dots is put in the local domain.
+    fn target_labels(&self) -> (Vec<String>, Option<Ipv4Addr>) {
+        if let Ok(address) = self.host.parse::<Ipv4Addr>() {
+            (vec![self.host.replace('.', "-"), "local".to_string()], Some(address))
+        } else if !self.host.contains('.') {
+            (vec![self.host.clone(), "local".to_string()], None)
+        } else {
+            (self.host.split('.').map(|label| label.to_string()).collect(), None)
+        }
+    }
+}
+
+/// Encodes a query for the instances of the OPC UA service
+pub fn encode_query(id: u16) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(64);
+    write_header(&mut buf, id, 0, 1, 0);
+    write_name(&mut buf, &service_labels());
+    write_u16(&mut buf, TYPE_PTR);
+    write_u16(&mut buf, CLASS_IN);
+    buf
+}
+
+/// Decodes the message and, if it is a query for instances of the OPC UA service, returns its id
+pub fn decode_query(buf: &[u8]) -> Option<u16> {
+    decode_service_question(buf).map(|(id, _, _, _)| id)
+}
+
+/// Encodes a response announcing the endpoints. A time to live of 0 tells listeners that the
+/// endpoints are going away.
+pub fn encode_announcements(id: u16, announcements: &[ServiceAnnouncement], ttl: u32) -> Vec<u8> {
+    encode_response(id, None, announcements, ttl, CLASS_CACHE_FLUSH)
+}
+
+/// Encodes the answer to a legacy unicast query, i.e. one sent from a port other than the mDNS
+/// port by a querier that is not a full mDNS responder. Returns None if the query is not for
+/// instances of the OPC UA service. As RFC 6762 section 6.7 requires, the answer repeats the
+/// question, its records live no longer than `LEGACY_UNICAST_MAX_TTL` and they do not flush caches.
+pub fn encode_legacy_unicast_answer(query: &[u8], announcements: &[ServiceAnnouncement], ttl: u32) -> Option<Vec<u8>> {
+    let (id, name, question_type, class) = decode_service_question(query)?;
+    Some(encode_response(id, Some((&name, question_type, class)), announcements, ttl.min(LEGACY_UNICAST_MAX_TTL), 0))
+}
+
+fn encode_response(id: u16, question: Option<(&[String], u16, u16)>, announcements: &[ServiceAnnouncement], ttl: u32, cache_flush: u16) -> Vec<u8> {
+    let mut records = Vec::with_capacity(announcements.len() * 4);
+    for announcement in announcements {
+        let instance_labels = announcement.instance_labels();
+        let (target_labels, address) = announcement.target_labels();
+
+        let mut rdata = Vec::new();
+        write_name(&mut rdata, &instance_labels);
+        records.push((service_labels(), TYPE_PTR, CLASS_IN, rdata));
+
+        let mut rdata = Vec::new();
+        write_u16(&mut rdata, 0);
+        write_u16(&mut rdata, 0);
+        write_u16(&mut rdata, announcement.port);
+        write_name(&mut rdata, &target_labels);
+        records.push((instance_labels.clone(), TYPE_SRV, CLASS_IN | cache_flush, rdata));
+
+        let mut rdata = Vec::new();
+        write_character_string(&mut rdata, &format!("path={}", announcement.path));
+        write_character_string(&mut rdata, &format!("caps={}", announcement.capabilities.join(",")));
+        records.push((instance_labels, TYPE_TXT, CLASS_IN | cache_flush, rdata));
+
+        if let Some(address) = address {
+            records.push((target_labels, TYPE_A, CLASS_IN | cache_flush, address.octets().to_vec()));
+        }
+    }
+
+    let mut buf = Vec::with_capacity(512);
+    let question_count = if question.is_some() { 1 } else { 0 };
+    write_header(&mut buf, id, FLAG_RESPONSE | FLAG_AUTHORITATIVE, question_count, records.len() as u16);
+    if let Some((name, question_type, class)) = question {
+        write_name(&mut buf, name);
+        write_u16(&mut buf, question_type);
+        write_u16(&mut buf, class);
+    }
+    for (name, record_type, class, rdata) in records {
+        write_name(&mut buf, &name);
+        write_u16(&mut buf, record_type);
+        write_u16(&mut buf, class);
+        write_u32(&mut buf, ttl);
+        write_u16(&mut buf, rdata.len() as u16);
+        buf.extend(rdata);
+    }
+    buf
+}
+
+/// Decodes the instances of the OPC UA service that a response announces. Instances that are
+/// going away, i.e. whose records have a time to live of 0, are left out.
+pub fn decode_announcements(buf: &[u8]) -> Result<Vec<ServiceAnnouncement>, StatusCode> {
+    let mut pos = 0;
+    let (_, flags, question_count, record_count) = read_header(buf, &mut pos)?;
+    if flags & FLAG_RESPONSE == 0 {
+        return Ok(Vec::new());
+    }
+    for _ in 0..question_count {
+        let _ = read_name(buf, &mut pos)?;
+        pos += 4;
+    }
+
+    let service_labels = service_labels();
+    let mut instances: Vec<Vec<String>> = Vec::new();
+    let mut services = Vec::new();
+    let mut texts = Vec::new();
+    let mut addresses = Vec::new();
+    for _ in 0..record_count {
+        let name = read_name(buf, &mut pos)?;
+        let record_type = read_u16(buf, &mut pos)?;
+        let _ = read_u16(buf, &mut pos)?;
+        let ttl = read_u32(buf, &mut pos)?;
+        let rdata_len = read_u16(buf, &mut pos)? as usize;
+        let rdata_end = pos + rdata_len;
+        if rdata_end > buf.len() {
+            return Err(BAD_DECODING_ERROR);
+        }
+        let mut rdata_pos = pos;
+        match record_type {
+            TYPE_PTR if names_match(&name, &service_labels) => {
+                let instance = read_name(buf, &mut rdata_pos)?;
+                if ttl > 0 && is_service_instance(&instance, &service_labels) && !instances.iter().any(|i| names_match(i, &instance)) {
+                    instances.push(instance);
+                }
+            }
+            TYPE_SRV => {
+                rdata_pos += 4;
+                let port = read_u16(buf, &mut rdata_pos)?;
+                let target = read_name(buf, &mut rdata_pos)?;
+                if ttl > 0 && is_service_instance(&name, &service_labels) && !instances.iter().any(|i| names_match(i, &name)) {
+                    instances.push(name.clone());
+                }
+                services.push((name, port, target, ttl));
+            }
+            TYPE_TXT => {
+                let mut strings = Vec::new();
+                while rdata_pos < rdata_end {
+                    let len = buf[rdata_pos] as usize;
+                    let end = (rdata_pos + 1 + len).min(rdata_end);
+                    strings.push(String::from_utf8_lossy(&buf[rdata_pos + 1..end]).to_string());
+                    rdata_pos = end;
+                }
+                texts.push((name, strings));
+            }
+            TYPE_A if rdata_len == 4 => {
+                addresses.push((name, Ipv4Addr::new(buf[pos], buf[pos + 1], buf[pos + 2], buf[pos + 3])));
+            }
+            _ => {}
+        }
+        pos = rdata_end;
+    }
+
+    let mut announcements = Vec::with_capacity(instances.len());
+    for instance in &instances {
+        let service = services.iter().find(|&&(ref name, _, _, _)| names_match(name, instance));
+        if let Some(&(_, port, ref target, ttl)) = service {
+            if ttl == 0 {
+                continue;
+            }
+            let host = if let Some(&(_, address)) = addresses.iter().find(|&&(ref name, _)| names_match(name, target)) {
+                address.to_string()
+            } else if target.len() > 1 && target.last().unwrap().eq_ignore_ascii_case("local") {
+                target[..target.len() - 1].join(".")
+            } else {
+                target.join(".")
+            };
+            let mut path = "/".to_string();
+            let mut capabilities = Vec::new();
+            if let Some(&(_, ref strings)) = texts.iter().find(|&&(ref name, _)| names_match(name, instance)) {
+                for s in strings {
+                    let lower = s.to_lowercase();
+                    if lower.starts_with("path=") {
+                        path = s[5..].to_string();
+                    } else if lower.starts_with("caps=") {
+                        capabilities = s[5..].split(',').filter(|c| !c.is_empty()).map(|c| c.to_string()).collect();
+                    }
+                }
+            }
+            announcements.push(ServiceAnnouncement {
+                instance_name: instance[0].clone(),
+                host,
+                port,
+                path,
+                capabilities,
+            });
+        }
+    }
+    Ok(announcements)
+}
+
+fn service_labels() -> Vec<String> {
+    OPCUA_TCP_SERVICE.iter().map(|label| label.to_string()).collect()
+}
+
+/// Finds the question for instances of the OPC UA service in a query, returning the id of the
+/// query with the name, type and class of the question
+fn decode_service_question(buf: &[u8]) -> Option<(u16, Vec<String>, u16, u16)> {
+    let mut pos = 0;
+    let (id, flags, question_count, _) = read_header(buf, &mut pos).ok()?;
+    if flags & FLAG_RESPONSE != 0 {
+        return None;
+    }
+    let service_labels = service_labels();
+    for _ in 0..question_count {
+        let name = read_name(buf, &mut pos).ok()?;
+        let question_type = read_u16(buf, &mut pos).ok()?;
+        let class = read_u16(buf, &mut pos).ok()?;
+        if (question_type == TYPE_PTR || question_type == TYPE_ANY) && names_match(&name, &service_labels) {
+            return Some((id, name, question_type, class));
+        }
+    }
+    None
+}
+
+/// Names are compared without regard to case
+fn names_match(name1: &[String], name2: &[String]) -> bool {
+    name1.len() == name2.len() && name1.iter().zip(name2.iter()).all(|(label1, label2)| label1.eq_ignore_ascii_case(label2))
+}
+
+/// Tests if the name is that of an instance of the service, i.e. the service's name with one more label
+fn is_service_instance(name: &[String], service_labels: &[String]) -> bool {
+    name.len() == service_labels.len() + 1 && names_match(&name[1..], service_labels)
+}
+
+fn write_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.push((value >> 8) as u8);
+    buf.push(value as u8);
+}
+
+fn write_u32(buf: &mut Vec<u8>, value: u32) {
+    write_u16(buf, (value >> 16) as u16);
+    write_u16(buf, value as u16);
+}
+
+fn write_header(buf: &mut Vec<u8>, id: u16, flags: u16, question_count: u16, answer_count: u16) {
+    write_u16(buf, id);
+    write_u16(buf, flags);
+    write_u16(buf, question_count);
+    write_u16(buf, answer_count);
+    write_u16(buf, 0);
+    write_u16(buf, 0);
+}
+
+/// Names are written uncompressed
+fn write_name(buf: &mut Vec<u8>, labels: &[String]) {
+    for label in labels {
+        let label = &label.as_bytes()[..label.len().min(MAX_LABEL_LEN)];
+        buf.push(label.len() as u8);
+        buf.extend_from_slice(label);
+    }
+    buf.push(0);
+}
+
+fn write_character_string(buf: &mut Vec<u8>, value: &str) {
+    let value = &value.as_bytes()[..value.len().min(255)];
+    buf.push(value.len() as u8);
+    buf.extend_from_slice(value);
+}
+
+fn read_u16(buf: &[u8], pos: &mut usize) -> Result<u16, StatusCode> {
+    if *pos + 2 > buf.len() {
+        Err(BAD_DECODING_ERROR)
+    } else {
+        let value = ((buf[*pos] as u16) << 8) | buf[*pos + 1] as u16;
+        *pos += 2;
+        Ok(value)
+    }
+}
+
+fn read_u32(buf: &[u8], pos: &mut usize) -> Result<u32, StatusCode> {
+    let high = read_u16(buf, pos)? as u32;
+    let low = read_u16(buf, pos)? as u32;
+    Ok((high << 16) | low)
+}
+
+/// Reads the id, flags, question count and the count of all records from the header
+fn read_header(buf: &[u8], pos: &mut usize) -> Result<(u16, u16, u16, u16), StatusCode> {
+    let id = read_u16(buf, pos)?;
+    let flags = read_u16(buf, pos)?;
+    let question_count = read_u16(buf, pos)?;
+    let answer_count = read_u16(buf, pos)?;
+    let authority_count = read_u16(buf, pos)?;
+    let additional_count = read_u16(buf, pos)?;
+    Ok((id, flags, question_count, answer_count.saturating_add(authority_count).saturating_add(additional_count)))
+}
+
+/// Reads a name, following the pointers of a compressed name
+fn read_name(buf: &[u8], pos: &mut usize) -> Result<Vec<String>, StatusCode> {
+    let mut labels = Vec::new();
+    let mut label_pos = *pos;
+    let mut pointers = 0;
+    loop {
+        if label_pos >= buf.len() {
+            return Err(BAD_DECODING_ERROR);
+        }
+        let len = buf[label_pos] as usize;
+        if len == 0 {
+            if pointers == 0 {
+                *pos = label_pos + 1;
+            }
+            return Ok(labels);
+        } else if len & 0xc0 == 0xc0 {
+            if label_pos + 2 > buf.len() || pointers == MAX_NAME_POINTERS {
+                return Err(BAD_DECODING_ERROR);
+            }
+            if pointers == 0 {
+                *pos = label_pos + 2;
+            }
+            pointers += 1;
+            label_pos = ((len & 0x3f) << 8) | buf[label_pos + 1] as usize;
+        } else {
+            if label_pos + 1 + len > buf.len() {
+                return Err(BAD_DECODING_ERROR);
+            }
+            labels.push(String::from_utf8_lossy(&buf[label_pos + 1..label_pos + 1 + len]).to_string());
+            label_pos += 1 + len;
+        }
+    }
+}
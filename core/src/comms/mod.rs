@@ -21,6 +21,7 @@ pub const MAX_CHUNK_COUNT: usize = 1;
 
 pub mod chunker;
 pub mod handshake;
+pub mod mdns;
 pub mod message_buffer;
 pub mod message_chunk;
 pub mod message_chunk_info;
@@ -31,6 +32,7 @@ pub mod prelude {
     pub use super::MAX_CHUNK_COUNT;
     pub use super::chunker::*;
     pub use super::handshake::*;
+    pub use super::mdns::*;
     pub use super::message_buffer::*;
     pub use super::message_chunk::*;
     pub use super::message_chunk_info::*;
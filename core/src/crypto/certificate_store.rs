@@ -108,10 +108,13 @@ impl CertificateStore {
             builder.set_not_after(&Asn1Time::days_from_now(args.certificate_duration_days).unwrap()).unwrap();
             builder.set_pubkey(&pkey).unwrap();
 
-            // Subject alt names - Alt hostnames, ip addresses for application instance cert
-            if !args.alt_host_names.is_empty() {
+            // Subject alt names - Application uri, alt hostnames, ip addresses for application instance cert
+            if !args.application_uri.is_empty() || !args.alt_host_names.is_empty() {
                 let subject_alternative_name = {
                     let mut subject_alternative_name = SubjectAlternativeName::new();
+                    if !args.application_uri.is_empty() {
+                        subject_alternative_name.uri(&args.application_uri);
+                    }
                     for alt_host_name in args.alt_host_names.iter() {
                         subject_alternative_name.dns(alt_host_name);
                     }
@@ -34,6 +34,8 @@ pub struct X509Data {
     pub country: String,
     pub state: String,
     pub alt_host_names: Vec<String>,
+    /// The application uri, written to the subjectAltName of the cert if it is not empty
+    pub application_uri: String,
     pub certificate_duration_days: u32,
 }
 
@@ -62,6 +64,7 @@ impl X509Data {
             country: "IE".to_string(),
             state: "Dublin".to_string(),
             alt_host_names,
+            application_uri: String::new(),
             certificate_duration_days: 365,
         }
     }
@@ -195,6 +198,66 @@ impl X509 {
         Thumbprint::new(&digest)
     }
 
+    /// Returns the application uri held in the subjectAltName extension of the cert, if there is one
+    pub fn application_uri(&self) -> Option<String> {
+        // The bindings do not expose uniformResourceIdentifier names so the extension is read from
+        // the DER form of the cert
+        let der = self.value.to_der().ok()?;
+        let general_names = Self::find_subject_alt_name(&der)?;
+        let mut pos = 0;
+        while pos < general_names.len() {
+            let (tag, value, next) = Self::read_der_element(general_names, pos)?;
+            // [6] IMPLICIT IA5String
+            if tag == 0x86 {
+                return String::from_utf8(value.to_vec()).ok();
+            }
+            pos = next;
+        }
+        None
+    }
+
+    /// Returns the encoded GeneralNames of the subjectAltName extension
+    fn find_subject_alt_name(der: &[u8]) -> Option<&[u8]> {
+        // OID 2.5.29.17
+        const SUBJECT_ALT_NAME_OID: [u8; 5] = [0x06, 0x03, 0x55, 0x1d, 0x11];
+        let oid_pos = der.windows(SUBJECT_ALT_NAME_OID.len()).position(|w| w == SUBJECT_ALT_NAME_OID)?;
+        let (mut tag, mut value, next) = Self::read_der_element(der, oid_pos + SUBJECT_ALT_NAME_OID.len())?;
+        // Skip the critical flag if it is present
+        if tag == 0x01 {
+            let element = Self::read_der_element(der, next)?;
+            tag = element.0;
+            value = element.1;
+        }
+        // The OCTET STRING holds a SEQUENCE of GeneralName
+        if tag != 0x04 {
+            return None;
+        }
+        let (tag, general_names, _) = Self::read_der_element(value, 0)?;
+        if tag == 0x30 { Some(general_names) } else { None }
+    }
+
+    /// Reads the DER element at the position and returns its tag, its value and the position of
+    /// the element that follows it
+    fn read_der_element(der: &[u8], pos: usize) -> Option<(u8, &[u8], usize)> {
+        let tag = *der.get(pos)?;
+        let first_length_byte = *der.get(pos + 1)? as usize;
+        let (length, value_pos) = if first_length_byte < 0x80 {
+            (first_length_byte, pos + 2)
+        } else {
+            let length_bytes = first_length_byte & 0x7f;
+            if length_bytes == 0 || length_bytes > 4 {
+                return None;
+            }
+            let mut length = 0usize;
+            for i in 0..length_bytes {
+                length = (length << 8) | *der.get(pos + 2 + i)? as usize;
+            }
+            (length, pos + 2 + length_bytes)
+        };
+        let value = der.get(value_pos..value_pos.checked_add(length)?)?;
+        Some((tag, value, value_pos + length))
+    }
+
     /// Turn the Asn1 values into useful portable types
     pub fn not_before(&self) -> Result<DateTime<UTC>, ()> {
         let date = self.value.not_before().to_string();
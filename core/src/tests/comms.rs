@@ -3,6 +3,7 @@ use std::io::*;
 use opcua_types::*;
 
 use comms::handshake::*;
+use comms::mdns::*;
 use comms::secure_channel::*;
 use crypto::SecurityPolicy;
 
@@ -62,4 +63,78 @@ pub fn secure_channel_nonce() {
     assert!(sc.set_their_nonce(&ByteString::from_bytes(b"012345678901234567890123456789012")).is_err());
     // Nonce which is 32 bytes long is good
     assert!(sc.set_their_nonce(&ByteString::from_bytes(b"01234567890123456789012345678901")).is_ok());
-}
\ No newline at end of file
+}
+
+#[test]
+pub fn mdns_announcements() {
+    let capabilities = vec!["LDS".to_string(), "DA".to_string()];
+    let announcements = vec![
+        ServiceAnnouncement::from_endpoint_url("Server A", "opc.tcp://127.0.0.1:4855/a", &capabilities).unwrap(),
+        ServiceAnnouncement::from_endpoint_url("Server B", "opc.tcp://myhost", &[]).unwrap(),
+    ];
+    assert_eq!(announcements[0].endpoint_url(), "opc.tcp://127.0.0.1:4855/a");
+    assert_eq!(announcements[1].endpoint_url(), "opc.tcp://myhost:4840/");
+    assert!(ServiceAnnouncement::from_endpoint_url("Server C", "http://myhost/", &[]).is_none());
+
+    // Announcements survive encoding
+    let buf = encode_announcements(0, &announcements, 120);
+    assert_eq!(decode_announcements(&buf).unwrap(), announcements);
+    assert!(decode_query(&buf).is_none());
+
+    // Listeners forget endpoints that are going away
+    let buf = encode_announcements(0, &announcements, 0);
+    assert!(decode_announcements(&buf).unwrap().is_empty());
+
+    // A query is recognised and is not an announcement
+    let buf = encode_query(1234);
+    assert_eq!(decode_query(&buf), Some(1234));
+    assert!(decode_announcements(&buf).unwrap().is_empty());
+    assert!(decode_announcements(&buf[..5]).is_err());
+
+    // A legacy unicast answer repeats the id and question of the query and lives briefly
+    let answer = encode_legacy_unicast_answer(&buf, &announcements, 120).unwrap();
+    assert_eq!(&answer[..2], &buf[..2]);
+    assert_eq!(&answer[4..6], &[0x00, 0x01]);
+    assert_eq!(&answer[12..buf.len()], &buf[12..]);
+    assert_eq!(decode_announcements(&answer).unwrap(), announcements);
+    // The TTL of the first record follows its name, type and class
+    let ttl_pos = buf.len() + 23 + 4;
+    assert_eq!(&answer[ttl_pos..ttl_pos + 4], &[0x00, 0x00, 0x00, LEGACY_UNICAST_MAX_TTL as u8]);
+    assert!(encode_legacy_unicast_answer(&encode_announcements(0, &announcements, 120), &announcements, 120).is_none());
+}
+
+#[test]
+pub fn mdns_compressed_announcement() {
+    let mut buf = vec![0x00, 0x00, 0x84, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00];
+    // PTR record of the service at offset 12 naming the instance at offset 45
+    buf.extend_from_slice(b"\x0a_opcua-tcp\x04_tcp\x05local\x00");
+    buf.extend_from_slice(&[0x00, 0x0c, 0x00, 0x01, 0x00, 0x00, 0x11, 0x94, 0x00, 0x07]);
+    buf.extend_from_slice(b"\x04Test\xc0\x0c");
+    // SRV record of the instance whose target is in the local domain at offset 28
+    buf.extend_from_slice(&[0xc0, 0x2d, 0x00, 0x21, 0x80, 0x01, 0x00, 0x00, 0x00, 0x78, 0x00, 0x0d, 0x00, 0x00, 0x00, 0x00, 0x10, 0xe1]);
+    buf.extend_from_slice(b"\x04host\xc0\x1c");
+    // TXT record of the instance
+    buf.extend_from_slice(&[0xc0, 0x2d, 0x00, 0x10, 0x80, 0x01, 0x00, 0x00, 0x00, 0x78, 0x00, 0x10]);
+    buf.extend_from_slice(b"\x07path=/x\x07caps=DA");
+
+    let announcements = decode_announcements(&buf).unwrap();
+    assert_eq!(announcements, vec![ServiceAnnouncement {
+        instance_name: "Test".to_string(),
+        host: "host".to_string(),
+        port: 4321,
+        path: "/x".to_string(),
+        capabilities: vec!["DA".to_string()],
+    }]);
+
+    // A PTR record naming the root is not an instance, even if an SRV record is on the root
+    let mut buf = vec![0x00, 0x00, 0x84, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00];
+    buf.extend_from_slice(b"\x0a_opcua-tcp\x04_tcp\x05local\x00");
+    buf.extend_from_slice(&[0x00, 0x0c, 0x00, 0x01, 0x00, 0x00, 0x00, 0x78, 0x00, 0x01, 0x00]);
+    buf.extend_from_slice(&[0x00, 0x00, 0x21, 0x00, 0x01, 0x00, 0x00, 0x00, 0x78, 0x00, 0x07, 0x00, 0x00, 0x00, 0x00, 0x10, 0xe1, 0x00]);
+    assert!(decode_announcements(&buf).unwrap().is_empty());
+
+    // A pointer to itself is not followed forever
+    let mut buf = vec![0x00, 0x00, 0x84, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00];
+    buf.extend_from_slice(&[0xc0, 0x0c]);
+    assert!(decode_announcements(&buf).is_err());
+}
@@ -80,6 +80,7 @@ fn create_own_cert_in_pki() {
         country: "EN".to_string(),
         state: "London".to_string(),
         alt_host_names: vec!["host1".to_string(), "host2".to_string()],
+        application_uri: String::new(),
         certificate_duration_days: 60,
     };
 
@@ -97,6 +98,30 @@ fn create_own_cert_in_pki() {
     drop(tmp_dir)
 }
 
+#[test]
+fn application_uri_in_cert() {
+    // A cert without an application uri has none
+    let (cert, _) = make_test_cert();
+    assert_eq!(cert.application_uri(), None);
+
+    // The application uri is written to and read from the subjectAltName
+    let args = X509Data {
+        key_size: 2048,
+        common_name: "x".to_string(),
+        organization: "x.org".to_string(),
+        organizational_unit: "x.org ops".to_string(),
+        country: "EN".to_string(),
+        state: "London".to_string(),
+        alt_host_names: vec!["host1".to_string(), "host2".to_string()],
+        application_uri: "urn:host1:x".to_string(),
+        certificate_duration_days: 60,
+    };
+    let (cert, _) = CertificateStore::create_cert_and_pkey(&args).unwrap();
+    assert_eq!(cert.application_uri(), Some("urn:host1:x".to_string()));
+    let cert = X509::from_byte_string(&cert.as_byte_string()).unwrap();
+    assert_eq!(cert.application_uri(), Some("urn:host1:x".to_string()));
+}
+
 #[test]
 fn create_rejected_cert_in_pki() {
     let (tmp_dir, cert_store) = make_certificate_store();
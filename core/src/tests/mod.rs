@@ -68,6 +68,7 @@ fn make_test_cert() -> (X509, PKey) {
         country: "EN".to_string(),
         state: "London".to_string(),
         alt_host_names: vec!["host1".to_string(), "host2".to_string()],
+        application_uri: String::new(),
         certificate_duration_days: 60,
     };
     let cert = CertificateStore::create_cert_and_pkey(&args);
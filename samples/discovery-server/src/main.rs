@@ -0,0 +1,19 @@
+//! This is a local discovery server (LDS) for OPC UA. It listens on the well known discovery port
+//! and other servers on the machine register themselves with it. Clients ask it which servers
+//! there are with FindServers or FindServersOnNetwork.
+extern crate opcua_core;
+extern crate opcua_server;
+
+use opcua_server::prelude::*;
+
+fn main() {
+    // This enables logging via env_logger & log crate macros. If you don't need logging or want
+    // to implement your own, omit this line.
+    opcua_core::init_logging();
+
+    // Create an OPC UA server in local discovery server mode
+    let mut server = Server::new(ServerConfig::default_local_discovery_server());
+
+    // Run the server. This does not ordinarily exit so you must Ctrl+C to terminate
+    server.run();
+}
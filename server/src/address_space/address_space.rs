@@ -1,16 +1,22 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 use std::sync::{Arc, Mutex};
 
 use opcua_types::*;
 use opcua_types::ServerState as ServerStateType;
 
-use server::ServerState;
+use server::{ServerState, ServerDiagnostics};
 use constants;
 
 use address_space::object::Object;
 use address_space::variable::Variable;
+use address_space::method::Method;
 use address_space::node::{Node, NodeType};
 use address_space::AttrFnGetter;
+use events::event::{Event, EVENT_NOTIFIER_SUBSCRIBE_TO_EVENTS};
+use history::HistoricalDataProvider;
+use history::aggregates::AggregateType;
+use history::memory_provider::MemoryHistoricalDataProvider;
 
 /// The `NodeId` is the target node. The reference is held in a list by the source node.
 /// The target node does not need to exist.
@@ -29,11 +35,34 @@ impl Reference {
     }
 }
 
-#[derive(Debug)]
 pub struct AddressSpace {
     pub node_map: HashMap<NodeId, NodeType>,
     pub references: HashMap<NodeId, Vec<Reference>>,
     pub inverse_references: HashMap<NodeId, Vec<Reference>>,
+    /// Recently raised events, oldest first, each with the index it was raised with
+    events: VecDeque<(usize, Event)>,
+    /// The index of the most recently raised event
+    last_event_index: usize,
+    /// Recently changed variable values, oldest first, each with the index it was recorded with
+    value_changes: VecDeque<(usize, NodeId)>,
+    /// The index of the most recently recorded value change
+    last_value_change_index: usize,
+    /// Stores the history of historizing variables
+    historical_data_provider: Arc<Mutex<HistoricalDataProvider + Send>>,
+}
+
+impl fmt::Debug for AddressSpace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AddressSpace")
+            .field("node_map", &self.node_map)
+            .field("references", &self.references)
+            .field("inverse_references", &self.inverse_references)
+            .field("events", &self.events)
+            .field("last_event_index", &self.last_event_index)
+            .field("value_changes", &self.value_changes)
+            .field("last_value_change_index", &self.last_value_change_index)
+            .finish()
+    }
 }
 
 impl AddressSpace {
@@ -43,8 +72,21 @@ impl AddressSpace {
             node_map: HashMap::new(),
             references: HashMap::new(),
             inverse_references: HashMap::new(),
+            events: VecDeque::new(),
+            last_event_index: 0,
+            value_changes: VecDeque::new(),
+            last_value_change_index: 0,
+            historical_data_provider: Arc::new(Mutex::new(MemoryHistoricalDataProvider::new(constants::DEFAULT_HISTORY_CAPACITY))),
         };
         address_space.add_default_nodes();
+        // The server object is the notifier for all events
+        if let Some(&mut NodeType::Object(ref mut server)) = address_space.find_node_mut(&ObjectId::Server.as_node_id()) {
+            server.set_event_notifier(EVENT_NOTIFIER_SUBSCRIBE_TO_EVENTS);
+        }
+        // The aggregates the server supports
+        for aggregate_type in AggregateType::supported() {
+            address_space.add_organizes(&ObjectId::Server_ServerCapabilities_AggregateFunctions.as_node_id(), &aggregate_type.object_id().as_node_id());
+        }
         address_space
     }
 
@@ -70,10 +112,10 @@ impl AddressSpace {
             self.set_value_by_variable_id(Server_ServerCapabilities_MaxArrayLength, Variant::UInt32(server_config.max_array_length));
             self.set_value_by_variable_id(Server_ServerCapabilities_MaxStringLength, Variant::UInt32(server_config.max_string_length));
             self.set_value_by_variable_id(Server_ServerCapabilities_MaxByteStringLength, Variant::UInt32(server_config.max_byte_string_length));
-            self.set_value_by_variable_id(Server_ServerCapabilities_MaxBrowseContinuationPoints, Variant::UInt32(0));
-            self.set_value_by_variable_id(Server_ServerCapabilities_MaxHistoryContinuationPoints, Variant::UInt32(0));
+            self.set_value_by_variable_id(Server_ServerCapabilities_MaxBrowseContinuationPoints, Variant::UInt32(server_config.max_browse_continuation_points));
+            self.set_value_by_variable_id(Server_ServerCapabilities_MaxHistoryContinuationPoints, Variant::UInt32(server_config.max_history_continuation_points));
             self.set_value_by_variable_id(Server_ServerCapabilities_MaxQueryContinuationPoints, Variant::UInt32(0));
-            self.set_value_by_variable_id(Server_ServerCapabilities_MinSupportedSampleRate, Variant::Double(constants::MIN_SAMPLING_INTERVAL));
+            self.set_value_by_variable_id(Server_ServerCapabilities_MinSupportedSampleRate, Variant::Double(server_config.min_sampling_interval));
 
             // Server_ServerCapabilities_ServerProfileArray
             if let Some(ref mut v) = self.find_variable_by_variable_id(Server_ServerCapabilities_ServerProfileArray) {
@@ -123,29 +165,75 @@ impl AddressSpace {
             // Server_ServerCapabilities_MinSupportedSampleRate
         }
 
+        // ServerDiagnostics
         {
             // Server_ServerDiagnostics_ServerDiagnosticsSummary
-            // Server_ServerDiagnostics_ServerDiagnosticsSummary_ServerViewCount
-            // Server_ServerDiagnostics_ServerDiagnosticsSummary_CurrentSessionCount
-            // Server_ServerDiagnostics_ServerDiagnosticsSummary_CumulatedSessionCount
-            // Server_ServerDiagnostics_ServerDiagnosticsSummary_SecurityRejectedSessionCount
-            // Server_ServerDiagnostics_ServerDiagnosticsSummary_SessionTimeoutCount
-            // Server_ServerDiagnostics_ServerDiagnosticsSummary_SessionAbortCount
-            // Server_ServerDiagnostics_ServerDiagnosticsSummary_PublishingIntervalCount
-            // Server_ServerDiagnostics_ServerDiagnosticsSummary_CurrentSubscriptionCount
-            // Server_ServerDiagnostics_ServerDiagnosticsSummary_CumulatedSubscriptionCount
-            // Server_ServerDiagnostics_ServerDiagnosticsSummary_SecurityRejectedRequestsCount
-            // Server_ServerDiagnostics_ServerDiagnosticsSummary_RejectedRequestsCount
+            if let Some(ref mut v) = self.find_variable_by_variable_id(Server_ServerDiagnostics_ServerDiagnosticsSummary) {
+                let diagnostics = server_state.diagnostics.clone();
+                let getter = AttrFnGetter::new(move |_: NodeId, _: AttributeId| -> Option<DataValue> {
+                    let summary = diagnostics.lock().unwrap().summary();
+                    Some(DataValue::new(Variant::ExtensionObject(Box::new(ExtensionObject::from_encodable(ObjectId::ServerDiagnosticsSummaryDataType_Encoding_DefaultBinary.as_node_id(), summary)))))
+                });
+                v.set_value_getter(Arc::new(Mutex::new(getter)));
+            }
+
+            // Each count in the summary is also a variable of its own
+            let counts: [(VariableId, fn(&ServerDiagnosticsSummaryDataType) -> UInt32); 12] = [
+                (Server_ServerDiagnostics_ServerDiagnosticsSummary_ServerViewCount, |s| s.server_view_count),
+                (Server_ServerDiagnostics_ServerDiagnosticsSummary_CurrentSessionCount, |s| s.current_session_count),
+                (Server_ServerDiagnostics_ServerDiagnosticsSummary_CumulatedSessionCount, |s| s.cumulated_session_count),
+                (Server_ServerDiagnostics_ServerDiagnosticsSummary_SecurityRejectedSessionCount, |s| s.security_rejected_session_count),
+                (Server_ServerDiagnostics_ServerDiagnosticsSummary_RejectedSessionCount, |s| s.rejected_session_count),
+                (Server_ServerDiagnostics_ServerDiagnosticsSummary_SessionTimeoutCount, |s| s.session_timeout_count),
+                (Server_ServerDiagnostics_ServerDiagnosticsSummary_SessionAbortCount, |s| s.session_abort_count),
+                (Server_ServerDiagnostics_ServerDiagnosticsSummary_PublishingIntervalCount, |s| s.publishing_interval_count),
+                (Server_ServerDiagnostics_ServerDiagnosticsSummary_CurrentSubscriptionCount, |s| s.current_subscription_count),
+                (Server_ServerDiagnostics_ServerDiagnosticsSummary_CumulatedSubscriptionCount, |s| s.cumulated_subscription_count),
+                (Server_ServerDiagnostics_ServerDiagnosticsSummary_SecurityRejectedRequestsCount, |s| s.security_rejected_requests_count),
+                (Server_ServerDiagnostics_ServerDiagnosticsSummary_RejectedRequestsCount, |s| s.rejected_requests_count),
+            ];
+            for &(variable_id, count) in counts.iter() {
+                if let Some(ref mut v) = self.find_variable_by_variable_id(variable_id) {
+                    let diagnostics = server_state.diagnostics.clone();
+                    let getter = AttrFnGetter::new(move |_: NodeId, _: AttributeId| -> Option<DataValue> {
+                        let summary = diagnostics.lock().unwrap().summary();
+                        Some(DataValue::new(Variant::UInt32(count(&summary))))
+                    });
+                    v.set_value_getter(Arc::new(Mutex::new(getter)));
+                }
+            }
+
             // Server_ServerDiagnostics_SamplingIntervalDiagnosticsArray
+
             // Server_ServerDiagnostics_SubscriptionDiagnosticsArray
-            // Server_ServerDiagnostics_EnabledFlag
+            if let Some(ref mut v) = self.find_variable_by_variable_id(Server_ServerDiagnostics_SubscriptionDiagnosticsArray) {
+                let diagnostics = server_state.diagnostics.clone();
+                let getter = AttrFnGetter::new(move |_: NodeId, _: AttributeId| -> Option<DataValue> {
+                    let subscription_diagnostics = diagnostics.lock().unwrap().subscription_diagnostics(None);
+                    Some(AddressSpace::subscription_diagnostics_value(subscription_diagnostics))
+                });
+                v.set_value_getter(Arc::new(Mutex::new(getter)));
+            }
+
+            // Server_ServerDiagnostics_SessionsDiagnosticsSummary_SessionDiagnosticsArray
+            if let Some(ref mut v) = self.find_variable_by_variable_id(Server_ServerDiagnostics_SessionsDiagnosticsSummary_SessionDiagnosticsArray) {
+                let diagnostics = server_state.diagnostics.clone();
+                let getter = AttrFnGetter::new(move |_: NodeId, _: AttributeId| -> Option<DataValue> {
+                    let session_diagnostics = diagnostics.lock().unwrap().session_diagnostics().into_iter().map(|session_diagnostics| {
+                        AddressSpace::session_diagnostics_variant(session_diagnostics)
+                    }).collect();
+                    Some(DataValue::new(Variant::Array(Box::new(session_diagnostics))))
+                });
+                v.set_value_getter(Arc::new(Mutex::new(getter)));
+            }
+
+            self.set_value_by_variable_id(Server_ServerDiagnostics_EnabledFlag, Variant::Boolean(true));
         }
 
         // ServiceLevel - 0-255 worst to best quality of service
         self.set_value_by_variable_id(Server_ServiceLevel, Variant::Byte(255));
 
         // Auditing - var
-        // ServerDiagnostics
         // VendorServiceInfo
         // ServerRedundancy
 
@@ -186,6 +274,92 @@ impl AddressSpace {
         }
     }
 
+    /// Adds an object under Server_ServerDiagnostics_SessionsDiagnosticsSummary for each of the
+    /// sessions, with variables that read the diagnostics of the session and its subscriptions.
+    /// The objects of sessions that are not in the list are removed.
+    pub fn update_session_diagnostics_nodes(&mut self, session_ids: &[NodeId], diagnostics: &Arc<Mutex<ServerDiagnostics>>) {
+        let summary_id = ObjectId::Server_ServerDiagnostics_SessionsDiagnosticsSummary.as_node_id();
+        let existing_object_ids = self.find_references_from(&summary_id, Some((ReferenceTypeId::HasComponent, false))).unwrap_or(Vec::new()).into_iter()
+            .map(|r| r.node_id)
+            .filter(|node_id| if let Some(&NodeType::Object(_)) = self.find_node(node_id) { true } else { false })
+            .collect::<HashSet<NodeId>>();
+        let object_ids = session_ids.iter()
+            .map(|session_id| (AddressSpace::session_diagnostics_node_id(session_id, None), session_id))
+            .collect::<HashMap<NodeId, &NodeId>>();
+
+        // Objects of sessions that have gone are removed along with their variables
+        for object_id in existing_object_ids.iter().filter(|object_id| !object_ids.contains_key(object_id)) {
+            self.delete_node(object_id, true);
+        }
+
+        for (object_id, session_id) in object_ids.into_iter().filter(|&(ref object_id, _)| !existing_object_ids.contains(object_id)) {
+            // The object is named after the session
+            let session_name = diagnostics.lock().unwrap().find_session_diagnostics(session_id)
+                .map(|session_diagnostics| session_diagnostics.session_name.as_ref().to_string())
+                .unwrap_or(String::new());
+            let session_name = if session_name.is_empty() { session_id.to_string() } else { session_name };
+            self.insert(Object::new_node(&object_id, &session_name, &session_name, ""));
+            self.add_has_component(&summary_id, &object_id);
+            self.set_object_type(&object_id, &ObjectTypeId::SessionDiagnosticsObjectType);
+
+            // SessionDiagnostics
+            let variable_id = AddressSpace::session_diagnostics_node_id(session_id, Some("SessionDiagnostics"));
+            let mut variable = Variable::new(&variable_id, "SessionDiagnostics", "SessionDiagnostics", "", DataTypeId::SessionDiagnosticsDataType, DataValue::null());
+            {
+                let diagnostics = diagnostics.clone();
+                let session_id = session_id.clone();
+                let getter = AttrFnGetter::new(move |_: NodeId, _: AttributeId| -> Option<DataValue> {
+                    diagnostics.lock().unwrap().find_session_diagnostics(&session_id).map(|session_diagnostics| {
+                        DataValue::new(AddressSpace::session_diagnostics_variant(session_diagnostics))
+                    })
+                });
+                variable.set_value_getter(Arc::new(Mutex::new(getter)));
+            }
+            self.insert(NodeType::Variable(variable));
+            self.add_has_component(&object_id, &variable_id);
+            self.set_variable_type(&variable_id, &VariableTypeId::SessionDiagnosticsVariableType);
+
+            // SubscriptionDiagnosticsArray
+            let variable_id = AddressSpace::session_diagnostics_node_id(session_id, Some("SubscriptionDiagnosticsArray"));
+            let mut variable = Variable::new(&variable_id, "SubscriptionDiagnosticsArray", "SubscriptionDiagnosticsArray", "", DataTypeId::SubscriptionDiagnosticsDataType, DataValue::null());
+            {
+                let diagnostics = diagnostics.clone();
+                let session_id = session_id.clone();
+                let getter = AttrFnGetter::new(move |_: NodeId, _: AttributeId| -> Option<DataValue> {
+                    let subscription_diagnostics = diagnostics.lock().unwrap().subscription_diagnostics(Some(&session_id));
+                    Some(AddressSpace::subscription_diagnostics_value(subscription_diagnostics))
+                });
+                variable.set_value_getter(Arc::new(Mutex::new(getter)));
+            }
+            self.insert(NodeType::Variable(variable));
+            self.add_has_component(&object_id, &variable_id);
+            self.set_variable_type(&variable_id, &VariableTypeId::SubscriptionDiagnosticsArrayType);
+        }
+    }
+
+    /// Returns the id of the diagnostics object of the session, or of the named variable of that
+    /// object. The ids are strings made from the session id in the server's application
+    /// namespace, as namespace 0 is reserved for the nodes defined by the standard.
+    pub fn session_diagnostics_node_id(session_id: &NodeId, variable_name: Option<&str>) -> NodeId {
+        let object_name = format!("SessionDiagnostics[{}]", session_id.to_string());
+        if let Some(variable_name) = variable_name {
+            NodeId::new_string(constants::APPLICATION_NAMESPACE, &format!("{}.{}", object_name, variable_name))
+        } else {
+            NodeId::new_string(constants::APPLICATION_NAMESPACE, &object_name)
+        }
+    }
+
+    fn session_diagnostics_variant(session_diagnostics: SessionDiagnosticsDataType) -> Variant {
+        Variant::ExtensionObject(Box::new(ExtensionObject::from_encodable(ObjectId::SessionDiagnosticsDataType_Encoding_DefaultBinary.as_node_id(), session_diagnostics)))
+    }
+
+    fn subscription_diagnostics_value(subscription_diagnostics: Vec<SubscriptionDiagnosticsDataType>) -> DataValue {
+        let values = subscription_diagnostics.into_iter().map(|subscription_diagnostics| {
+            Variant::ExtensionObject(Box::new(ExtensionObject::from_encodable(ObjectId::SubscriptionDiagnosticsDataType_Encoding_DefaultBinary.as_node_id(), subscription_diagnostics)))
+        }).collect();
+        DataValue::new(Variant::Array(Box::new(values)))
+    }
+
     pub fn root_folder_id() -> NodeId {
         ObjectId::RootFolder.as_node_id()
     }
@@ -369,6 +543,89 @@ impl AddressSpace {
         }
     }
 
+    /// Adds a method as a component of the specified object. The input and output arguments, if
+    /// any, are added as the InputArguments and OutputArguments properties of the method.
+    pub fn add_method(&mut self, method: Method, object_id: &NodeId, input_arguments: &[Argument], output_arguments: &[Argument]) -> Result<NodeId, ()> {
+        let node_id = method.node_id();
+        if self.node_map.contains_key(&node_id) || !self.node_map.contains_key(object_id) {
+            Err(())
+        } else {
+            self.add_has_component(object_id, &node_id);
+            self.insert(NodeType::Method(method));
+            if !input_arguments.is_empty() {
+                self.add_method_arguments(&node_id, "InputArguments", input_arguments);
+            }
+            if !output_arguments.is_empty() {
+                self.add_method_arguments(&node_id, "OutputArguments", output_arguments);
+            }
+            Ok(node_id)
+        }
+    }
+
+    /// Adds a property to the method holding an array of arguments
+    fn add_method_arguments(&mut self, method_id: &NodeId, browse_name: &str, arguments: &[Argument]) {
+        let node_id = NodeId::next_numeric();
+        let value = arguments.iter().map(|argument| {
+            Variant::new_extension_object(ExtensionObject::from_encodable(ObjectId::Argument_Encoding_DefaultBinary.as_node_id(), argument.clone()))
+        }).collect();
+        let variable = Variable::new_array(&node_id, browse_name, browse_name, "", DataTypeId::Argument, DataValue::new(Variant::Array(Box::new(value))), &[arguments.len() as UInt32]);
+        self.insert(NodeType::Variable(variable));
+        self.add_has_property(method_id, &node_id);
+        self.set_variable_as_property_type(&node_id);
+    }
+
+    /// Finds the input arguments of a method. A method with no InputArguments property has none.
+    pub fn method_input_arguments(&self, method_id: &NodeId) -> Vec<Argument> {
+        self.method_arguments(method_id, "InputArguments")
+    }
+
+    /// Finds the output arguments of a method. A method with no OutputArguments property has none.
+    pub fn method_output_arguments(&self, method_id: &NodeId) -> Vec<Argument> {
+        self.method_arguments(method_id, "OutputArguments")
+    }
+
+    fn method_arguments(&self, method_id: &NodeId, browse_name: &str) -> Vec<Argument> {
+        if let Some(variable) = self.find_property(method_id, browse_name) {
+            if let Some(Variant::Array(values)) = variable.value().value {
+                return values.iter().filter_map(|value| {
+                    if let &Variant::ExtensionObject(ref value) = value {
+                        value.decode_inner::<Argument>().ok()
+                    } else {
+                        None
+                    }
+                }).collect();
+            }
+        }
+        Vec::new()
+    }
+
+    /// Finds the property of the node with the specified browse name, i.e. a variable that the
+    /// node references with HasProperty
+    pub fn find_property(&self, node_id: &NodeId, browse_name: &str) -> Option<&Variable> {
+        let browse_name = QualifiedName::new(0, browse_name);
+        if let Some(references) = self.find_references_from(node_id, Some((ReferenceTypeId::HasProperty, false))) {
+            for reference in references {
+                if let Some(&NodeType::Variable(ref variable)) = self.find_node(&reference.node_id) {
+                    if variable.browse_name() == browse_name {
+                        return Some(variable);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the value of the node's EURange property, the range that the value of an analog
+    /// variable normally operates within, or None if there is no such property
+    pub fn find_eu_range(&self, node_id: &NodeId) -> Option<Range> {
+        let variable = self.find_property(node_id, "EURange")?;
+        if let Some(Variant::ExtensionObject(value)) = variable.value().value {
+            value.decode_inner::<Range>().ok()
+        } else {
+            None
+        }
+    }
+
     /// Adds a reference between one node and a target
     fn add_reference(reference_map: &mut HashMap<NodeId, Vec<Reference>>, node_id: &NodeId, reference: Reference) {
         if reference_map.contains_key(node_id) {
@@ -400,11 +657,86 @@ impl AddressSpace {
 
     /// Set a variable value
     pub fn set_value_by_node_id(&mut self, node_id: &NodeId, value: Variant) -> bool {
-        if let Some(ref mut variable) = self.find_variable_by_node_id(node_id) {
+        let found = if let Some(ref mut variable) = self.find_variable_by_node_id(node_id) {
             variable.set_value_direct(&DateTime::now(), value);
             true
         } else {
             false
+        };
+        if found {
+            self.record_value_change(node_id);
+            self.historize_value(node_id);
+        }
+        found
+    }
+
+    /// Replaces the provider that stores the history of historizing variables. By default history
+    /// is held in memory.
+    pub fn set_historical_data_provider(&mut self, historical_data_provider: Arc<Mutex<HistoricalDataProvider + Send>>) {
+        self.historical_data_provider = historical_data_provider;
+    }
+
+    /// Returns the provider that stores the history of historizing variables
+    pub fn historical_data_provider(&self) -> Arc<Mutex<HistoricalDataProvider + Send>> {
+        self.historical_data_provider.clone()
+    }
+
+    /// Hands the current value of the variable to the historical data provider if the variable is
+    /// historizing. This is called when the value is set through the address space or written by
+    /// a client. Code that sets the value on the variable node directly should call this afterwards.
+    pub fn historize_value(&self, node_id: &NodeId) {
+        if let Some(&NodeType::Variable(ref variable)) = self.find_node(node_id) {
+            if variable.historizing() {
+                let mut historical_data_provider = self.historical_data_provider.lock().unwrap();
+                historical_data_provider.record_value(node_id, &variable.value());
+            }
+        }
+    }
+
+    /// Records that the value of the variable changed so exception-based monitored items sample it.
+    /// This is called when the value is set through the address space or written by a client.
+    /// Code that sets the value on the variable node directly should call this afterwards.
+    pub fn record_value_change(&mut self, node_id: &NodeId) {
+        self.last_value_change_index += 1;
+        if self.value_changes.len() == constants::MAX_BUFFERED_VALUE_CHANGES {
+            let _ = self.value_changes.pop_front();
+        }
+        self.value_changes.push_back((self.last_value_change_index, node_id.clone()));
+    }
+
+    /// Returns the index of the most recently recorded value change
+    pub fn last_value_change_index(&self) -> usize {
+        self.last_value_change_index
+    }
+
+    /// Returns the nodes whose values changed after the specified index, oldest first, or None
+    /// if some of those changes are no longer buffered
+    pub fn value_changes_since(&self, value_change_index: usize) -> Option<Vec<&NodeId>> {
+        let oldest_index = self.value_changes.front().map(|v| v.0).unwrap_or(self.last_value_change_index + 1);
+        if value_change_index + 1 < oldest_index {
+            None
+        } else {
+            Some(self.value_changes.iter().filter(|v| v.0 > value_change_index).map(|v| &v.1).collect())
+        }
+    }
+
+    /// Tests if changes to the value of the node are recorded, i.e. the node is a variable whose
+    /// value is held by the address space rather than supplied by a getter
+    pub fn is_value_change_recorded(&self, node_id: &NodeId) -> bool {
+        if let Some(&NodeType::Variable(ref variable)) = self.find_node(node_id) {
+            !variable.has_value_getter()
+        } else {
+            false
+        }
+    }
+
+    /// Returns the minimum sampling interval of the variable, or None if the node is not a variable
+    /// or its minimum sampling interval is not set or indeterminate
+    pub fn minimum_sampling_interval(&self, node_id: &NodeId) -> Option<Double> {
+        if let Some(&NodeType::Variable(ref variable)) = self.find_node(node_id) {
+            variable.minimum_sampling_interval()
+        } else {
+            None
         }
     }
 
@@ -415,7 +747,91 @@ impl AddressSpace {
         self.set_value_by_node_id(&variable_id.as_node_id(), value)
     }
 
-    fn reference_type_matches(&self, r1: ReferenceTypeId, r2: ReferenceTypeId, include_subtypes: bool) -> bool {
+    /// Tests if the node is an object or view whose EventNotifier attribute allows clients to
+    /// subscribe to events
+    pub fn is_event_notifier(&self, node_id: &NodeId) -> bool {
+        let event_notifier = match self.find_node(node_id) {
+            Some(&NodeType::Object(ref node)) => node.find_attribute(AttributeId::EventNotifier),
+            Some(&NodeType::View(ref node)) => node.find_attribute(AttributeId::EventNotifier),
+            _ => None
+        };
+        match event_notifier.and_then(|v| v.value) {
+            Some(Variant::Byte(event_notifier)) => event_notifier & EVENT_NOTIFIER_SUBSCRIBE_TO_EVENTS != 0,
+            _ => false
+        }
+    }
+
+    /// Tests if events raised by the source node are reported by the notifier node. A node is the
+    /// notifier of its own events, the Server object is the notifier of all events and any other
+    /// node is the notifier of the nodes it reaches through HasEventSource (or HasNotifier) references.
+    pub fn is_event_notifier_for(&self, notifier_id: &NodeId, source_id: &NodeId) -> bool {
+        if notifier_id == source_id || *notifier_id == ObjectId::Server.as_node_id() {
+            return true;
+        }
+        let mut visited = HashSet::new();
+        let mut to_visit = vec![notifier_id.clone()];
+        while let Some(node_id) = to_visit.pop() {
+            if let Some(references) = self.find_references_from(&node_id, Some((ReferenceTypeId::HasEventSource, true))) {
+                for reference in references {
+                    if reference.node_id == *source_id {
+                        return true;
+                    }
+                    if visited.insert(reference.node_id.clone()) {
+                        to_visit.push(reference.node_id);
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    /// Tests if the type is the base type or a subtype of it by following HasSubtype references
+    /// from the type back towards the base type. Every supertype is followed and each type is
+    /// visited once, so a hierarchy with loops in it cannot hang the search.
+    pub fn is_subtype(&self, type_id: &NodeId, base_type_id: &NodeId) -> bool {
+        let mut visited = HashSet::new();
+        let mut type_ids = vec![type_id.clone()];
+        while let Some(type_id) = type_ids.pop() {
+            if type_id == *base_type_id {
+                return true;
+            }
+            if let Some(references) = self.find_references_to(&type_id, Some((ReferenceTypeId::HasSubtype, false))) {
+                type_ids.extend(references.into_iter().map(|r| r.node_id).filter(|node_id| !visited.contains(node_id)));
+            }
+            visited.insert(type_id);
+        }
+        false
+    }
+
+    /// Raises an event which is picked up by event monitored items whose notifier reports events
+    /// from the event's source node. The event type must be BaseEventType or a subtype of it and
+    /// the source node must exist.
+    pub fn raise_event(&mut self, event: Event) -> Result<(), StatusCode> {
+        if !self.is_subtype(&event.event_type, &ObjectTypeId::BaseEventType.as_node_id()) {
+            return Err(BAD_TYPE_DEFINITION_INVALID);
+        }
+        if !self.node_exists(&event.source_node) {
+            return Err(BAD_SOURCE_NODE_ID_INVALID);
+        }
+        self.last_event_index += 1;
+        if self.events.len() == constants::MAX_BUFFERED_EVENTS {
+            let _ = self.events.pop_front();
+        }
+        self.events.push_back((self.last_event_index, event));
+        Ok(())
+    }
+
+    /// Returns the index of the most recently raised event
+    pub fn last_event_index(&self) -> usize {
+        self.last_event_index
+    }
+
+    /// Returns the events raised after the specified event index, oldest first
+    pub fn events_since(&self, event_index: usize) -> Vec<&(usize, Event)> {
+        self.events.iter().filter(|e| e.0 > event_index).collect()
+    }
+
+    pub fn reference_type_matches(&self, r1: ReferenceTypeId, r2: ReferenceTypeId, include_subtypes: bool) -> bool {
         if r1 == r2 {
             true
         } else if include_subtypes {
@@ -558,6 +974,79 @@ impl AddressSpace {
         AddressSpace::add_reference(&mut self.inverse_references, node_id_to, Reference::new(reference_type_id, node_id_from));
     }
 
+    /// Tests if a reference of the specified type exists from one node to another
+    pub fn has_reference(&self, node_id_from: &NodeId, node_id_to: &NodeId, reference_type_id: ReferenceTypeId) -> bool {
+        if let Some(references) = self.references.get(node_id_from) {
+            references.iter().any(|r| r.reference_type_id == reference_type_id && r.node_id == *node_id_to)
+        } else {
+            false
+        }
+    }
+
+    /// Deletes a reference of the specified type from one node to another. Returns true if the
+    /// reference existed.
+    pub fn delete_reference(&mut self, node_id_from: &NodeId, node_id_to: &NodeId, reference_type_id: ReferenceTypeId) -> bool {
+        let found = AddressSpace::remove_reference(&mut self.references, node_id_from, node_id_to, reference_type_id);
+        AddressSpace::remove_reference(&mut self.inverse_references, node_id_to, node_id_from, reference_type_id);
+        found
+    }
+
+    /// Deletes a node, the references from it and the components and properties that it owns,
+    /// i.e. those that no other node holds as a component or property. If
+    /// `delete_target_references` is true then references from other nodes to this node are
+    /// deleted too, otherwise they are left to dangle. Returns true if the node existed.
+    pub fn delete_node(&mut self, node_id: &NodeId, delete_target_references: bool) -> bool {
+        if self.node_map.remove(node_id).is_none() {
+            return false;
+        }
+        let mut owned_node_ids = Vec::new();
+        if let Some(references) = self.references.remove(node_id) {
+            for r in references {
+                AddressSpace::remove_reference(&mut self.inverse_references, &r.node_id, node_id, r.reference_type_id);
+                if AddressSpace::is_owning_reference(r.reference_type_id) {
+                    owned_node_ids.push(r.node_id);
+                }
+            }
+        }
+        // The references to the node are forgotten from its side either way
+        if let Some(references) = self.inverse_references.remove(node_id) {
+            if delete_target_references {
+                for r in references {
+                    AddressSpace::remove_reference(&mut self.references, &r.node_id, node_id, r.reference_type_id);
+                }
+            }
+        }
+        for owned_node_id in owned_node_ids {
+            let has_other_owner = self.inverse_references.get(&owned_node_id).map_or(false, |references| {
+                references.iter().any(|r| AddressSpace::is_owning_reference(r.reference_type_id))
+            });
+            if !has_other_owner {
+                self.delete_node(&owned_node_id, delete_target_references);
+            }
+        }
+        true
+    }
+
+    /// Tests if the reference makes its source the owner of its target, so the target is deleted
+    /// with the source
+    fn is_owning_reference(reference_type_id: ReferenceTypeId) -> bool {
+        match reference_type_id {
+            ReferenceTypeId::HasComponent | ReferenceTypeId::HasOrderedComponent | ReferenceTypeId::HasProperty => true,
+            _ => false
+        }
+    }
+
+    /// Removes a reference held by a node in the reference map
+    fn remove_reference(reference_map: &mut HashMap<NodeId, Vec<Reference>>, node_id: &NodeId, target_node_id: &NodeId, reference_type_id: ReferenceTypeId) -> bool {
+        if let Some(references) = reference_map.get_mut(node_id) {
+            let len = references.len();
+            references.retain(|r| !(r.reference_type_id == reference_type_id && r.node_id == *target_node_id));
+            references.len() != len
+        } else {
+            false
+        }
+    }
+
     pub fn set_object_type(&mut self, node_id: &NodeId, object_type: &ObjectTypeId) {
         self.insert_reference(node_id, &object_type.as_node_id(), ReferenceTypeId::HasTypeDefinition);
     }
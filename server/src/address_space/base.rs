@@ -99,6 +99,16 @@ impl Node for Base {
             self.attributes[attribute_idx].clone()
         }
     }
+
+    fn set_attribute(&mut self, attribute_id: AttributeId, value: DataValue) {
+        if let Some(setter) = self.attribute_setters.get(&attribute_id) {
+            let mut setter = setter.lock().unwrap();
+            setter.set(self.node_id(), attribute_id, value);
+        } else {
+            let attribute_idx = Base::attribute_idx(attribute_id);
+            self.attributes[attribute_idx] = Some(value);
+        }
+    }
 }
 
 impl Base {
@@ -142,18 +152,12 @@ impl Base {
         self.attribute_getters.insert(attribute_id, getter);
     }
 
-    pub fn set_attribute_setter(&mut self, attribute_id: AttributeId, setter: Arc<Mutex<AttributeSetter + Send>>) {
-        self.attribute_setters.insert(attribute_id, setter);
+    pub fn has_attribute_getter(&self, attribute_id: AttributeId) -> bool {
+        self.attribute_getters.contains_key(&attribute_id)
     }
 
-    pub fn set_attribute(&mut self, attribute_id: AttributeId, value: DataValue) {
-        let attribute_idx = Base::attribute_idx(attribute_id);
-        if let Some(setter) = self.attribute_setters.get(&attribute_id) {
-            let mut setter = setter.lock().unwrap();
-            setter.set(self.node_id(), attribute_id, value);
-        } else {
-            self.attributes[attribute_idx] = Some(value);
-        }
+    pub fn set_attribute_setter(&mut self, attribute_id: AttributeId, setter: Arc<Mutex<AttributeSetter + Send>>) {
+        self.attribute_setters.insert(attribute_id, setter);
     }
 
     pub fn set_attribute_value(&mut self, attribute_id: AttributeId, value: Variant, server_timestamp: &DateTime, source_timestamp: &DateTime) {
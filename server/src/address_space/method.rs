@@ -1,9 +1,22 @@
+use std;
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Mutex};
+
 use address_space::base::Base;
 use address_space::node::{Node, NodeType};
+use address_space::MethodCallback;
 
-#[derive(Debug)]
 pub struct Method {
     base: Base,
+    /// The callback that is invoked when the method is called
+    callback: Option<Arc<Mutex<MethodCallback + Send>>>,
+}
+
+impl Debug for Method {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        // The callback is not debuggable
+        write!(f, "Method {{ base: {:?} }}", self.base)
+    }
 }
 
 node_impl!(Method);
@@ -22,6 +35,7 @@ impl Method {
         ];
         Method {
             base: Base::new(NodeClass::Method, node_id, browse_name, display_name, description, attributes),
+            callback: None,
         }
     }
 
@@ -36,4 +50,20 @@ impl Method {
     pub fn user_executable(&self) -> Boolean {
         find_attribute_value_mandatory!(&self.base, UserExecutable, Boolean)
     }
+
+    /// Sets the callback that is invoked when a client calls the method
+    pub fn set_callback(&mut self, callback: Arc<Mutex<MethodCallback + Send>>) {
+        self.callback = Some(callback);
+    }
+
+    /// Calls the method on the specified object, returning the output arguments. A method without
+    /// a callback is not implemented.
+    pub fn call(&self, object_id: &NodeId, input_arguments: &[Variant]) -> Result<Vec<Variant>, StatusCode> {
+        if let Some(ref callback) = self.callback {
+            let mut callback = callback.lock().unwrap();
+            callback.call(object_id, input_arguments)
+        } else {
+            Err(BAD_NOT_IMPLEMENTED)
+        }
+    }
 }
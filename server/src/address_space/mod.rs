@@ -2,7 +2,7 @@
 /// member.
 ///
 
-use opcua_types::{NodeId, AttributeId, DataValue};
+use opcua_types::{NodeId, AttributeId, DataValue, Variant, StatusCode};
 
 /// An attribute getter is used to obtain the datavalue associated with the particular attribute id
 pub trait AttributeGetter {
@@ -45,6 +45,30 @@ impl<F> AttrFnSetter<F> where F: FnMut(NodeId, AttributeId, DataValue) + Send {
     pub fn new(setter: F) -> AttrFnSetter<F> { AttrFnSetter { setter } }
 }
 
+/// A method callback is invoked when a client calls a method. It receives the id of the object
+/// the method is called on and the input arguments and returns the output arguments.
+///
+/// The address space is locked while the callback runs so it must not try to lock it again.
+pub trait MethodCallback {
+    /// Calls the method, returning the output arguments or a status code on failure
+    fn call(&mut self, object_id: &NodeId, input_arguments: &[Variant]) -> Result<Vec<Variant>, StatusCode>;
+}
+
+/// An implementation of method callback that can be easily constructed using a mutable function
+pub struct MethodFnCallback<F> where F: FnMut(&NodeId, &[Variant]) -> Result<Vec<Variant>, StatusCode> + Send {
+    callback: F
+}
+
+impl<F> MethodCallback for MethodFnCallback<F> where F: FnMut(&NodeId, &[Variant]) -> Result<Vec<Variant>, StatusCode> + Send {
+    fn call(&mut self, object_id: &NodeId, input_arguments: &[Variant]) -> Result<Vec<Variant>, StatusCode> {
+        (self.callback)(object_id, input_arguments)
+    }
+}
+
+impl<F> MethodFnCallback<F> where F: FnMut(&NodeId, &[Variant]) -> Result<Vec<Variant>, StatusCode> + Send {
+    pub fn new(callback: F) -> MethodFnCallback<F> { MethodFnCallback { callback } }
+}
+
 macro_rules! node_impl {
     ( $node_struct:ty ) => {
         use opcua_types::*;
@@ -57,6 +81,7 @@ macro_rules! node_impl {
             fn write_mask(&self) -> Option<UInt32> { self.base.write_mask() }
             fn user_write_mask(&self) -> Option<UInt32> { self.base.user_write_mask() }
             fn find_attribute(&self, attribute_id: AttributeId) -> Option<DataValue> { self.base.find_attribute(attribute_id) }
+            fn set_attribute(&mut self, attribute_id: AttributeId, value: DataValue) { self.base.set_attribute(attribute_id, value) }
         }
     };
 }
@@ -111,7 +136,7 @@ pub mod data_type;
 pub mod view;
 
 pub mod types {
-    pub use super::{AttrFnGetter, AttrFnSetter};
+    pub use super::{AttrFnGetter, AttrFnSetter, MethodFnCallback};
     pub use super::address_space::AddressSpace;
     pub use super::data_type::DataType;
     pub use super::object::Object;
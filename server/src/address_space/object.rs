@@ -23,7 +23,12 @@ impl Object {
         }
     }
 
-    pub fn event_notifier(&self) -> Boolean {
-        find_attribute_value_mandatory!(&self.base, EventNotifier, Boolean)
+    pub fn event_notifier(&self) -> Byte {
+        find_attribute_value_mandatory!(&self.base, EventNotifier, Byte)
+    }
+
+    pub fn set_event_notifier(&mut self, event_notifier: Byte) {
+        let now = DateTime::now();
+        self.base.set_attribute_value(AttributeId::EventNotifier, Variant::Byte(event_notifier), &now, &now);
     }
 }
\ No newline at end of file
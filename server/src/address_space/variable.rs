@@ -6,18 +6,18 @@ use address_space::base::Base;
 use address_space::node::{Node, NodeType};
 use address_space::{AttributeGetter, AttributeSetter};
 
-const ACCESS_LEVEL_CURRENT_READ: Byte = 1;
-const ACCESS_LEVEL_CURRENT_WRITE: Byte = 1 << 1;
-//const ACCESS_LEVEL_HISTORY_READ: Byte = 1 << 2;
-//const ACCESS_LEVEL_HISTORY_WRITE: Byte = 1 << 3;
+pub const ACCESS_LEVEL_CURRENT_READ: Byte = 1;
+pub const ACCESS_LEVEL_CURRENT_WRITE: Byte = 1 << 1;
+pub const ACCESS_LEVEL_HISTORY_READ: Byte = 1 << 2;
+pub const ACCESS_LEVEL_HISTORY_WRITE: Byte = 1 << 3;
 //const ACCESS_LEVEL_SEMANTIC_CHANGE: Byte = 1 << 4;
 //const ACCESS_LEVEL_STATUS_WRITE: Byte = 1 << 5;
 //const ACCESS_LEVEL_TIMESTAMP_WRITE: Byte = 1 << 6;
 
-const USER_ACCESS_LEVEL_CURRENT_READ: Byte = 1;
-const USER_ACCESS_LEVEL_CURRENT_WRITE: Byte = 1 << 1;
-//const USER_ACCESS_LEVEL_HISTORY_READ: Byte = 1 << 2;
-//const USER_ACCESS_LEVEL_HISTORY_WRITE: Byte = 1 << 3;
+pub const USER_ACCESS_LEVEL_CURRENT_READ: Byte = 1;
+pub const USER_ACCESS_LEVEL_CURRENT_WRITE: Byte = 1 << 1;
+pub const USER_ACCESS_LEVEL_HISTORY_READ: Byte = 1 << 2;
+pub const USER_ACCESS_LEVEL_HISTORY_WRITE: Byte = 1 << 3;
 //const USER_ACCESS_LEVEL_STATUS_WRITE: Byte = 1 << 5;
 //const USER_ACCESS_LEVEL_TIMESTAMP_WRITE: Byte = 1 << 6;
 
@@ -131,6 +131,11 @@ impl Variable {
         self.base.set_attribute_getter(AttributeId::Value, getter);
     }
 
+    /// Tests if the value is supplied by a getter rather than held by the variable
+    pub fn has_value_getter(&self) -> bool {
+        self.base.has_attribute_getter(AttributeId::Value)
+    }
+
     pub fn set_value_setter(&mut self, setter: Arc<Mutex<AttributeSetter + Send>>) {
         self.base.set_attribute_setter(AttributeId::Value, setter);
     }
@@ -156,6 +161,17 @@ impl Variable {
         self.base.set_attribute_value(AttributeId::MinimumSamplingInterval, Variant::Int32(minimum_sampling_interval), &now, &now);
     }
 
+    /// Returns the minimum sampling interval in milliseconds, or None if it is not set or is
+    /// indeterminate
+    pub fn minimum_sampling_interval(&self) -> Option<Double> {
+        let minimum_sampling_interval = match self.base.find_attribute(AttributeId::MinimumSamplingInterval).and_then(|v| v.value) {
+            Some(Variant::Int32(v)) => v as Double,
+            Some(Variant::Double(v)) => v,
+            _ => return None
+        };
+        if minimum_sampling_interval < 0f64 { None } else { Some(minimum_sampling_interval) }
+    }
+
     /// Sets the variables value directly, updating the timestamp
     pub fn set_value_direct(&mut self, now: &DateTime, value: Variant) {
         let mut data_value = self.value();
@@ -183,6 +199,35 @@ impl Variable {
         (self.user_access_level() & USER_ACCESS_LEVEL_CURRENT_WRITE) != 0
     }
 
+    pub fn is_history_readable(&self) -> bool {
+        (self.access_level() & ACCESS_LEVEL_HISTORY_READ) != 0
+    }
+
+    pub fn is_user_history_readable(&self) -> bool {
+        (self.user_access_level() & USER_ACCESS_LEVEL_HISTORY_READ) != 0
+    }
+
+    pub fn is_history_writable(&self) -> bool {
+        (self.access_level() & ACCESS_LEVEL_HISTORY_WRITE) != 0
+    }
+
+    pub fn is_user_history_writable(&self) -> bool {
+        (self.user_access_level() & USER_ACCESS_LEVEL_HISTORY_WRITE) != 0
+    }
+
+    /// Sets the access level, e.g. `ACCESS_LEVEL_CURRENT_READ | ACCESS_LEVEL_CURRENT_WRITE` to
+    /// make the value writable
+    pub fn set_access_level(&mut self, access_level: Byte) {
+        let now = DateTime::now();
+        self.base.set_attribute_value(AttributeId::AccessLevel, Variant::Byte(access_level), &now, &now);
+    }
+
+    /// Sets the user access level
+    pub fn set_user_access_level(&mut self, user_access_level: Byte) {
+        let now = DateTime::now();
+        self.base.set_attribute_value(AttributeId::UserAccessLevel, Variant::Byte(user_access_level), &now, &now);
+    }
+
     pub fn access_level(&self) -> Byte {
         find_attribute_value_mandatory!(&self.base, AccessLevel, Byte)
     }
@@ -195,7 +240,19 @@ impl Variable {
         find_attribute_value_mandatory!(&self.base, ValueRank, Int32)
     }
 
+    pub fn data_type(&self) -> NodeId {
+        let result = find_attribute_value_mandatory!(&self.base, DataType, NodeId);
+        result.as_ref().clone()
+    }
+
     pub fn historizing(&self) -> Boolean {
         find_attribute_value_mandatory!(&self.base, Historizing, Boolean)
     }
+
+    /// Sets whether the server records the history of the variable's value. The access level
+    /// must also allow history to be read for clients to read it.
+    pub fn set_historizing(&mut self, historizing: Boolean) {
+        let now = DateTime::now();
+        self.base.set_attribute_value(AttributeId::Historizing, Variant::Boolean(historizing), &now, &now);
+    }
 }
\ No newline at end of file
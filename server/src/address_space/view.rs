@@ -11,10 +11,10 @@ node_impl!(View);
 // NodeClass::View
 
 impl View {
-    pub fn new(node_id: &NodeId, browse_name: &str, display_name: &str, description: &str, event_notifier: Boolean, contains_no_loops: Boolean) -> View {
+    pub fn new(node_id: &NodeId, browse_name: &str, display_name: &str, description: &str, event_notifier: Byte, contains_no_loops: Boolean) -> View {
         // Mandatory
         let attributes = vec![
-            (AttributeId::EventNotifier, Variant::Boolean(event_notifier)),
+            (AttributeId::EventNotifier, Variant::Byte(event_notifier)),
             (AttributeId::ContainsNoLoops, Variant::Boolean(contains_no_loops)),
         ];
         View {
@@ -22,8 +22,13 @@ impl View {
         }
     }
 
-    pub fn event_notifier(&self) -> Boolean {
-        find_attribute_value_mandatory!(&self.base, EventNotifier, Boolean)
+    pub fn event_notifier(&self) -> Byte {
+        find_attribute_value_mandatory!(&self.base, EventNotifier, Byte)
+    }
+
+    pub fn set_event_notifier(&mut self, event_notifier: Byte) {
+        let now = DateTime::now();
+        self.base.set_attribute_value(AttributeId::EventNotifier, Variant::Byte(event_notifier), &now, &now);
     }
 
     pub fn contains_no_loops(&self) -> Boolean {
@@ -114,6 +114,11 @@ impl TcpTransport {
 
         let mut message_buffer = MessageBuffer::new(RECEIVE_BUFFER_SIZE);
 
+        let diagnostics = {
+            let server_state = self.server_state.lock().unwrap();
+            server_state.diagnostics.clone()
+        };
+
         loop {
             // Check for abort
             {
@@ -135,6 +140,25 @@ impl TcpTransport {
                 }
             }
 
+            // Session is closed if the client makes no requests within the session timeout
+            {
+                let mut server_state = self.server_state.lock().unwrap();
+                let mut session = self.session.lock().unwrap();
+                if session.is_timed_out(&now) {
+                    error!("Session {:?} timed out after {} ms of inactivity", session.session_id, session.session_timeout);
+                    let mut diagnostics = diagnostics.lock().unwrap();
+                    diagnostics.on_session_timeout();
+                    diagnostics.on_destroy_session(&session.session_id);
+                    server_state.session_manager.deregister_session(&session.authentication_token);
+                    server_state.subscriptions.remove_session(&session.session_id);
+                    session.session_id = NodeId::null();
+                    session.authentication_token = NodeId::null();
+                    session.activated = false;
+                    session_status_code = BAD_TIMEOUT;
+                    break;
+                }
+            }
+
             // Process subscription timer events
             if let Ok(result) = subscription_timer_rx.try_recv() {
                 match result {
@@ -221,6 +245,28 @@ impl TcpTransport {
         drop(subscription_timer_guard);
         drop(subscription_timer);
 
+        // A session that was never closed by the client is detached from the connection so the
+        // client may reactivate it on a new one before it times out. If the server is aborting
+        // then so is the session.
+        {
+            let mut server_state = self.server_state.lock().unwrap();
+            let mut session = self.session.lock().unwrap();
+            if session.is_created() {
+                if server_state.abort {
+                    info!("Session {:?} aborted because the server is aborting", session.session_id);
+                    let mut diagnostics = diagnostics.lock().unwrap();
+                    diagnostics.on_session_abort();
+                    diagnostics.on_destroy_session(&session.session_id);
+                    server_state.session_manager.deregister_session(&session.authentication_token);
+                    server_state.subscriptions.remove_session(&session.session_id);
+                    session.session_id = NodeId::null();
+                } else {
+                    info!("Session {:?} is detached because its connection is terminating", session.session_id);
+                    session.detached = true;
+                }
+            }
+        }
+
         // As a final act, the session sends a status code to the client if one should be sent
         if session_status_code == GOOD || session_status_code == BAD_CONNECTION_CLOSED {
             warn!("Sending session terminating error {:?}", session_status_code);
@@ -254,7 +300,7 @@ impl TcpTransport {
         // so it can control the scope of events.
         let subscription_timer = timer::Timer::new();
         let subscription_timer_guard = subscription_timer.schedule_repeating(time::Duration::milliseconds(constants::SUBSCRIPTION_TIMER_RATE_MS), move || {
-            let server_state = server_state.lock().unwrap();
+            let mut server_state = server_state.lock().unwrap();
             let mut session = session.lock().unwrap();
 
             // Request queue might contain stale publish requests
@@ -262,9 +308,14 @@ impl TcpTransport {
                 let _ = subscription_timer_tx.send(SubscriptionEvent::PublishResponses(publish_responses));
             }
 
+            // Publish requests cancelled by the client are answered
+            if let Some(publish_responses) = session.take_cancelled_publish_responses() {
+                let _ = subscription_timer_tx.send(SubscriptionEvent::PublishResponses(publish_responses));
+            }
+
             // Process subscriptions
             {
-                if let Some(publish_responses) = session.tick_subscriptions(&server_state, false) {
+                if let Some(publish_responses) = session.tick_subscriptions(&mut server_state, false) {
                     trace!("Sending publish responses to session thread");
                     let sent = subscription_timer_tx.send(SubscriptionEvent::PublishResponses(publish_responses));
                     if sent.is_err() {
@@ -375,7 +426,9 @@ impl TcpTransport {
         let message = self.turn_received_chunks_into_message(&in_chunks)?;
         let response = match message_header.message_type {
             MessageChunkType::OpenSecureChannel => {
-                self.secure_channel_service.open_secure_channel(&chunk_info.security_header, self.client_protocol_version, &message)?
+                let response = self.secure_channel_service.open_secure_channel(&chunk_info.security_header, self.client_protocol_version, &message)?;
+                self.message_handler.set_secure_channel(&self.secure_channel_service.secure_channel);
+                response
             }
             MessageChunkType::CloseSecureChannel => {
                 self.secure_channel_service.close_secure_channel(&message)?
@@ -8,7 +8,7 @@ use std::str::FromStr;
 
 use std::result::Result;
 
-use opcua_types::MessageSecurityMode;
+use opcua_types::{MessageSecurityMode, is_opc_ua_binary_url};
 use opcua_types::constants as opcua_types_constants;
 
 use opcua_core::crypto::SecurityPolicy;
@@ -40,6 +40,8 @@ pub struct ServerEndpoint {
     /// Allow user name / password access
     pub user: Option<String>,
     pub pass: Option<String>,
+    /// Allow clients of this endpoint to add and delete nodes and references (default false)
+    pub node_management: Option<bool>,
 }
 
 const DEFAULT_ENDPOINT_NAME: &'static str = "Default";
@@ -55,6 +57,7 @@ impl ServerEndpoint {
             pass: if user.is_empty() { None } else { Some(String::from_utf8(pass.to_vec()).unwrap()) },
             security_policy: security_policy.to_string(),
             security_mode: security_mode.to_string(),
+            node_management: None,
         }
     }
 
@@ -141,14 +144,41 @@ pub struct ServerConfig {
     /// Autocreates public / private keypair if they don't exist. For testing/samples only
     /// since you do not have control of the values
     pub create_sample_keypair: bool,
-    /// Flag turns on or off discovery service
+    /// Flag turns on or off the FindServers discovery service. GetEndpoints is always available
+    /// since clients need it to connect.
     pub discovery_service: bool,
+    /// Runs the server as a local discovery server (LDS) that other servers on the machine
+    /// register with and that answers FindServers and FindServersOnNetwork on their behalf
+    pub local_discovery_server: bool,
+    /// Urls of the discovery servers that the server registers itself with
+    pub discovery_server_urls: Vec<String>,
+    /// Interval in seconds between registrations with the discovery servers
+    pub discovery_registration_interval: u32,
+    /// Announces the endpoints of the server through multicast DNS (mDNS) so clients on the
+    /// local network can find it
+    pub multicast_discovery: bool,
     /// tcp configuration information
     pub tcp_config: TcpConfig,
     /// Endpoints supported by the server
     pub endpoints: Vec<ServerEndpoint>,
+    /// Maximum number of sessions
+    pub max_sessions: u32,
     /// Maximum number of subscriptions in a session
     pub max_subscriptions: u32,
+    /// Maximum number of publish requests queued in a session
+    pub max_publish_requests: u32,
+    /// Minimum sampling interval in milliseconds of monitored items
+    pub min_sampling_interval: f64,
+    /// Maximum sampling interval in milliseconds of monitored items, 0 means no limit
+    pub max_sampling_interval: f64,
+    /// Maximum queue size of data change monitored items
+    pub max_data_change_queue_size: u32,
+    /// Maximum queue size of event monitored items
+    pub max_event_queue_size: u32,
+    /// Maximum number of browse continuation points in a session
+    pub max_browse_continuation_points: u32,
+    /// Maximum number of history continuation points in a session
+    pub max_history_continuation_points: u32,
     /// Max array length in elements
     pub max_array_length: u32,
     /// Max string length in characters
@@ -177,6 +207,10 @@ impl ServerConfig {
             application_uri,
             product_uri,
             discovery_service: true,
+            local_discovery_server: false,
+            discovery_server_urls: Vec::new(),
+            discovery_registration_interval: constants::DEFAULT_DISCOVERY_REGISTRATION_INTERVAL_SECONDS,
+            multicast_discovery: false,
             pki_dir: pki_dir.into_os_string().into_string().unwrap(),
             create_sample_keypair: false,
             tcp_config: TcpConfig {
@@ -188,7 +222,15 @@ impl ServerConfig {
             max_array_length: opcua_types_constants::MAX_ARRAY_LENGTH,
             max_string_length: opcua_types_constants::MAX_STRING_LENGTH,
             max_byte_string_length: opcua_types_constants::MAX_BYTE_STRING_LENGTH,
+            max_sessions: constants::DEFAULT_MAX_SESSIONS,
             max_subscriptions: constants::DEFAULT_MAX_SUBSCRIPTIONS,
+            max_publish_requests: constants::DEFAULT_MAX_PUBLISH_REQUESTS,
+            min_sampling_interval: constants::MIN_SAMPLING_INTERVAL,
+            max_sampling_interval: constants::DEFAULT_MAX_SAMPLING_INTERVAL,
+            max_data_change_queue_size: constants::MAX_DATA_CHANGE_QUEUE_SIZE as u32,
+            max_event_queue_size: constants::MAX_EVENT_QUEUE_SIZE as u32,
+            max_browse_continuation_points: constants::DEFAULT_MAX_BROWSE_CONTINUATION_POINTS,
+            max_history_continuation_points: constants::DEFAULT_MAX_HISTORY_CONTINUATION_POINTS,
         }
     }
 
@@ -205,6 +247,23 @@ impl ServerConfig {
         ServerConfig::default(vec![ServerEndpoint::default_basic128rsa15_sign_encrypt()])
     }
 
+    /// Returns the default configuration of a local discovery server, listening on the well known
+    /// discovery port. Clients find servers anonymously while servers register through the signed
+    /// endpoint, so the sample certificate and private key are created if they do not exist.
+    pub fn default_local_discovery_server() -> ServerConfig {
+        let mut config = ServerConfig::default(vec![
+            ServerEndpoint::default_anonymous(),
+            ServerEndpoint::default_basic256sha256_sign(),
+        ]);
+        config.application_name = "OPCUA-Rust-LDS".to_string();
+        config.application_uri = format!("urn:{}", config.application_name);
+        config.product_uri = format!("urn:{}", config.application_name);
+        config.tcp_config.port = constants::DEFAULT_OPC_UA_DISCOVERY_SERVER_PORT;
+        config.local_discovery_server = true;
+        config.create_sample_keypair = true;
+        config
+    }
+
     /// Sample mode turns on everything including a hard coded user/pass
     pub fn default_sample() -> ServerConfig {
         let mut config = ServerConfig::default(vec![
@@ -268,6 +327,28 @@ impl ServerConfig {
             error!("Server configuration is invalid.  Max byte string length is invalid");
             valid = false;
         }
+        if self.min_sampling_interval < 0f64 || (self.max_sampling_interval != 0f64 && self.max_sampling_interval < self.min_sampling_interval) {
+            error!("Server configuration is invalid.  Min / max sampling interval is invalid");
+            valid = false;
+        }
+        if self.local_discovery_server && !self.discovery_service {
+            error!("Server configuration is invalid.  A local discovery server requires the discovery service");
+            valid = false;
+        }
+        for url in &self.discovery_server_urls {
+            if !is_opc_ua_binary_url(url) {
+                error!("Server configuration is invalid.  Discovery server url {} is invalid", url);
+                valid = false;
+            }
+        }
+        if !self.discovery_server_urls.is_empty() && self.discovery_registration_interval == 0 {
+            error!("Server configuration is invalid.  Discovery registration interval is invalid");
+            valid = false;
+        }
+        if self.max_data_change_queue_size == 0 || self.max_event_queue_size == 0 {
+            error!("Server configuration is invalid.  Max data change or event queue size is invalid");
+            valid = false;
+        }
         valid
     }
 }
\ No newline at end of file
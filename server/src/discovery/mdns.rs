@@ -0,0 +1,86 @@
+use std::io;
+use std::net::{Ipv4Addr, SocketAddrV4, UdpSocket};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time;
+
+use opcua_core::comms::mdns::*;
+
+use constants;
+
+/// Announces the endpoints of the server through multicast DNS (mDNS) so clients on the local
+/// network can find it by browsing for the `_opcua-tcp._tcp` service. The endpoints are announced
+/// when the responder starts, in answer to queries for the service and, with a time to live of 0,
+/// when the responder is dropped so listeners forget them.
+///
+/// The responder must bind the mDNS port so it cannot run alongside another responder on the
+/// same machine, e.g. a system mDNS daemon.
+pub struct MdnsResponder {
+    socket: UdpSocket,
+    multicast_address: SocketAddrV4,
+    announcements: Vec<ServiceAnnouncement>,
+    running: Arc<AtomicBool>,
+}
+
+impl Drop for MdnsResponder {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        let buf = encode_announcements(0, &self.announcements, 0);
+        let _ = self.socket.send_to(&buf, self.multicast_address);
+    }
+}
+
+impl MdnsResponder {
+    /// Starts a responder that listens at the mDNS multicast address and port
+    pub fn new(announcements: Vec<ServiceAnnouncement>) -> io::Result<MdnsResponder> {
+        MdnsResponder::new_at(mdns_multicast_address(), announcements)
+    }
+
+    /// Starts a responder that listens at the multicast address and port
+    pub fn new_at(multicast_address: SocketAddrV4, announcements: Vec<ServiceAnnouncement>) -> io::Result<MdnsResponder> {
+        let any = Ipv4Addr::new(0, 0, 0, 0);
+        let socket = UdpSocket::bind(SocketAddrV4::new(any, multicast_address.port()))?;
+        socket.join_multicast_v4(multicast_address.ip(), &any)?;
+        // The listening thread wakes now and again to see if the responder has been dropped
+        socket.set_read_timeout(Some(time::Duration::from_millis(constants::MDNS_RECEIVE_TIMEOUT_MS)))?;
+
+        let ttl = constants::MDNS_ANNOUNCEMENT_TTL_SECONDS;
+        let buf = encode_announcements(0, &announcements, ttl);
+        socket.send_to(&buf, multicast_address)?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        {
+            let socket = socket.try_clone()?;
+            let announcements = announcements.clone();
+            let running = running.clone();
+            thread::spawn(move || {
+                let mut buf = [0u8; 9000];
+                while running.load(Ordering::Relaxed) {
+                    if let Ok((len, source)) = socket.recv_from(&mut buf) {
+                        let query = &buf[..len];
+                        if source.port() == multicast_address.port() {
+                            if decode_query(query).is_some() {
+                                debug!("Answering mDNS query from {}", source);
+                                let _ = socket.send_to(&encode_announcements(0, &announcements, ttl), multicast_address);
+                            }
+                        } else if let Some(answer) = encode_legacy_unicast_answer(query, &announcements, ttl) {
+                            // A querier that does not listen on the mDNS port is answered directly with its
+                            // query id and question
+                            debug!("Answering legacy unicast mDNS query from {}", source);
+                            let _ = socket.send_to(&answer, source);
+                        }
+                    }
+                }
+                debug!("mDNS responder has stopped");
+            });
+        }
+
+        Ok(MdnsResponder {
+            socket,
+            multicast_address,
+            announcements,
+            running,
+        })
+    }
+}
@@ -0,0 +1,7 @@
+//! The discovery module holds what a server needs to be found by clients besides the discovery
+//! services, i.e. acting as a local discovery server (LDS) for the other servers on its machine,
+//! registering itself with discovery servers and announcing itself through mDNS.
+
+pub mod registered_servers;
+pub mod registration;
+pub mod mdns;
@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use time;
+
+use opcua_types::*;
+
+use DateTimeUTC;
+
+/// A server that has registered itself with the local discovery server
+#[derive(Debug, Clone, PartialEq)]
+pub struct Registration {
+    /// Identifies the registration in the records returned by FindServersOnNetwork. It is kept
+    /// when the server renews its registration.
+    pub record_id: UInt32,
+    /// The server as it described itself when it last registered
+    pub server: RegisteredServer,
+    /// The mDNS configuration supplied through RegisterServer2, if any
+    pub mdns_configuration: Option<MdnsDiscoveryConfiguration>,
+    /// The time the server last registered
+    pub last_registration_time: DateTimeUTC,
+}
+
+impl Registration {
+    /// Returns a description of the registered server for a FindServers response. The server
+    /// name is the first whose locale is in the requested locales, or the first name when none is.
+    pub fn application_description(&self, locale_ids: &[UAString]) -> ApplicationDescription {
+        let server = &self.server;
+        let application_name = if let Some(ref server_names) = server.server_names {
+            server_names.iter().find(|name| locale_ids.contains(&name.locale))
+                .or(server_names.first())
+                .cloned()
+                .unwrap_or(LocalizedText::new("", ""))
+        } else {
+            LocalizedText::new("", "")
+        };
+        ApplicationDescription {
+            application_uri: server.server_uri.clone(),
+            product_uri: server.product_uri.clone(),
+            application_name,
+            application_type: server.server_type,
+            gateway_server_uri: server.gateway_server_uri.clone(),
+            discovery_profile_uri: UAString::null(),
+            discovery_urls: server.discovery_urls.clone(),
+        }
+    }
+
+    /// Returns the record of the registered server for a FindServersOnNetwork response. The
+    /// record holds the first discovery url of the server.
+    pub fn server_on_network(&self) -> ServerOnNetwork {
+        let server = &self.server;
+        let (server_name, server_capabilities) = if let Some(ref mdns_configuration) = self.mdns_configuration {
+            (mdns_configuration.mdns_server_name.clone(), mdns_configuration.server_capabilities.clone())
+        } else {
+            let server_name = server.server_names.as_ref()
+                .and_then(|server_names| server_names.first())
+                .map(|name| name.text.clone())
+                .unwrap_or(UAString::null());
+            (server_name, None)
+        };
+        let discovery_url = server.discovery_urls.as_ref()
+            .and_then(|discovery_urls| discovery_urls.first())
+            .cloned()
+            .unwrap_or(UAString::null());
+        ServerOnNetwork {
+            record_id: self.record_id,
+            server_name,
+            discovery_url,
+            server_capabilities: Some(server_capabilities.unwrap_or(Vec::new())),
+        }
+    }
+
+    /// Tests if the server has a semaphore file and the file has been removed
+    fn is_semaphore_file_missing(&self) -> bool {
+        let semaphore_file_path = self.server.semaphore_file_path.as_ref();
+        !semaphore_file_path.is_empty() && !Path::new(semaphore_file_path).exists()
+    }
+}
+
+/// The servers registered with a local discovery server through RegisterServer and
+/// RegisterServer2. A server must renew its registration within the registration timeout or it
+/// is removed, as it is when the server unregisters or its semaphore file is removed.
+pub struct RegisteredServers {
+    /// Registrations by server uri
+    registrations: BTreeMap<String, Registration>,
+    /// The last record id handed to a registration
+    last_record_id: UInt32,
+    /// The time that record ids started counting from
+    last_counter_reset_time: DateTime,
+    /// Time in MS that a registration lasts without being renewed
+    registration_timeout: i64,
+    /// The most registrations that are held at once
+    max_registrations: usize,
+}
+
+impl RegisteredServers {
+    pub fn new(registration_timeout: i64, max_registrations: usize) -> RegisteredServers {
+        RegisteredServers {
+            registrations: BTreeMap::new(),
+            last_record_id: 0,
+            last_counter_reset_time: DateTime::now(),
+            registration_timeout,
+            max_registrations,
+        }
+    }
+
+    /// Registers the server, renews its registration or, when the server says it is no longer
+    /// online, removes its registration. Returns a bad status code if the registration is invalid
+    /// or there are already as many registrations as are allowed.
+    pub fn register(&mut self, server: &RegisteredServer, mdns_configuration: Option<MdnsDiscoveryConfiguration>, now: &DateTimeUTC) -> StatusCode {
+        let result = RegisteredServers::validate(server);
+        if result.is_bad() {
+            return result;
+        }
+        let server_uri = server.server_uri.as_ref().to_string();
+        if !server.is_online {
+            debug!("Server {} has unregistered", server_uri);
+            self.registrations.remove(&server_uri);
+        } else if let Some(registration) = self.registrations.get_mut(&server_uri) {
+            debug!("Server {} has renewed its registration", server_uri);
+            registration.server = server.clone();
+            registration.mdns_configuration = mdns_configuration;
+            registration.last_registration_time = *now;
+        } else if self.registrations.len() >= self.max_registrations {
+            warn!("Server {} cannot register because there are already {} registrations", server_uri, self.registrations.len());
+            return BAD_TOO_MANY_OPERATIONS;
+        } else {
+            debug!("Server {} has registered", server_uri);
+            self.last_record_id += 1;
+            let registration = Registration {
+                record_id: self.last_record_id,
+                server: server.clone(),
+                mdns_configuration,
+                last_registration_time: *now,
+            };
+            self.registrations.insert(server_uri, registration);
+        }
+        GOOD
+    }
+
+    /// Removes registrations that were not renewed within the registration timeout and those
+    /// whose semaphore file has been removed. Returns the number of registrations removed.
+    pub fn expire(&mut self, now: &DateTimeUTC) -> usize {
+        let registration_timeout = time::Duration::milliseconds(self.registration_timeout);
+        let count = self.registrations.len();
+        self.registrations.retain(|server_uri, registration| {
+            if now.signed_duration_since(registration.last_registration_time) > registration_timeout {
+                info!("Registration of server {} has expired", server_uri);
+                false
+            } else if registration.is_semaphore_file_missing() {
+                info!("Registration of server {} is removed because its semaphore file has gone", server_uri);
+                false
+            } else {
+                true
+            }
+        });
+        count - self.registrations.len()
+    }
+
+    /// Returns the registrations ordered by server uri
+    pub fn registrations(&self) -> Vec<&Registration> {
+        self.registrations.values().collect()
+    }
+
+    /// Returns the time that record ids started counting from
+    pub fn last_counter_reset_time(&self) -> DateTime {
+        self.last_counter_reset_time.clone()
+    }
+
+    /// Tests that a server supplied everything a registration needs
+    fn validate(server: &RegisteredServer) -> StatusCode {
+        let semaphore_file_path = server.semaphore_file_path.as_ref();
+        if server.server_uri.as_ref().is_empty() {
+            BAD_SERVER_URI_INVALID
+        } else if server.server_names.as_ref().map_or(true, |server_names| server_names.is_empty()) {
+            BAD_SERVER_NAME_MISSING
+        } else if server.discovery_urls.as_ref().map_or(true, |discovery_urls| discovery_urls.is_empty()) {
+            BAD_DISCOVERY_URL_MISSING
+        } else if server.server_type == ApplicationType::Client {
+            BAD_INVALID_ARGUMENT
+        } else if server.is_online && !semaphore_file_path.is_empty() && !Path::new(semaphore_file_path).exists() {
+            BAD_SEMPAHORE_FILE_MISSING
+        } else {
+            GOOD
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::sync::{Arc, Mutex};
+
+use opcua_types::*;
+use opcua_core::crypto::{SecurityPolicy, CertificateStore};
+use opcua_client::session::Session as ClientSession;
+
+use server::ServerState;
+
+/// Registers the server with each of the discovery servers in its configuration. A server that is
+/// going away registers with is_online set to false so the discovery servers forget it. Failures
+/// are logged since the next registration may succeed.
+pub fn register_with_discovery_servers(server_state: &Arc<Mutex<ServerState>>, is_online: bool) {
+    // The server state is not locked while talking to the discovery servers
+    let (discovery_server_urls, registered_server, certificate_store) = {
+        let server_state = server_state.lock().unwrap();
+        let config = server_state.config.lock().unwrap();
+        (config.discovery_server_urls.clone(), server_state.registered_server(is_online), server_state.certificate_store.clone())
+    };
+    for discovery_server_url in &discovery_server_urls {
+        let result = register_with_discovery_server(certificate_store.clone(), discovery_server_url, &registered_server);
+        if let Err(err) = result {
+            warn!("Server could not register with discovery server {}, error = {:?}", discovery_server_url, err);
+        } else {
+            debug!("Server has registered with discovery server {}, is_online = {}", discovery_server_url, is_online);
+        }
+    }
+}
+
+/// Registers the server with the discovery server through a secure channel that is closed
+/// afterwards. Discovery servers only accept registrations over a channel that authenticates the
+/// server, so the channel is signed with the server's own certificate using the security policy of
+/// one of the discovery server's secure endpoints.
+pub fn register_with_discovery_server(certificate_store: Arc<Mutex<CertificateStore>>, discovery_server_url: &str, registered_server: &RegisteredServer) -> Result<(), StatusCode> {
+    let endpoint = find_secure_endpoint(certificate_store.clone(), discovery_server_url)?;
+    let security_policy = SecurityPolicy::from_uri(endpoint.security_policy_uri.as_ref());
+    let mut session = ClientSession::new(certificate_store, discovery_server_url, security_policy);
+    session.set_endpoint(endpoint);
+    session.connect()?;
+    let result = session.register_server(registered_server.clone());
+    session.disconnect();
+    result
+}
+
+/// Asks the discovery server for its endpoints and returns the first that signs messages, if any
+fn find_secure_endpoint(certificate_store: Arc<Mutex<CertificateStore>>, discovery_server_url: &str) -> Result<EndpointDescription, StatusCode> {
+    let mut session = ClientSession::new(certificate_store, discovery_server_url, SecurityPolicy::None);
+    session.connect()?;
+    let endpoints = session.get_endpoints();
+    session.disconnect();
+    let endpoints = endpoints?.unwrap_or(Vec::new());
+    endpoints.into_iter().find(|endpoint| {
+        let security_policy = SecurityPolicy::from_uri(endpoint.security_policy_uri.as_ref());
+        security_policy != SecurityPolicy::None && security_policy != SecurityPolicy::Unknown &&
+            (endpoint.security_mode == MessageSecurityMode::Sign || endpoint.security_mode == MessageSecurityMode::SignAndEncrypt)
+    }).ok_or_else(|| {
+        error!("Discovery server {} has no endpoint that signs messages", discovery_server_url);
+        BAD_SECURITY_POLICY_REJECTED
+    })
+}
@@ -0,0 +1,85 @@
+use opcua_types::*;
+
+/// Bit of the EventNotifier attribute that indicates a node can be subscribed to for events
+pub const EVENT_NOTIFIER_SUBSCRIBE_TO_EVENTS: Byte = 1;
+
+/// An event of BaseEventType or one of its subtypes. The mandatory fields of BaseEventType are
+/// members of the struct. Any further fields defined by the event's type are held as properties
+/// keyed by their browse name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    /// Unique identifier of this event occurrence
+    pub event_id: ByteString,
+    /// The node id of the event's type, BaseEventType or a subtype of it
+    pub event_type: NodeId,
+    /// The node that the event originated from
+    pub source_node: NodeId,
+    /// Description of the source of the event
+    pub source_name: UAString,
+    /// Time the event occurred
+    pub time: DateTime,
+    /// Time the server received the event
+    pub receive_time: DateTime,
+    /// Human readable description of the event
+    pub message: LocalizedText,
+    /// Urgency of the event from 1 (lowest) to 1000 (highest)
+    pub severity: UInt16,
+    /// Additional fields of the event type
+    pub properties: Vec<(QualifiedName, Variant)>,
+}
+
+impl Event {
+    pub fn new(event_type: &NodeId, source_node: &NodeId, source_name: &str, message: &str, severity: UInt16) -> Event {
+        let now = DateTime::now();
+        Event {
+            event_id: ByteString::random(16),
+            event_type: event_type.clone(),
+            source_node: source_node.clone(),
+            source_name: UAString::from_str(source_name),
+            time: now.clone(),
+            receive_time: now,
+            message: LocalizedText::new("", message),
+            severity,
+            properties: Vec::new(),
+        }
+    }
+
+    /// Sets the value of an additional field of the event, replacing any previous value
+    pub fn set_property(&mut self, browse_name: QualifiedName, value: Variant) {
+        if let Some(property) = self.properties.iter_mut().find(|p| p.0 == browse_name) {
+            property.1 = value;
+            return;
+        }
+        self.properties.push((browse_name, value));
+    }
+
+    /// Returns the value of the field identified by the browse path relative to the event type.
+    /// Fields which the event does not have are returned as an empty variant.
+    pub fn field(&self, browse_path: &[QualifiedName]) -> Variant {
+        if browse_path.len() != 1 {
+            return Variant::Empty;
+        }
+        let browse_name = &browse_path[0];
+        if browse_name.namespace_index == 0 {
+            let value = match browse_name.name.as_ref() {
+                "EventId" => Some(Variant::ByteString(self.event_id.clone())),
+                "EventType" => Some(Variant::new_node_id(self.event_type.clone())),
+                "SourceNode" => Some(Variant::new_node_id(self.source_node.clone())),
+                "SourceName" => Some(Variant::String(self.source_name.clone())),
+                "Time" => Some(Variant::DateTime(self.time.clone())),
+                "ReceiveTime" => Some(Variant::DateTime(self.receive_time.clone())),
+                "Message" => Some(Variant::new_localized_text(self.message.clone())),
+                "Severity" => Some(Variant::UInt16(self.severity)),
+                _ => None
+            };
+            if let Some(value) = value {
+                return value;
+            }
+        }
+        if let Some(property) = self.properties.iter().find(|p| p.0 == *browse_name) {
+            property.1.clone()
+        } else {
+            Variant::Empty
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use std::cmp::Ordering;
+
+use opcua_types::*;
+
+use constants;
+use address_space::address_space::AddressSpace;
+use events::event::Event;
+
+/// An operand of a where clause element, decoded from its extension object
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    /// The result of another element in the where clause
+    Element(usize),
+    /// A literal value
+    Literal(Variant),
+    /// A field of the event
+    SimpleAttribute(SimpleAttributeOperand),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct FilterElement {
+    filter_operator: FilterOperator,
+    operands: Vec<Operand>,
+}
+
+/// An event filter that has been validated and had its where clause decoded so it can be
+/// evaluated against each event that is raised.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedEventFilter {
+    select_clauses: Vec<SimpleAttributeOperand>,
+    where_clause: Vec<FilterElement>,
+}
+
+impl ParsedEventFilter {
+    /// Validates and decodes the event filter. The filter must have at least one select clause and
+    /// the where clause may only use the supported operators with the correct number of operands.
+    pub fn parse(event_filter: &EventFilter) -> Result<ParsedEventFilter, StatusCode> {
+        let select_clauses = if let Some(ref select_clauses) = event_filter.select_clauses {
+            select_clauses.clone()
+        } else {
+            Vec::new()
+        };
+        if select_clauses.is_empty() {
+            return Err(BAD_EVENT_FILTER_INVALID);
+        }
+        for select_clause in &select_clauses {
+            ParsedEventFilter::validate_simple_attribute_operand(select_clause)?;
+        }
+
+        let mut where_clause = Vec::new();
+        if let Some(ref elements) = event_filter.where_clause.elements {
+            if elements.len() > constants::MAX_WHERE_CLAUSE_ELEMENTS {
+                return Err(BAD_CONTENT_FILTER_INVALID);
+            }
+            for (idx, element) in elements.iter().enumerate() {
+                where_clause.push(ParsedEventFilter::parse_element(idx, elements.len(), element)?);
+            }
+        }
+
+        Ok(ParsedEventFilter {
+            select_clauses,
+            where_clause,
+        })
+    }
+
+    /// Tests if the event passes the where clause. An empty where clause passes every event.
+    pub fn matches(&self, address_space: &AddressSpace, event: &Event) -> bool {
+        if self.where_clause.is_empty() {
+            true
+        } else {
+            ParsedEventFilter::as_bool(&self.evaluate(address_space, event))
+        }
+    }
+
+    /// Returns the value of each select clause for the event
+    pub fn select(&self, address_space: &AddressSpace, event: &Event) -> Vec<Variant> {
+        self.select_clauses.iter().map(|select_clause| {
+            ParsedEventFilter::event_field(address_space, event, select_clause)
+        }).collect()
+    }
+
+    fn validate_simple_attribute_operand(operand: &SimpleAttributeOperand) -> Result<(), StatusCode> {
+        if AttributeId::from_u32(operand.attribute_id).is_err() {
+            return Err(BAD_ATTRIBUTE_ID_INVALID);
+        }
+        let _ = NumericRange::parse(&operand.index_range)?;
+        Ok(())
+    }
+
+    fn parse_element(idx: usize, num_elements: usize, element: &ContentFilterElement) -> Result<FilterElement, StatusCode> {
+        // Check the operator is supported and has the correct number of operands
+        let (min_operands, max_operands) = match element.filter_operator {
+            FilterOperator::IsNull | FilterOperator::Not => (1, 1),
+            FilterOperator::Equals | FilterOperator::GreaterThan | FilterOperator::LessThan |
+            FilterOperator::GreaterThanOrEqual | FilterOperator::LessThanOrEqual | FilterOperator::Like |
+            FilterOperator::And | FilterOperator::Or |
+            FilterOperator::BitwiseAnd | FilterOperator::BitwiseOr => (2, 2),
+            FilterOperator::Between => (3, 3),
+            FilterOperator::InList => (2, usize::max_value()),
+            FilterOperator::Cast => {
+                return Err(BAD_FILTER_OPERATOR_UNSUPPORTED);
+            }
+        };
+        let filter_operands: &[ExtensionObject] = if let Some(ref filter_operands) = element.filter_operands {
+            filter_operands
+        } else {
+            &[]
+        };
+        if filter_operands.len() < min_operands || filter_operands.len() > max_operands {
+            return Err(BAD_FILTER_OPERAND_COUNT_MISMATCH);
+        }
+
+        let mut operands = Vec::with_capacity(filter_operands.len());
+        for filter_operand in filter_operands {
+            let operand_type_id = &filter_operand.node_id;
+            let operand = if operand_type_id == &ObjectId::ElementOperand_Encoding_DefaultBinary.as_node_id() {
+                let operand = filter_operand.decode_inner::<ElementOperand>()?;
+                // Elements may only refer forwards to prevent loops
+                let index = operand.index as usize;
+                if index <= idx || index >= num_elements {
+                    return Err(BAD_FILTER_OPERAND_INVALID);
+                }
+                Operand::Element(index)
+            } else if operand_type_id == &ObjectId::LiteralOperand_Encoding_DefaultBinary.as_node_id() {
+                Operand::Literal(filter_operand.decode_inner::<LiteralOperand>()?.value)
+            } else if operand_type_id == &ObjectId::SimpleAttributeOperand_Encoding_DefaultBinary.as_node_id() {
+                let operand = filter_operand.decode_inner::<SimpleAttributeOperand>()?;
+                ParsedEventFilter::validate_simple_attribute_operand(&operand)?;
+                Operand::SimpleAttribute(operand)
+            } else {
+                return Err(BAD_FILTER_OPERAND_INVALID);
+            };
+            operands.push(operand);
+        }
+
+        // Patterns are matched against every event so a client may not supply an enormous one
+        if element.filter_operator == FilterOperator::Like {
+            if let Operand::Literal(ref pattern) = operands[1] {
+                if ParsedEventFilter::as_string(pattern).map_or(false, |pattern| pattern.chars().count() > constants::MAX_LIKE_PATTERN_LENGTH) {
+                    return Err(BAD_FILTER_OPERAND_INVALID);
+                }
+            }
+        }
+
+        Ok(FilterElement {
+            filter_operator: element.filter_operator,
+            operands,
+        })
+    }
+
+    /// Returns the value of the event field described by the operand. The field is empty if the
+    /// event is not of the operand's type or the operand is for something other than a value.
+    fn event_field(address_space: &AddressSpace, event: &Event, operand: &SimpleAttributeOperand) -> Variant {
+        if !operand.type_definition_id.is_null() && !address_space.is_subtype(&event.event_type, &operand.type_definition_id) {
+            return Variant::Empty;
+        }
+        if operand.attribute_id != AttributeId::Value as UInt32 {
+            return Variant::Empty;
+        }
+        let value = if let Some(ref browse_path) = operand.browse_path {
+            event.field(browse_path)
+        } else {
+            Variant::Empty
+        };
+        match NumericRange::parse(&operand.index_range) {
+            Ok(NumericRange::None) => value,
+            Ok(index_range) => value.range_of(&index_range).unwrap_or(Variant::Empty),
+            Err(_) => Variant::Empty
+        }
+    }
+
+    fn operand_value(address_space: &AddressSpace, event: &Event, operand: &Operand, results: &[Variant]) -> Variant {
+        match operand {
+            &Operand::Element(index) => results[index].clone(),
+            &Operand::Literal(ref value) => value.clone(),
+            &Operand::SimpleAttribute(ref operand) => ParsedEventFilter::event_field(address_space, event, operand),
+        }
+    }
+
+    /// Evaluates the where clause and returns the result of its first element. Elements only
+    /// refer to the elements after them so each is evaluated once, from the last to the first.
+    fn evaluate(&self, address_space: &AddressSpace, event: &Event) -> Variant {
+        let mut results = vec![Variant::Empty; self.where_clause.len()];
+        for index in (0..self.where_clause.len()).rev() {
+            results[index] = self.evaluate_element(address_space, event, index, &results);
+        }
+        results.swap_remove(0)
+    }
+
+    /// Evaluates the element at the index in the where clause from the results of the elements
+    /// after it
+    fn evaluate_element(&self, address_space: &AddressSpace, event: &Event, index: usize, results: &[Variant]) -> Variant {
+        let element = &self.where_clause[index];
+        let operands: Vec<Variant> = element.operands.iter().map(|operand| {
+            ParsedEventFilter::operand_value(address_space, event, operand, results)
+        }).collect();
+        match element.filter_operator {
+            FilterOperator::Equals => {
+                Variant::Boolean(ParsedEventFilter::compare(&operands[0], &operands[1]) == Some(Ordering::Equal))
+            }
+            FilterOperator::IsNull => {
+                Variant::Boolean(operands[0] == Variant::Empty)
+            }
+            FilterOperator::GreaterThan => {
+                Variant::Boolean(ParsedEventFilter::compare(&operands[0], &operands[1]) == Some(Ordering::Greater))
+            }
+            FilterOperator::LessThan => {
+                Variant::Boolean(ParsedEventFilter::compare(&operands[0], &operands[1]) == Some(Ordering::Less))
+            }
+            FilterOperator::GreaterThanOrEqual => {
+                let result = ParsedEventFilter::compare(&operands[0], &operands[1]);
+                Variant::Boolean(result == Some(Ordering::Greater) || result == Some(Ordering::Equal))
+            }
+            FilterOperator::LessThanOrEqual => {
+                let result = ParsedEventFilter::compare(&operands[0], &operands[1]);
+                Variant::Boolean(result == Some(Ordering::Less) || result == Some(Ordering::Equal))
+            }
+            FilterOperator::Like => {
+                let result = match (ParsedEventFilter::as_string(&operands[0]), ParsedEventFilter::as_string(&operands[1])) {
+                    (Some(value), Some(pattern)) => {
+                        let value: Vec<char> = value.chars().collect();
+                        let pattern: Vec<char> = pattern.chars().collect();
+                        ParsedEventFilter::like(&value, &pattern)
+                    }
+                    _ => false
+                };
+                Variant::Boolean(result)
+            }
+            FilterOperator::Not => {
+                Variant::Boolean(!ParsedEventFilter::as_bool(&operands[0]))
+            }
+            FilterOperator::Between => {
+                let low = ParsedEventFilter::compare(&operands[0], &operands[1]);
+                let high = ParsedEventFilter::compare(&operands[0], &operands[2]);
+                Variant::Boolean((low == Some(Ordering::Greater) || low == Some(Ordering::Equal)) &&
+                    (high == Some(Ordering::Less) || high == Some(Ordering::Equal)))
+            }
+            FilterOperator::InList => {
+                Variant::Boolean(operands[1..].iter().any(|v| ParsedEventFilter::compare(&operands[0], v) == Some(Ordering::Equal)))
+            }
+            FilterOperator::And => {
+                Variant::Boolean(ParsedEventFilter::as_bool(&operands[0]) && ParsedEventFilter::as_bool(&operands[1]))
+            }
+            FilterOperator::Or => {
+                Variant::Boolean(ParsedEventFilter::as_bool(&operands[0]) || ParsedEventFilter::as_bool(&operands[1]))
+            }
+            FilterOperator::BitwiseAnd | FilterOperator::BitwiseOr => {
+                match (ParsedEventFilter::as_i64(&operands[0]), ParsedEventFilter::as_i64(&operands[1])) {
+                    (Some(v1), Some(v2)) => {
+                        Variant::Int64(if element.filter_operator == FilterOperator::BitwiseAnd { v1 & v2 } else { v1 | v2 })
+                    }
+                    _ => Variant::Empty
+                }
+            }
+            FilterOperator::Cast => {
+                // Rejected when the filter was parsed
+                Variant::Empty
+            }
+        }
+    }
+
+    /// Compares two values. Numbers are compared by value regardless of their type, strings are
+    /// compared lexically and anything else may only be equal or not.
+    fn compare(v1: &Variant, v2: &Variant) -> Option<Ordering> {
+        if v1.is_numeric() && v2.is_numeric() {
+            v1.as_f64().unwrap().partial_cmp(&v2.as_f64().unwrap())
+        } else {
+            match (ParsedEventFilter::as_string(v1), ParsedEventFilter::as_string(v2)) {
+                (Some(s1), Some(s2)) => Some(s1.cmp(s2)),
+                _ => if v1 == v2 { Some(Ordering::Equal) } else { None }
+            }
+        }
+    }
+
+    /// Returns the text of a string or localized text
+    fn as_string(value: &Variant) -> Option<&str> {
+        match value {
+            &Variant::String(ref value) => Some(value.as_ref()),
+            &Variant::LocalizedText(ref value) => Some(value.text.as_ref()),
+            _ => None
+        }
+    }
+
+    fn as_bool(value: &Variant) -> bool {
+        match value {
+            &Variant::Boolean(value) => value,
+            _ => false
+        }
+    }
+
+    fn as_i64(value: &Variant) -> Option<i64> {
+        match value {
+            &Variant::SByte(value) => Some(value as i64),
+            &Variant::Byte(value) => Some(value as i64),
+            &Variant::Int16(value) => Some(value as i64),
+            &Variant::UInt16(value) => Some(value as i64),
+            &Variant::Int32(value) => Some(value as i64),
+            &Variant::UInt32(value) => Some(value as i64),
+            &Variant::Int64(value) => Some(value),
+            &Variant::UInt64(value) => Some(value as i64),
+            _ => None
+        }
+    }
+
+    /// Matches a value against a Like pattern where '%' matches any number of characters, '_'
+    /// matches a single character and '\' escapes the next character of the pattern. The match
+    /// takes time in proportion to the length of the value times the length of the pattern.
+    fn like(value: &[char], pattern: &[char]) -> bool {
+        // A run of '%' matches the same as a single one
+        let mut tokens = Vec::with_capacity(pattern.len());
+        let mut i = 0;
+        while i < pattern.len() {
+            let token = match pattern[i] {
+                '%' => LikeToken::AnyChars,
+                '_' => LikeToken::AnyChar,
+                '\\' if i + 1 < pattern.len() => {
+                    i += 1;
+                    LikeToken::Char(pattern[i])
+                }
+                c => LikeToken::Char(c),
+            };
+            if token != LikeToken::AnyChars || tokens.last() != Some(&LikeToken::AnyChars) {
+                tokens.push(token);
+            }
+            i += 1;
+        }
+
+        // matched[j] is true if the tokens so far match the first j characters of the value
+        let mut matched = vec![false; value.len() + 1];
+        matched[0] = true;
+        for token in &tokens {
+            match *token {
+                LikeToken::AnyChars => {
+                    for j in 1..matched.len() {
+                        matched[j] = matched[j] || matched[j - 1];
+                    }
+                }
+                LikeToken::AnyChar | LikeToken::Char(_) => {
+                    for j in (1..matched.len()).rev() {
+                        let char_matches = if let LikeToken::Char(c) = *token { value[j - 1] == c } else { true };
+                        matched[j] = matched[j - 1] && char_matches;
+                    }
+                    matched[0] = false;
+                }
+            }
+        }
+        matched[value.len()]
+    }
+}
+
+/// A part of a Like pattern
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LikeToken {
+    /// Any number of characters
+    AnyChars,
+    /// A single character
+    AnyChar,
+    /// The character
+    Char(char),
+}
@@ -0,0 +1,407 @@
+//! Standard aggregates of Part 13 which compute a value from the raw values of each interval of
+//! a time range. They are used to service HistoryRead processed requests and by monitored items
+//! with an aggregate filter.
+//!
+//! The quality of a computed value is based on the number of good and bad raw values in the
+//! interval compared against the PercentDataGood and PercentDataBad of the aggregate configuration.
+
+use opcua_types::*;
+
+use constants;
+use history::history_timestamp;
+
+/// Number of ticks (100ns) in a millisecond
+const TICKS_PER_MILLISECOND: f64 = 10_000.0;
+/// Number of ticks (100ns) in a second
+const TICKS_PER_SECOND: f64 = 10_000_000.0;
+
+/// The aggregates supported by the server
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AggregateType {
+    /// The value interpolated at the start of the interval
+    Interpolative,
+    /// The mean of the good values in the interval
+    Average,
+    /// The time weighted average of the interval using sloped interpolation
+    TimeAverage,
+    /// The time average multiplied by the duration of the interval in seconds
+    Total,
+    /// The smallest good value in the interval
+    Minimum,
+    /// The largest good value in the interval
+    Maximum,
+    /// The number of good values in the interval
+    Count,
+}
+
+impl AggregateType {
+    /// All the aggregates supported by the server
+    pub fn supported() -> Vec<AggregateType> {
+        vec![
+            AggregateType::Interpolative,
+            AggregateType::Average,
+            AggregateType::TimeAverage,
+            AggregateType::Total,
+            AggregateType::Minimum,
+            AggregateType::Maximum,
+            AggregateType::Count,
+        ]
+    }
+
+    /// Returns the aggregate identified by the node id of its AggregateFunction object
+    pub fn from_node_id(node_id: &NodeId) -> Result<AggregateType, StatusCode> {
+        AggregateType::supported().into_iter().find(|aggregate_type| aggregate_type.object_id().as_node_id() == *node_id).ok_or(BAD_AGGREGATE_NOT_SUPPORTED)
+    }
+
+    /// Returns the id of the AggregateFunction object of the aggregate
+    pub fn object_id(&self) -> ObjectId {
+        match *self {
+            AggregateType::Interpolative => ObjectId::AggregateFunction_Interpolative,
+            AggregateType::Average => ObjectId::AggregateFunction_Average,
+            AggregateType::TimeAverage => ObjectId::AggregateFunction_TimeAverage,
+            AggregateType::Total => ObjectId::AggregateFunction_Total,
+            AggregateType::Minimum => ObjectId::AggregateFunction_Minimum,
+            AggregateType::Maximum => ObjectId::AggregateFunction_Maximum,
+            AggregateType::Count => ObjectId::AggregateFunction_Count,
+        }
+    }
+}
+
+/// Returns the configuration that aggregates are computed with. The server's defaults are used
+/// if the client asks for them, otherwise the client's configuration is checked.
+pub fn configuration(aggregate_configuration: &AggregateConfiguration) -> Result<AggregateConfiguration, StatusCode> {
+    if aggregate_configuration.use_server_capabilities_defaults {
+        Ok(AggregateConfiguration {
+            use_server_capabilities_defaults: true,
+            treat_uncertain_as_bad: true,
+            percent_data_bad: 100,
+            percent_data_good: 100,
+            use_sloped_extrapolation: false,
+        })
+    } else if aggregate_configuration.percent_data_bad > 100 || aggregate_configuration.percent_data_good > 100 {
+        Err(BAD_AGGREGATE_CONFIGURATION_REJECTED)
+    } else {
+        Ok(aggregate_configuration.clone())
+    }
+}
+
+/// Computes the aggregate for each interval of the processing interval (in milliseconds) between
+/// the start and end time. A processing interval of 0 makes one interval of the whole range. When
+/// the start time is later than the end time the intervals run backwards from the start time.
+/// Each value is timestamped with the earlier time of its interval.
+///
+/// The raw values must be ordered oldest first and should include the values either side of the
+/// range so values at the edges can be interpolated.
+pub fn process(aggregate_type: AggregateType, configuration: &AggregateConfiguration, data_values: &[DataValue], start_time: &DateTime, end_time: &DateTime, processing_interval: Double) -> Vec<DataValue> {
+    // Missing bounds are not data
+    let data_values: Vec<DataValue> = data_values.iter().filter(|v| v.status != Some(BAD_BOUND_NOT_FOUND)).cloned().collect();
+
+    let start_time = start_time.checked_ticks();
+    let end_time = end_time.checked_ticks();
+    let processing_interval = if processing_interval > 0.0 {
+        (processing_interval * TICKS_PER_MILLISECOND) as i64
+    } else {
+        (start_time - end_time).abs()
+    };
+    let mut result = Vec::new();
+    if processing_interval == 0 {
+        return result;
+    }
+    if start_time <= end_time {
+        let mut interval_start = start_time;
+        while interval_start < end_time {
+            let interval_end = (interval_start + processing_interval).min(end_time);
+            result.push(aggregate_ticks(aggregate_type, configuration, &data_values, interval_start, interval_end));
+            interval_start = interval_end;
+        }
+    } else {
+        let mut interval_end = start_time;
+        while interval_end > end_time {
+            let interval_start = (interval_end - processing_interval).max(end_time);
+            result.push(aggregate_ticks(aggregate_type, configuration, &data_values, interval_start, interval_end));
+            interval_end = interval_start;
+        }
+    }
+    result
+}
+
+/// Returns the number of intervals that `process()` would compute for the range
+pub fn interval_count(start_time: &DateTime, end_time: &DateTime, processing_interval: Double) -> usize {
+    let range = (start_time.checked_ticks() - end_time.checked_ticks()).abs() as f64;
+    if processing_interval > 0.0 {
+        (range / (processing_interval * TICKS_PER_MILLISECOND)).ceil() as usize
+    } else if range > 0.0 {
+        1
+    } else {
+        0
+    }
+}
+
+/// Computes the aggregate of a single interval from its start time inclusive to its end time
+/// exclusive. The raw values must be ordered oldest first.
+pub fn aggregate(aggregate_type: AggregateType, configuration: &AggregateConfiguration, data_values: &[DataValue], interval_start: &DateTime, interval_end: &DateTime) -> DataValue {
+    aggregate_ticks(aggregate_type, configuration, data_values, interval_start.checked_ticks(), interval_end.checked_ticks())
+}
+
+fn aggregate_ticks(aggregate_type: AggregateType, configuration: &AggregateConfiguration, data_values: &[DataValue], interval_start: i64, interval_end: i64) -> DataValue {
+    let in_interval: Vec<&DataValue> = data_values.iter().filter(|v| {
+        let timestamp = history_timestamp(v);
+        timestamp >= interval_start && timestamp < interval_end
+    }).collect();
+    let good_values: Vec<&DataValue> = in_interval.iter().filter(|v| is_good(configuration, v)).cloned().collect();
+    let quality = quality(configuration, good_values.len(), in_interval.len() - good_values.len());
+
+    let (value, status) = match aggregate_type {
+        AggregateType::Count => {
+            let status = if in_interval.is_empty() { GOOD } else { quality };
+            (Some(Variant::Int32(good_values.len() as Int32)), status)
+        }
+        AggregateType::Minimum | AggregateType::Maximum => {
+            match numeric_values(&good_values) {
+                Ok(ref values) if values.is_empty() => (None, BAD_NO_DATA),
+                Ok(values) => {
+                    let minimum = aggregate_type == AggregateType::Minimum;
+                    let mut extreme = values[0];
+                    for v in values.into_iter().skip(1) {
+                        if (minimum && v.1 < extreme.1) || (!minimum && v.1 > extreme.1) {
+                            extreme = v;
+                        }
+                    }
+                    (extreme.0.value.clone(), quality)
+                }
+                Err(status) => (None, status)
+            }
+        }
+        AggregateType::Average => {
+            match numeric_values(&good_values) {
+                Ok(ref values) if values.is_empty() => (None, BAD_NO_DATA),
+                Ok(values) => {
+                    let sum: f64 = values.iter().map(|v| v.1).sum();
+                    (Some(Variant::Double(sum / values.len() as f64)), quality)
+                }
+                Err(status) => (None, status)
+            }
+        }
+        AggregateType::Interpolative => {
+            match interpolate(configuration, data_values, interval_start) {
+                Ok((value, status)) => (Some(value), status),
+                Err(status) => (None, status)
+            }
+        }
+        AggregateType::TimeAverage | AggregateType::Total => {
+            let quality = if in_interval.is_empty() { None } else { Some(quality) };
+            match time_average(configuration, data_values, &good_values, quality, interval_start, interval_end) {
+                Ok((time_average, duration, status)) => {
+                    let value = if aggregate_type == AggregateType::Total {
+                        time_average * duration as f64 / TICKS_PER_SECOND
+                    } else {
+                        time_average
+                    };
+                    (Some(Variant::Double(value)), status)
+                }
+                Err(status) => (None, status)
+            }
+        }
+    };
+
+    let timestamp = DateTime::from_ticks(interval_start);
+    DataValue {
+        value,
+        status: Some(status),
+        source_timestamp: Some(timestamp.clone()),
+        source_picoseconds: Some(0),
+        server_timestamp: Some(timestamp),
+        server_picoseconds: Some(0),
+    }
+}
+
+/// Tests if a raw value is good for the purposes of the aggregate configuration
+fn is_good(configuration: &AggregateConfiguration, data_value: &DataValue) -> bool {
+    let status = data_value.status.unwrap_or(GOOD);
+    status.is_good() || (status.is_uncertain() && !configuration.treat_uncertain_as_bad)
+}
+
+/// Works out the quality of an aggregate from the number of good and bad values it was computed from
+fn quality(configuration: &AggregateConfiguration, good_count: usize, bad_count: usize) -> StatusCode {
+    let total = good_count + bad_count;
+    if total == 0 {
+        BAD_NO_DATA
+    } else if bad_count * 100 >= configuration.percent_data_bad as usize * total {
+        BAD_NO_DATA
+    } else if good_count * 100 >= configuration.percent_data_good as usize * total {
+        GOOD
+    } else {
+        UNCERTAIN_DATA_SUB_NORMAL
+    }
+}
+
+/// Pairs each value with its numeric value, failing if any value is not numeric
+fn numeric_values<'a>(data_values: &[&'a DataValue]) -> Result<Vec<(&'a DataValue, f64)>, StatusCode> {
+    data_values.iter().map(|v| {
+        v.value.as_ref().and_then(|value| value.as_f64()).map(|value| (*v, value)).ok_or(BAD_AGGREGATE_INVALID_INPUTS)
+    }).collect()
+}
+
+/// Returns the value at a time, either a good raw value at exactly that time or interpolated
+/// from the good values either side of it. If there is no later value the earlier value is
+/// extrapolated, flat or sloped according to the configuration, and the result is uncertain.
+fn interpolate(configuration: &AggregateConfiguration, data_values: &[DataValue], timestamp: i64) -> Result<(Variant, StatusCode), StatusCode> {
+    let good_values: Vec<(i64, f64)> = data_values.iter()
+        .filter(|v| is_good(configuration, v))
+        .filter_map(|v| v.value.as_ref().and_then(|value| value.as_f64()).map(|value| (history_timestamp(v), value)))
+        .collect();
+    if let Some(&(_, value)) = good_values.iter().find(|v| v.0 == timestamp) {
+        return Ok((Variant::Double(value), GOOD));
+    }
+    let before: Vec<&(i64, f64)> = good_values.iter().filter(|v| v.0 < timestamp).collect();
+    let after = good_values.iter().find(|v| v.0 > timestamp);
+    match (before.last(), after) {
+        (Some(&&(t1, v1)), Some(&(t2, v2))) => {
+            let value = v1 + (v2 - v1) * (timestamp - t1) as f64 / (t2 - t1) as f64;
+            Ok((Variant::Double(value), GOOD))
+        }
+        (Some(&&(t1, v1)), None) => {
+            let value = if configuration.use_sloped_extrapolation && before.len() > 1 {
+                let (t0, v0) = *before[before.len() - 2];
+                v1 + (v1 - v0) * (timestamp - t1) as f64 / (t1 - t0) as f64
+            } else {
+                v1
+            };
+            Ok((Variant::Double(value), UNCERTAIN_DATA_SUB_NORMAL))
+        }
+        _ => Err(BAD_NO_DATA)
+    }
+}
+
+/// Computes the time weighted average of an interval by integrating the good values, joined by
+/// straight lines, between the values interpolated at the start and end of the interval. The
+/// quality is that of the raw values in the interval, if there are any. Returns the average, the
+/// duration it was averaged over and the status.
+fn time_average(configuration: &AggregateConfiguration, data_values: &[DataValue], good_values: &[&DataValue], quality: Option<StatusCode>, interval_start: i64, interval_end: i64) -> Result<(f64, i64, StatusCode), StatusCode> {
+    let mut status = quality.unwrap_or(GOOD);
+    if status.is_bad() {
+        return Err(status);
+    }
+    let mut points = Vec::with_capacity(good_values.len() + 2);
+    if let Ok((Variant::Double(value), interpolated_status)) = interpolate(configuration, data_values, interval_start) {
+        points.push((interval_start, value));
+        if interpolated_status != GOOD {
+            status = interpolated_status;
+        }
+    }
+    for (v, value) in numeric_values(good_values)? {
+        let timestamp = history_timestamp(v);
+        if timestamp > interval_start {
+            points.push((timestamp, value));
+        }
+    }
+    if let Ok((Variant::Double(value), interpolated_status)) = interpolate(configuration, data_values, interval_end) {
+        points.push((interval_end, value));
+        if interpolated_status != GOOD {
+            status = interpolated_status;
+        }
+    }
+    if points.is_empty() {
+        return Err(BAD_NO_DATA);
+    }
+
+    let duration = points[points.len() - 1].0 - points[0].0;
+    if duration == 0 {
+        Ok((points[0].1, 0, status))
+    } else {
+        let area: f64 = points.windows(2).map(|w| (w[0].1 + w[1].1) / 2.0 * (w[1].0 - w[0].0) as f64).sum();
+        Ok((area / duration as f64, duration, status))
+    }
+}
+
+/// An aggregate filter of a monitored item. The values sampled by the item are collected and
+/// the aggregate is computed from them as each processing interval completes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedAggregateFilter {
+    pub aggregate_type: AggregateType,
+    pub configuration: AggregateConfiguration,
+    /// Revised time that the first interval starts from
+    pub start_time: DateTime,
+    /// Revised processing interval in milliseconds
+    pub processing_interval: Double,
+    /// Sampled values of the current interval and the last value before it
+    samples: Vec<DataValue>,
+    /// Start of the current interval in ticks
+    interval_start: Option<i64>,
+}
+
+impl ParsedAggregateFilter {
+    /// Parses the filter, revising the start time to now if it is not set and the processing
+    /// interval to the rate that subscriptions are polled at if it is faster.
+    pub fn parse(filter: &AggregateFilter) -> Result<ParsedAggregateFilter, StatusCode> {
+        let aggregate_type = AggregateType::from_node_id(&filter.aggregate_type)?;
+        let configuration = configuration(&filter.aggregate_configuration)?;
+        let start_time = if filter.start_time.checked_ticks() == 0 {
+            DateTime::now()
+        } else {
+            filter.start_time.clone()
+        };
+        let min_processing_interval = constants::SUBSCRIPTION_TIMER_RATE_MS as Double;
+        let processing_interval = if filter.processing_interval < min_processing_interval {
+            min_processing_interval
+        } else {
+            filter.processing_interval
+        };
+        Ok(ParsedAggregateFilter {
+            aggregate_type,
+            configuration,
+            start_time,
+            processing_interval,
+            samples: Vec::new(),
+            interval_start: None,
+        })
+    }
+
+    /// The filter result that tells the client the revised values
+    pub fn filter_result(&self) -> AggregateFilterResult {
+        AggregateFilterResult {
+            revised_start_time: self.start_time.clone(),
+            revised_processing_interval: self.processing_interval,
+            revised_aggregate_configuration: self.configuration.clone(),
+        }
+    }
+
+    /// Adds a sampled value to the current interval
+    pub fn add_sample(&mut self, data_value: DataValue) {
+        self.samples.push(data_value);
+    }
+
+    /// Computes the aggregate of each interval that has completed by now and moves on to the
+    /// interval that now falls in. The interval that the filter was created in is only part
+    /// sampled.
+    pub fn aggregate_completed_intervals(&mut self, now: &DateTime) -> Vec<DataValue> {
+        let now = now.checked_ticks();
+        let start_time = self.start_time.checked_ticks();
+        let processing_interval = (self.processing_interval * TICKS_PER_MILLISECOND) as i64;
+        let mut interval_start = match self.interval_start {
+            Some(interval_start) => interval_start,
+            None => {
+                if now <= start_time {
+                    start_time
+                } else {
+                    start_time + (now - start_time) / processing_interval * processing_interval
+                }
+            }
+        };
+
+        let mut result = Vec::new();
+        while interval_start + processing_interval <= now {
+            let interval_end = interval_start + processing_interval;
+            result.push(aggregate_ticks(self.aggregate_type, &self.configuration, &self.samples, interval_start, interval_end));
+            interval_start = interval_end;
+        }
+        self.interval_start = Some(interval_start);
+
+        // Samples of completed intervals are discarded except for the last one, which is needed to
+        // interpolate at the start of the current interval
+        if let Some(idx) = self.samples.iter().rposition(|v| history_timestamp(v) < interval_start) {
+            let _ = self.samples.drain(..idx);
+        }
+        result
+    }
+}
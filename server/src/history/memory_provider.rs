@@ -0,0 +1,165 @@
+use std::collections::{HashMap, VecDeque};
+
+use opcua_types::*;
+
+use history::{HistoricalDataProvider, history_timestamp};
+
+/// Holds the most recent values of each historizing variable in memory. Each node has a ring
+/// buffer of values ordered by timestamp, so when a buffer is full the oldest value is discarded
+/// to make room for a new one. History is lost when the server stops.
+///
+/// Values read at a time between two recorded values use stepped interpolation, i.e. the value
+/// before the time is returned.
+pub struct MemoryHistoricalDataProvider {
+    /// The maximum number of values held per node
+    capacity: usize,
+    /// Values of each node ordered by timestamp, oldest first
+    values: HashMap<NodeId, VecDeque<DataValue>>,
+}
+
+impl HistoricalDataProvider for MemoryHistoricalDataProvider {
+    fn record_value(&mut self, node_id: &NodeId, value: &DataValue) {
+        if self.capacity == 0 {
+            return;
+        }
+        if !self.values.contains_key(node_id) {
+            self.values.insert(node_id.clone(), VecDeque::new());
+        }
+        let values = self.values.get_mut(node_id).unwrap();
+        if values.len() == self.capacity {
+            let _ = values.pop_front();
+        }
+        // Values normally arrive in order but insert by timestamp in case they don't
+        let timestamp = history_timestamp(value);
+        let idx = values.iter().rposition(|v| history_timestamp(v) <= timestamp).map(|idx| idx + 1).unwrap_or(0);
+        values.insert(idx, value.clone());
+    }
+
+    fn read_raw(&self, node_id: &NodeId, start_time: &DateTime, end_time: &DateTime, return_bounds: bool) -> Result<Vec<DataValue>, StatusCode> {
+        let start_time = start_time.checked_ticks();
+        let end_time = end_time.checked_ticks();
+        let values = if let Some(values) = self.values.get(node_id) {
+            values
+        } else {
+            return Ok(Vec::new());
+        };
+
+        let mut result: Vec<DataValue> = values.iter().filter(|v| {
+            let timestamp = history_timestamp(v);
+            timestamp >= start_time && timestamp <= end_time
+        }).cloned().collect();
+
+        if return_bounds {
+            // The bounds are the values either side of the range unless a value is exactly on it
+            if result.first().map(|v| history_timestamp(v) != start_time).unwrap_or(true) {
+                let bound = values.iter().rev().find(|v| history_timestamp(v) < start_time).cloned();
+                result.insert(0, bound.unwrap_or_else(|| MemoryHistoricalDataProvider::bound_not_found(start_time)));
+            }
+            if result.last().map(|v| history_timestamp(v) != end_time).unwrap_or(true) {
+                let bound = values.iter().find(|v| history_timestamp(v) > end_time).cloned();
+                result.push(bound.unwrap_or_else(|| MemoryHistoricalDataProvider::bound_not_found(end_time)));
+            }
+        }
+        Ok(result)
+    }
+
+    fn read_at_time(&self, node_id: &NodeId, req_times: &[DateTime], _: bool) -> Result<Vec<DataValue>, StatusCode> {
+        let empty = VecDeque::new();
+        let values = self.values.get(node_id).unwrap_or(&empty);
+        let result = req_times.iter().map(|req_time| {
+            let timestamp = req_time.checked_ticks();
+            if let Some(value) = values.iter().rev().find(|v| history_timestamp(v) <= timestamp) {
+                let mut value = value.clone();
+                if history_timestamp(&value) != timestamp {
+                    // Stepped interpolation, the earlier value at the requested time
+                    value.source_timestamp = Some(req_time.clone());
+                    value.source_picoseconds = Some(0);
+                    value.server_timestamp = None;
+                    value.server_picoseconds = None;
+                }
+                value
+            } else {
+                DataValue {
+                    value: None,
+                    status: Some(BAD_NO_DATA),
+                    source_timestamp: Some(req_time.clone()),
+                    source_picoseconds: Some(0),
+                    server_timestamp: None,
+                    server_picoseconds: None,
+                }
+            }
+        }).collect();
+        Ok(result)
+    }
+
+    fn update_values(&mut self, node_id: &NodeId, perform_update_type: PerformUpdateType, values: &[DataValue]) -> Result<Vec<StatusCode>, StatusCode> {
+        let result = values.iter().map(|value| {
+            let timestamp = history_timestamp(value);
+            let existing_idx = self.values.get(node_id).and_then(|values| values.iter().position(|v| history_timestamp(v) == timestamp));
+            match (perform_update_type, existing_idx) {
+                (PerformUpdateType::Insert, Some(_)) => BAD_ENTRY_EXISTS,
+                (PerformUpdateType::Replace, None) => BAD_NO_ENTRY_EXISTS,
+                (_, Some(idx)) => {
+                    self.values.get_mut(node_id).unwrap()[idx] = value.clone();
+                    GOOD_ENTRY_REPLACED
+                }
+                (_, None) => {
+                    self.record_value(node_id, value);
+                    GOOD_ENTRY_INSERTED
+                }
+            }
+        }).collect();
+        Ok(result)
+    }
+
+    fn delete_raw(&mut self, node_id: &NodeId, start_time: &DateTime, end_time: &DateTime) -> Result<usize, StatusCode> {
+        let start_time = start_time.checked_ticks();
+        let end_time = end_time.checked_ticks();
+        if let Some(values) = self.values.get_mut(node_id) {
+            let len = values.len();
+            values.retain(|v| {
+                let timestamp = history_timestamp(v);
+                timestamp < start_time || timestamp > end_time
+            });
+            Ok(len - values.len())
+        } else {
+            Ok(0)
+        }
+    }
+
+    fn delete_at_time(&mut self, node_id: &NodeId, req_times: &[DateTime]) -> Result<Vec<StatusCode>, StatusCode> {
+        let result = req_times.iter().map(|req_time| {
+            let timestamp = req_time.checked_ticks();
+            let values = self.values.get_mut(node_id);
+            if let Some(values) = values {
+                if let Some(idx) = values.iter().position(|v| history_timestamp(v) == timestamp) {
+                    let _ = values.remove(idx);
+                    return GOOD;
+                }
+            }
+            BAD_NO_ENTRY_EXISTS
+        }).collect();
+        Ok(result)
+    }
+}
+
+impl MemoryHistoricalDataProvider {
+    /// Creates a provider holding up to `capacity` values per node
+    pub fn new(capacity: usize) -> MemoryHistoricalDataProvider {
+        MemoryHistoricalDataProvider {
+            capacity,
+            values: HashMap::new(),
+        }
+    }
+
+    fn bound_not_found(timestamp: i64) -> DataValue {
+        DataValue {
+            value: None,
+            status: Some(BAD_BOUND_NOT_FOUND),
+            source_timestamp: Some(DateTime::from_ticks(timestamp)),
+            source_picoseconds: Some(0),
+            server_timestamp: None,
+            server_picoseconds: None,
+        }
+    }
+}
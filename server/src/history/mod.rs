@@ -0,0 +1,71 @@
+//! Historical data access. The server reads and updates history through a
+//! `HistoricalDataProvider` which may be backed by anything. An in-memory provider is installed
+//! by default which records the value changes of variables that are historizing.
+
+use opcua_types::*;
+
+pub mod aggregates;
+pub mod memory_provider;
+
+/// Implemented by a backend that stores the historical values of variables. The server calls the
+/// provider to service HistoryRead requests and hands it the values of historizing variables
+/// as they change.
+///
+/// The server takes care of validating requests, the order of returned values, limiting the
+/// number of values per node, continuation points and which timestamps are returned.
+///
+/// Reading modified values and the functions that update history have default implementations
+/// which return BAD_HISTORY_OPERATION_UNSUPPORTED so a provider need not implement them.
+pub trait HistoricalDataProvider {
+    /// Records a new value of a variable that is historizing
+    fn record_value(&mut self, node_id: &NodeId, value: &DataValue);
+
+    /// Returns the raw values of the node with a timestamp between the start and end time
+    /// inclusive, oldest first. The start time is never later than the end time. When bounds are
+    /// requested the values immediately before the start time and after the end time are also
+    /// returned, or a value with status BAD_BOUND_NOT_FOUND if there is none.
+    fn read_raw(&self, node_id: &NodeId, start_time: &DateTime, end_time: &DateTime, return_bounds: bool) -> Result<Vec<DataValue>, StatusCode>;
+
+    /// Returns a value of the node for each requested time. Where no value has the exact time,
+    /// the value is interpolated from the values around it.
+    fn read_at_time(&self, node_id: &NodeId, req_times: &[DateTime], use_simple_bounds: bool) -> Result<Vec<DataValue>, StatusCode>;
+
+    /// Returns the values of the node that were replaced or deleted and have a timestamp between
+    /// the start and end time inclusive, oldest first. The start time is never later than the end
+    /// time.
+    fn read_modified(&self, _node_id: &NodeId, _start_time: &DateTime, _end_time: &DateTime) -> Result<Vec<DataValue>, StatusCode> {
+        Err(BAD_HISTORY_OPERATION_UNSUPPORTED)
+    }
+
+    /// Inserts, replaces or updates (inserts or replaces) values of the node identified by their
+    /// timestamp. Returns a status code for each value, e.g. GOOD_ENTRY_INSERTED,
+    /// GOOD_ENTRY_REPLACED, BAD_ENTRY_EXISTS or BAD_NO_ENTRY_EXISTS. The perform update type is
+    /// never `Remove`.
+    fn update_values(&mut self, _node_id: &NodeId, _perform_update_type: PerformUpdateType, _values: &[DataValue]) -> Result<Vec<StatusCode>, StatusCode> {
+        Err(BAD_HISTORY_OPERATION_UNSUPPORTED)
+    }
+
+    /// Deletes the values of the node with a timestamp between the start and end time inclusive.
+    /// The start time is never later than the end time. Returns the number of values deleted.
+    fn delete_raw(&mut self, _node_id: &NodeId, _start_time: &DateTime, _end_time: &DateTime) -> Result<usize, StatusCode> {
+        Err(BAD_HISTORY_OPERATION_UNSUPPORTED)
+    }
+
+    /// Deletes the values of the node at each of the times. Returns a status code for each time,
+    /// GOOD or BAD_NO_ENTRY_EXISTS.
+    fn delete_at_time(&mut self, _node_id: &NodeId, _req_times: &[DateTime]) -> Result<Vec<StatusCode>, StatusCode> {
+        Err(BAD_HISTORY_OPERATION_UNSUPPORTED)
+    }
+}
+
+/// Returns the timestamp that history is ordered by, i.e. the source timestamp or the server
+/// timestamp if there is no source timestamp, in ticks.
+pub fn history_timestamp(data_value: &DataValue) -> i64 {
+    if let Some(ref source_timestamp) = data_value.source_timestamp {
+        source_timestamp.checked_ticks()
+    } else if let Some(ref server_timestamp) = data_value.server_timestamp {
+        server_timestamp.checked_ticks()
+    } else {
+        0
+    }
+}
@@ -11,6 +11,7 @@ extern crate timer;
 
 extern crate opcua_types;
 extern crate opcua_core;
+extern crate opcua_client;
 
 type DateTimeUTC = chrono::DateTime<chrono::UTC>;
 
@@ -22,6 +23,9 @@ pub mod server;
 pub mod subscriptions;
 pub mod config;
 pub mod address_space;
+pub mod events;
+pub mod history;
+pub mod discovery;
 pub mod util;
 
 pub mod prelude {
@@ -32,6 +36,13 @@ pub mod prelude {
     pub use subscriptions::*;
     pub use subscriptions::subscription::*;
     pub use subscriptions::monitored_item::*;
+    pub use events::event::*;
+    pub use events::event_filter::*;
+    pub use history::*;
+    pub use history::memory_provider::*;
+    pub use history::aggregates::{AggregateType, ParsedAggregateFilter};
+    pub use discovery::registered_servers::*;
+    pub use discovery::mdns::*;
     pub use util::*;
 }
 
@@ -43,10 +54,18 @@ pub mod constants {
     pub const DEFAULT_HELLO_TIMEOUT_SECONDS: u32 = 120;
     /// Default OPC UA server port
     pub const DEFAULT_OPC_UA_SERVER_PORT: u16 = 1234;
+    /// Default maximum number of sessions
+    pub const DEFAULT_MAX_SESSIONS: u32 = 20;
     /// Default maximum number of subscriptions in a session
     pub const DEFAULT_MAX_SUBSCRIPTIONS: u32 = 100;
+    /// Default maximum number of publish requests queued in a session
+    pub const DEFAULT_MAX_PUBLISH_REQUESTS: u32 = 200;
+    /// Default maximum number of browse continuation points in a session
+    pub const DEFAULT_MAX_BROWSE_CONTINUATION_POINTS: u32 = 20;
+    /// Default maximum number of history continuation points in a session
+    pub const DEFAULT_MAX_HISTORY_CONTINUATION_POINTS: u32 = 10;
     /// Default, "well known address for TCP discovery server
-    //pub const DEFAULT_OPC_UA_DISCOVERY_SERVER_PORT: u16 = 4840;
+    pub const DEFAULT_OPC_UA_DISCOVERY_SERVER_PORT: u16 = 4840;
 
     /// Sequence numbers wrap when they exceed this value
     pub const SEQUENCE_NUMBER_WRAPAROUND: u32 = 4294966271;
@@ -57,20 +76,77 @@ pub mod constants {
     pub const MIN_PUBLISHING_INTERVAL: Double = 0.05f64;
     /// Minimum sampling interval in seconds allowed by clients on subscriptions or monitored_items
     pub const MIN_SAMPLING_INTERVAL: Double = 0.05f64;
+    /// Default maximum sampling interval in milliseconds allowed by clients on monitored items
+    pub const DEFAULT_MAX_SAMPLING_INTERVAL: Double = 60000f64;
     /// Default data change queue size
     pub const DEFAULT_DATA_CHANGE_QUEUE_SIZE: usize = 1;
     /// Minimum data change queue allowed by clients on monitored items
     pub const MIN_DATA_CHANGE_QUEUE_SIZE: usize = 1;
     /// Maximum data change queue allowed by clients on monitored items
     pub const MAX_DATA_CHANGE_QUEUE_SIZE: usize = 10;
+    /// Default event queue size
+    pub const DEFAULT_EVENT_QUEUE_SIZE: usize = 10;
+    /// Maximum event queue allowed by clients on monitored items
+    pub const MAX_EVENT_QUEUE_SIZE: usize = 100;
+    /// Maximum number of sent but unacknowledged notification messages a subscription holds for
+    /// republishing. The oldest is discarded to make room for a new one.
+    pub const MAX_RETRANSMISSION_QUEUE_SIZE: usize = 20;
+    /// Maximum number of elements in the where clause of an event filter
+    pub const MAX_WHERE_CLAUSE_ELEMENTS: usize = 64;
+    /// Maximum number of characters in a literal pattern of a Like element of an event filter
+    pub const MAX_LIKE_PATTERN_LENGTH: usize = 1024;
+    /// Maximum number of raised events held by the address space for event monitored items to pick up
+    pub const MAX_BUFFERED_EVENTS: usize = 1000;
+    /// Maximum number of variable value changes held by the address space for exception-based
+    /// monitored items to pick up
+    pub const MAX_BUFFERED_VALUE_CHANGES: usize = 1000;
+    /// Numeric identifiers of the aliases that RegisterNodes hands out for nodes start from this
+    /// value. Aliases are in the server's internal namespace, where AddNodes rejects identifiers
+    /// in their range, and a node the application adds in the range takes precedence over them.
+    pub const REGISTERED_NODE_ID_BASE: u64 = 0x8000_0000;
+    /// The namespace of registered node aliases, which is the server's internal namespace
+    pub const REGISTERED_NODE_NAMESPACE: u16 = 1;
+    /// The namespace of the server's application uri, which holds the nodes the server adds for
+    /// its sessions
+    pub const APPLICATION_NAMESPACE: u16 = 2;
+    /// The highest alias identifier, less REGISTERED_NODE_ID_BASE, so aliases fit in a UInt32
+    pub const MAX_REGISTERED_NODE_ID: u32 = 0x7fff_ffff;
+    /// Number of values of each historizing variable held by the default in-memory history
+    pub const DEFAULT_HISTORY_CAPACITY: usize = 1000;
+    /// Maximum number of intervals a processed history read may compute aggregates for per node
+    pub const MAX_HISTORY_PROCESSED_INTERVALS: usize = 10000;
     /// The default size of preallocated vecs of monitored items per subscription
     pub const DEFAULT_MONITORED_ITEM_CAPACITY: usize = 100;
     /// Sampling interval in MS used internally to poll subscriptions. The more finegrained this is
     /// the more often subscriptions will be checked to see if their subscription interval has elapsed
     /// therefore the value should be < min sampling interval
     pub const SUBSCRIPTION_TIMER_RATE_MS: i64 = 10;
-    /// Time in MS that a session will timeout after with inactivity
+    /// Time in MS that a session will timeout after with inactivity. This is the longest timeout
+    /// a client may ask for and the timeout of clients that do not ask for one.
     pub const SESSION_TIMEOUT: f64 = 50000f64;
+    /// Shortest time in MS that a client may ask for a session to timeout after with inactivity
+    pub const MIN_SESSION_TIMEOUT: f64 = 1000f64;
+    /// Interval in MS between checks for detached sessions that have timed out
+    pub const SESSION_EXPIRY_INTERVAL_MS: u32 = 1000;
+    /// Time in MS that a server's registration with a local discovery server lasts unless the
+    /// server registers again
+    pub const REGISTERED_SERVER_TIMEOUT_MS: i64 = 600000;
+    /// Maximum number of servers that can be registered with a local discovery server at once
+    pub const MAX_REGISTERED_SERVERS: usize = 100;
+    /// Default interval in seconds between registrations of the server with discovery servers
+    pub const DEFAULT_DISCOVERY_REGISTRATION_INTERVAL_SECONDS: u32 = 30;
+    /// Time in seconds that listeners hold the records of endpoints announced through mDNS
+    pub const MDNS_ANNOUNCEMENT_TTL_SECONDS: u32 = 120;
+    /// Time in MS that the mDNS responder waits for a query before checking if it should stop
+    pub const MDNS_RECEIVE_TIMEOUT_MS: u64 = 500;
+    /// The capability that a local discovery server announces for itself
+    pub const LDS_SERVER_CAPABILITY: &'static str = "LDS";
+    /// The capability that a server announces through mDNS when it says nothing of its capabilities
+    pub const NO_SERVER_CAPABILITY: &'static str = "NA";
+    /// Interval in MS between checks of whether the server is due to register with discovery servers
+    pub const DISCOVERY_REGISTRATION_POLL_INTERVAL_MS: u32 = 1000;
+    /// Interval in MS between checks of a local discovery server for registrations that have expired
+    pub const REGISTERED_SERVER_EXPIRY_INTERVAL_MS: u32 = 5000;
     /// Maximum size in bytes that a request message is allowed to be
     pub const MAX_REQUEST_MESSAGE_SIZE: u32 = 32768;
     /// Maxmimum keep alive count
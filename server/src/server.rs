@@ -1,21 +1,32 @@
 //! The server module defines types related to the server, it's current running state
 //! and end point information.
 
+use std::collections::HashMap;
 use std::net::{TcpListener, TcpStream};
 use std::sync::{Arc, Mutex};
 use std::path::PathBuf;
 use std::thread;
 use std::str::FromStr;
 
+use chrono::UTC;
+use time;
+
 use opcua_types::*;
 use opcua_types::profiles;
 
 use opcua_core::prelude::*;
 
+use DateTimeUTC;
 use constants;
 use address_space::types::AddressSpace;
 use comms::tcp_transport::*;
 use config::ServerConfig;
+use discovery::registered_servers::RegisteredServers;
+use discovery::registration;
+use discovery::mdns::MdnsResponder;
+use session::{Session, SessionManager};
+use subscriptions::monitored_item::MonitoredItemLimits;
+use subscriptions::subscriptions::Subscriptions;
 use util::PollingAction;
 
 #[derive(Clone)]
@@ -27,6 +38,8 @@ pub struct Endpoint {
     pub anonymous: bool,
     pub user: Option<String>,
     pub pass: Option<Vec<u8>>,
+    /// Clients of this endpoint may add and delete nodes and references
+    pub node_management: bool,
 }
 
 impl Endpoint {
@@ -35,7 +48,8 @@ impl Endpoint {
         let mut result = BAD_IDENTITY_TOKEN_REJECTED;
         let identity_token_id = user_identity_token.node_id.clone();
         debug!("Validating identity token {:?}", identity_token_id);
-        if identity_token_id == ObjectId::AnonymousIdentityToken_Encoding_DefaultBinary.as_node_id() {
+        // A null token is treated as anonymous, see Part 4 5.6.3
+        if identity_token_id.is_null() || identity_token_id == ObjectId::AnonymousIdentityToken_Encoding_DefaultBinary.as_node_id() {
             if self.anonymous {
                 result = GOOD;
             } else {
@@ -71,13 +85,107 @@ impl Endpoint {
     }
 }
 
-#[derive(Clone)]
-/// Structure that captures diagnostics information for the server
-pub struct ServerDiagnostics {}
+#[derive(Clone, Debug)]
+/// Structure that captures diagnostics information for the server. The counts are shown in the
+/// address space by the Server_ServerDiagnostics_ServerDiagnosticsSummary variables.
+pub struct ServerDiagnostics {
+    summary: ServerDiagnosticsSummaryDataType,
+    /// The latest diagnostics of each session and its subscriptions, by session id. Sessions
+    /// push their diagnostics here so they can be read without locking the session.
+    sessions: HashMap<NodeId, (SessionDiagnosticsDataType, Vec<SubscriptionDiagnosticsDataType>)>,
+}
 
 impl ServerDiagnostics {
     pub fn new() -> ServerDiagnostics {
-        ServerDiagnostics {}
+        ServerDiagnostics {
+            summary: ServerDiagnosticsSummaryDataType {
+                server_view_count: 0,
+                current_session_count: 0,
+                cumulated_session_count: 0,
+                security_rejected_session_count: 0,
+                rejected_session_count: 0,
+                session_timeout_count: 0,
+                session_abort_count: 0,
+                current_subscription_count: 0,
+                cumulated_subscription_count: 0,
+                publishing_interval_count: 0,
+                security_rejected_requests_count: 0,
+                rejected_requests_count: 0,
+            },
+            sessions: HashMap::new(),
+        }
+    }
+
+    /// Returns a summary of the diagnostics
+    pub fn summary(&self) -> ServerDiagnosticsSummaryDataType {
+        self.summary.clone()
+    }
+
+    /// Replaces the diagnostics held for the session and its subscriptions with their current values
+    pub fn update_session(&mut self, session: &Session, subscriptions: &Subscriptions) {
+        self.sessions.insert(session.session_id.clone(), (session.session_diagnostics(subscriptions), subscriptions.diagnostics(&session.session_id)));
+    }
+
+    /// Returns the ids of the sessions that diagnostics are held for
+    pub fn session_ids(&self) -> Vec<NodeId> {
+        self.sessions.keys().cloned().collect()
+    }
+
+    /// Returns the diagnostics of every session
+    pub fn session_diagnostics(&self) -> Vec<SessionDiagnosticsDataType> {
+        self.sessions.values().map(|&(ref session, _)| session.clone()).collect()
+    }
+
+    /// Returns the diagnostics of the session with the id
+    pub fn find_session_diagnostics(&self, session_id: &NodeId) -> Option<SessionDiagnosticsDataType> {
+        self.sessions.get(session_id).map(|&(ref session, _)| session.clone())
+    }
+
+    /// Returns the diagnostics of every subscription, or only those of the session with the id
+    pub fn subscription_diagnostics(&self, session_id: Option<&NodeId>) -> Vec<SubscriptionDiagnosticsDataType> {
+        self.sessions.iter().filter(|&(id, _)| session_id.is_none() || session_id == Some(id)).flat_map(|(_, &(_, ref subscriptions))| subscriptions.iter().cloned()).collect()
+    }
+
+    /// Called when a session is created
+    pub fn on_create_session(&mut self) {
+        self.summary.current_session_count = self.summary.current_session_count.wrapping_add(1);
+        self.summary.cumulated_session_count = self.summary.cumulated_session_count.wrapping_add(1);
+    }
+
+    /// Called when a session is closed, whether by the client, a timeout or an abort
+    pub fn on_destroy_session(&mut self, session_id: &NodeId) {
+        if self.summary.current_session_count > 0 {
+            self.summary.current_session_count -= 1;
+        }
+        self.sessions.remove(session_id);
+    }
+
+    /// Called when a request to create a session is rejected. Security rejections are for
+    /// reasons such as an untrusted certificate.
+    pub fn on_rejected_session(&mut self, security_rejected: bool) {
+        self.summary.rejected_session_count = self.summary.rejected_session_count.wrapping_add(1);
+        if security_rejected {
+            self.summary.security_rejected_session_count = self.summary.security_rejected_session_count.wrapping_add(1);
+        }
+    }
+
+    /// Called when a session is closed because the client made no request within its timeout
+    pub fn on_session_timeout(&mut self) {
+        self.summary.session_timeout_count = self.summary.session_timeout_count.wrapping_add(1);
+    }
+
+    /// Called when a session ends because its connection ended without the session being closed
+    pub fn on_session_abort(&mut self) {
+        self.summary.session_abort_count = self.summary.session_abort_count.wrapping_add(1);
+    }
+
+    /// Called when a request is rejected with a service fault. Security rejections are for
+    /// reasons such as an invalid authentication token.
+    pub fn on_rejected_request(&mut self, security_rejected: bool) {
+        self.summary.rejected_requests_count = self.summary.rejected_requests_count.wrapping_add(1);
+        if security_rejected {
+            self.summary.security_rejected_requests_count = self.summary.security_rejected_requests_count.wrapping_add(1);
+        }
     }
 }
 
@@ -113,8 +221,18 @@ pub struct ServerState {
     /// The next subscription id - subscriptions are shared across the whole server. Initial value
     /// is a random u32.
     pub last_subscription_id: UInt32,
+    /// Maximum number of sessions, 0 means no limit (danger)
+    pub max_sessions: usize,
     /// Maximum number of subscriptions per session, 0 means no limit (danger)
     pub max_subscriptions: usize,
+    /// Maximum number of publish requests queued per session, 0 means no limit (danger)
+    pub max_publish_requests: usize,
+    /// Maximum number of browse continuation points per session, 0 means no limit (danger)
+    pub max_browse_continuation_points: usize,
+    /// Maximum number of history continuation points per session, 0 means no limit (danger)
+    pub max_history_continuation_points: usize,
+    /// Limits on the sampling interval and queue size of monitored items
+    pub monitored_item_limits: MonitoredItemLimits,
     /// Minimum publishing interval
     pub min_publishing_interval: Duration,
     /// Maxmimum keep alive count
@@ -122,10 +240,65 @@ pub struct ServerState {
     /// Sets the abort flag that terminates the associated server
     pub abort: bool,
     /// Diagnostic information
-    pub diagnostics: ServerDiagnostics,
+    pub diagnostics: Arc<Mutex<ServerDiagnostics>>,
+    /// Sessions of the server, including those whose connection has ended
+    pub session_manager: SessionManager,
+    /// Subscriptions of the server, each owned by one of the sessions
+    pub subscriptions: Subscriptions,
+    /// Answers FindServers requests when set
+    pub discovery_service: bool,
+    /// The servers registered with this server when it runs as a local discovery server, otherwise None
+    pub registered_servers: Option<RegisteredServers>,
 }
 
 impl ServerState {
+    /// Removes sessions whose connection has ended and which were not reactivated within their
+    /// timeout
+    pub fn expire_detached_sessions(&mut self, now: &DateTimeUTC) {
+        let expired = self.session_manager.expire_detached_sessions(now);
+        if !expired.is_empty() {
+            let mut diagnostics = self.diagnostics.lock().unwrap();
+            for session_id in &expired {
+                diagnostics.on_session_timeout();
+                diagnostics.on_destroy_session(session_id);
+                self.subscriptions.remove_session(session_id);
+            }
+        }
+    }
+
+    /// Ticks the subscriptions that are kept after their session was closed
+    pub fn tick_orphaned_subscriptions(&mut self) {
+        let address_space = self.address_space.lock().unwrap();
+        self.subscriptions.tick_orphans(&address_space);
+    }
+
+    /// Removes the registrations of servers that have not registered again within the
+    /// registration timeout, when the server is a local discovery server
+    pub fn expire_registered_servers(&mut self, now: &DateTimeUTC) {
+        if let Some(ref mut registered_servers) = self.registered_servers {
+            registered_servers.expire(now);
+        }
+    }
+
+    /// Refreshes the diagnostics held for each session and its subscriptions. This is done
+    /// periodically rather than on every request, so the diagnostics lag the sessions slightly.
+    pub fn update_session_diagnostics(&self) {
+        for session in self.session_manager.sessions() {
+            let session = session.lock().unwrap();
+            if session.is_created() {
+                self.diagnostics.lock().unwrap().update_session(&session, &self.subscriptions);
+            }
+        }
+    }
+
+    /// Adds an object to the address space for each session that has diagnostics and removes
+    /// those of sessions that have gone
+    pub fn update_session_diagnostics_nodes(&self) {
+        let session_ids = self.diagnostics.lock().unwrap().session_ids();
+        let mut address_space = self.address_space.lock().unwrap();
+        address_space.update_session_diagnostics_nodes(&session_ids, &self.diagnostics);
+    }
+
     pub fn endpoints(&self) -> Vec<EndpointDescription> {
         let mut endpoints: Vec<EndpointDescription> = Vec::with_capacity(self.endpoints.len());
         for e in &self.endpoints {
@@ -134,6 +307,69 @@ impl ServerState {
         endpoints
     }
 
+    /// Returns the urls that clients can discover the server through, i.e. the distinct urls
+    /// of its endpoints
+    pub fn discovery_urls(&self) -> Vec<String> {
+        let mut discovery_urls: Vec<String> = Vec::with_capacity(self.endpoints.len());
+        for e in &self.endpoints {
+            if !discovery_urls.contains(&e.endpoint_url) {
+                discovery_urls.push(e.endpoint_url.clone());
+            }
+        }
+        discovery_urls
+    }
+
+    /// Returns the description of the server that is supplied with its endpoints and by FindServers
+    pub fn application_description(&self) -> ApplicationDescription {
+        let discovery_urls = self.discovery_urls().iter().map(|url| UAString::from_str(url)).collect();
+        ApplicationDescription {
+            application_uri: self.application_uri.clone(),
+            product_uri: self.product_uri.clone(),
+            application_name: self.application_name.clone(),
+            application_type: if self.registered_servers.is_some() { ApplicationType::DiscoveryServer } else { ApplicationType::Server },
+            gateway_server_uri: UAString::null(),
+            discovery_profile_uri: UAString::null(),
+            discovery_urls: Some(discovery_urls),
+        }
+    }
+
+    /// Returns the description of the server that it registers with discovery servers
+    pub fn registered_server(&self, is_online: bool) -> RegisteredServer {
+        let application_description = self.application_description();
+        RegisteredServer {
+            server_uri: application_description.application_uri,
+            product_uri: application_description.product_uri,
+            server_names: Some(vec![application_description.application_name]),
+            server_type: application_description.application_type,
+            gateway_server_uri: UAString::null(),
+            discovery_urls: application_description.discovery_urls,
+            semaphore_file_path: UAString::null(),
+            is_online,
+        }
+    }
+
+    /// Returns the announcements of the server's endpoints for mDNS, one for each distinct url.
+    /// The instance names of a server with several urls are numbered to tell them apart.
+    pub fn service_announcements(&self) -> Vec<ServiceAnnouncement> {
+        let capabilities = if self.registered_servers.is_some() {
+            vec![constants::LDS_SERVER_CAPABILITY.to_string()]
+        } else {
+            vec![constants::NO_SERVER_CAPABILITY.to_string()]
+        };
+        let mut endpoint_urls: Vec<String> = Vec::new();
+        for e in self.endpoints() {
+            let endpoint_url = e.endpoint_url.as_ref().to_string();
+            if !endpoint_urls.contains(&endpoint_url) {
+                endpoint_urls.push(endpoint_url);
+            }
+        }
+        let application_name = self.application_name.text.as_ref();
+        endpoint_urls.iter().enumerate().filter_map(|(idx, endpoint_url)| {
+            let instance_name = if endpoint_urls.len() > 1 { format!("{} ({})", application_name, idx + 1) } else { application_name.to_string() };
+            ServiceAnnouncement::from_endpoint_url(&instance_name, endpoint_url, &capabilities)
+        }).collect()
+    }
+
     pub fn find_endpoint(&self, endpoint_url: &str) -> Option<Endpoint> {
         for e in &self.endpoints {
             if let Ok(result) = url_matches_except_host(&e.endpoint_url, endpoint_url) {
@@ -166,15 +402,7 @@ impl ServerState {
 
         EndpointDescription {
             endpoint_url: UAString::from_str(&endpoint.endpoint_url),
-            server: ApplicationDescription {
-                application_uri: self.application_uri.clone(),
-                product_uri: self.product_uri.clone(),
-                application_name: self.application_name.clone(),
-                application_type: ApplicationType::Server,
-                gateway_server_uri: UAString::null(),
-                discovery_profile_uri: UAString::null(),
-                discovery_urls: None,
-            },
+            server: self.application_description(),
             server_certificate: self.server_certificate_as_byte_string(),
             security_mode: endpoint.security_mode,
             security_policy_uri: endpoint.security_policy_uri.clone(),
@@ -220,9 +448,25 @@ impl Server {
         let start_time = DateTime::now();
         let servers = vec![config.application_uri.clone()];
         let base_endpoint = format!("opc.tcp://{}:{}", config.tcp_config.host, config.tcp_config.port);
+        let max_sessions = config.max_sessions as usize;
         let max_subscriptions = config.max_subscriptions as usize;
+        let max_publish_requests = config.max_publish_requests as usize;
+        let max_browse_continuation_points = config.max_browse_continuation_points as usize;
+        let max_history_continuation_points = config.max_history_continuation_points as usize;
+        let monitored_item_limits = MonitoredItemLimits {
+            min_sampling_interval: config.min_sampling_interval,
+            max_sampling_interval: config.max_sampling_interval,
+            max_data_change_queue_size: config.max_data_change_queue_size as usize,
+            max_event_queue_size: config.max_event_queue_size as usize,
+        };
         let address_space = Arc::new(Mutex::new(AddressSpace::new()));
-        let diagnostics = ServerDiagnostics::new();
+        let diagnostics = Arc::new(Mutex::new(ServerDiagnostics::new()));
+        let discovery_service = config.discovery_service;
+        let registered_servers = if config.local_discovery_server {
+            Some(RegisteredServers::new(constants::REGISTERED_SERVER_TIMEOUT_MS, constants::MAX_REGISTERED_SERVERS))
+        } else {
+            None
+        };
         // TODO max string, byte string and array lengths
 
         let mut endpoints = Vec::new();
@@ -243,6 +487,7 @@ impl Server {
                 anonymous,
                 user: e.user.clone(),
                 pass: if e.pass.is_some() { Some(e.pass.as_ref().unwrap().clone().into_bytes()) } else { None },
+                node_management: if let Some(node_management) = e.node_management.as_ref() { *node_management } else { false },
             });
         }
 
@@ -262,7 +507,9 @@ impl Server {
                 // a certificate and private key if they do not exist.
                 if config.create_sample_keypair {
                     info!("Creating sample application instance certificate and private key");
-                    let result = certificate_store.create_and_store_application_instance_cert(&X509Data::sample_cert(), false);
+                    let mut args = X509Data::sample_cert();
+                    args.application_uri = config.application_uri.clone();
+                    let result = certificate_store.create_and_store_application_instance_cert(&args, false);
                     if let Err(err) = result {
                         error!("Certificate creation failed, error = {}", err);
                         (None, None)
@@ -300,11 +547,20 @@ impl Server {
             server_pkey,
             address_space,
             last_subscription_id: 0,
+            max_sessions,
             max_subscriptions,
+            max_publish_requests,
+            max_browse_continuation_points,
+            max_history_continuation_points,
+            monitored_item_limits,
             min_publishing_interval: constants::MIN_PUBLISHING_INTERVAL,
             max_keep_alive_count: constants::MAX_KEEP_ALIVE_COUNT,
             diagnostics,
             abort: false,
+            session_manager: SessionManager::new(),
+            subscriptions: Subscriptions::new(),
+            discovery_service,
+            registered_servers,
         };
 
         // Set some values in the address space from the server state
@@ -331,16 +587,21 @@ impl Server {
 
     // Terminates the running server
     pub fn abort(&mut self) {
-        let mut server_state = self.server_state.lock().unwrap();
-        server_state.abort = true;
+        {
+            let mut server_state = self.server_state.lock().unwrap();
+            server_state.abort = true;
+        }
+        // Tell the discovery servers that the server is going away
+        registration::register_with_discovery_servers(&self.server_state, false);
     }
 
     /// Runs the server
     pub fn run(&mut self) {
-        let (host, port, _) = {
+        let (host, port, _, discovery_registration_interval, multicast_discovery) = {
             let server_state = self.server_state.lock().unwrap();
             let config = server_state.config.lock().unwrap();
-            (config.tcp_config.host.clone(), config.tcp_config.port, server_state.base_endpoint.clone())
+            let discovery_registration_interval = if config.discovery_server_urls.is_empty() { None } else { Some(config.discovery_registration_interval) };
+            (config.tcp_config.host.clone(), config.tcp_config.port, server_state.base_endpoint.clone(), discovery_registration_interval, config.multicast_discovery)
         };
         let sock_addr = (host.as_str(), port);
         let listener = TcpListener::bind(&sock_addr).unwrap();
@@ -362,6 +623,66 @@ impl Server {
                 }
             }
         }
+        // Sessions whose connection has ended are expired if they are not reactivated in time
+        let _session_expiry = {
+            let server_state = self.server_state.clone();
+            PollingAction::new(constants::SESSION_EXPIRY_INTERVAL_MS, move || {
+                let mut server_state = server_state.lock().unwrap();
+                server_state.expire_detached_sessions(&UTC::now());
+                server_state.update_session_diagnostics();
+                server_state.update_session_diagnostics_nodes();
+            })
+        };
+        // Subscriptions of sessions that were closed without deleting them run out their lifetime
+        // unless another session takes them
+        let _orphaned_subscriptions = {
+            let server_state = self.server_state.clone();
+            PollingAction::new(constants::SUBSCRIPTION_TIMER_RATE_MS as u32, move || {
+                let mut server_state = server_state.lock().unwrap();
+                server_state.tick_orphaned_subscriptions();
+            })
+        };
+        // A local discovery server forgets servers that stop registering
+        let _registration_expiry = {
+            let server_state = self.server_state.clone();
+            PollingAction::new(constants::REGISTERED_SERVER_EXPIRY_INTERVAL_MS, move || {
+                let mut server_state = server_state.lock().unwrap();
+                server_state.expire_registered_servers(&UTC::now());
+            })
+        };
+
+        // The server registers with discovery servers on the first poll and then now and again
+        // before its registration expires. Registering happens on the timer so a discovery server
+        // that cannot be reached does not hold up the server.
+        let _discovery_registration = if let Some(discovery_registration_interval) = discovery_registration_interval {
+            let server_state = self.server_state.clone();
+            let discovery_registration_interval = time::Duration::seconds(discovery_registration_interval as i64);
+            let mut next_registration = UTC::now();
+            Some(PollingAction::new(constants::DISCOVERY_REGISTRATION_POLL_INTERVAL_MS, move || {
+                let now = UTC::now();
+                if now >= next_registration {
+                    registration::register_with_discovery_servers(&server_state, true);
+                    next_registration = now + discovery_registration_interval;
+                }
+            }))
+        } else {
+            None
+        };
+
+        // The server announces its endpoints on the local network for as long as it runs
+        let _mdns_responder = if multicast_discovery {
+            let announcements = self.server_state.lock().unwrap().service_announcements();
+            match MdnsResponder::new(announcements) {
+                Ok(mdns_responder) => Some(mdns_responder),
+                Err(err) => {
+                    error!("Server cannot announce its endpoints through mDNS, error = {}", err);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
         info!("Waiting for Connection");
 
         loop {
@@ -2,10 +2,114 @@ use std::result::Result;
 
 use opcua_types::*;
 
+use address_space::address_space::AddressSpace;
+use address_space::types::NodeType;
+use constants;
+use history::aggregates::{self, AggregateType};
 use server::ServerState;
 use session::Session;
 use services::Service;
 
+/// The kinds of history read that are supported
+enum HistoryReadDetails {
+    RawModified(ReadRawModifiedDetails),
+    AtTime(ReadAtTimeDetails),
+    Processed(ReadProcessedDetails),
+}
+
+impl HistoryReadDetails {
+    /// Decodes and validates the details of a history read
+    fn from_extension_object(history_read_details: &ExtensionObject) -> Result<HistoryReadDetails, StatusCode> {
+        let details_type_id = &history_read_details.node_id;
+        if details_type_id == &ObjectId::ReadRawModifiedDetails_Encoding_DefaultBinary.as_node_id() {
+            let details = history_read_details.decode_inner::<ReadRawModifiedDetails>()?;
+            // At least two of the start time, end time and number of values must be specified
+            let start_time_specified = details.start_time.checked_ticks() != 0;
+            let end_time_specified = details.end_time.checked_ticks() != 0;
+            if !start_time_specified && !end_time_specified {
+                Err(BAD_HISTORY_OPERATION_INVALID)
+            } else if (!start_time_specified || !end_time_specified) && details.num_values_per_node == 0 {
+                Err(BAD_HISTORY_OPERATION_INVALID)
+            } else {
+                Ok(HistoryReadDetails::RawModified(details))
+            }
+        } else if details_type_id == &ObjectId::ReadAtTimeDetails_Encoding_DefaultBinary.as_node_id() {
+            let details = history_read_details.decode_inner::<ReadAtTimeDetails>()?;
+            if details.req_times.as_ref().map(|req_times| req_times.is_empty()).unwrap_or(true) {
+                Err(BAD_HISTORY_OPERATION_INVALID)
+            } else {
+                Ok(HistoryReadDetails::AtTime(details))
+            }
+        } else if details_type_id == &ObjectId::ReadProcessedDetails_Encoding_DefaultBinary.as_node_id() {
+            let details = history_read_details.decode_inner::<ReadProcessedDetails>()?;
+            if details.start_time.checked_ticks() == 0 || details.end_time.checked_ticks() == 0 || details.processing_interval < 0.0 {
+                Err(BAD_HISTORY_OPERATION_INVALID)
+            } else if aggregates::interval_count(&details.start_time, &details.end_time, details.processing_interval) > constants::MAX_HISTORY_PROCESSED_INTERVALS {
+                Err(BAD_HISTORY_OPERATION_INVALID)
+            } else {
+                Ok(HistoryReadDetails::Processed(details))
+            }
+        } else if details_type_id == &ObjectId::ReadEventDetails_Encoding_DefaultBinary.as_node_id() {
+            Err(BAD_HISTORY_OPERATION_UNSUPPORTED)
+        } else {
+            Err(BAD_HISTORY_OPERATION_INVALID)
+        }
+    }
+}
+
+/// The kinds of history update that are supported
+enum HistoryUpdateDetails {
+    UpdateData(UpdateDataDetails),
+    DeleteRawModified(DeleteRawModifiedDetails),
+    DeleteAtTime(DeleteAtTimeDetails),
+}
+
+impl HistoryUpdateDetails {
+    /// Decodes and validates the details of a history update
+    fn from_extension_object(history_update_details: &ExtensionObject) -> Result<HistoryUpdateDetails, StatusCode> {
+        let details_type_id = &history_update_details.node_id;
+        if details_type_id == &ObjectId::UpdateDataDetails_Encoding_DefaultBinary.as_node_id() {
+            let details = history_update_details.decode_inner::<UpdateDataDetails>()?;
+            // Remove only applies to events
+            if details.perform_insert_replace == PerformUpdateType::Remove {
+                Err(BAD_HISTORY_OPERATION_INVALID)
+            } else if details.update_values.as_ref().map(|update_values| update_values.is_empty()).unwrap_or(true) {
+                Err(BAD_HISTORY_OPERATION_INVALID)
+            } else {
+                Ok(HistoryUpdateDetails::UpdateData(details))
+            }
+        } else if details_type_id == &ObjectId::DeleteRawModifiedDetails_Encoding_DefaultBinary.as_node_id() {
+            let details = history_update_details.decode_inner::<DeleteRawModifiedDetails>()?;
+            if details.start_time.checked_ticks() == 0 || details.end_time.checked_ticks() == 0 {
+                Err(BAD_HISTORY_OPERATION_INVALID)
+            } else {
+                Ok(HistoryUpdateDetails::DeleteRawModified(details))
+            }
+        } else if details_type_id == &ObjectId::DeleteAtTimeDetails_Encoding_DefaultBinary.as_node_id() {
+            let details = history_update_details.decode_inner::<DeleteAtTimeDetails>()?;
+            if details.req_times.as_ref().map(|req_times| req_times.is_empty()).unwrap_or(true) {
+                Err(BAD_HISTORY_OPERATION_INVALID)
+            } else {
+                Ok(HistoryUpdateDetails::DeleteAtTime(details))
+            }
+        } else if details_type_id == &ObjectId::UpdateEventDetails_Encoding_DefaultBinary.as_node_id() ||
+            details_type_id == &ObjectId::DeleteEventDetails_Encoding_DefaultBinary.as_node_id() ||
+            details_type_id == &ObjectId::UpdateStructureDataDetails_Encoding_DefaultBinary.as_node_id() {
+            Err(BAD_HISTORY_OPERATION_UNSUPPORTED)
+        } else {
+            Err(BAD_HISTORY_OPERATION_INVALID)
+        }
+    }
+
+    fn node_id(&self) -> &NodeId {
+        match self {
+            &HistoryUpdateDetails::UpdateData(ref details) => &details.node_id,
+            &HistoryUpdateDetails::DeleteRawModified(ref details) => &details.node_id,
+            &HistoryUpdateDetails::DeleteAtTime(ref details) => &details.node_id,
+        }
+    }
+}
+
 pub struct AttributeService {}
 
 impl Service for AttributeService {}
@@ -23,7 +127,7 @@ impl AttributeService {
     /// elements or to read ranges of elements of the composite. Servers may make historical
     /// values available to Clients using this Service, although the historical values themselves
     /// are not visible in the AddressSpace.
-    pub fn read(&self, server_state: &mut ServerState, _: &mut Session, request: ReadRequest) -> Result<SupportedMessage, StatusCode> {
+    pub fn read(&self, server_state: &mut ServerState, session: &mut Session, request: ReadRequest) -> Result<SupportedMessage, StatusCode> {
         // Read nodes and their attributes
         let timestamps_to_return = request.timestamps_to_return;
 
@@ -50,15 +154,25 @@ impl AttributeService {
                 };
 
                 // Node node found
-                if let Some(node) = address_space.find_node(&node_to_read.node_id) {
+                if let Some(node) = address_space.find_node(session.resolve_node_id(&address_space, &node_to_read.node_id)) {
                     if let Ok(attribute_id) = AttributeId::from_u32(node_to_read.attribute_id) {
                         if let Some(attribute) = node.as_node().find_attribute(attribute_id) {
-                            if !node_to_read.index_range.is_null() {
-                                // Index ranges are not supported
-                                result_value.status = Some(BAD_NOT_READABLE);
+                            // Result value is clone from the attribute, or the part of it in the index range
+                            let value = match NumericRange::parse(&node_to_read.index_range) {
+                                Ok(NumericRange::None) => Ok(attribute.value.clone()),
+                                Ok(index_range) => {
+                                    if let Some(ref value) = attribute.value {
+                                        value.range_of(&index_range).map(|value| Some(value))
+                                    } else {
+                                        Err(BAD_INDEX_RANGE_NO_DATA)
+                                    }
+                                }
+                                Err(err) => Err(err)
+                            };
+                            if let Err(err) = value {
+                                result_value.status = Some(err);
                             } else {
-                                // Result value is clone from the attribute
-                                result_value.value = attribute.value.clone();
+                                result_value.value = value.unwrap();
                                 result_value.status = attribute.status;
                                 match timestamps_to_return {
                                     TimestampsToReturn::Source => {
@@ -117,34 +231,35 @@ impl AttributeService {
     /// constructed Attribute values whose elements are indexed, such as an array, this Service
     /// allows Clients to write the entire set of indexed values as a composite, to write individual
     /// elements or to write ranges of elements of the composite.
-    pub fn write(&self, server_state: &mut ServerState, _: &mut Session, request: WriteRequest) -> Result<SupportedMessage, StatusCode> {
+    pub fn write(&self, server_state: &mut ServerState, session: &mut Session, request: WriteRequest) -> Result<SupportedMessage, StatusCode> {
         let results = if let Some(ref nodes_to_write) = request.nodes_to_write {
             let mut results: Vec<StatusCode> = Vec::with_capacity(nodes_to_write.len());
 
-            let address_space = server_state.address_space.lock().unwrap();
+            let mut address_space = server_state.address_space.lock().unwrap();
 
             for node_to_write in nodes_to_write {
-                if let Some(node) = address_space.find_node(&node_to_write.node_id) {
+                let node_id = session.resolve_node_id(&address_space, &node_to_write.node_id);
+                let result = if let Some(node) = address_space.find_node_mut(node_id) {
                     if let Ok(attribute_id) = AttributeId::from_u32(node_to_write.attribute_id) {
-                        let write_result;
-                        // Index ranges are not supported
-                        if !node_to_write.index_range.is_null() {
-                            write_result = BAD_WRITE_NOT_SUPPORTED;
-                        } else if node.as_node().find_attribute(attribute_id).is_some() {
-                            // TODO implement write, checking masks to see if the action is allowed
-                            write_result = BAD_WRITE_NOT_SUPPORTED;
-                        } else {
-                            write_result = BAD_WRITE_NOT_SUPPORTED;
+                        match NumericRange::parse(&node_to_write.index_range) {
+                            Ok(index_range) => AttributeService::write_node_attribute(node, attribute_id, &index_range, &node_to_write.value),
+                            Err(err) => err
                         }
-                        results.push(write_result);
                     } else {
                         warn!("Attribute id {} is invalid", node_to_write.attribute_id);
-                        results.push(BAD_ATTRIBUTE_ID_INVALID);
+                        BAD_ATTRIBUTE_ID_INVALID
                     }
                 } else {
                     warn!("Cannot find node id {:?}", node_to_write.node_id);
-                    results.push(BAD_NODE_ID_UNKNOWN);
+                    BAD_NODE_ID_UNKNOWN
+                };
+                // Written values are picked up by exception-based monitored items and values of
+                // historizing variables go into their history
+                if result == GOOD && node_to_write.attribute_id == AttributeId::Value as UInt32 {
+                    address_space.record_value_change(node_id);
+                    address_space.historize_value(node_id);
                 }
+                results.push(result);
             }
             Some(results)
         } else {
@@ -160,4 +275,434 @@ impl AttributeService {
 
         Ok(SupportedMessage::WriteResponse(response))
     }
+
+    /// Spec:
+    ///
+    /// This Service is used to read historical values or Events of one or more Nodes.
+    ///
+    /// Raw, at time and processed reads of variable values are supported. The values come from
+    /// the address space's historical data provider.
+    pub fn history_read(&self, server_state: &mut ServerState, session: &mut Session, request: HistoryReadRequest) -> Result<SupportedMessage, StatusCode> {
+        if request.nodes_to_read.is_none() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+        let nodes_to_read = request.nodes_to_read.as_ref().unwrap();
+        if nodes_to_read.is_empty() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+        if request.timestamps_to_return == TimestampsToReturn::Neither {
+            return Ok(self.service_fault(&request.request_header, BAD_TIMESTAMPS_TO_RETURN_INVALID));
+        }
+
+        let results = if request.release_continuation_points {
+            // Release the continuation points and return no data
+            nodes_to_read.iter().map(|node_to_read| {
+                let status_code = if session.remove_history_continuation_point(&node_to_read.continuation_point).is_some() {
+                    GOOD
+                } else {
+                    BAD_CONTINUATION_POINT_INVALID
+                };
+                AttributeService::history_read_result(status_code, ByteString::null(), ExtensionObject::null())
+            }).collect()
+        } else {
+            let details = match HistoryReadDetails::from_extension_object(&request.history_read_details) {
+                Ok(details) => details,
+                Err(status_code) => {
+                    return Ok(self.service_fault(&request.request_header, status_code));
+                }
+            };
+            // A processed read has an aggregate for each node
+            if let HistoryReadDetails::Processed(ref details) = details {
+                if details.aggregate_type.as_ref().map(|aggregate_type| aggregate_type.len()).unwrap_or(0) != nodes_to_read.len() {
+                    return Ok(self.service_fault(&request.request_header, BAD_AGGREGATE_LIST_MISMATCH));
+                }
+            }
+            let max_history_continuation_points = server_state.max_history_continuation_points;
+            let address_space = server_state.address_space.lock().unwrap();
+            nodes_to_read.iter().enumerate().map(|(node_index, node_to_read)| {
+                AttributeService::history_read_node(&address_space, session, max_history_continuation_points, &details, request.timestamps_to_return, node_index, node_to_read)
+            }).collect()
+        };
+
+        let response = HistoryReadResponse {
+            response_header: ResponseHeader::new_good(&request.request_header),
+            results: Some(results),
+            diagnostic_infos: None,
+        };
+        Ok(SupportedMessage::HistoryReadResponse(response))
+    }
+
+    /// Reads the history of a single node, either continuing from a continuation point or by
+    /// asking the historical data provider for values. Processed reads compute the aggregate at
+    /// the node's index from the raw values.
+    fn history_read_node(address_space: &AddressSpace, session: &mut Session, max_history_continuation_points: usize, details: &HistoryReadDetails, timestamps_to_return: TimestampsToReturn, node_index: usize, node_to_read: &HistoryReadValueId) -> HistoryReadResult {
+        let node_id = &node_to_read.node_id;
+
+        // Continue a previous read
+        if !node_to_read.continuation_point.is_null() {
+            return match session.remove_history_continuation_point(&node_to_read.continuation_point) {
+                Some(continuation_point) => {
+                    if continuation_point.node_id != *node_id {
+                        AttributeService::history_read_result(BAD_CONTINUATION_POINT_INVALID, ByteString::null(), ExtensionObject::null())
+                    } else {
+                        AttributeService::history_data_result(session, max_history_continuation_points, node_id, continuation_point.num_values_per_node, continuation_point.data_values)
+                    }
+                }
+                None => AttributeService::history_read_result(BAD_CONTINUATION_POINT_INVALID, ByteString::null(), ExtensionObject::null())
+            };
+        }
+
+        // Only the history of variables that may be read
+        let status_code = match address_space.find_node(node_id) {
+            Some(&NodeType::Variable(ref variable)) => {
+                if !variable.is_history_readable() {
+                    BAD_NOT_READABLE
+                } else if !variable.is_user_history_readable() {
+                    BAD_USER_ACCESS_DENIED
+                } else {
+                    GOOD
+                }
+            }
+            Some(_) => BAD_HISTORY_OPERATION_UNSUPPORTED,
+            None => BAD_NODE_ID_UNKNOWN
+        };
+        if status_code != GOOD {
+            return AttributeService::history_read_result(status_code, ByteString::null(), ExtensionObject::null());
+        }
+        let index_range = match NumericRange::parse(&node_to_read.index_range) {
+            Ok(index_range) => index_range,
+            Err(status_code) => {
+                return AttributeService::history_read_result(status_code, ByteString::null(), ExtensionObject::null());
+            }
+        };
+
+        let historical_data_provider = address_space.historical_data_provider();
+        let historical_data_provider = historical_data_provider.lock().unwrap();
+        let result = match details {
+            &HistoryReadDetails::RawModified(ref details) => {
+                // The provider returns values oldest first so the range is put in order and the
+                // values reversed afterwards if the client asked for them backwards. An unspecified
+                // end time reads forwards from the start time and an unspecified start time reads
+                // backwards from the end time. Bounds are only returned for raw values when both
+                // are specified.
+                let start_time_specified = details.start_time.checked_ticks() != 0;
+                let end_time_specified = details.end_time.checked_ticks() != 0;
+                let (start_time, end_time, reverse, return_bounds) = if !end_time_specified {
+                    (details.start_time.clone(), DateTime::from_chrono(&endtimes_chrono()), false, false)
+                } else if !start_time_specified {
+                    (details.start_time.clone(), details.end_time.clone(), true, false)
+                } else if details.start_time.checked_ticks() <= details.end_time.checked_ticks() {
+                    (details.start_time.clone(), details.end_time.clone(), false, details.return_bounds)
+                } else {
+                    (details.end_time.clone(), details.start_time.clone(), true, details.return_bounds)
+                };
+                let data_values = if details.is_read_modified {
+                    historical_data_provider.read_modified(node_id, &start_time, &end_time)
+                } else {
+                    historical_data_provider.read_raw(node_id, &start_time, &end_time, return_bounds)
+                };
+                data_values.map(|mut data_values| {
+                    if reverse {
+                        data_values.reverse();
+                    }
+                    (details.num_values_per_node as usize, data_values)
+                })
+            }
+            &HistoryReadDetails::AtTime(ref details) => {
+                let req_times = details.req_times.as_ref().unwrap();
+                historical_data_provider.read_at_time(node_id, req_times, details.use_simple_bounds).map(|data_values| (0, data_values))
+            }
+            &HistoryReadDetails::Processed(ref details) => {
+                let aggregate_type = &details.aggregate_type.as_ref().unwrap()[node_index];
+                AggregateType::from_node_id(aggregate_type).and_then(|aggregate_type| {
+                    let configuration = aggregates::configuration(&details.aggregate_configuration)?;
+                    let (start_time, end_time) = if details.start_time.checked_ticks() <= details.end_time.checked_ticks() {
+                        (&details.start_time, &details.end_time)
+                    } else {
+                        (&details.end_time, &details.start_time)
+                    };
+                    // The bounds are needed to interpolate at the start and end
+                    let data_values = historical_data_provider.read_raw(node_id, start_time, end_time, true)?;
+                    Ok((0, aggregates::process(aggregate_type, &configuration, &data_values, &details.start_time, &details.end_time, details.processing_interval)))
+                })
+            }
+        };
+
+        match result {
+            Ok((num_values_per_node, data_values)) => {
+                let data_values = data_values.into_iter().map(|data_value| {
+                    AttributeService::history_data_value(data_value, &index_range, timestamps_to_return)
+                }).collect();
+                AttributeService::history_data_result(session, max_history_continuation_points, node_id, num_values_per_node, data_values)
+            }
+            Err(status_code) => AttributeService::history_read_result(status_code, ByteString::null(), ExtensionObject::null())
+        }
+    }
+
+    /// Creates the result for a history read holding up to num_values_per_node values (0 means no
+    /// limit). Any values beyond that are stored in a continuation point on the session.
+    fn history_data_result(session: &mut Session, max_history_continuation_points: usize, node_id: &NodeId, num_values_per_node: usize, mut data_values: Vec<DataValue>) -> HistoryReadResult {
+        let continuation_point = if num_values_per_node == 0 || data_values.len() <= num_values_per_node {
+            ByteString::null()
+        } else {
+            let remaining_data_values = data_values.split_off(num_values_per_node);
+            match session.add_history_continuation_point(max_history_continuation_points, node_id, num_values_per_node, remaining_data_values) {
+                Ok(continuation_point) => continuation_point,
+                Err(status_code) => {
+                    return AttributeService::history_read_result(status_code, ByteString::null(), ExtensionObject::null());
+                }
+            }
+        };
+        let status_code = if data_values.is_empty() { GOOD_NO_DATA } else { GOOD };
+        let history_data = ExtensionObject::from_encodable(ObjectId::HistoryData_Encoding_DefaultBinary.as_node_id(), HistoryData {
+            data_values: Some(data_values),
+        });
+        AttributeService::history_read_result(status_code, continuation_point, history_data)
+    }
+
+    fn history_read_result(status_code: StatusCode, continuation_point: ByteString, history_data: ExtensionObject) -> HistoryReadResult {
+        HistoryReadResult {
+            status_code,
+            continuation_point,
+            history_data,
+        }
+    }
+
+    /// Reduces a historical value to the part in the index range and the requested timestamps
+    fn history_data_value(mut data_value: DataValue, index_range: &NumericRange, timestamps_to_return: TimestampsToReturn) -> DataValue {
+        if *index_range != NumericRange::None {
+            let value = if let Some(ref value) = data_value.value {
+                value.range_of(index_range)
+            } else {
+                Err(BAD_INDEX_RANGE_NO_DATA)
+            };
+            match value {
+                Ok(value) => {
+                    data_value.value = Some(value);
+                }
+                Err(err) => {
+                    data_value.value = None;
+                    data_value.status = Some(err);
+                }
+            }
+        }
+        match timestamps_to_return {
+            TimestampsToReturn::Source => {
+                data_value.server_timestamp = None;
+                data_value.server_picoseconds = None;
+            }
+            TimestampsToReturn::Server => {
+                data_value.source_timestamp = None;
+                data_value.source_picoseconds = None;
+            }
+            TimestampsToReturn::Both | TimestampsToReturn::Neither => {}
+        }
+        data_value
+    }
+
+    /// Spec:
+    ///
+    /// This Service is used to update historical values or Events of one or more Nodes. Several
+    /// request parameters indicate how the Server is to update the historical value or Event.
+    /// Valid actions are Insert, Replace or Delete.
+    ///
+    /// Values of variables may be inserted, replaced, updated and deleted. Events and structure
+    /// data are not supported.
+    pub fn history_update(&self, server_state: &mut ServerState, _: &mut Session, request: HistoryUpdateRequest) -> Result<SupportedMessage, StatusCode> {
+        if request.history_update_details.is_none() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+        let history_update_details = request.history_update_details.as_ref().unwrap();
+        if history_update_details.is_empty() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+
+        let address_space = server_state.address_space.lock().unwrap();
+        let results = history_update_details.iter().map(|details| {
+            let (status_code, operation_results) = match HistoryUpdateDetails::from_extension_object(details) {
+                Ok(details) => AttributeService::history_update_node(&address_space, &details),
+                Err(status_code) => (status_code, None)
+            };
+            HistoryUpdateResult {
+                status_code,
+                operation_results,
+                diagnostic_infos: None,
+            }
+        }).collect();
+
+        let response = HistoryUpdateResponse {
+            response_header: ResponseHeader::new_good(&request.request_header),
+            results: Some(results),
+            diagnostic_infos: None,
+        };
+        Ok(SupportedMessage::HistoryUpdateResponse(response))
+    }
+
+    /// Updates the history of a single node through the historical data provider, returning the
+    /// status code of the update and the results of each operation in it.
+    fn history_update_node(address_space: &AddressSpace, details: &HistoryUpdateDetails) -> (StatusCode, Option<Vec<StatusCode>>) {
+        let node_id = details.node_id();
+
+        // Only the history of variables that may be written
+        let status_code = match address_space.find_node(node_id) {
+            Some(&NodeType::Variable(ref variable)) => {
+                if !variable.is_history_writable() {
+                    BAD_NOT_WRITABLE
+                } else if !variable.is_user_history_writable() {
+                    BAD_USER_ACCESS_DENIED
+                } else {
+                    GOOD
+                }
+            }
+            Some(_) => BAD_HISTORY_OPERATION_UNSUPPORTED,
+            None => BAD_NODE_ID_UNKNOWN
+        };
+        if status_code != GOOD {
+            return (status_code, None);
+        }
+
+        let historical_data_provider = address_space.historical_data_provider();
+        let mut historical_data_provider = historical_data_provider.lock().unwrap();
+        let result = match details {
+            &HistoryUpdateDetails::UpdateData(ref details) => {
+                let update_values = details.update_values.as_ref().unwrap();
+                historical_data_provider.update_values(node_id, details.perform_insert_replace, update_values).map(|operation_results| (GOOD, Some(operation_results)))
+            }
+            &HistoryUpdateDetails::DeleteRawModified(ref details) if details.is_delete_modified => {
+                // Modified values are not kept
+                Err(BAD_HISTORY_OPERATION_UNSUPPORTED)
+            }
+            &HistoryUpdateDetails::DeleteRawModified(ref details) => {
+                let (start_time, end_time) = if details.start_time.checked_ticks() <= details.end_time.checked_ticks() {
+                    (&details.start_time, &details.end_time)
+                } else {
+                    (&details.end_time, &details.start_time)
+                };
+                historical_data_provider.delete_raw(node_id, start_time, end_time).map(|deleted| {
+                    (if deleted == 0 { GOOD_NO_DATA } else { GOOD }, None)
+                })
+            }
+            &HistoryUpdateDetails::DeleteAtTime(ref details) => {
+                let req_times = details.req_times.as_ref().unwrap();
+                historical_data_provider.delete_at_time(node_id, req_times).map(|operation_results| (GOOD, Some(operation_results)))
+            }
+        };
+        match result {
+            Ok(result) => result,
+            Err(status_code) => (status_code, None)
+        }
+    }
+
+    /// Writes a value to the attribute of a node, returning the per item status code. The value
+    /// of a variable is governed by its AccessLevel / UserAccessLevel, all other attributes by
+    /// the WriteMask / UserWriteMask of the node. An index range writes the value into the part
+    /// of the existing array that it describes.
+    fn write_node_attribute(node: &mut NodeType, attribute_id: AttributeId, index_range: &NumericRange, data_value: &DataValue) -> StatusCode {
+        let current_value = node.as_node().find_attribute(attribute_id);
+        if current_value.is_none() {
+            // Node does not have this attribute
+            return BAD_ATTRIBUTE_ID_INVALID;
+        }
+
+        // Test if the attribute may be written
+        let is_variable_value = if let NodeType::Variable(_) = *node { attribute_id == AttributeId::Value } else { false };
+        match *node {
+            NodeType::Variable(ref variable) if is_variable_value => {
+                if !variable.is_writeable() {
+                    return BAD_NOT_WRITABLE;
+                } else if !variable.is_user_writeable() {
+                    return BAD_USER_ACCESS_DENIED;
+                }
+            }
+            _ => {
+                let write_mask_bit = AttributeService::write_mask_bit(attribute_id);
+                if node.as_node().write_mask().unwrap_or(0) & write_mask_bit == 0 {
+                    return BAD_NOT_WRITABLE;
+                } else if node.as_node().user_write_mask().unwrap_or(0) & write_mask_bit == 0 {
+                    return BAD_USER_ACCESS_DENIED;
+                }
+            }
+        }
+
+        if data_value.value.is_none() {
+            return BAD_TYPE_MISMATCH;
+        }
+
+        // Merge the value into the existing value when there is an index range
+        let current_value = current_value.unwrap().value;
+        let value = if *index_range == NumericRange::None {
+            data_value.value.clone().unwrap()
+        } else if let Some(mut value) = current_value.clone() {
+            if let Err(err) = value.set_range_of(index_range, data_value.value.as_ref().unwrap()) {
+                return err;
+            }
+            value
+        } else {
+            return BAD_INDEX_RANGE_NO_DATA;
+        };
+
+        // Test the type of the value
+        let type_matches = match *node {
+            NodeType::Variable(ref variable) if is_variable_value => {
+                value.is_valid_for(&variable.data_type(), variable.value_rank())
+            }
+            _ => {
+                // Other attributes must keep the type of their existing value
+                if let Some(ref current_value) = current_value {
+                    ::std::mem::discriminant(current_value) == ::std::mem::discriminant(&value)
+                } else {
+                    false
+                }
+            }
+        };
+        if !type_matches {
+            return BAD_TYPE_MISMATCH;
+        }
+
+        // The setter, if there is one, is called by the node
+        node.as_mut_node().set_attribute(attribute_id, AttributeService::value_to_write(value, data_value));
+        GOOD
+    }
+
+    /// Makes the data value that is stored from the one that was written. The server timestamp
+    /// is always set by the server, the source timestamp is taken from the client if it has one.
+    fn value_to_write(value: Variant, data_value: &DataValue) -> DataValue {
+        let now = DateTime::now();
+        DataValue {
+            value: Some(value),
+            status: Some(data_value.status.unwrap_or(GOOD)),
+            source_timestamp: Some(data_value.source_timestamp.clone().unwrap_or(now.clone())),
+            source_picoseconds: Some(data_value.source_picoseconds.unwrap_or(0)),
+            server_timestamp: Some(now),
+            server_picoseconds: Some(0),
+        }
+    }
+
+    /// Returns the bit in the WriteMask / UserWriteMask that controls writing the attribute.
+    fn write_mask_bit(attribute_id: AttributeId) -> UInt32 {
+        match attribute_id {
+            AttributeId::AccessLevel => WRITE_MASK_ACCESS_LEVEL,
+            AttributeId::ArrayDimensions => WRITE_MASK_ARRAY_DIMENSTIONS,
+            AttributeId::BrowseName => WRITE_MASK_BROWSE_NAME,
+            AttributeId::ContainsNoLoops => WRITE_MASK_CONTAINS_NO_LOOPS,
+            AttributeId::DataType => WRITE_MASK_DATA_TYPE,
+            AttributeId::Description => WRITE_MASK_DESCRIPTION,
+            AttributeId::DisplayName => WRITE_MASK_DISPLAY_NAME,
+            AttributeId::EventNotifier => WRITE_MASK_EVENT_NOTIFIER,
+            AttributeId::Executable => WRITE_MASK_EXECUTABLE,
+            AttributeId::Historizing => WRITE_MASK_HISTORIZING,
+            AttributeId::InverseName => WRITE_MASK_INVERSE_NAME,
+            AttributeId::IsAbstract => WRITE_MASK_IS_ABSTRACT,
+            AttributeId::MinimumSamplingInterval => WRITE_MASK_MINIMUM_SAMPLING_INTERVAL,
+            AttributeId::NodeClass => WRITE_MASK_NODE_CLASS,
+            AttributeId::NodeId => WRITE_MASK_NODE_ID,
+            AttributeId::Symmetric => WRITE_MASK_SYMMETRIC,
+            AttributeId::UserAccessLevel => WRITE_MASK_USER_ACCESS_LEVEL,
+            AttributeId::UserExecutable => WRITE_MASK_USER_EXECUTABLE,
+            AttributeId::UserWriteMask => WRITE_MASK_USER_WRITE_MASK,
+            AttributeId::ValueRank => WRITE_MASK_VALUE_RANK,
+            AttributeId::WriteMask => WRITE_MASK_WRITE_MASK,
+            AttributeId::Value => WRITE_MASK_VALUE_FOR_VARIABLE_TYPE,
+        }
+    }
 }
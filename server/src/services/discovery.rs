@@ -1,10 +1,14 @@
 use std::result::Result;
 
+use chrono::UTC;
+
 use opcua_types::*;
+use opcua_core::crypto::X509;
 
 use server::ServerState;
 use session::Session;
 use services::Service;
+use constants;
 
 pub struct DiscoveryService {}
 
@@ -22,5 +26,143 @@ impl DiscoveryService {
         };
         Ok(SupportedMessage::GetEndpointsResponse(response))
     }
-}
 
+    /// Returns a description of this server and, for a local discovery server, of each server
+    /// registered with it. The descriptions are limited to the requested server uris if any.
+    pub fn find_servers(&self, server_state: &mut ServerState, _: &mut Session, request: FindServersRequest) -> Result<SupportedMessage, StatusCode> {
+        if !server_state.discovery_service {
+            return Ok(self.service_fault(&request.request_header, BAD_SERVICE_UNSUPPORTED));
+        }
+        let locale_ids = request.locale_ids.unwrap_or(Vec::new());
+        let mut servers = vec![server_state.application_description()];
+        if let Some(ref registered_servers) = server_state.registered_servers {
+            servers.extend(registered_servers.registrations().iter().map(|registration| registration.application_description(&locale_ids)));
+        }
+        if let Some(ref server_uris) = request.server_uris {
+            if !server_uris.is_empty() {
+                servers.retain(|server| server_uris.contains(&server.application_uri));
+            }
+        }
+        let response = FindServersResponse {
+            response_header: ResponseHeader::new_good(&request.request_header),
+            servers: Some(servers),
+        };
+        Ok(SupportedMessage::FindServersResponse(response))
+    }
+
+    /// Returns a record for this local discovery server and for each server registered with it,
+    /// starting from the requested record id and having all of the requested capabilities
+    pub fn find_servers_on_network(&self, server_state: &mut ServerState, _: &mut Session, request: FindServersOnNetworkRequest) -> Result<SupportedMessage, StatusCode> {
+        let registered_servers = if let Some(ref registered_servers) = server_state.registered_servers {
+            registered_servers
+        } else {
+            return Ok(self.service_fault(&request.request_header, BAD_SERVICE_UNSUPPORTED));
+        };
+        let discovery_url = server_state.discovery_urls().first().map(|url| UAString::from_str(url)).unwrap_or(UAString::null());
+        let mut servers = vec![ServerOnNetwork {
+            record_id: 0,
+            server_name: server_state.application_name.text.clone(),
+            discovery_url,
+            server_capabilities: Some(vec![UAString::from_str(constants::LDS_SERVER_CAPABILITY)]),
+        }];
+        servers.extend(registered_servers.registrations().iter().map(|registration| registration.server_on_network()));
+        servers.sort_by_key(|server| server.record_id);
+        let server_capability_filter = request.server_capability_filter.clone().unwrap_or(Vec::new());
+        servers.retain(|server| {
+            let server_capabilities = server.server_capabilities.as_ref().unwrap();
+            server.record_id >= request.starting_record_id && server_capability_filter.iter().all(|capability| server_capabilities.contains(capability))
+        });
+        if request.max_records_to_return > 0 {
+            servers.truncate(request.max_records_to_return as usize);
+        }
+        let response = FindServersOnNetworkResponse {
+            response_header: ResponseHeader::new_good(&request.request_header),
+            last_counter_reset_time: registered_servers.last_counter_reset_time(),
+            servers: Some(servers),
+        };
+        Ok(SupportedMessage::FindServersOnNetworkResponse(response))
+    }
+
+    /// Registers a server with this local discovery server
+    pub fn register_server(&self, server_state: &mut ServerState, _: &mut Session, client_certificate: Option<&X509>, request: RegisterServerRequest) -> Result<SupportedMessage, StatusCode> {
+        let result = DiscoveryService::validate_registering_server(server_state, client_certificate, &request.server);
+        let result = if result.is_bad() {
+            result
+        } else if let Some(ref mut registered_servers) = server_state.registered_servers {
+            registered_servers.register(&request.server, None, &UTC::now())
+        } else {
+            BAD_SERVICE_UNSUPPORTED
+        };
+        if result.is_bad() {
+            Ok(self.service_fault(&request.request_header, result))
+        } else {
+            let response = RegisterServerResponse {
+                response_header: ResponseHeader::new_good(&request.request_header),
+            };
+            Ok(SupportedMessage::RegisterServerResponse(response))
+        }
+    }
+
+    /// Registers a server with this local discovery server along with its discovery configuration.
+    /// Only the mDNS configuration is supported.
+    pub fn register_server2(&self, server_state: &mut ServerState, _: &mut Session, client_certificate: Option<&X509>, request: RegisterServer2Request) -> Result<SupportedMessage, StatusCode> {
+        let result = DiscoveryService::validate_registering_server(server_state, client_certificate, &request.server);
+        if result.is_bad() {
+            return Ok(self.service_fault(&request.request_header, result));
+        }
+        let mut mdns_configuration = None;
+        let configuration_results = if let Some(ref discovery_configuration) = request.discovery_configuration {
+            let results = discovery_configuration.iter().map(|configuration| {
+                if configuration.node_id != ObjectId::MdnsDiscoveryConfiguration_Encoding_DefaultBinary.as_node_id() {
+                    BAD_NOT_SUPPORTED
+                } else if let Ok(configuration) = configuration.decode_inner::<MdnsDiscoveryConfiguration>() {
+                    mdns_configuration = Some(configuration);
+                    GOOD
+                } else {
+                    BAD_DECODING_ERROR
+                }
+            }).collect();
+            Some(results)
+        } else {
+            None
+        };
+        let result = if let Some(ref mut registered_servers) = server_state.registered_servers {
+            registered_servers.register(&request.server, mdns_configuration, &UTC::now())
+        } else {
+            BAD_SERVICE_UNSUPPORTED
+        };
+        if result.is_bad() {
+            Ok(self.service_fault(&request.request_header, result))
+        } else {
+            let response = RegisterServer2Response {
+                response_header: ResponseHeader::new_good(&request.request_header),
+                configuration_results,
+                diagnostic_infos: None,
+            };
+            Ok(SupportedMessage::RegisterServer2Response(response))
+        }
+    }
+
+    /// Tests that the registration comes from the server it describes. Only a local discovery
+    /// server takes registrations and they must arrive on a secure channel signed with a trusted
+    /// certificate whose application uri is the uri of the registering server.
+    fn validate_registering_server(server_state: &ServerState, client_certificate: Option<&X509>, server: &RegisteredServer) -> StatusCode {
+        if server_state.registered_servers.is_none() {
+            BAD_SERVICE_UNSUPPORTED
+        } else if let Some(client_certificate) = client_certificate {
+            let result = server_state.certificate_store.lock().unwrap().validate_or_reject_application_instance_cert(client_certificate);
+            if result.is_bad() {
+                error!("Registration of server {} is rejected because its certificate is not trusted", server.server_uri.as_ref());
+                result
+            } else if client_certificate.application_uri().as_ref().map(|uri| uri.as_str()) != Some(server.server_uri.as_ref()) {
+                error!("Registration of server {} is rejected because its certificate has application uri {:?}", server.server_uri.as_ref(), client_certificate.application_uri());
+                BAD_CERTIFICATE_URI_INVALID
+            } else {
+                GOOD
+            }
+        } else {
+            error!("Registration of server {} is rejected because its secure channel is not signed", server.server_uri.as_ref());
+            BAD_SECURITY_CHECKS_FAILED
+        }
+    }
+}
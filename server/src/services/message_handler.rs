@@ -1,13 +1,19 @@
 use std::sync::{Arc, Mutex};
 
+use chrono::UTC;
+
 use opcua_types::*;
+use opcua_core::comms::secure_channel::SecureChannel;
+use opcua_core::crypto::{SecurityPolicy, X509};
 
 use server::ServerState;
-use session::Session;
+use session::{Session, UserIdentity};
 
 use services::attribute::*;
 use services::discovery::*;
+use services::method::*;
 use services::monitored_item::*;
+use services::node_management::*;
 use services::session::*;
 use services::subscription::*;
 use services::view::*;
@@ -18,12 +24,18 @@ pub struct MessageHandler {
     server_state: Arc<Mutex<ServerState>>,
     /// Session state
     session: Arc<Mutex<Session>>,
+    /// Certificate of the client if it signs the messages of its secure channel
+    client_certificate: Option<X509>,
     /// Attribute service
     attribute_service: AttributeService,
     /// Discovery service
     discovery_service: DiscoveryService,
+    /// Method service
+    method_service: MethodService,
     /// MonitoredItem service
     monitored_item_service: MonitoredItemService,
+    /// NodeManagement service
+    node_management_service: NodeManagementService,
     /// Session service
     session_service: SessionService,
     /// Subscription service
@@ -37,56 +49,135 @@ impl MessageHandler {
         MessageHandler {
             server_state: server_state,
             session: session,
+            client_certificate: None,
             attribute_service: AttributeService::new(),
             discovery_service: DiscoveryService::new(),
+            method_service: MethodService::new(),
             monitored_item_service: MonitoredItemService::new(),
+            node_management_service: NodeManagementService::new(),
             session_service: SessionService::new(),
             view_service: ViewService::new(),
             subscription_service: SubscriptionService::new(),
         }
     }
 
+    /// Takes note of the client certificate of the secure channel once it is opened. The certificate
+    /// authenticates the client only if the channel signs its messages.
+    pub fn set_secure_channel(&mut self, secure_channel: &SecureChannel) {
+        let signed = secure_channel.security_policy != SecurityPolicy::None &&
+            (secure_channel.security_mode == MessageSecurityMode::Sign || secure_channel.security_mode == MessageSecurityMode::SignAndEncrypt);
+        self.client_certificate = if signed { secure_channel.their_cert.clone() } else { None };
+    }
+
     /// Validates the request header information to ensure it is valid for the session.
     ///
     /// The request header should contain the session authentication token issued during a
     /// CreateSession or the request is invalid. An invalid token can cause the session to close.
     fn validate_request(&self, session: &mut Session, request_header: &RequestHeader) -> Result<(), SupportedMessage> {
-        // TODO if session's token is null, it might be possible to retrieve session state from a
-        // previously closed session and reassociate it if the authentication token is recognized
-
         if session.authentication_token != request_header.authentication_token {
             // Session should terminate
             session.terminate_session = true;
             Err(ServiceFault::new_supported_message(request_header, BAD_IDENTITY_TOKEN_REJECTED))
         } else {
+            // Any valid request keeps the session alive
+            session.last_service_request_time = UTC::now();
             Ok(())
         }
     }
 
+    /// Tests if the secure channel of this connection is signed with the certificate that the
+    /// session was created with. A session created without security has no certificate to compare.
+    fn is_session_client(&self, session: &Session) -> bool {
+        if SecurityPolicy::from_uri(&session.security_policy_uri) == SecurityPolicy::None {
+            true
+        } else if let Some(ref client_certificate) = self.client_certificate {
+            client_certificate.as_byte_string() == session.client_certificate
+        } else {
+            false
+        }
+    }
+
     pub fn handle_message(&mut self, request_id: UInt32, message: SupportedMessage) -> Result<SupportedMessage, StatusCode> {
         let mut server_state = self.server_state.lock().unwrap();
         let mut server_state = &mut server_state;
         let mut session = self.session.lock().unwrap();
         let mut session = &mut session;
 
+        let request_type_id = match message {
+            SupportedMessage::Invalid(_) | SupportedMessage::DoNothing => NodeId::null(),
+            _ => message.node_id(),
+        };
+
         let response = match message {
             SupportedMessage::GetEndpointsRequest(request) => {
                 self.discovery_service.get_endpoints(server_state, session, request)?
             }
+            SupportedMessage::FindServersRequest(request) => {
+                self.discovery_service.find_servers(server_state, session, request)?
+            }
+            SupportedMessage::FindServersOnNetworkRequest(request) => {
+                self.discovery_service.find_servers_on_network(server_state, session, request)?
+            }
+            SupportedMessage::RegisterServerRequest(request) => {
+                self.discovery_service.register_server(server_state, session, self.client_certificate.as_ref(), request)?
+            }
+            SupportedMessage::RegisterServer2Request(request) => {
+                self.discovery_service.register_server2(server_state, session, self.client_certificate.as_ref(), request)?
+            }
             SupportedMessage::CreateSessionRequest(request) => {
-                self.session_service.create_session(server_state, session, request)?
+                let response = self.session_service.create_session(server_state, session, request)?;
+                if let SupportedMessage::CreateSessionResponse(_) = response {
+                    server_state.session_manager.register_session(&session.authentication_token, self.session.clone());
+                }
+                response
             }
             SupportedMessage::CloseSessionRequest(request) => {
                 self.session_service.close_session(server_state, session, request)?
             }
             // ALL THE REQUESTS BELOW MUST BE VALIDATED AGAINST THE SESSION
             SupportedMessage::ActivateSessionRequest(request) => {
-                if let Err(response) = self.validate_request(session, &request.request_header) {
+                // A client may activate a detached session created on another connection, e.g. after
+                // its connection dropped, and carry on using it from this one. The session is only
+                // moved to this connection once the client's certificate, signature and identity are
+                // validated and the identity is the one the session is already activated with.
+                let detached_session = if !session.is_created() {
+                    server_state.session_manager.find_detached_session(&request.request_header.authentication_token)
+                } else {
+                    None
+                };
+                if let Some(detached_session) = detached_session {
+                    let authentication_token = request.request_header.authentication_token.clone();
+                    let response = {
+                        let mut detached_session = detached_session.lock().unwrap();
+                        if let Err(response) = self.validate_request(&mut detached_session, &request.request_header) {
+                            response
+                        } else if !self.is_session_client(&detached_session) {
+                            warn!("Session {:?} cannot be activated on a secure channel of another client", detached_session.session_id);
+                            ServiceFault::new_supported_message(&request.request_header, BAD_SECURITY_CHECKS_FAILED)
+                        } else if UserIdentity::from_identity_token(&request.user_identity_token) != detached_session.user_identity {
+                            warn!("Session {:?} cannot be activated as another user on a new connection", detached_session.session_id);
+                            ServiceFault::new_supported_message(&request.request_header, BAD_IDENTITY_TOKEN_REJECTED)
+                        } else {
+                            self.session_service.activate_session(server_state, &mut detached_session, request)?
+                        }
+                    };
+                    if let SupportedMessage::ActivateSessionResponse(_) = response {
+                        server_state.session_manager.reattach_session(&authentication_token, session, self.session.clone());
+                    }
+                    response
+                } else if let Err(response) = self.validate_request(session, &request.request_header) {
                     response
                 } else {
                     self.session_service.activate_session(server_state, session, request)?
                 }
             }
+            SupportedMessage::CancelRequest(request) => {
+                if let Err(response) = self.validate_request(session, &request.request_header) {
+                    response
+                } else {
+                    self.session_service.cancel(server_state, session, request)?
+                }
+            }
             SupportedMessage::CreateSubscriptionRequest(request) => {
                 if let Err(response) = self.validate_request(session, &request.request_header) {
                     response
@@ -115,6 +206,13 @@ impl MessageHandler {
                     self.subscription_service.set_publishing_mode(server_state, session, request)?
                 }
             }
+            SupportedMessage::TransferSubscriptionsRequest(request) => {
+                if let Err(response) = self.validate_request(session, &request.request_header) {
+                    response
+                } else {
+                    self.subscription_service.transfer_subscriptions(server_state, session, request)?
+                }
+            }
             SupportedMessage::PublishRequest(request) => {
                 if let Err(response) = self.validate_request(session, &request.request_header) {
                     response
@@ -150,6 +248,20 @@ impl MessageHandler {
                     self.view_service.translate_browse_paths_to_node_ids(server_state, session, request)?
                 }
             }
+            SupportedMessage::RegisterNodesRequest(request) => {
+                if let Err(response) = self.validate_request(session, &request.request_header) {
+                    response
+                } else {
+                    self.view_service.register_nodes(server_state, session, request)?
+                }
+            }
+            SupportedMessage::UnregisterNodesRequest(request) => {
+                if let Err(response) = self.validate_request(session, &request.request_header) {
+                    response
+                } else {
+                    self.view_service.unregister_nodes(server_state, session, request)?
+                }
+            }
             SupportedMessage::ReadRequest(request) => {
                 if let Err(response) = self.validate_request(session, &request.request_header) {
                     response
@@ -164,6 +276,20 @@ impl MessageHandler {
                     self.attribute_service.write(server_state, session, request)?
                 }
             }
+            SupportedMessage::HistoryReadRequest(request) => {
+                if let Err(response) = self.validate_request(session, &request.request_header) {
+                    response
+                } else {
+                    self.attribute_service.history_read(server_state, session, request)?
+                }
+            }
+            SupportedMessage::HistoryUpdateRequest(request) => {
+                if let Err(response) = self.validate_request(session, &request.request_header) {
+                    response
+                } else {
+                    self.attribute_service.history_update(server_state, session, request)?
+                }
+            }
             SupportedMessage::CreateMonitoredItemsRequest(request) => {
                 if let Err(response) = self.validate_request(session, &request.request_header) {
                     response
@@ -185,11 +311,81 @@ impl MessageHandler {
                     self.monitored_item_service.delete_monitored_items(server_state, session, request)?
                 }
             }
+            SupportedMessage::SetMonitoringModeRequest(request) => {
+                if let Err(response) = self.validate_request(session, &request.request_header) {
+                    response
+                } else {
+                    self.monitored_item_service.set_monitoring_mode(server_state, session, request)?
+                }
+            }
+            SupportedMessage::SetTriggeringRequest(request) => {
+                if let Err(response) = self.validate_request(session, &request.request_header) {
+                    response
+                } else {
+                    self.monitored_item_service.set_triggering(server_state, session, request)?
+                }
+            }
+            SupportedMessage::CallRequest(request) => {
+                if let Err(response) = self.validate_request(session, &request.request_header) {
+                    response
+                } else {
+                    self.method_service.call(server_state, session, request)?
+                }
+            }
+            SupportedMessage::AddNodesRequest(request) => {
+                if let Err(response) = self.validate_request(session, &request.request_header) {
+                    response
+                } else {
+                    self.node_management_service.add_nodes(server_state, session, request)?
+                }
+            }
+            SupportedMessage::AddReferencesRequest(request) => {
+                if let Err(response) = self.validate_request(session, &request.request_header) {
+                    response
+                } else {
+                    self.node_management_service.add_references(server_state, session, request)?
+                }
+            }
+            SupportedMessage::DeleteNodesRequest(request) => {
+                if let Err(response) = self.validate_request(session, &request.request_header) {
+                    response
+                } else {
+                    self.node_management_service.delete_nodes(server_state, session, request)?
+                }
+            }
+            SupportedMessage::DeleteReferencesRequest(request) => {
+                if let Err(response) = self.validate_request(session, &request.request_header) {
+                    response
+                } else {
+                    self.node_management_service.delete_references(server_state, session, request)?
+                }
+            }
             _ => {
                 debug!("Message handler does not handle this kind of message {:?}", message);
                 return Err(BAD_SERVICE_UNSUPPORTED);
             }
         };
+        let service_result = if let SupportedMessage::ServiceFault(ref fault) = response {
+            let security_rejected = MessageHandler::is_security_rejection(fault.response_header.service_result);
+            server_state.diagnostics.lock().unwrap().on_rejected_request(security_rejected);
+            fault.response_header.service_result
+        } else {
+            GOOD
+        };
+        // The request counts towards the diagnostics of the session it was made on
+        if session.is_created() {
+            session.diagnostics.on_request(&request_type_id, service_result);
+        }
         Ok(response)
     }
+
+    /// Tests if a service fault's result means the request was rejected for security reasons
+    pub fn is_security_rejection(service_result: StatusCode) -> bool {
+        match service_result {
+            BAD_IDENTITY_TOKEN_REJECTED | BAD_IDENTITY_TOKEN_INVALID | BAD_SECURITY_CHECKS_FAILED |
+            BAD_USER_ACCESS_DENIED | BAD_CERTIFICATE_INVALID | BAD_CERTIFICATE_UNTRUSTED |
+            BAD_CERTIFICATE_TIME_INVALID | BAD_CERTIFICATE_REVOKED => true,
+            _ => false
+        }
+    }
 }
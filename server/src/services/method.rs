@@ -0,0 +1,119 @@
+use std::result::Result;
+
+use opcua_types::*;
+
+use address_space::address_space::AddressSpace;
+use address_space::node::NodeType;
+use server::ServerState;
+use session::Session;
+use services::Service;
+
+pub struct MethodService {}
+
+impl Service for MethodService {}
+
+impl MethodService {
+    pub fn new() -> MethodService {
+        MethodService {}
+    }
+
+    pub fn call(&self, server_state: &mut ServerState, _: &mut Session, request: CallRequest) -> Result<SupportedMessage, StatusCode> {
+        if request.methods_to_call.is_none() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+        let methods_to_call = request.methods_to_call.as_ref().unwrap();
+        if methods_to_call.is_empty() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+
+        let address_space = server_state.address_space.lock().unwrap();
+        let results = methods_to_call.iter().map(|method_to_call| {
+            MethodService::call_method(&address_space, method_to_call)
+        }).collect();
+
+        let response = CallResponse {
+            response_header: ResponseHeader::new_good(&request.request_header),
+            results: Some(results),
+            diagnostic_infos: None,
+        };
+        Ok(SupportedMessage::CallResponse(response))
+    }
+
+    /// Validates the method call against the address space and the method's input arguments and
+    /// invokes the method's callback when the call is valid.
+    fn call_method(address_space: &AddressSpace, method_to_call: &CallMethodRequest) -> CallMethodResult {
+        let object_id = &method_to_call.object_id;
+        let method_id = &method_to_call.method_id;
+
+        // The object must exist and be an object or object type
+        match address_space.find_node(object_id) {
+            Some(&NodeType::Object(_)) | Some(&NodeType::ObjectType(_)) => {}
+            _ => {
+                return MethodService::call_method_result(BAD_NODE_ID_UNKNOWN, None, None);
+            }
+        }
+
+        // The method must exist and be a component of the object
+        let method = if let Some(&NodeType::Method(ref method)) = address_space.find_node(method_id) {
+            method
+        } else {
+            return MethodService::call_method_result(BAD_METHOD_INVALID, None, None);
+        };
+        let is_component = if let Some(references) = address_space.find_references_from(object_id, Some((ReferenceTypeId::HasComponent, false))) {
+            references.iter().any(|r| r.node_id == *method_id)
+        } else {
+            false
+        };
+        if !is_component {
+            return MethodService::call_method_result(BAD_METHOD_INVALID, None, None);
+        }
+
+        // There is no BadNotExecutable status code so a method which is not executable is denied
+        if !method.executable() || !method.user_executable() {
+            return MethodService::call_method_result(BAD_USER_ACCESS_DENIED, None, None);
+        }
+
+        // Validate the number of arguments and the type of each
+        let input_arguments: &[Variant] = if let Some(ref input_arguments) = method_to_call.input_arguments {
+            input_arguments
+        } else {
+            &[]
+        };
+        let expected_arguments = address_space.method_input_arguments(method_id);
+        if input_arguments.len() < expected_arguments.len() {
+            return MethodService::call_method_result(BAD_ARGUMENTS_MISSING, None, None);
+        } else if input_arguments.len() > expected_arguments.len() {
+            return MethodService::call_method_result(BAD_TOO_MANY_ARGUMENTS, None, None);
+        }
+        let input_argument_results: Vec<StatusCode> = input_arguments.iter().zip(expected_arguments.iter()).map(|(value, argument)| {
+            if value.is_valid_for(&argument.data_type, argument.value_rank) {
+                GOOD
+            } else {
+                BAD_TYPE_MISMATCH
+            }
+        }).collect();
+        if input_argument_results.iter().any(|r| *r != GOOD) {
+            return MethodService::call_method_result(BAD_INVALID_ARGUMENT, Some(input_argument_results), None);
+        }
+
+        // Call the method
+        match method.call(object_id, input_arguments) {
+            Ok(output_arguments) => {
+                let input_argument_results = if input_argument_results.is_empty() { None } else { Some(input_argument_results) };
+                MethodService::call_method_result(GOOD, input_argument_results, Some(output_arguments))
+            }
+            Err(status_code) => {
+                MethodService::call_method_result(status_code, None, None)
+            }
+        }
+    }
+
+    fn call_method_result(status_code: StatusCode, input_argument_results: Option<Vec<StatusCode>>, output_arguments: Option<Vec<Variant>>) -> CallMethodResult {
+        CallMethodResult {
+            status_code,
+            input_argument_results,
+            input_argument_diagnostic_infos: None,
+            output_arguments,
+        }
+    }
+}
@@ -11,7 +11,9 @@ trait Service {
 
 pub mod attribute;
 pub mod discovery;
+pub mod method;
 pub mod monitored_item;
+pub mod node_management;
 pub mod session;
 pub mod subscription;
 pub mod view;
@@ -15,13 +15,20 @@ impl MonitoredItemService {
         MonitoredItemService {}
     }
 
-    pub fn create_monitored_items(&self, _: &mut ServerState, session: &mut Session, request: CreateMonitoredItemsRequest) -> Result<SupportedMessage, StatusCode> {
+    pub fn create_monitored_items(&self, server_state: &mut ServerState, session: &mut Session, request: CreateMonitoredItemsRequest) -> Result<SupportedMessage, StatusCode> {
         // pub timestamps_to_return: TimestampsToReturn,
         let results = if let Some(ref items_to_create) = request.items_to_create {
+            let address_space = server_state.address_space.lock().unwrap();
+            // Items monitor the nodes that registered node aliases stand for
+            let items_to_create = items_to_create.iter().map(|item_to_create| {
+                let mut item_to_create = item_to_create.clone();
+                item_to_create.item_to_monitor.node_id = session.resolve_node_id(&address_space, &item_to_create.item_to_monitor.node_id).clone();
+                item_to_create
+            }).collect::<Vec<MonitoredItemCreateRequest>>();
             // Find subscription and add items to it
             let subscription_id = request.subscription_id;
-            if let Some(subscription) = session.subscriptions.get_mut(subscription_id) {
-                Some(subscription.create_monitored_items(items_to_create))
+            if let Some(subscription) = server_state.subscriptions.get_mut(&session.session_id, subscription_id) {
+                Some(subscription.create_monitored_items(&address_space, &server_state.monitored_item_limits, &items_to_create))
             } else {
                 // No matching subscription
                 return Ok(self.service_fault(&request.request_header, BAD_SUBSCRIPTION_ID_INVALID));
@@ -38,12 +45,13 @@ impl MonitoredItemService {
         Ok(SupportedMessage::CreateMonitoredItemsResponse(response))
     }
 
-    pub fn modify_monitored_items(&self, _: &mut ServerState, session: &mut Session, request: ModifyMonitoredItemsRequest) -> Result<SupportedMessage, StatusCode> {
+    pub fn modify_monitored_items(&self, server_state: &mut ServerState, session: &mut Session, request: ModifyMonitoredItemsRequest) -> Result<SupportedMessage, StatusCode> {
         let results = if let Some(ref items_to_modify) = request.items_to_modify {
             // Find subscription and modify items in it
             let subscription_id = request.subscription_id;
-            if let Some(subscription) = session.subscriptions.get_mut(subscription_id) {
-                Some(subscription.modify_monitored_items(items_to_modify))
+            if let Some(subscription) = server_state.subscriptions.get_mut(&session.session_id, subscription_id) {
+                let address_space = server_state.address_space.lock().unwrap();
+                Some(subscription.modify_monitored_items(&address_space, &server_state.monitored_item_limits, items_to_modify))
             } else {
                 // No matching subscription
                 return Ok(self.service_fault(&request.request_header, BAD_SUBSCRIPTION_ID_INVALID));
@@ -60,11 +68,11 @@ impl MonitoredItemService {
         Ok(SupportedMessage::ModifyMonitoredItemsResponse(response))
     }
 
-    pub fn delete_monitored_items(&self, _: &mut ServerState, session: &mut Session, request: DeleteMonitoredItemsRequest) -> Result<SupportedMessage, StatusCode> {
+    pub fn delete_monitored_items(&self, server_state: &mut ServerState, session: &mut Session, request: DeleteMonitoredItemsRequest) -> Result<SupportedMessage, StatusCode> {
         let results = if let Some(ref items_to_delete) = request.monitored_item_ids {
             // Find subscription and delete items from it
             let subscription_id = request.subscription_id;
-            if let Some(subscription) = session.subscriptions.get_mut(subscription_id) {
+            if let Some(subscription) = server_state.subscriptions.get_mut(&session.session_id, subscription_id) {
                 Some(subscription.delete_monitored_items(items_to_delete))
             } else {
                 // No matching subscription
@@ -82,4 +90,57 @@ impl MonitoredItemService {
         };
         Ok(SupportedMessage::DeleteMonitoredItemsResponse(response))
     }
-}
\ No newline at end of file
+
+    pub fn set_monitoring_mode(&self, server_state: &mut ServerState, session: &mut Session, request: SetMonitoringModeRequest) -> Result<SupportedMessage, StatusCode> {
+        let results = if let Some(ref monitored_item_ids) = request.monitored_item_ids {
+            // Find subscription and set the mode of items in it
+            let subscription_id = request.subscription_id;
+            if let Some(subscription) = server_state.subscriptions.get_mut(&session.session_id, subscription_id) {
+                Some(subscription.set_monitoring_mode(request.monitoring_mode, monitored_item_ids))
+            } else {
+                // No matching subscription
+                return Ok(self.service_fault(&request.request_header, BAD_SUBSCRIPTION_ID_INVALID));
+            }
+        } else {
+            // No items to change so nothing to do
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        };
+        let response = SetMonitoringModeResponse {
+            response_header: ResponseHeader::new_good(&request.request_header),
+            results,
+            diagnostic_infos: None
+        };
+        Ok(SupportedMessage::SetMonitoringModeResponse(response))
+    }
+
+    pub fn set_triggering(&self, server_state: &mut ServerState, session: &mut Session, request: SetTriggeringRequest) -> Result<SupportedMessage, StatusCode> {
+        let links_to_add = request.links_to_add.as_ref().map(|v| v.as_slice()).unwrap_or(&[]);
+        let links_to_remove = request.links_to_remove.as_ref().map(|v| v.as_slice()).unwrap_or(&[]);
+        if links_to_add.is_empty() && links_to_remove.is_empty() {
+            // No links to change so nothing to do
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+
+        // Find subscription and set the links of the triggering item in it
+        let subscription_id = request.subscription_id;
+        let (add_results, remove_results) = if let Some(subscription) = server_state.subscriptions.get_mut(&session.session_id, subscription_id) {
+            match subscription.set_triggering(request.triggering_item_id, links_to_add, links_to_remove) {
+                Ok(results) => results,
+                Err(status_code) => {
+                    return Ok(self.service_fault(&request.request_header, status_code));
+                }
+            }
+        } else {
+            // No matching subscription
+            return Ok(self.service_fault(&request.request_header, BAD_SUBSCRIPTION_ID_INVALID));
+        };
+        let response = SetTriggeringResponse {
+            response_header: ResponseHeader::new_good(&request.request_header),
+            add_results: if request.links_to_add.is_some() { Some(add_results) } else { None },
+            add_diagnostic_infos: None,
+            remove_results: if request.links_to_remove.is_some() { Some(remove_results) } else { None },
+            remove_diagnostic_infos: None,
+        };
+        Ok(SupportedMessage::SetTriggeringResponse(response))
+    }
+}
@@ -0,0 +1,432 @@
+use std::result::Result;
+
+use opcua_types::*;
+
+use constants;
+use address_space::address_space::AddressSpace;
+use address_space::types::*;
+use server::ServerState;
+use session::Session;
+use services::Service;
+
+// Bits in the specified_attributes mask of node attributes, Part 4 7.19
+
+const NODE_ATTRIBUTES_MASK_ACCESS_LEVEL: UInt32 = 1;
+const NODE_ATTRIBUTES_MASK_ARRAY_DIMENSIONS: UInt32 = 1 << 1;
+const NODE_ATTRIBUTES_MASK_CONTAINS_NO_LOOPS: UInt32 = 1 << 3;
+const NODE_ATTRIBUTES_MASK_DATA_TYPE: UInt32 = 1 << 4;
+const NODE_ATTRIBUTES_MASK_DESCRIPTION: UInt32 = 1 << 5;
+const NODE_ATTRIBUTES_MASK_DISPLAY_NAME: UInt32 = 1 << 6;
+const NODE_ATTRIBUTES_MASK_EVENT_NOTIFIER: UInt32 = 1 << 7;
+const NODE_ATTRIBUTES_MASK_EXECUTABLE: UInt32 = 1 << 8;
+const NODE_ATTRIBUTES_MASK_HISTORIZING: UInt32 = 1 << 9;
+const NODE_ATTRIBUTES_MASK_INVERSE_NAME: UInt32 = 1 << 10;
+const NODE_ATTRIBUTES_MASK_IS_ABSTRACT: UInt32 = 1 << 11;
+const NODE_ATTRIBUTES_MASK_MINIMUM_SAMPLING_INTERVAL: UInt32 = 1 << 12;
+const NODE_ATTRIBUTES_MASK_SYMMETRIC: UInt32 = 1 << 15;
+const NODE_ATTRIBUTES_MASK_USER_ACCESS_LEVEL: UInt32 = 1 << 16;
+const NODE_ATTRIBUTES_MASK_USER_EXECUTABLE: UInt32 = 1 << 17;
+const NODE_ATTRIBUTES_MASK_USER_WRITE_MASK: UInt32 = 1 << 18;
+const NODE_ATTRIBUTES_MASK_VALUE_RANK: UInt32 = 1 << 19;
+const NODE_ATTRIBUTES_MASK_WRITE_MASK: UInt32 = 1 << 20;
+const NODE_ATTRIBUTES_MASK_VALUE: UInt32 = 1 << 21;
+
+/// The node management service adds and deletes nodes and references. Clients may only use it
+/// on endpoints that are configured to allow node management.
+pub struct NodeManagementService {}
+
+impl Service for NodeManagementService {}
+
+impl NodeManagementService {
+    pub fn new() -> NodeManagementService {
+        NodeManagementService {}
+    }
+
+    pub fn add_nodes(&self, server_state: &mut ServerState, session: &mut Session, request: AddNodesRequest) -> Result<SupportedMessage, StatusCode> {
+        if !NodeManagementService::is_node_management_allowed(server_state, session) {
+            return Ok(self.service_fault(&request.request_header, BAD_USER_ACCESS_DENIED));
+        }
+        if request.nodes_to_add.is_none() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+        let nodes_to_add = request.nodes_to_add.as_ref().unwrap();
+        if nodes_to_add.is_empty() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+
+        let mut address_space = server_state.address_space.lock().unwrap();
+        let results = nodes_to_add.iter().map(|node_to_add| {
+            match NodeManagementService::add_node(&mut address_space, node_to_add) {
+                Ok(added_node_id) => AddNodesResult {
+                    status_code: GOOD,
+                    added_node_id,
+                },
+                Err(status_code) => AddNodesResult {
+                    status_code,
+                    added_node_id: NodeId::null(),
+                }
+            }
+        }).collect();
+
+        let response = AddNodesResponse {
+            response_header: ResponseHeader::new_good(&request.request_header),
+            results: Some(results),
+            diagnostic_infos: None,
+        };
+        Ok(SupportedMessage::AddNodesResponse(response))
+    }
+
+    pub fn add_references(&self, server_state: &mut ServerState, session: &mut Session, request: AddReferencesRequest) -> Result<SupportedMessage, StatusCode> {
+        if !NodeManagementService::is_node_management_allowed(server_state, session) {
+            return Ok(self.service_fault(&request.request_header, BAD_USER_ACCESS_DENIED));
+        }
+        if request.references_to_add.is_none() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+        let references_to_add = request.references_to_add.as_ref().unwrap();
+        if references_to_add.is_empty() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+
+        let mut address_space = server_state.address_space.lock().unwrap();
+        let results = references_to_add.iter().map(|reference_to_add| {
+            match NodeManagementService::add_reference(&mut address_space, reference_to_add) {
+                Ok(_) => GOOD,
+                Err(status_code) => status_code
+            }
+        }).collect();
+
+        let response = AddReferencesResponse {
+            response_header: ResponseHeader::new_good(&request.request_header),
+            results: Some(results),
+            diagnostic_infos: None,
+        };
+        Ok(SupportedMessage::AddReferencesResponse(response))
+    }
+
+    pub fn delete_nodes(&self, server_state: &mut ServerState, session: &mut Session, request: DeleteNodesRequest) -> Result<SupportedMessage, StatusCode> {
+        if !NodeManagementService::is_node_management_allowed(server_state, session) {
+            return Ok(self.service_fault(&request.request_header, BAD_USER_ACCESS_DENIED));
+        }
+        if request.nodes_to_delete.is_none() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+        let nodes_to_delete = request.nodes_to_delete.as_ref().unwrap();
+        if nodes_to_delete.is_empty() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+
+        let mut address_space = server_state.address_space.lock().unwrap();
+        let results = nodes_to_delete.iter().map(|node_to_delete| {
+            if address_space.delete_node(&node_to_delete.node_id, node_to_delete.delete_target_references) {
+                GOOD
+            } else {
+                BAD_NODE_ID_UNKNOWN
+            }
+        }).collect();
+
+        let response = DeleteNodesResponse {
+            response_header: ResponseHeader::new_good(&request.request_header),
+            results: Some(results),
+            diagnostic_infos: None,
+        };
+        Ok(SupportedMessage::DeleteNodesResponse(response))
+    }
+
+    pub fn delete_references(&self, server_state: &mut ServerState, session: &mut Session, request: DeleteReferencesRequest) -> Result<SupportedMessage, StatusCode> {
+        if !NodeManagementService::is_node_management_allowed(server_state, session) {
+            return Ok(self.service_fault(&request.request_header, BAD_USER_ACCESS_DENIED));
+        }
+        if request.references_to_delete.is_none() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+        let references_to_delete = request.references_to_delete.as_ref().unwrap();
+        if references_to_delete.is_empty() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+
+        let mut address_space = server_state.address_space.lock().unwrap();
+        let results = references_to_delete.iter().map(|reference_to_delete| {
+            match NodeManagementService::delete_reference(&mut address_space, reference_to_delete) {
+                Ok(_) => GOOD,
+                Err(status_code) => status_code
+            }
+        }).collect();
+
+        let response = DeleteReferencesResponse {
+            response_header: ResponseHeader::new_good(&request.request_header),
+            results: Some(results),
+            diagnostic_infos: None,
+        };
+        Ok(SupportedMessage::DeleteReferencesResponse(response))
+    }
+
+    /// Node management is only allowed on endpoints which are configured to permit it
+    fn is_node_management_allowed(server_state: &ServerState, session: &Session) -> bool {
+        if session.endpoint_url.is_null() {
+            false
+        } else if let Some(endpoint) = server_state.find_endpoint(session.endpoint_url.as_ref()) {
+            endpoint.node_management
+        } else {
+            false
+        }
+    }
+
+    fn add_node(address_space: &mut AddressSpace, item: &AddNodesItem) -> Result<NodeId, StatusCode> {
+        // Nodes can only be added to this server
+        if item.requested_new_node_id.server_index != 0 {
+            return Err(BAD_NODE_ID_REJECTED);
+        }
+        let node_id = if item.requested_new_node_id.is_null() {
+            NodeId::next_numeric()
+        } else {
+            item.requested_new_node_id.node_id.clone()
+        };
+        if address_space.node_exists(&node_id) {
+            return Err(BAD_NODE_ID_EXISTS);
+        }
+        // Identifiers in the range of registered node aliases are reserved
+        if node_id.namespace == constants::REGISTERED_NODE_NAMESPACE {
+            if let Identifier::Numeric(value) = node_id.identifier {
+                if value >= constants::REGISTERED_NODE_ID_BASE {
+                    return Err(BAD_NODE_ID_REJECTED);
+                }
+            }
+        }
+
+        // The parent must exist and be referenced by a hierarchical reference
+        let parent_node_id = &item.parent_node_id.node_id;
+        if item.parent_node_id.server_index != 0 || !address_space.node_exists(parent_node_id) {
+            return Err(BAD_PARENT_NODE_ID_INVALID);
+        }
+        let reference_type_id = item.reference_type_id.as_reference_type_id().map_err(|_| BAD_REFERENCE_TYPE_ID_INVALID)?;
+        if !address_space.reference_type_matches(ReferenceTypeId::HierarchicalReferences, reference_type_id, true) {
+            return Err(BAD_REFERENCE_NOT_ALLOWED);
+        }
+
+        // Browse name must be set and unique amongst the children of the parent
+        if item.browse_name.name.is_null() || item.browse_name.name.as_ref().is_empty() {
+            return Err(BAD_BROWSE_NAME_INVALID);
+        }
+        if let Some(references) = address_space.find_references_from(parent_node_id, None) {
+            let duplicate = references.iter().any(|r| {
+                if let Some(node) = address_space.find_node(&r.node_id) {
+                    node.as_node().browse_name() == item.browse_name
+                } else {
+                    false
+                }
+            });
+            if duplicate {
+                return Err(BAD_BROWSE_NAME_DUPLICATED);
+            }
+        }
+
+        // Objects and variables must have a type definition of the correct kind, other node
+        // classes must not have one
+        let type_definition_id = &item.type_definition.node_id;
+        match item.node_class {
+            NodeClass::Object => {
+                if let Some(&NodeType::ObjectType(_)) = address_space.find_node(type_definition_id) {} else {
+                    return Err(BAD_TYPE_DEFINITION_INVALID);
+                }
+            }
+            NodeClass::Variable => {
+                if let Some(&NodeType::VariableType(_)) = address_space.find_node(type_definition_id) {} else {
+                    return Err(BAD_TYPE_DEFINITION_INVALID);
+                }
+            }
+            _ => {
+                if !item.type_definition.is_null() {
+                    return Err(BAD_TYPE_DEFINITION_INVALID);
+                }
+            }
+        }
+
+        let node = NodeManagementService::create_node(&node_id, item)?;
+        address_space.insert(node);
+        address_space.insert_reference(parent_node_id, &node_id, reference_type_id);
+        if !item.type_definition.is_null() {
+            address_space.insert_reference(&node_id, type_definition_id, ReferenceTypeId::HasTypeDefinition);
+        }
+        Ok(node_id)
+    }
+
+    /// Creates a node of the requested class from the node attributes held in the item
+    fn create_node(node_id: &NodeId, item: &AddNodesItem) -> Result<NodeType, StatusCode> {
+        let name = item.browse_name.name.as_ref();
+        let node_attributes = &item.node_attributes;
+
+        // Each kind of node is created with default values and then the attributes which the
+        // client specified are set on it.
+        let (mut node, specified_attributes, attributes) = match item.node_class {
+            NodeClass::Object => {
+                let a = NodeManagementService::decode_attributes::<ObjectAttributes>(node_attributes, ObjectId::ObjectAttributes_Encoding_DefaultBinary)?;
+                let mut attributes = NodeManagementService::common_attributes(a.display_name, a.description, a.write_mask, a.user_write_mask);
+                attributes.push((NODE_ATTRIBUTES_MASK_EVENT_NOTIFIER, AttributeId::EventNotifier, Variant::Byte(a.event_notifier)));
+                (Object::new_node(node_id, name, name, ""), a.specified_attributes, attributes)
+            }
+            NodeClass::Variable => {
+                let a = NodeManagementService::decode_attributes::<VariableAttributes>(node_attributes, ObjectId::VariableAttributes_Encoding_DefaultBinary)?;
+                if a.specified_attributes & NODE_ATTRIBUTES_MASK_VALUE != 0 && a.specified_attributes & NODE_ATTRIBUTES_MASK_DATA_TYPE != 0 {
+                    let value_rank = if a.specified_attributes & NODE_ATTRIBUTES_MASK_VALUE_RANK != 0 { a.value_rank } else { -1 };
+                    if !a.value.is_valid_for(&a.data_type, value_rank) {
+                        return Err(BAD_NODE_ATTRIBUTES_INVALID);
+                    }
+                }
+                let mut attributes = NodeManagementService::common_attributes(a.display_name, a.description, a.write_mask, a.user_write_mask);
+                attributes.push((NODE_ATTRIBUTES_MASK_VALUE, AttributeId::Value, a.value));
+                attributes.push((NODE_ATTRIBUTES_MASK_DATA_TYPE, AttributeId::DataType, Variant::new_node_id(a.data_type)));
+                attributes.push((NODE_ATTRIBUTES_MASK_VALUE_RANK, AttributeId::ValueRank, Variant::Int32(a.value_rank)));
+                if let Some(array_dimensions) = a.array_dimensions {
+                    attributes.push((NODE_ATTRIBUTES_MASK_ARRAY_DIMENSIONS, AttributeId::ArrayDimensions, Variant::new_u32_array(&array_dimensions)));
+                }
+                attributes.push((NODE_ATTRIBUTES_MASK_ACCESS_LEVEL, AttributeId::AccessLevel, Variant::Byte(a.access_level)));
+                attributes.push((NODE_ATTRIBUTES_MASK_USER_ACCESS_LEVEL, AttributeId::UserAccessLevel, Variant::Byte(a.user_access_level)));
+                attributes.push((NODE_ATTRIBUTES_MASK_MINIMUM_SAMPLING_INTERVAL, AttributeId::MinimumSamplingInterval, Variant::Double(a.minimum_sampling_interval)));
+                attributes.push((NODE_ATTRIBUTES_MASK_HISTORIZING, AttributeId::Historizing, Variant::Boolean(a.historizing)));
+                (Variable::new_node(node_id, name, name, "", DataTypeId::BaseDataType, DataValue::new(Variant::Empty)), a.specified_attributes, attributes)
+            }
+            NodeClass::Method => {
+                let a = NodeManagementService::decode_attributes::<MethodAttributes>(node_attributes, ObjectId::MethodAttributes_Encoding_DefaultBinary)?;
+                let mut attributes = NodeManagementService::common_attributes(a.display_name, a.description, a.write_mask, a.user_write_mask);
+                attributes.push((NODE_ATTRIBUTES_MASK_EXECUTABLE, AttributeId::Executable, Variant::Boolean(a.executable)));
+                attributes.push((NODE_ATTRIBUTES_MASK_USER_EXECUTABLE, AttributeId::UserExecutable, Variant::Boolean(a.user_executable)));
+                (Method::new_node(node_id, name, name, "", false, false, false), a.specified_attributes, attributes)
+            }
+            NodeClass::ObjectType => {
+                let a = NodeManagementService::decode_attributes::<ObjectTypeAttributes>(node_attributes, ObjectId::ObjectTypeAttributes_Encoding_DefaultBinary)?;
+                let mut attributes = NodeManagementService::common_attributes(a.display_name, a.description, a.write_mask, a.user_write_mask);
+                attributes.push((NODE_ATTRIBUTES_MASK_IS_ABSTRACT, AttributeId::IsAbstract, Variant::Boolean(a.is_abstract)));
+                (ObjectType::new_node(node_id, name, name, "", false), a.specified_attributes, attributes)
+            }
+            NodeClass::VariableType => {
+                let a = NodeManagementService::decode_attributes::<VariableTypeAttributes>(node_attributes, ObjectId::VariableTypeAttributes_Encoding_DefaultBinary)?;
+                let mut attributes = NodeManagementService::common_attributes(a.display_name, a.description, a.write_mask, a.user_write_mask);
+                attributes.push((NODE_ATTRIBUTES_MASK_VALUE, AttributeId::Value, a.value));
+                attributes.push((NODE_ATTRIBUTES_MASK_DATA_TYPE, AttributeId::DataType, Variant::new_node_id(a.data_type)));
+                attributes.push((NODE_ATTRIBUTES_MASK_VALUE_RANK, AttributeId::ValueRank, Variant::Int32(a.value_rank)));
+                if let Some(array_dimensions) = a.array_dimensions {
+                    attributes.push((NODE_ATTRIBUTES_MASK_ARRAY_DIMENSIONS, AttributeId::ArrayDimensions, Variant::new_u32_array(&array_dimensions)));
+                }
+                attributes.push((NODE_ATTRIBUTES_MASK_IS_ABSTRACT, AttributeId::IsAbstract, Variant::Boolean(a.is_abstract)));
+                (VariableType::new_node(node_id, name, name, "", false, -1), a.specified_attributes, attributes)
+            }
+            NodeClass::ReferenceType => {
+                let a = NodeManagementService::decode_attributes::<ReferenceTypeAttributes>(node_attributes, ObjectId::ReferenceTypeAttributes_Encoding_DefaultBinary)?;
+                let mut attributes = NodeManagementService::common_attributes(a.display_name, a.description, a.write_mask, a.user_write_mask);
+                attributes.push((NODE_ATTRIBUTES_MASK_IS_ABSTRACT, AttributeId::IsAbstract, Variant::Boolean(a.is_abstract)));
+                attributes.push((NODE_ATTRIBUTES_MASK_SYMMETRIC, AttributeId::Symmetric, Variant::Boolean(a.symmetric)));
+                attributes.push((NODE_ATTRIBUTES_MASK_INVERSE_NAME, AttributeId::InverseName, Variant::new_localized_text(a.inverse_name)));
+                (ReferenceType::new_node(node_id, name, name, "", None, false, false), a.specified_attributes, attributes)
+            }
+            NodeClass::DataType => {
+                let a = NodeManagementService::decode_attributes::<DataTypeAttributes>(node_attributes, ObjectId::DataTypeAttributes_Encoding_DefaultBinary)?;
+                let mut attributes = NodeManagementService::common_attributes(a.display_name, a.description, a.write_mask, a.user_write_mask);
+                attributes.push((NODE_ATTRIBUTES_MASK_IS_ABSTRACT, AttributeId::IsAbstract, Variant::Boolean(a.is_abstract)));
+                (DataType::new_node(node_id, name, name, "", false), a.specified_attributes, attributes)
+            }
+            NodeClass::View => {
+                let a = NodeManagementService::decode_attributes::<ViewAttributes>(node_attributes, ObjectId::ViewAttributes_Encoding_DefaultBinary)?;
+                let mut attributes = NodeManagementService::common_attributes(a.display_name, a.description, a.write_mask, a.user_write_mask);
+                attributes.push((NODE_ATTRIBUTES_MASK_CONTAINS_NO_LOOPS, AttributeId::ContainsNoLoops, Variant::Boolean(a.contains_no_loops)));
+                attributes.push((NODE_ATTRIBUTES_MASK_EVENT_NOTIFIER, AttributeId::EventNotifier, Variant::Byte(a.event_notifier)));
+                (NodeType::View(View::new(node_id, name, name, "", 0, false)), a.specified_attributes, attributes)
+            }
+            _ => {
+                return Err(BAD_NODE_CLASS_INVALID);
+            }
+        };
+
+        {
+            let node = node.as_mut_node();
+            node.set_attribute(AttributeId::BrowseName, DataValue::new(Variant::new_qualified_name(item.browse_name.clone())));
+            for (mask, attribute_id, value) in attributes {
+                if specified_attributes & mask != 0 {
+                    node.set_attribute(attribute_id, DataValue::new(value));
+                }
+            }
+        }
+        Ok(node)
+    }
+
+    /// Attributes which are common to all node attribute structures
+    fn common_attributes(display_name: LocalizedText, description: LocalizedText, write_mask: UInt32, user_write_mask: UInt32) -> Vec<(UInt32, AttributeId, Variant)> {
+        vec![
+            (NODE_ATTRIBUTES_MASK_DISPLAY_NAME, AttributeId::DisplayName, Variant::new_localized_text(display_name)),
+            (NODE_ATTRIBUTES_MASK_DESCRIPTION, AttributeId::Description, Variant::new_localized_text(description)),
+            (NODE_ATTRIBUTES_MASK_WRITE_MASK, AttributeId::WriteMask, Variant::UInt32(write_mask)),
+            (NODE_ATTRIBUTES_MASK_USER_WRITE_MASK, AttributeId::UserWriteMask, Variant::UInt32(user_write_mask)),
+        ]
+    }
+
+    /// Decodes the node attributes, which must be of the expected type
+    fn decode_attributes<T: BinaryEncoder<T>>(node_attributes: &ExtensionObject, object_id: ObjectId) -> Result<T, StatusCode> {
+        if node_attributes.node_id != object_id.as_node_id() {
+            Err(BAD_NODE_ATTRIBUTES_INVALID)
+        } else {
+            node_attributes.decode_inner::<T>().map_err(|_| BAD_NODE_ATTRIBUTES_INVALID)
+        }
+    }
+
+    fn add_reference(address_space: &mut AddressSpace, item: &AddReferencesItem) -> Result<(), StatusCode> {
+        if !address_space.node_exists(&item.source_node_id) {
+            return Err(BAD_SOURCE_NODE_ID_INVALID);
+        }
+        // References to other servers are unsupported
+        if !item.target_server_uri.is_null() || item.target_node_id.server_index != 0 {
+            return Err(BAD_REFERENCE_LOCAL_ONLY);
+        }
+        let target_node_id = &item.target_node_id.node_id;
+        let target_node_class = if let Some(node) = address_space.find_node(target_node_id) {
+            node.as_node().node_class()
+        } else {
+            return Err(BAD_TARGET_NODE_ID_INVALID);
+        };
+        if item.target_node_class != NodeClass::Unspecified && item.target_node_class != target_node_class {
+            return Err(BAD_NODE_CLASS_INVALID);
+        }
+        if item.source_node_id == *target_node_id {
+            return Err(BAD_INVALID_SELF_REFERENCE);
+        }
+        let reference_type_id = item.reference_type_id.as_reference_type_id().map_err(|_| BAD_REFERENCE_TYPE_ID_INVALID)?;
+
+        // An inverse reference is held as a forward reference from the target
+        let (node_id_from, node_id_to) = if item.is_forward {
+            (&item.source_node_id, target_node_id)
+        } else {
+            (target_node_id, &item.source_node_id)
+        };
+        if address_space.has_reference(node_id_from, node_id_to, reference_type_id) {
+            Err(BAD_DUPLICATE_REFERENCE_NOT_ALLOWED)
+        } else if reference_type_id == ReferenceTypeId::HasSubtype && address_space.is_subtype(node_id_from, node_id_to) {
+            // The type hierarchy may not loop
+            Err(BAD_REFERENCE_NOT_ALLOWED)
+        } else {
+            address_space.insert_reference(node_id_from, node_id_to, reference_type_id);
+            Ok(())
+        }
+    }
+
+    /// Deletes a reference. The address space always holds both directions of a reference so
+    /// `delete_bidirectional` is implied.
+    fn delete_reference(address_space: &mut AddressSpace, item: &DeleteReferencesItem) -> Result<(), StatusCode> {
+        if !address_space.node_exists(&item.source_node_id) {
+            return Err(BAD_SOURCE_NODE_ID_INVALID);
+        }
+        if item.target_node_id.server_index != 0 {
+            return Err(BAD_REFERENCE_LOCAL_ONLY);
+        }
+        let target_node_id = &item.target_node_id.node_id;
+        let reference_type_id = item.reference_type_id.as_reference_type_id().map_err(|_| BAD_REFERENCE_TYPE_ID_INVALID)?;
+        let (node_id_from, node_id_to) = if item.is_forward {
+            (&item.source_node_id, target_node_id)
+        } else {
+            (target_node_id, &item.source_node_id)
+        };
+        if address_space.delete_reference(node_id_from, node_id_to, reference_type_id) {
+            Ok(())
+        } else {
+            Err(BAD_NOT_FOUND)
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use std::result::Result;
 
+use chrono::UTC;
+
 use opcua_types::*;
 
 use opcua_core::crypto;
@@ -7,8 +9,9 @@ use opcua_core::crypto::SecurityPolicy;
 
 use constants;
 use server::{Endpoint, ServerState};
-use session::Session;
+use session::{Session, SessionDiagnostics, UserIdentity};
 use services::Service;
+use services::message_handler::MessageHandler;
 
 pub struct SessionService {}
 
@@ -44,12 +47,29 @@ impl SessionService {
         } else {
             GOOD
         };
-        let response = if service_result.is_bad() {
-            self.service_fault(&request.request_header, service_result)
+        if service_result.is_bad() {
+            server_state.diagnostics.lock().unwrap().on_rejected_session(MessageHandler::is_security_rejection(service_result));
+            return Ok(self.service_fault(&request.request_header, service_result));
+        }
+
+        // A session created again on the same connection replaces the existing one
+        let mut diagnostics = server_state.diagnostics.lock().unwrap();
+        if session.is_created() {
+            diagnostics.on_destroy_session(&session.session_id);
+            server_state.session_manager.deregister_session(&session.authentication_token);
+            server_state.subscriptions.remove_session(&session.session_id);
+            session.session_id = NodeId::null();
+        }
+
+        // Check the server has room for another session
+        let response = if server_state.max_sessions > 0 && diagnostics.summary().current_session_count as usize >= server_state.max_sessions {
+            warn!("Session rejected because the server already has the maximum of {} sessions", server_state.max_sessions);
+            diagnostics.on_rejected_session(false);
+            self.service_fault(&request.request_header, BAD_TOO_MANY_SESSIONS)
         } else {
-            let session_id = session.next_session_id();
+            let session_id = server_state.session_manager.next_session_id();
             let authentication_token = NodeId::new_byte_string(0, ByteString::random(32));
-            let session_timeout = constants::SESSION_TIMEOUT;
+            let session_timeout = SessionService::revise_session_timeout(request.requested_session_timeout);
             let max_request_message_size = constants::MAX_REQUEST_MESSAGE_SIZE;
 
             // Calculate a signature (assuming there is a pkey)
@@ -74,6 +94,15 @@ impl SessionService {
             session.user_identity = None;
             session.client_certificate = request.client_certificate.clone();
             session.session_nonce = server_nonce.clone();
+            session.last_service_request_time = UTC::now();
+            session.clear_registered_nodes();
+            session.browse_continuation_points.clear();
+            session.history_continuation_points.clear();
+            session.diagnostics = SessionDiagnostics::new();
+            session.diagnostics.session_name = request.session_name.clone();
+            session.diagnostics.client_description = request.client_description.clone();
+            session.diagnostics.server_uri = request.server_uri.clone();
+            diagnostics.on_create_session();
 
             SupportedMessage::CreateSessionResponse(CreateSessionResponse {
                 response_header: ResponseHeader::new_good(&request.request_header),
@@ -102,29 +131,35 @@ impl SessionService {
                 if let Ok(client_cert) = crypto::X509::from_byte_string(&session.client_certificate) {
                     let server_certificate = server_state.server_certificate.as_ref().unwrap().as_byte_string();
                     service_result = crypto::verify_signature(&client_cert, &request.client_signature, &server_certificate, &session.session_nonce).unwrap();
-                    if service_result.is_good() {
-                        // TODO crypto secure channel verification
-                        let endpoint = SessionService::get_session_endpoint(server_state, session);
-                        if endpoint.is_none() {
-                            return Err(BAD_TCP_ENDPOINT_URL_INVALID);
-                        }
-                        let endpoint = endpoint.unwrap();
-                        endpoint.validate_identity_token(&request.user_identity_token);
-                        session.session_nonce = server_nonce.clone();
-                        session.activated = true;
-                        service_result = GOOD;
-                    }
                 }
             }
             service_result
         } else {
-            session.session_nonce = server_nonce.clone();
             GOOD
         };
 
+        // The identity token must be acceptable to the endpoint the session was created on
+        let service_result = if service_result.is_good() {
+            // TODO crypto secure channel verification
+            let endpoint = SessionService::get_session_endpoint(server_state, session);
+            if endpoint.is_none() {
+                return Err(BAD_TCP_ENDPOINT_URL_INVALID);
+            }
+            endpoint.unwrap().validate_identity_token(&request.user_identity_token)
+        } else {
+            service_result
+        };
+
+        if service_result.is_good() {
+            session.session_nonce = server_nonce.clone();
+            session.user_identity = UserIdentity::from_identity_token(&request.user_identity_token);
+            session.activated = true;
+        }
+
         let response = if service_result.is_bad() {
             self.service_fault(&request.request_header, service_result)
         } else {
+            session.diagnostics.locale_ids = request.locale_ids.clone();
             let diagnostic_infos = None;
             SupportedMessage::ActivateSessionResponse(ActivateSessionResponse {
                 response_header: ResponseHeader::new_good(&request.request_header),
@@ -136,16 +171,52 @@ impl SessionService {
         Ok(response)
     }
 
-    pub fn close_session(&self, _: &mut ServerState, session: &mut Session, request: CloseSessionRequest) -> Result<SupportedMessage, StatusCode> {
+    pub fn close_session(&self, server_state: &mut ServerState, session: &mut Session, request: CloseSessionRequest) -> Result<SupportedMessage, StatusCode> {
+        if session.is_created() {
+            server_state.diagnostics.lock().unwrap().on_destroy_session(&session.session_id);
+            server_state.session_manager.deregister_session(&session.authentication_token);
+            // Subscriptions that are not deleted may be transferred to another session
+            if request.delete_subscriptions {
+                server_state.subscriptions.remove_session(&session.session_id);
+            } else {
+                server_state.subscriptions.orphan_session(&session.session_id, &session.user_identity, &session.client_certificate);
+            }
+            session.session_id = NodeId::null();
+        }
         session.authentication_token = NodeId::null();
         session.user_identity = None;
         session.activated = false;
+        session.browse_continuation_points.clear();
+        session.history_continuation_points.clear();
         let response = CloseSessionResponse {
             response_header: ResponseHeader::new_good(&request.request_header),
         };
         Ok(SupportedMessage::CloseSessionResponse(response))
     }
 
+    /// Handles a CancelRequest. Outstanding requests of the session with the request handle are
+    /// completed with BAD_REQUEST_CANCELLED_BY_CLIENT.
+    pub fn cancel(&self, _: &mut ServerState, session: &mut Session, request: CancelRequest) -> Result<SupportedMessage, StatusCode> {
+        let cancel_count = session.cancel_requests(request.request_handle);
+        let response = CancelResponse {
+            response_header: ResponseHeader::new_good(&request.request_header),
+            cancel_count,
+        };
+        Ok(SupportedMessage::CancelResponse(response))
+    }
+
+    /// Revises the session timeout requested by a client to be within the limits of the server. A
+    /// timeout of zero or one that is too long is given the server's default.
+    pub fn revise_session_timeout(requested_session_timeout: Double) -> Double {
+        if !(requested_session_timeout > 0f64) || requested_session_timeout > constants::SESSION_TIMEOUT {
+            constants::SESSION_TIMEOUT
+        } else if requested_session_timeout < constants::MIN_SESSION_TIMEOUT {
+            constants::MIN_SESSION_TIMEOUT
+        } else {
+            requested_session_timeout
+        }
+    }
+
     fn get_session_endpoint(server_state: &ServerState, session: &Session) -> Option<Endpoint> {
         // Get security from endpoint url
         if session.endpoint_url.is_null() {
@@ -3,6 +3,7 @@ use std::result::Result;
 use opcua_types::*;
 
 use subscriptions::subscription::*;
+use subscriptions::subscriptions::SubscriptionOwner;
 use server::ServerState;
 use session::Session;
 use services::Service;
@@ -18,8 +19,7 @@ impl SubscriptionService {
 
     /// Handles a CreateSubscriptionRequest
     pub fn create_subscription(&self, server_state: &mut ServerState, session: &mut Session, request: CreateSubscriptionRequest) -> Result<SupportedMessage, StatusCode> {
-        let subscriptions = &mut session.subscriptions;
-        let response = if server_state.max_subscriptions > 0 && subscriptions.len() >= server_state.max_subscriptions {
+        let response = if server_state.max_subscriptions > 0 && server_state.subscriptions.len(&session.session_id) >= server_state.max_subscriptions {
             self.service_fault(&request.request_header, BAD_TOO_MANY_SUBSCRIPTIONS)
         } else {
             let subscription_id = server_state.create_subscription_id();
@@ -31,7 +31,7 @@ impl SubscriptionService {
             // Create a new subscription
             let publishing_enabled = request.publishing_enabled;
             let subscription = Subscription::new(subscription_id, publishing_enabled, revised_publishing_interval, revised_lifetime_count, revised_max_keep_alive_count, request.priority);
-            subscriptions.insert(subscription_id, subscription);
+            server_state.subscriptions.insert(&session.session_id, subscription_id, subscription);
 
             // Create the response
             SupportedMessage::CreateSubscriptionResponse(CreateSubscriptionResponse {
@@ -47,21 +47,17 @@ impl SubscriptionService {
 
     /// Handles a ModifySubscriptionRequest
     pub fn modify_subscription(&self, server_state: &mut ServerState, session: &mut Session, request: ModifySubscriptionRequest) -> Result<SupportedMessage, StatusCode> {
-        let subscriptions = &mut session.subscriptions;
         let subscription_id = request.subscription_id;
 
-        let response = if !subscriptions.contains(subscription_id) {
+        let response = if !server_state.subscriptions.contains(&session.session_id, subscription_id) {
             return Ok(self.service_fault(&request.request_header, BAD_SUBSCRIPTION_ID_INVALID));
         } else {
-            let subscription = subscriptions.get_mut(subscription_id).unwrap();
-
             let (revised_publishing_interval, revised_max_keep_alive_count, revised_lifetime_count) =
                 SubscriptionService::revise_subscription_values(server_state, request.requested_publishing_interval, request.requested_max_keep_alive_count, request.requested_lifetime_count);
 
-            subscription.publishing_interval = revised_publishing_interval;
-            subscription.max_keep_alive_count = revised_max_keep_alive_count;
-            subscription.max_lifetime_count = revised_lifetime_count;
-            subscription.priority = request.priority;
+            let subscription = server_state.subscriptions.get_mut(&session.session_id, subscription_id).unwrap();
+
+            subscription.modify(revised_publishing_interval, revised_lifetime_count, revised_max_keep_alive_count, request.priority);
             // ...max_notifications_per_publish??
 
             ModifySubscriptionResponse {
@@ -76,7 +72,7 @@ impl SubscriptionService {
     }
 
     /// Handles a DeleteSubscriptionsRequest
-    pub fn delete_subscriptions(&self, _: &mut ServerState, session: &mut Session, request: DeleteSubscriptionsRequest) -> Result<SupportedMessage, StatusCode> {
+    pub fn delete_subscriptions(&self, server_state: &mut ServerState, session: &mut Session, request: DeleteSubscriptionsRequest) -> Result<SupportedMessage, StatusCode> {
         if request.subscription_ids.is_none() {
             return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
         }
@@ -84,9 +80,8 @@ impl SubscriptionService {
             let subscription_ids = request.subscription_ids.as_ref().unwrap();
             let mut results = Vec::with_capacity(subscription_ids.len());
 
-            let subscriptions = &mut session.subscriptions;
             for subscription_id in subscription_ids {
-                let subscription = subscriptions.remove(*subscription_id);
+                let subscription = server_state.subscriptions.remove(&session.session_id, *subscription_id);
                 if subscription.is_some() {
                     results.push(GOOD);
                 } else {
@@ -104,8 +99,84 @@ impl SubscriptionService {
         Ok(SupportedMessage::DeleteSubscriptionsResponse(response))
     }
 
+    /// Handles a TransferSubscriptionsRequest. Subscriptions are moved from whichever session owns
+    /// them into this one with their sequence numbers and unacknowledged notifications intact.
+    /// The user of both sessions must be the same.
+    pub fn transfer_subscriptions(&self, server_state: &mut ServerState, session: &mut Session, request: TransferSubscriptionsRequest) -> Result<SupportedMessage, StatusCode> {
+        if request.subscription_ids.is_none() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+        let subscription_ids = request.subscription_ids.as_ref().unwrap();
+        let mut results = Vec::with_capacity(subscription_ids.len());
+        for subscription_id in subscription_ids {
+            let subscription_id = *subscription_id;
+            let status_code = if server_state.subscriptions.contains(&session.session_id, subscription_id) {
+                // Already belongs to the session
+                GOOD
+            } else if server_state.max_subscriptions > 0 && server_state.subscriptions.len(&session.session_id) >= server_state.max_subscriptions {
+                BAD_TOO_MANY_SUBSCRIPTIONS
+            } else {
+                SubscriptionService::transfer_subscription(server_state, session, subscription_id)
+            };
+            let available_sequence_numbers = if status_code.is_good() {
+                let subscription = server_state.subscriptions.get_mut(&session.session_id, subscription_id).unwrap();
+                if request.send_initial_values {
+                    subscription.resend_data();
+                }
+                subscription.available_sequence_numbers()
+            } else {
+                None
+            };
+            results.push(TransferResult {
+                status_code,
+                available_sequence_numbers,
+            });
+        }
+        let response = TransferSubscriptionsResponse {
+            response_header: ResponseHeader::new_good(&request.request_header),
+            results: Some(results),
+            diagnostic_infos: None,
+        };
+        Ok(SupportedMessage::TransferSubscriptionsResponse(response))
+    }
+
+    /// Moves the subscription to the session from the session that owns it, or from the session
+    /// that was closed without deleting it. Either must have been of the same user.
+    fn transfer_subscription(server_state: &mut ServerState, session: &Session, subscription_id: UInt32) -> StatusCode {
+        match server_state.subscriptions.owner(subscription_id).cloned() {
+            Some(SubscriptionOwner::Session(owner_session_id)) => {
+                let other_session = server_state.session_manager.find_session_by_id(&owner_session_id, &session.authentication_token);
+                if other_session.is_none() {
+                    return BAD_SUBSCRIPTION_ID_INVALID;
+                }
+                let other_session = other_session.unwrap();
+                let mut other_session = other_session.lock().unwrap();
+                if other_session.user_identity != session.user_identity {
+                    BAD_USER_ACCESS_DENIED
+                } else {
+                    // Sessions of the same client share its application instance certificate
+                    let same_client = other_session.client_certificate == session.client_certificate;
+                    let status_change = server_state.subscriptions.transfer(&session.session_id, subscription_id).unwrap().on_transfer(same_client);
+                    // The session that had the subscription is told that it was transferred
+                    other_session.publish_requests.enqueue_status_change(subscription_id, status_change);
+                    GOOD
+                }
+            }
+            Some(SubscriptionOwner::Closed(user_identity, client_certificate)) => {
+                if user_identity != session.user_identity {
+                    BAD_USER_ACCESS_DENIED
+                } else {
+                    let same_client = client_certificate == session.client_certificate;
+                    let _ = server_state.subscriptions.transfer(&session.session_id, subscription_id).unwrap().on_transfer(same_client);
+                    GOOD
+                }
+            }
+            None => BAD_SUBSCRIPTION_ID_INVALID
+        }
+    }
+
     /// Handles a SerPublishingModeRequest
-    pub fn set_publishing_mode(&self, _: &mut ServerState, session: &mut Session, request: SetPublishingModeRequest) -> Result<SupportedMessage, StatusCode> {
+    pub fn set_publishing_mode(&self, server_state: &mut ServerState, session: &mut Session, request: SetPublishingModeRequest) -> Result<SupportedMessage, StatusCode> {
         if request.subscription_ids.is_none() {
             return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
         }
@@ -113,10 +184,9 @@ impl SubscriptionService {
             let publishing_enabled = request.publishing_enabled;
             let subscription_ids = request.subscription_ids.as_ref().unwrap();
             let mut results = Vec::with_capacity(subscription_ids.len());
-            let subscriptions = &mut session.subscriptions;
             for subscription_id in subscription_ids {
-                if let Some(subscription) = subscriptions.get_mut(*subscription_id) {
-                    subscription.publishing_enabled = publishing_enabled;
+                if let Some(subscription) = server_state.subscriptions.get_mut(&session.session_id, *subscription_id) {
+                    subscription.set_publishing_enabled(publishing_enabled);
                     results.push(GOOD);
                 } else {
                     results.push(BAD_SUBSCRIPTION_ID_INVALID);
@@ -136,7 +206,13 @@ impl SubscriptionService {
     /// Handles a PublishRequest
     pub fn publish(&self, server_state: &mut ServerState, session: &mut Session, request_id: UInt32, request: PublishRequest) -> Result<SupportedMessage, StatusCode> {
         trace!("--> Receive a PublishRequest {:?}", request);
-        let publish_responses = session.enqueue_publish_request(server_state, request_id, request)?;
+        let request_header = request.request_header.clone();
+        let publish_responses = match session.enqueue_publish_request(server_state, request_id, request) {
+            Ok(publish_responses) => publish_responses,
+            Err(status_code) => {
+                return Ok(self.service_fault(&request_header, status_code));
+            }
+        };
         if publish_responses.is_some() {
             let mut publish_responses = publish_responses.unwrap();
             if publish_responses.len() != 1 {
@@ -151,9 +227,22 @@ impl SubscriptionService {
     }
 
     /// Handles a RepublishRequest
-    pub fn republish(&self, _: &mut ServerState, _: &mut Session, request: RepublishRequest) -> Result<SupportedMessage, StatusCode> {
-        // TODO look for the subscription id and sequence number in the sent items and resend it
-        Ok(self.service_fault(&request.request_header, BAD_MESSAGE_NOT_AVAILABLE))
+    pub fn republish(&self, server_state: &mut ServerState, session: &mut Session, request: RepublishRequest) -> Result<SupportedMessage, StatusCode> {
+        let notification_message = if let Some(subscription) = server_state.subscriptions.get_mut(&session.session_id, request.subscription_id) {
+            subscription.republish(request.retransmit_sequence_number)
+        } else {
+            return Ok(self.service_fault(&request.request_header, BAD_SUBSCRIPTION_ID_INVALID));
+        };
+        let response = if let Some(notification_message) = notification_message {
+            SupportedMessage::RepublishResponse(RepublishResponse {
+                response_header: ResponseHeader::new_good(&request.request_header),
+                notification_message,
+            })
+        } else {
+            // The message was acknowledged, discarded or never existed
+            self.service_fault(&request.request_header, BAD_MESSAGE_NOT_AVAILABLE)
+        };
+        Ok(response)
     }
 
     /// This function takes the requested values passed in a create / modify and returns revised
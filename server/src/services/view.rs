@@ -25,7 +25,7 @@ impl ViewService {
         ViewService {}
     }
 
-    pub fn browse(&self, server_state: &mut ServerState, _: &mut Session, request: BrowseRequest) -> Result<SupportedMessage, StatusCode> {
+    pub fn browse(&self, server_state: &mut ServerState, session: &mut Session, request: BrowseRequest) -> Result<SupportedMessage, StatusCode> {
         let browse_results = if request.nodes_to_browse.is_some() {
             let nodes_to_browse = request.nodes_to_browse.as_ref().unwrap();
             let mut browse_results: Vec<BrowseResult> = Vec::new();
@@ -36,15 +36,14 @@ impl ViewService {
                 return Ok(self.service_fault(&request.request_header, BAD_VIEW_ID_UNKNOWN));
             }
 
+            let max_browse_continuation_points = server_state.max_browse_continuation_points;
+            let max_references_per_node = request.requested_max_references_per_node as usize;
             let address_space = server_state.address_space.lock().unwrap();
 
-            // TODO number of results should be limited to browse limit and when it is exceeded,
-            // create a continuation point on the session to continue the browse when they call
-            // BrowseNext
-
-            // Nodes to browse
+            // Nodes to browse. References that exceed the requested max per node are held in a
+            // continuation point on the session to be returned by BrowseNext
             for node_to_browse in nodes_to_browse {
-                let references = ViewService::reference_descriptions(&address_space, node_to_browse, request.requested_max_references_per_node);
+                let references = ViewService::reference_descriptions(&address_space, node_to_browse);
                 let browse_result = if references.is_err() {
                     BrowseResult {
                         status_code: references.unwrap_err(),
@@ -52,11 +51,7 @@ impl ViewService {
                         references: None
                     }
                 } else {
-                    BrowseResult {
-                        status_code: GOOD,
-                        continuation_point: ByteString::null(),
-                        references: Some(references.unwrap())
-                    }
+                    ViewService::browse_result(session, max_browse_continuation_points, max_references_per_node, references.unwrap())
                 };
                 browse_results.push(browse_result);
             }
@@ -77,12 +72,48 @@ impl ViewService {
         Ok(SupportedMessage::BrowseResponse(response))
     }
 
-    pub fn browse_next(&self, _: &mut ServerState, _: &mut Session, request: BrowseNextRequest) -> Result<SupportedMessage, StatusCode> {
-        // BrowseNext does nothing
+    pub fn browse_next(&self, server_state: &mut ServerState, session: &mut Session, request: BrowseNextRequest) -> Result<SupportedMessage, StatusCode> {
+        if request.continuation_points.is_none() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+        let continuation_points = request.continuation_points.as_ref().unwrap();
+        if continuation_points.is_empty() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+
+        let max_browse_continuation_points = server_state.max_browse_continuation_points;
+
+        let mut browse_results: Vec<BrowseResult> = Vec::with_capacity(continuation_points.len());
+        for continuation_point in continuation_points {
+            // A continuation point is consumed whether it is released or used to fetch more
+            // references. If references remain they go into a fresh continuation point.
+            let browse_result = if let Some(continuation_point) = session.remove_browse_continuation_point(continuation_point) {
+                if request.release_continuation_points {
+                    BrowseResult {
+                        status_code: GOOD,
+                        continuation_point: ByteString::null(),
+                        references: None
+                    }
+                } else {
+                    ViewService::browse_result(session, max_browse_continuation_points, continuation_point.max_references_per_node, continuation_point.references)
+                }
+            } else {
+                BrowseResult {
+                    status_code: BAD_CONTINUATION_POINT_INVALID,
+                    continuation_point: ByteString::null(),
+                    references: None
+                }
+            };
+            browse_results.push(browse_result);
+        }
 
-        // TODO use the continuation point to return more nodes
+        let response = BrowseNextResponse {
+            response_header: ResponseHeader::new_good(&request.request_header),
+            results: Some(browse_results),
+            diagnostic_infos: None,
+        };
 
-        return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        Ok(SupportedMessage::BrowseNextResponse(response))
     }
 
     pub fn translate_browse_paths_to_node_ids(&self, server_state: &mut ServerState, _: &mut Session, request: TranslateBrowsePathsToNodeIdsRequest) -> Result<SupportedMessage, StatusCode> {
@@ -149,7 +180,82 @@ impl ViewService {
         Ok(SupportedMessage::TranslateBrowsePathsToNodeIdsResponse(response))
     }
 
-    fn reference_descriptions(address_space: &AddressSpace, node_to_browse: &BrowseDescription, max_references_per_node: UInt32) -> Result<Vec<ReferenceDescription>, StatusCode> {
+    /// Handles a RegisterNodesRequest. Each node is given a numeric alias that stands for it in
+    /// later requests of the session. Nodes that do not exist are returned unchanged.
+    pub fn register_nodes(&self, server_state: &mut ServerState, session: &mut Session, request: RegisterNodesRequest) -> Result<SupportedMessage, StatusCode> {
+        if request.nodes_to_register.is_none() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+        let nodes_to_register = request.nodes_to_register.as_ref().unwrap();
+        if nodes_to_register.is_empty() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+
+        let address_space = server_state.address_space.lock().unwrap();
+        let registered_node_ids = nodes_to_register.iter().map(|node_id| {
+            if address_space.node_exists(node_id) {
+                session.register_node(node_id)
+            } else {
+                node_id.clone()
+            }
+        }).collect();
+
+        let response = RegisterNodesResponse {
+            response_header: ResponseHeader::new_good(&request.request_header),
+            registered_node_ids: Some(registered_node_ids),
+        };
+
+        Ok(SupportedMessage::RegisterNodesResponse(response))
+    }
+
+    /// Handles an UnregisterNodesRequest. Ids that are not registered aliases are ignored.
+    pub fn unregister_nodes(&self, _: &mut ServerState, session: &mut Session, request: UnregisterNodesRequest) -> Result<SupportedMessage, StatusCode> {
+        if request.nodes_to_unregister.is_none() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+        let nodes_to_unregister = request.nodes_to_unregister.as_ref().unwrap();
+        if nodes_to_unregister.is_empty() {
+            return Ok(self.service_fault(&request.request_header, BAD_NOTHING_TO_DO));
+        }
+
+        for node_id in nodes_to_unregister {
+            session.unregister_node(node_id);
+        }
+
+        let response = UnregisterNodesResponse {
+            response_header: ResponseHeader::new_good(&request.request_header),
+        };
+
+        Ok(SupportedMessage::UnregisterNodesResponse(response))
+    }
+
+    /// Creates a browse result holding up to max_references_per_node references (0 means no limit).
+    /// Any references beyond that are stored in a continuation point on the session.
+    fn browse_result(session: &mut Session, max_browse_continuation_points: usize, max_references_per_node: usize, mut references: Vec<ReferenceDescription>) -> BrowseResult {
+        if max_references_per_node == 0 || references.len() <= max_references_per_node {
+            BrowseResult {
+                status_code: GOOD,
+                continuation_point: ByteString::null(),
+                references: Some(references)
+            }
+        } else {
+            let remaining_references = references.split_off(max_references_per_node);
+            match session.add_browse_continuation_point(max_browse_continuation_points, max_references_per_node, remaining_references) {
+                Ok(continuation_point) => BrowseResult {
+                    status_code: GOOD,
+                    continuation_point,
+                    references: Some(references)
+                },
+                Err(status_code) => BrowseResult {
+                    status_code,
+                    continuation_point: ByteString::null(),
+                    references: None
+                }
+            }
+        }
+    }
+
+    fn reference_descriptions(address_space: &AddressSpace, node_to_browse: &BrowseDescription) -> Result<Vec<ReferenceDescription>, StatusCode> {
         // Node must exist or there will be no references
         if node_to_browse.node_id.is_null() || !address_space.node_exists(&node_to_browse.node_id) {
             return Err(BAD_NODE_ID_UNKNOWN);
@@ -176,10 +282,6 @@ impl ViewService {
         // Construct descriptions for each reference
         let mut reference_descriptions: Vec<ReferenceDescription> = Vec::new();
         for (idx, reference) in references.iter().enumerate() {
-            if reference_descriptions.len() > max_references_per_node as usize {
-                break;
-            }
-
             let target_node_id = reference.node_id.clone();
             if target_node_id.is_null() {
                 continue;
@@ -1,9 +1,18 @@
+use std::collections::HashMap;
+use std::mem;
+use std::sync::{Arc, Mutex};
+
+use chrono::UTC;
+
 use opcua_types::*;
 
 use DateTimeUTC;
+use constants;
 use subscriptions::PublishResponseEntry;
+use subscriptions::publish_requests::PublishRequests;
 use subscriptions::subscriptions::Subscriptions;
 use server::ServerState;
+use address_space::address_space::AddressSpace;
 
 /// Session info holds information about a session created by CreateSession service
 #[derive(Clone)]
@@ -11,24 +20,255 @@ pub struct SessionInfo {}
 
 /// Structure that captures diagnostics information for the session
 #[derive(Clone)]
-pub struct SessionDiagnostics {}
+pub struct SessionDiagnostics {
+    /// The name the client gave the session
+    pub session_name: UAString,
+    /// Describes the client that created the session
+    pub client_description: ApplicationDescription,
+    /// The server uri the client asked for when it created the session
+    pub server_uri: UAString,
+    /// The locales the client asked for when it activated the session
+    pub locale_ids: Option<Vec<UAString>>,
+    /// The time the session was created
+    pub client_connection_time: DateTime,
+    /// Count of every request made on the session
+    total_request_count: ServiceCounterDataType,
+    /// Count of requests rejected because the user is not authorized to make them
+    unauthorized_request_count: UInt32,
+    /// Count of requests made to each service, by the type id of the request
+    service_counts: HashMap<NodeId, ServiceCounterDataType>,
+}
 
 impl SessionDiagnostics {
     pub fn new() -> SessionDiagnostics {
-        SessionDiagnostics {}
+        SessionDiagnostics {
+            session_name: UAString::null(),
+            client_description: ApplicationDescription {
+                application_uri: UAString::null(),
+                product_uri: UAString::null(),
+                application_name: LocalizedText::null(),
+                application_type: ApplicationType::Client,
+                gateway_server_uri: UAString::null(),
+                discovery_profile_uri: UAString::null(),
+                discovery_urls: None,
+            },
+            server_uri: UAString::null(),
+            locale_ids: None,
+            client_connection_time: DateTime::now(),
+            total_request_count: SessionDiagnostics::new_service_counter(),
+            unauthorized_request_count: 0,
+            service_counts: HashMap::new(),
+        }
+    }
+
+    /// Called when a request is handled, with the type id of the request and the result of the
+    /// service
+    pub fn on_request(&mut self, request_type_id: &NodeId, service_result: StatusCode) {
+        let is_error = service_result.is_bad();
+        SessionDiagnostics::count(&mut self.total_request_count, is_error);
+        SessionDiagnostics::count(self.service_counts.entry(request_type_id.clone()).or_insert_with(SessionDiagnostics::new_service_counter), is_error);
+        if service_result == BAD_USER_ACCESS_DENIED {
+            self.unauthorized_request_count = self.unauthorized_request_count.wrapping_add(1);
+        }
+    }
+
+    /// Returns the count of requests made to the service with the request type
+    fn service_count(&self, request_type_id: ObjectId) -> ServiceCounterDataType {
+        self.service_counts.get(&request_type_id.as_node_id()).cloned().unwrap_or_else(SessionDiagnostics::new_service_counter)
+    }
+
+    fn new_service_counter() -> ServiceCounterDataType {
+        ServiceCounterDataType {
+            total_count: 0,
+            error_count: 0,
+        }
+    }
+
+    fn count(service_counter: &mut ServiceCounterDataType, is_error: bool) {
+        service_counter.total_count = service_counter.total_count.wrapping_add(1);
+        if is_error {
+            service_counter.error_count = service_counter.error_count.wrapping_add(1);
+        }
+    }
+}
+
+/// The session manager holds every session on the server by its authentication token so that a
+/// session outlives the connection it was created on and may be reactivated on another.
+pub struct SessionManager {
+    /// Sessions by authentication token
+    sessions: HashMap<NodeId, Arc<Mutex<Session>>>,
+    /// Internal value used to create new session ids.
+    last_session_id: UInt32,
+}
+
+impl SessionManager {
+    pub fn new() -> SessionManager {
+        SessionManager {
+            sessions: HashMap::new(),
+            last_session_id: 0,
+        }
+    }
+
+    /// Returns a session id that is unique within the server
+    pub fn next_session_id(&mut self) -> NodeId {
+        self.last_session_id += 1;
+        NodeId::new_numeric(1, self.last_session_id as u64)
+    }
+
+    /// Returns the number of sessions, including detached sessions
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Registers the session that holds the authentication token, replacing any that held it before
+    pub fn register_session(&mut self, authentication_token: &NodeId, session: Arc<Mutex<Session>>) {
+        self.sessions.insert(authentication_token.clone(), session);
+    }
+
+    /// Removes the session with the authentication token from the manager
+    pub fn deregister_session(&mut self, authentication_token: &NodeId) -> Option<Arc<Mutex<Session>>> {
+        self.sessions.remove(authentication_token)
+    }
+
+    /// Returns every session, including detached sessions
+    pub fn sessions(&self) -> Vec<Arc<Mutex<Session>>> {
+        self.sessions.values().cloned().collect()
+    }
+
+    /// Finds the session with the authentication token
+    pub fn find_session(&self, authentication_token: &NodeId) -> Option<Arc<Mutex<Session>>> {
+        self.sessions.get(authentication_token).cloned()
+    }
+
+    /// Finds the session with the authentication token if it is detached from its connection
+    pub fn find_detached_session(&self, authentication_token: &NodeId) -> Option<Arc<Mutex<Session>>> {
+        self.find_session(authentication_token).and_then(|session| {
+            let detached = session.lock().unwrap().detached;
+            if detached { Some(session) } else { None }
+        })
+    }
+
+    /// Moves the detached session with the authentication token into the supplied session, which
+    /// belongs to a new connection, and registers that in its place. The session keeps its
+    /// subscriptions and monitored items but publish requests queued on the old connection are
+    /// discarded. Returns false if there is no such detached session.
+    pub fn reattach_session(&mut self, authentication_token: &NodeId, session: &mut Session, session_ref: Arc<Mutex<Session>>) -> bool {
+        if let Some(existing_session) = self.find_detached_session(authentication_token) {
+            {
+                let mut existing_session = existing_session.lock().unwrap();
+                mem::swap(session, &mut existing_session);
+            }
+            info!("Session {:?} is reattached to a new connection", session.session_id);
+            session.detached = false;
+            session.terminate_session = false;
+            session.publish_requests.clear();
+            self.register_session(authentication_token, session_ref);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Finds the session with the session id, other than the session with the supplied
+    /// authentication token, which the caller may hold the lock of
+    pub fn find_session_by_id(&self, session_id: &NodeId, except_authentication_token: &NodeId) -> Option<Arc<Mutex<Session>>> {
+        self.sessions.iter().find(|&(authentication_token, session)| {
+            authentication_token != except_authentication_token && session.lock().unwrap().session_id == *session_id
+        }).map(|(_, session)| session.clone())
+    }
+
+    /// Removes detached sessions that have not been reactivated within their timeout and returns
+    /// the ids of those that were removed
+    pub fn expire_detached_sessions(&mut self, now: &DateTimeUTC) -> Vec<NodeId> {
+        let expired = self.sessions.iter().filter(|&(_, session)| {
+            let session = session.lock().unwrap();
+            session.detached && session.is_timed_out(now)
+        }).map(|(authentication_token, _)| authentication_token.clone()).collect::<Vec<NodeId>>();
+        expired.iter().filter_map(|authentication_token| {
+            self.sessions.remove(authentication_token).map(|session| {
+                let session_id = session.lock().unwrap().session_id.clone();
+                info!("Detached session {:?} has timed out", session_id);
+                session_id
+            })
+        }).collect()
+    }
+}
+
+/// A browse continuation point holds the references of a browsed node that did not fit into a
+/// response so they can be returned by a later BrowseNext.
+#[derive(Clone, Debug)]
+pub struct BrowseContinuationPoint {
+    /// Identifier handed back to the client
+    pub id: ByteString,
+    /// The max references per node requested in the original browse
+    pub max_references_per_node: usize,
+    /// References that are yet to be returned
+    pub references: Vec<ReferenceDescription>,
+}
+
+/// A history continuation point holds the values of a history read that did not fit into a
+/// response so they can be returned by a later HistoryRead.
+#[derive(Clone, Debug)]
+pub struct HistoryContinuationPoint {
+    /// Identifier handed back to the client
+    pub id: ByteString,
+    /// The node the values belong to
+    pub node_id: NodeId,
+    /// The max values per node requested in the original read
+    pub num_values_per_node: usize,
+    /// Values that are yet to be returned
+    pub data_values: Vec<DataValue>,
+}
+
+/// The user that a session is activated as, resolved from the identity token of the activation
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserIdentity {
+    /// The id of the user token policy the token was issued under
+    pub policy_id: UAString,
+    /// The name of the user, or None for an anonymous user
+    pub user_name: Option<String>,
+}
+
+impl UserIdentity {
+    /// Resolves the user of an identity token that the endpoint has accepted. A null token is
+    /// anonymous.
+    pub fn from_identity_token(user_identity_token: &ExtensionObject) -> Option<UserIdentity> {
+        let identity_token_id = &user_identity_token.node_id;
+        if identity_token_id.is_null() {
+            Some(UserIdentity {
+                policy_id: UAString::null(),
+                user_name: None,
+            })
+        } else if *identity_token_id == ObjectId::AnonymousIdentityToken_Encoding_DefaultBinary.as_node_id() {
+            user_identity_token.decode_inner::<AnonymousIdentityToken>().ok().map(|token| UserIdentity {
+                policy_id: token.policy_id,
+                user_name: None,
+            })
+        } else if *identity_token_id == ObjectId::UserNameIdentityToken_Encoding_DefaultBinary.as_node_id() {
+            user_identity_token.decode_inner::<UserNameIdentityToken>().ok().map(|token| UserIdentity {
+                policy_id: token.policy_id,
+                user_name: Some(token.user_name.as_ref().to_string()),
+            })
+        } else {
+            None
+        }
     }
 }
 
 /// Session state is anything associated with the session at the message / service level
 pub struct Session {
-    /// Subscriptions associated with the session
-    pub subscriptions: Subscriptions,
+    /// Publish requests of the session. The subscriptions they are used by are held by the
+    /// server, see ServerState::subscriptions.
+    pub publish_requests: PublishRequests,
     /// The session identifier
     pub session_id: NodeId,
     /// Indicates if the session has received an ActivateSession
     pub activated: bool,
     /// Flag to indicate session should be terminated
     pub terminate_session: bool,
+    /// Indicates the connection of the session has ended and the session is waiting for the
+    /// client to reactivate it on a new one
+    pub detached: bool,
     /// Security policy
     pub security_policy_uri: String,
     /// Client's certificate
@@ -37,10 +277,12 @@ pub struct Session {
     pub authentication_token: NodeId,
     /// Session nonce
     pub session_nonce: ByteString,
-    /// Session timeout
+    /// Session timeout, the time in MS after which the session expires if it receives no requests
     pub session_timeout: Double,
-    /// User identity token
-    pub user_identity: Option<ExtensionObject>,
+    /// Time of the last request made on the session, used to detect a timeout
+    pub last_service_request_time: DateTimeUTC,
+    /// The user the session was activated as
+    pub user_identity: Option<UserIdentity>,
     /// Negotiated max request message size
     pub max_request_message_size: UInt32,
     /// Negotiated max response message size
@@ -49,49 +291,253 @@ pub struct Session {
     pub endpoint_url: UAString,
     /// Diagnostics associated with the session
     pub diagnostics: SessionDiagnostics,
-    /// Internal value used to create new session ids.
-    last_session_id: UInt32,
+    /// Browse continuation points, oldest first
+    pub browse_continuation_points: Vec<BrowseContinuationPoint>,
+    /// History continuation points, oldest first
+    pub history_continuation_points: Vec<HistoryContinuationPoint>,
+    /// Nodes registered by the client, by the identifier of their alias less
+    /// REGISTERED_NODE_ID_BASE
+    registered_nodes: HashMap<UInt32, NodeId>,
+    /// The identifier of the next alias, less REGISTERED_NODE_ID_BASE. Aliases are not reused
+    /// so an unregistered alias never stands for another node.
+    next_registered_node_id: UInt32,
 }
 
 impl Session {
     pub fn new() -> Session {
         Session {
-            subscriptions: Subscriptions::new(),
+            publish_requests: PublishRequests::new(),
             session_id: NodeId::null(),
             activated: false,
             terminate_session: false,
+            detached: false,
             client_certificate: ByteString::null(),
             security_policy_uri: String::new(),
             authentication_token: NodeId::null(),
             session_nonce: ByteString::null(),
             session_timeout: 0f64,
+            last_service_request_time: UTC::now(),
             user_identity: None,
             max_request_message_size: 0,
             max_response_message_size: 0,
             endpoint_url: UAString::null(),
             diagnostics: SessionDiagnostics::new(),
-            last_session_id: 0,
+            browse_continuation_points: Vec::new(),
+            history_continuation_points: Vec::new(),
+            registered_nodes: HashMap::new(),
+            next_registered_node_id: 0,
         }
     }
 
-    pub fn next_session_id(&mut self) -> NodeId {
-        self.last_session_id += 1;
-        NodeId::new_numeric(1, self.last_session_id as u64)
+    /// Tests if a session has been created on the connection and not yet closed
+    pub fn is_created(&self) -> bool {
+        !self.session_id.is_null()
+    }
+
+    /// Returns the diagnostics of the session, whose subscriptions are among those supplied
+    pub fn session_diagnostics(&self, subscriptions: &Subscriptions) -> SessionDiagnosticsDataType {
+        use opcua_types::ObjectId::*;
+        let diagnostics = &self.diagnostics;
+        SessionDiagnosticsDataType {
+            session_id: self.session_id.clone(),
+            session_name: diagnostics.session_name.clone(),
+            client_description: diagnostics.client_description.clone(),
+            server_uri: diagnostics.server_uri.clone(),
+            endpoint_url: self.endpoint_url.clone(),
+            locale_ids: diagnostics.locale_ids.clone(),
+            actual_session_timeout: self.session_timeout,
+            max_response_message_size: self.max_response_message_size,
+            client_connection_time: diagnostics.client_connection_time.clone(),
+            client_last_contact_time: DateTime::from_chrono(&self.last_service_request_time),
+            current_subscriptions_count: subscriptions.len(&self.session_id) as UInt32,
+            current_monitored_items_count: subscriptions.monitored_item_count(&self.session_id) as UInt32,
+            current_publish_requests_in_queue: self.publish_requests.queue.len() as UInt32,
+            total_request_count: diagnostics.total_request_count.clone(),
+            unauthorized_request_count: diagnostics.unauthorized_request_count,
+            read_count: diagnostics.service_count(ReadRequest_Encoding_DefaultBinary),
+            history_read_count: diagnostics.service_count(HistoryReadRequest_Encoding_DefaultBinary),
+            write_count: diagnostics.service_count(WriteRequest_Encoding_DefaultBinary),
+            history_update_count: diagnostics.service_count(HistoryUpdateRequest_Encoding_DefaultBinary),
+            call_count: diagnostics.service_count(CallRequest_Encoding_DefaultBinary),
+            create_monitored_items_count: diagnostics.service_count(CreateMonitoredItemsRequest_Encoding_DefaultBinary),
+            modify_monitored_items_count: diagnostics.service_count(ModifyMonitoredItemsRequest_Encoding_DefaultBinary),
+            set_monitoring_mode_count: diagnostics.service_count(SetMonitoringModeRequest_Encoding_DefaultBinary),
+            set_triggering_count: diagnostics.service_count(SetTriggeringRequest_Encoding_DefaultBinary),
+            delete_monitored_items_count: diagnostics.service_count(DeleteMonitoredItemsRequest_Encoding_DefaultBinary),
+            create_subscription_count: diagnostics.service_count(CreateSubscriptionRequest_Encoding_DefaultBinary),
+            modify_subscription_count: diagnostics.service_count(ModifySubscriptionRequest_Encoding_DefaultBinary),
+            set_publishing_mode_count: diagnostics.service_count(SetPublishingModeRequest_Encoding_DefaultBinary),
+            publish_count: diagnostics.service_count(PublishRequest_Encoding_DefaultBinary),
+            republish_count: diagnostics.service_count(RepublishRequest_Encoding_DefaultBinary),
+            transfer_subscriptions_count: diagnostics.service_count(TransferSubscriptionsRequest_Encoding_DefaultBinary),
+            delete_subscriptions_count: diagnostics.service_count(DeleteSubscriptionsRequest_Encoding_DefaultBinary),
+            add_nodes_count: diagnostics.service_count(AddNodesRequest_Encoding_DefaultBinary),
+            add_references_count: diagnostics.service_count(AddReferencesRequest_Encoding_DefaultBinary),
+            delete_nodes_count: diagnostics.service_count(DeleteNodesRequest_Encoding_DefaultBinary),
+            delete_references_count: diagnostics.service_count(DeleteReferencesRequest_Encoding_DefaultBinary),
+            browse_count: diagnostics.service_count(BrowseRequest_Encoding_DefaultBinary),
+            browse_next_count: diagnostics.service_count(BrowseNextRequest_Encoding_DefaultBinary),
+            translate_browse_paths_to_node_ids_count: diagnostics.service_count(TranslateBrowsePathsToNodeIdsRequest_Encoding_DefaultBinary),
+            query_first_count: diagnostics.service_count(QueryFirstRequest_Encoding_DefaultBinary),
+            query_next_count: diagnostics.service_count(QueryNextRequest_Encoding_DefaultBinary),
+            register_nodes_count: diagnostics.service_count(RegisterNodesRequest_Encoding_DefaultBinary),
+            unregister_nodes_count: diagnostics.service_count(UnregisterNodesRequest_Encoding_DefaultBinary),
+        }
+    }
+
+    /// Registers the node and returns a numeric alias for it. The alias spares the client from
+    /// sending a long identifier and the session resolves it by number, but the node is still
+    /// found in the address space by its own id. A node that is already registered keeps its
+    /// alias and a numeric node id is its own alias.
+    pub fn register_node(&mut self, node_id: &NodeId) -> NodeId {
+        if let Identifier::Numeric(_) = node_id.identifier {
+            return node_id.clone();
+        }
+        let id = if let Some((id, _)) = self.registered_nodes.iter().find(|&(_, n)| n == node_id) {
+            *id
+        } else {
+            // Skip ids that are still in use if the ids wrap around
+            loop {
+                let id = self.next_registered_node_id;
+                self.next_registered_node_id = if id >= constants::MAX_REGISTERED_NODE_ID { 0 } else { id + 1 };
+                if !self.registered_nodes.contains_key(&id) {
+                    self.registered_nodes.insert(id, node_id.clone());
+                    break id;
+                }
+            }
+        };
+        NodeId::new_numeric(constants::REGISTERED_NODE_NAMESPACE, constants::REGISTERED_NODE_ID_BASE + id as u64)
+    }
+
+    /// Unregisters the node with the alias. Returns false if the alias is not registered.
+    pub fn unregister_node(&mut self, alias: &NodeId) -> bool {
+        if let Some(id) = Session::registered_node_id(alias) {
+            self.registered_nodes.remove(&id).is_some()
+        } else {
+            false
+        }
+    }
+
+    /// Unregisters every node, e.g. when a new session is created on the connection
+    pub fn clear_registered_nodes(&mut self) {
+        self.registered_nodes.clear();
     }
 
-    pub fn enqueue_publish_request(&mut self, server_state: &ServerState, request_id: UInt32, request: PublishRequest) -> Result<Option<Vec<PublishResponseEntry>>, StatusCode> {
+    /// Returns the node that the id is an alias of, or the id itself if it is not an alias. A node
+    /// in the address space with the id takes precedence over an alias, so a node the application
+    /// adds in the range of aliases is never shadowed by one.
+    pub fn resolve_node_id<'a>(&'a self, address_space: &AddressSpace, node_id: &'a NodeId) -> &'a NodeId {
+        if address_space.node_exists(node_id) {
+            node_id
+        } else if let Some(registered_node_id) = Session::registered_node_id(node_id).and_then(|id| self.registered_nodes.get(&id)) {
+            registered_node_id
+        } else {
+            node_id
+        }
+    }
+
+    /// Returns the identifier, less REGISTERED_NODE_ID_BASE, of the alias that the node id is
+    /// if it is in the range of aliases
+    fn registered_node_id(node_id: &NodeId) -> Option<UInt32> {
+        if node_id.namespace == constants::REGISTERED_NODE_NAMESPACE {
+            if let Identifier::Numeric(value) = node_id.identifier {
+                if value >= constants::REGISTERED_NODE_ID_BASE && value - constants::REGISTERED_NODE_ID_BASE <= constants::MAX_REGISTERED_NODE_ID as u64 {
+                    return Some((value - constants::REGISTERED_NODE_ID_BASE) as UInt32);
+                }
+            }
+        }
+        None
+    }
+
+    /// Tests if the session has received no request within its timeout
+    pub fn is_timed_out(&self, now: &DateTimeUTC) -> bool {
+        if self.is_created() && self.session_timeout > 0f64 {
+            let elapsed = now.signed_duration_since(self.last_service_request_time);
+            elapsed.num_milliseconds() as f64 > self.session_timeout
+        } else {
+            false
+        }
+    }
+
+    /// Stores the remaining references of a browse in a new continuation point and returns its
+    /// identifier. Fails with BAD_NO_CONTINUATION_POINTS if the session already holds the maximum
+    /// number of continuation points.
+    pub fn add_browse_continuation_point(&mut self, max_browse_continuation_points: usize, max_references_per_node: usize, references: Vec<ReferenceDescription>) -> Result<ByteString, StatusCode> {
+        if max_browse_continuation_points > 0 && self.browse_continuation_points.len() >= max_browse_continuation_points {
+            Err(BAD_NO_CONTINUATION_POINTS)
+        } else {
+            let id = ByteString::random(16);
+            self.browse_continuation_points.push(BrowseContinuationPoint {
+                id: id.clone(),
+                max_references_per_node,
+                references,
+            });
+            Ok(id)
+        }
+    }
+
+    /// Removes the continuation point with the supplied identifier from the session and returns it
+    pub fn remove_browse_continuation_point(&mut self, id: &ByteString) -> Option<BrowseContinuationPoint> {
+        if let Some(idx) = self.browse_continuation_points.iter().position(|cp| cp.id == *id) {
+            Some(self.browse_continuation_points.remove(idx))
+        } else {
+            None
+        }
+    }
+
+    /// Stores the remaining values of a history read in a new continuation point and returns its
+    /// identifier. Fails with BAD_NO_CONTINUATION_POINTS if the session already holds the maximum
+    /// number of continuation points.
+    pub fn add_history_continuation_point(&mut self, max_history_continuation_points: usize, node_id: &NodeId, num_values_per_node: usize, data_values: Vec<DataValue>) -> Result<ByteString, StatusCode> {
+        if max_history_continuation_points > 0 && self.history_continuation_points.len() >= max_history_continuation_points {
+            Err(BAD_NO_CONTINUATION_POINTS)
+        } else {
+            let id = ByteString::random(16);
+            self.history_continuation_points.push(HistoryContinuationPoint {
+                id: id.clone(),
+                node_id: node_id.clone(),
+                num_values_per_node,
+                data_values,
+            });
+            Ok(id)
+        }
+    }
+
+    /// Removes the history continuation point with the supplied identifier from the session and
+    /// returns it
+    pub fn remove_history_continuation_point(&mut self, id: &ByteString) -> Option<HistoryContinuationPoint> {
+        if let Some(idx) = self.history_continuation_points.iter().position(|cp| cp.id == *id) {
+            Some(self.history_continuation_points.remove(idx))
+        } else {
+            None
+        }
+    }
+
+    pub fn enqueue_publish_request(&mut self, server_state: &mut ServerState, request_id: UInt32, request: PublishRequest) -> Result<Option<Vec<PublishResponseEntry>>, StatusCode> {
         let address_space = server_state.address_space.lock().unwrap();
-        self.subscriptions.enqueue_publish_request(&address_space, request_id, request)
+        server_state.subscriptions.enqueue_publish_request(&self.session_id, &mut self.publish_requests, &address_space, server_state.max_publish_requests, request_id, request)
     }
 
-    pub fn tick_subscriptions(&mut self, server_state: &ServerState, receive_publish_request: bool) -> Option<Vec<PublishResponseEntry>> {
+    pub fn tick_subscriptions(&mut self, server_state: &mut ServerState, receive_publish_request: bool) -> Option<Vec<PublishResponseEntry>> {
         let address_space = server_state.address_space.lock().unwrap();
-        self.subscriptions.tick(receive_publish_request, &address_space)
+        server_state.subscriptions.tick(&self.session_id, &mut self.publish_requests, receive_publish_request, &address_space)
     }
 
     /// Iterates through the existing queued publish requests and creates a timeout
     /// publish response any that have expired.
     pub fn expire_stale_publish_requests(&mut self, now: &DateTimeUTC) -> Option<Vec<PublishResponseEntry>> {
-        self.subscriptions.expire_stale_publish_requests(now)
+        self.publish_requests.expire_stale_publish_requests(now)
+    }
+
+    /// Cancels the outstanding requests of the session with the request handle and returns how
+    /// many were cancelled. Only publish requests are held by the session. Their responses are
+    /// sent by the subscription timer.
+    pub fn cancel_requests(&mut self, request_handle: UInt32) -> UInt32 {
+        self.publish_requests.cancel_publish_requests(request_handle) as UInt32
+    }
+
+    /// Takes the responses to publish requests that were cancelled so they can be sent
+    pub fn take_cancelled_publish_responses(&mut self) -> Option<Vec<PublishResponseEntry>> {
+        self.publish_requests.take_cancelled_publish_responses()
     }
 }
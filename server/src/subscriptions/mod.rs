@@ -18,5 +18,7 @@ pub struct PublishResponseEntry {
 }
 
 pub mod subscriptions;
+pub mod publish_requests;
 pub mod subscription;
 pub mod monitored_item;
+pub mod sampling_scheduler;
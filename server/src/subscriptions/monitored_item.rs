@@ -1,4 +1,5 @@
 use std::result::Result;
+use std::collections::BTreeSet;
 
 use chrono;
 use time;
@@ -9,11 +10,15 @@ use constants;
 
 use DateTimeUTC;
 use address_space::address_space::AddressSpace;
+use events::event_filter::ParsedEventFilter;
+use history::aggregates::ParsedAggregateFilter;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum FilterType {
     None,
     DataChangeFilter(DataChangeFilter),
+    EventFilter(ParsedEventFilter),
+    AggregateFilter(ParsedAggregateFilter),
 }
 
 impl FilterType {
@@ -25,17 +30,79 @@ impl FilterType {
             Ok(FilterType::None)
         } else if filter_type_id == &ObjectId::DataChangeFilter_Encoding_DefaultBinary.as_node_id() {
             Ok(FilterType::DataChangeFilter(filter.decode_inner::<DataChangeFilter>()?))
+        } else if filter_type_id == &ObjectId::EventFilter_Encoding_DefaultBinary.as_node_id() {
+            Ok(FilterType::EventFilter(ParsedEventFilter::parse(&filter.decode_inner::<EventFilter>()?)?))
+        } else if filter_type_id == &ObjectId::AggregateFilter_Encoding_DefaultBinary.as_node_id() {
+            Ok(FilterType::AggregateFilter(ParsedAggregateFilter::parse(&filter.decode_inner::<AggregateFilter>()?)?))
         } else {
             error!("Requested data filter type is not supported, {:?}", filter_type_id);
             Err(BAD_FILTER_NOT_ALLOWED)
         }
     }
+
+    /// Tests if the filter is a data change filter with a percent deadband, which compares values
+    /// against the EURange of the node
+    pub fn is_percent_deadband(&self) -> bool {
+        if let FilterType::DataChangeFilter(ref filter) = *self { filter.deadband_type == 2 } else { false }
+    }
+
+    /// Parses the filter and checks it suits the attribute being monitored. Event filters are
+    /// only for the EventNotifier attribute and the EventNotifier attribute requires an event filter.
+    /// Aggregate filters are only for the Value attribute.
+    fn from_filter_for_attribute(filter: &ExtensionObject, attribute_id: UInt32) -> Result<FilterType, StatusCode> {
+        let filter = FilterType::from_filter(filter)?;
+        let is_event_notifier = attribute_id == AttributeId::EventNotifier as UInt32;
+        match filter {
+            FilterType::EventFilter(_) => {
+                if !is_event_notifier {
+                    return Err(BAD_FILTER_NOT_ALLOWED);
+                }
+            }
+            FilterType::AggregateFilter(_) => {
+                if attribute_id != AttributeId::Value as UInt32 {
+                    return Err(BAD_FILTER_NOT_ALLOWED);
+                }
+            }
+            _ => {
+                if is_event_notifier {
+                    return Err(BAD_MONITORED_ITEM_FILTER_INVALID);
+                }
+            }
+        }
+        Ok(filter)
+    }
+}
+
+/// The server's limits on the sampling interval and queue size that clients may request for
+/// monitored items
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MonitoredItemLimits {
+    /// Minimum sampling interval in milliseconds
+    pub min_sampling_interval: Double,
+    /// Maximum sampling interval in milliseconds, 0 means no limit
+    pub max_sampling_interval: Double,
+    /// Maximum queue size of data change items
+    pub max_data_change_queue_size: usize,
+    /// Maximum queue size of event items
+    pub max_event_queue_size: usize,
+}
+
+impl Default for MonitoredItemLimits {
+    fn default() -> MonitoredItemLimits {
+        MonitoredItemLimits {
+            min_sampling_interval: constants::MIN_SAMPLING_INTERVAL,
+            max_sampling_interval: constants::DEFAULT_MAX_SAMPLING_INTERVAL,
+            max_data_change_queue_size: constants::MAX_DATA_CHANGE_QUEUE_SIZE,
+            max_event_queue_size: constants::MAX_EVENT_QUEUE_SIZE,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct MonitoredItem {
     pub monitored_item_id: UInt32,
     pub item_to_monitor: ReadValueId,
+    pub index_range: NumericRange,
     pub monitoring_mode: MonitoringMode,
     pub client_handle: UInt32,
     pub sampling_interval: Duration,
@@ -43,19 +110,32 @@ pub struct MonitoredItem {
     pub discard_oldest: Boolean,
     pub queue_size: usize,
     pub notification_queue: Vec<MonitoredItemNotification>,
+    pub event_queue: Vec<EventFieldList>,
     pub queue_overflow: bool,
+    /// The number of times the queue has overflowed, for diagnostics
+    pub queue_overflow_count: UInt32,
     last_sample_time: DateTimeUTC,
     last_data_value: Option<DataValue>,
+    /// Index of the last raised event that was examined by an event monitored item
+    last_event_index: Option<usize>,
+    /// Ids of the items in the same subscription that are reported when this item reports a change
+    pub triggered_items: BTreeSet<UInt32>,
 }
 
 impl MonitoredItem {
-    pub fn new(monitored_item_id: UInt32, request: &MonitoredItemCreateRequest) -> Result<MonitoredItem, StatusCode> {
-        let filter = FilterType::from_filter(&request.requested_parameters.filter)?;
-        let sampling_interval = MonitoredItem::sanitize_sampling_interval(request.requested_parameters.sampling_interval);
-        let queue_size = MonitoredItem::sanitize_queue_size(request.requested_parameters.queue_size as usize);
+    /// Creates a monitored item. The requested sampling interval and queue size are revised to suit
+    /// the server's limits and the minimum sampling interval of the monitored node, if it has one.
+    /// A negative sampling interval is replaced by the publishing interval of the subscription and
+    /// an interval of 0 is only kept for exception-based items.
+    pub fn new(monitored_item_id: UInt32, request: &MonitoredItemCreateRequest, limits: &MonitoredItemLimits, publishing_interval: Double, minimum_sampling_interval: Option<Double>, exception_based: bool) -> Result<MonitoredItem, StatusCode> {
+        let filter = FilterType::from_filter_for_attribute(&request.requested_parameters.filter, request.item_to_monitor.attribute_id)?;
+        let index_range = NumericRange::parse(&request.item_to_monitor.index_range)?;
+        let sampling_interval = MonitoredItem::sanitize_sampling_interval(&filter, request.requested_parameters.sampling_interval, limits, publishing_interval, minimum_sampling_interval, exception_based);
+        let queue_size = MonitoredItem::sanitize_queue_size(&filter, request.requested_parameters.queue_size as usize, limits);
         Ok(MonitoredItem {
             monitored_item_id,
             item_to_monitor: request.item_to_monitor.clone(),
+            index_range,
             monitoring_mode: request.monitoring_mode,
             client_handle: request.requested_parameters.client_handle,
             sampling_interval,
@@ -65,20 +145,48 @@ impl MonitoredItem {
             last_data_value: None,
             queue_size,
             notification_queue: Vec::with_capacity(queue_size),
-            queue_overflow: false
+            event_queue: Vec::new(),
+            queue_overflow: false,
+            queue_overflow_count: 0,
+            last_event_index: None,
+            triggered_items: BTreeSet::new(),
         })
     }
 
-    /// Modifies the existing item with the values of the modify request
-    pub fn modify(&mut self, request: &MonitoredItemModifyRequest) -> Result<(), StatusCode> {
-        self.filter = FilterType::from_filter(&request.requested_parameters.filter)?;
-        self.sampling_interval = MonitoredItem::sanitize_sampling_interval(request.requested_parameters.sampling_interval);
-        self.queue_size = MonitoredItem::sanitize_queue_size(request.requested_parameters.queue_size as usize);
+    /// Modifies the existing item with the values of the modify request, revising them in the same
+    /// way as when the item is created
+    pub fn modify(&mut self, request: &MonitoredItemModifyRequest, limits: &MonitoredItemLimits, publishing_interval: Double, minimum_sampling_interval: Option<Double>, exception_based: bool) -> Result<(), StatusCode> {
+        self.filter = FilterType::from_filter_for_attribute(&request.requested_parameters.filter, self.item_to_monitor.attribute_id)?;
+        self.sampling_interval = MonitoredItem::sanitize_sampling_interval(&self.filter, request.requested_parameters.sampling_interval, limits, publishing_interval, minimum_sampling_interval, exception_based);
+        self.queue_size = MonitoredItem::sanitize_queue_size(&self.filter, request.requested_parameters.queue_size as usize, limits);
         self.client_handle = request.requested_parameters.client_handle;
         self.discard_oldest = request.requested_parameters.discard_oldest;
         Ok(())
     }
 
+    /// Sets the monitoring mode. A disabled item discards what it has queued and reports the
+    /// current value once it is enabled again.
+    pub fn set_monitoring_mode(&mut self, monitoring_mode: MonitoringMode) {
+        if monitoring_mode == MonitoringMode::Disabled {
+            self.notification_queue.clear();
+            self.event_queue.clear();
+            self.queue_overflow = false;
+            self.last_data_value = None;
+            self.last_event_index = None;
+        }
+        self.monitoring_mode = monitoring_mode;
+    }
+
+    /// Links an item that is reported whenever this item reports a change
+    pub fn add_triggered_item(&mut self, monitored_item_id: UInt32) {
+        self.triggered_items.insert(monitored_item_id);
+    }
+
+    /// Removes the link to a triggered item, returning false if there was no link
+    pub fn remove_triggered_item(&mut self, monitored_item_id: UInt32) -> bool {
+        self.triggered_items.remove(&monitored_item_id)
+    }
+
     /// Called repeatedly on the monitored item.
     ///
     /// If the monitored item has a negative interval and subscription interval has elapsed,
@@ -87,6 +195,15 @@ impl MonitoredItem {
     ///
     /// Function returns true if a notification message was added to the queue
     pub fn tick(&mut self, address_space: &AddressSpace, now: &DateTimeUTC, publishing_timer_expired: bool) -> bool {
+        // Test if monitoring
+        if self.monitoring_mode == MonitoringMode::Disabled {
+            return false;
+        }
+
+        if self.is_event_item() {
+            return self.tick_events(address_space);
+        }
+
         let check_value = if self.sampling_interval > 0f64 {
             // Compare sample interval
             let sampling_interval = time::Duration::milliseconds(self.sampling_interval as i64);
@@ -103,28 +220,31 @@ impl MonitoredItem {
             self.last_data_value.is_none()
         };
 
+        if self.is_aggregate_item() {
+            return self.tick_aggregate(address_space, now, check_value);
+        }
+
         // Test the value (or don't)
         if !check_value {
             return false;
         }
 
-        // Test if monitoring
+        self.sample(address_space, now)
+    }
+
+    /// Samples the value of the item, queuing a notification if the value changed. This is called
+    /// by tick when the sampling interval elapses or directly when the item is due to be sampled.
+    ///
+    /// Function returns true if a notification message was added to the queue
+    pub fn sample(&mut self, address_space: &AddressSpace, now: &DateTimeUTC) -> bool {
         if self.monitoring_mode == MonitoringMode::Disabled {
             return false;
         }
 
         self.last_sample_time = *now;
 
-        if let Some(node) = address_space.find_node(&self.item_to_monitor.node_id) {
-            let node = node.as_node();
-            let attribute_id = AttributeId::from_u32(self.item_to_monitor.attribute_id);
-            if attribute_id.is_err() {
-                trace!("Item has no attribute_id {:?} so it hasn't changed, node {:?}", attribute_id, self.item_to_monitor.node_id);
-                return false;
-            }
-            let attribute_id = attribute_id.unwrap();
-            let data_value = node.find_attribute(attribute_id);
-            if let Some(data_value) = data_value {
+        if address_space.find_node(&self.item_to_monitor.node_id).is_some() {
+            if let Some(data_value) = self.sample_value(address_space) {
                 // Test for data change
                 let data_change = if self.last_data_value.is_none() {
                     // There is no previous check so yes it changed
@@ -133,11 +253,22 @@ impl MonitoredItem {
                 } else {
                     match self.filter {
                         FilterType::None => {
-                            data_value.value != self.last_data_value.as_ref().unwrap().value
+                            // Without a filter the trigger is status or value
+                            let last_data_value = self.last_data_value.as_ref().unwrap();
+                            data_value.status != last_data_value.status || data_value.value != last_data_value.value
                         }
                         FilterType::DataChangeFilter(ref filter) => {
-                            // Use filter to compare values
-                            !filter.compare(&data_value, self.last_data_value.as_ref().unwrap(), None)
+                            // Use filter to compare values, a percent deadband is a percentage of the EURange
+                            let eu_range = if self.filter.is_percent_deadband() {
+                                address_space.find_eu_range(&self.item_to_monitor.node_id).map(|eu_range| (eu_range.low, eu_range.high))
+                            } else {
+                                None
+                            };
+                            !filter.compare(&data_value, self.last_data_value.as_ref().unwrap(), eu_range)
+                        }
+                        FilterType::EventFilter(_) | FilterType::AggregateFilter(_) => {
+                            // Event and aggregate items are handled by their own tick
+                            false
                         }
                     }
                 };
@@ -168,26 +299,175 @@ impl MonitoredItem {
         }
     }
 
+    /// Forgets the last value so the current value is reported on the next sample even if it has
+    /// not changed. Event and aggregate items are unaffected.
+    pub fn resend_data(&mut self) {
+        if !self.is_event_item() && !self.is_aggregate_item() {
+            self.last_data_value = None;
+        }
+    }
+
+    /// Tests if the item monitors a node for events rather than an attribute for data changes
+    pub fn is_event_item(&self) -> bool {
+        if let FilterType::EventFilter(_) = self.filter { true } else { false }
+    }
+
+    /// Examines the events raised since the last tick. Events from sources that the monitored node
+    /// is the notifier for and which pass the filter's where clause are queued with the values of
+    /// the select clauses. Events raised before the first tick are not reported.
+    ///
+    /// Function returns true if an event was added to the queue to be reported
+    fn tick_events(&mut self, address_space: &AddressSpace) -> bool {
+        let last_event_index = self.last_event_index.unwrap_or(address_space.last_event_index());
+        self.last_event_index = Some(address_space.last_event_index());
+
+        if self.monitoring_mode == MonitoringMode::Disabled {
+            return false;
+        }
+
+        let mut event_fields = Vec::new();
+        if let FilterType::EventFilter(ref filter) = self.filter {
+            for &&(_, ref event) in &address_space.events_since(last_event_index) {
+                if address_space.is_event_notifier_for(&self.item_to_monitor.node_id, &event.source_node) && filter.matches(address_space, event) {
+                    event_fields.push(filter.select(address_space, event));
+                }
+            }
+        }
+        let events_queued = !event_fields.is_empty();
+        for event_fields in event_fields {
+            let client_handle = self.client_handle;
+            self.enqueue_event(EventFieldList {
+                client_handle,
+                event_fields: Some(event_fields),
+            });
+        }
+        // A sampling item keeps its events queued until it is triggered
+        events_queued && self.monitoring_mode == MonitoringMode::Reporting
+    }
+
+    /// Tests if the item reports aggregates of the sampled values rather than the values
+    pub fn is_aggregate_item(&self) -> bool {
+        if let FilterType::AggregateFilter(_) = self.filter { true } else { false }
+    }
+
+    /// Samples the value when it is time to and passes it to the aggregate filter if it changed.
+    /// The aggregate of each processing interval that has completed is queued.
+    ///
+    /// Function returns true if a notification message was added to the queue
+    fn tick_aggregate(&mut self, address_space: &AddressSpace, now: &DateTimeUTC, check_value: bool) -> bool {
+        if check_value {
+            self.last_sample_time = *now;
+            if let Some(data_value) = self.sample_value(address_space) {
+                if self.last_data_value.as_ref() != Some(&data_value) {
+                    self.last_data_value = Some(data_value.clone());
+                    if let FilterType::AggregateFilter(ref mut filter) = self.filter {
+                        filter.add_sample(data_value);
+                    }
+                }
+            }
+        }
+        let aggregate_values = if let FilterType::AggregateFilter(ref mut filter) = self.filter {
+            filter.aggregate_completed_intervals(&DateTime::from_chrono(now))
+        } else {
+            Vec::new()
+        };
+        let values_queued = !aggregate_values.is_empty();
+        for value in aggregate_values {
+            let client_handle = self.client_handle;
+            self.enqueue_notification_message(MonitoredItemNotification {
+                client_handle,
+                value,
+            });
+        }
+        values_queued
+    }
+
+    /// Reads the monitored attribute of the node, reduced to the index range
+    fn sample_value(&self, address_space: &AddressSpace) -> Option<DataValue> {
+        let node = address_space.find_node(&self.item_to_monitor.node_id)?;
+        let attribute_id = AttributeId::from_u32(self.item_to_monitor.attribute_id);
+        if attribute_id.is_err() {
+            trace!("Item has no attribute_id {:?} so it hasn't changed, node {:?}", attribute_id, self.item_to_monitor.node_id);
+            return None;
+        }
+        node.as_node().find_attribute(attribute_id.unwrap()).map(|data_value| self.apply_index_range(data_value))
+    }
+
+    /// Returns the result of the filter that is returned to the client when the item is created
+    /// or modified. Only aggregate filters have one.
+    pub fn filter_result(&self) -> ExtensionObject {
+        if let FilterType::AggregateFilter(ref filter) = self.filter {
+            ExtensionObject::from_encodable(ObjectId::AggregateFilterResult_Encoding_DefaultBinary.as_node_id(), filter.filter_result())
+        } else {
+            ExtensionObject::null()
+        }
+    }
+
+    /// Reduces the sampled value to the part described by the index range. If the range cannot
+    /// be applied the value is replaced by the error status.
+    fn apply_index_range(&self, mut data_value: DataValue) -> DataValue {
+        if self.index_range != NumericRange::None {
+            let value = if let Some(ref value) = data_value.value {
+                value.range_of(&self.index_range)
+            } else {
+                Err(BAD_INDEX_RANGE_NO_DATA)
+            };
+            match value {
+                Ok(value) => {
+                    data_value.value = Some(value);
+                }
+                Err(err) => {
+                    data_value.value = None;
+                    data_value.status = Some(err);
+                }
+            }
+        }
+        data_value
+    }
+
     /// Enqueues a notification message for the monitored item
     pub fn enqueue_notification_message(&mut self, notification: MonitoredItemNotification) {
-        // test for overflow
-        self.queue_overflow = if self.notification_queue.len() == self.queue_size {
+        if self.notification_queue.len() == self.queue_size {
             trace!("Data change overflow, node {:?}", self.item_to_monitor.node_id);
+        }
+        self.queue_overflow = MonitoredItem::enqueue(&mut self.notification_queue, self.queue_size, self.discard_oldest, notification);
+        if self.queue_overflow {
+            self.queue_overflow_count += 1;
+        }
+    }
+
+    /// Enqueues an event for the monitored item
+    pub fn enqueue_event(&mut self, event: EventFieldList) {
+        if self.event_queue.len() == self.queue_size {
+            trace!("Event overflow, node {:?}", self.item_to_monitor.node_id);
+        }
+        self.queue_overflow = MonitoredItem::enqueue(&mut self.event_queue, self.queue_size, self.discard_oldest, event);
+        if self.queue_overflow {
+            self.queue_overflow_count += 1;
+        }
+    }
+
+    /// Adds an item to the end of a queue, discarding the oldest or newest item if the queue is
+    /// full. Returns true if the queue overflowed.
+    fn enqueue<T>(queue: &mut Vec<T>, queue_size: usize, discard_oldest: bool, item: T) -> bool {
+        // test for overflow
+        let queue_overflow = if queue.len() == queue_size {
             // Overflow behaviour
-            if self.discard_oldest {
+            if discard_oldest {
                 // Throw away oldest item (the one at the start) to make space at the end
-                let _ = self.notification_queue.remove(0);
+                let _ = queue.remove(0);
             } else {
                 // Remove the last notification
-                self.notification_queue.pop();
+                queue.pop();
             }
             // Overflow only affects queues > 1 element
-            self.queue_size > 1
+            queue_size > 1
         } else {
             false
         };
         // Add to end
-        self.notification_queue.push(notification);
+        queue.push(item);
+        queue_overflow
     }
 
     /// Gets the oldest notification message from the notification queue
@@ -212,6 +492,16 @@ impl MonitoredItem {
         }
     }
 
+    /// Gets all the events from the queue
+    pub fn remove_all_events(&mut self) -> Option<Vec<EventFieldList>> {
+        if self.event_queue.is_empty() {
+            None
+        } else {
+            self.queue_overflow = false;
+            Some(self.event_queue.drain(..).collect())
+        }
+    }
+
     /// Gets the last notification (and discards the remainder to prevent out of sequence events) from
     /// the notification queue.
     pub fn remove_last_notification_message(&mut self) -> Option<MonitoredItemNotification> {
@@ -224,28 +514,39 @@ impl MonitoredItem {
     }
 
     /// Takes the requested sampling interval value supplied by client and ensures it is within
-    /// the range supported by the server
-    fn sanitize_sampling_interval(requested_sampling_interval: Double) -> Double {
-        if requested_sampling_interval < 0.0 {
-            // Defaults to the subscription's publishing interval
-            -1.0
-        } else if requested_sampling_interval == 0.0 || requested_sampling_interval < constants::MIN_SAMPLING_INTERVAL {
-            constants::MIN_SAMPLING_INTERVAL
+    /// the range supported by the server and no faster than the minimum sampling interval of the
+    /// node. Events are not sampled so event items have no interval.
+    fn sanitize_sampling_interval(filter: &FilterType, requested_sampling_interval: Double, limits: &MonitoredItemLimits, publishing_interval: Double, minimum_sampling_interval: Option<Double>, exception_based: bool) -> Double {
+        if let &FilterType::EventFilter(_) = filter {
+            return 0.0;
+        }
+        // A negative interval means the item is sampled at the subscription's publishing interval
+        let requested_sampling_interval = if requested_sampling_interval < 0.0 { publishing_interval } else { requested_sampling_interval };
+        let sampling_interval = if requested_sampling_interval == 0.0 && exception_based {
+            // Values held by the address space are reported as they change
+            0.0
+        } else if requested_sampling_interval < limits.min_sampling_interval {
+            limits.min_sampling_interval
+        } else if limits.max_sampling_interval > 0.0 && requested_sampling_interval > limits.max_sampling_interval {
+            limits.max_sampling_interval
         } else {
             requested_sampling_interval
+        };
+        // The node can't be sampled faster than it says it can
+        match minimum_sampling_interval {
+            Some(minimum_sampling_interval) if sampling_interval < minimum_sampling_interval => minimum_sampling_interval,
+            _ => sampling_interval
         }
     }
 
     /// Takes the requested queue size and ensures it is within the range supported by the server
-    fn sanitize_queue_size(requested_queue_size: usize) -> usize {
-        if requested_queue_size == 0 {
-            constants::DEFAULT_DATA_CHANGE_QUEUE_SIZE
-        } else if requested_queue_size == 1 {
-            constants::MIN_DATA_CHANGE_QUEUE_SIZE
-        } else if requested_queue_size > constants::MAX_DATA_CHANGE_QUEUE_SIZE {
-            constants::MAX_DATA_CHANGE_QUEUE_SIZE
+    fn sanitize_queue_size(filter: &FilterType, requested_queue_size: usize, limits: &MonitoredItemLimits) -> usize {
+        let (default_queue_size, max_queue_size) = if let &FilterType::EventFilter(_) = filter {
+            (constants::DEFAULT_EVENT_QUEUE_SIZE, limits.max_event_queue_size)
         } else {
-            requested_queue_size
-        }
+            (constants::DEFAULT_DATA_CHANGE_QUEUE_SIZE, limits.max_data_change_queue_size)
+        };
+        let queue_size = if requested_queue_size == 0 { default_queue_size } else { requested_queue_size };
+        if queue_size > max_queue_size { max_queue_size } else { queue_size }
     }
 }
\ No newline at end of file
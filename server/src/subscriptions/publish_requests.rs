@@ -0,0 +1,156 @@
+use std::collections::HashSet;
+use std::mem;
+
+use time;
+
+use opcua_types::*;
+
+use DateTimeUTC;
+use subscriptions::{PublishRequestEntry, PublishResponseEntry};
+
+const MAX_DEFAULT_PUBLISH_REQUEST_QUEUE_SIZE: usize = 100;
+const MAX_REQUEST_TIMEOUT: i64 = 30000;
+
+/// The publish requests of a session. Publish requests belong to the session they are sent on,
+/// whereas the subscriptions they are used by belong to the server.
+pub struct PublishRequests {
+    /// The publish requeust queue (requests by the client on the session)
+    pub queue: Vec<PublishRequestEntry>,
+    /// Responses to publish requests that the client cancelled, waiting to be sent
+    cancelled_publish_responses: Vec<PublishResponseEntry>,
+    /// Status changes of subscriptions that were transferred away from the session, by
+    /// subscription id, waiting for a publish request to be sent with
+    status_changes: Vec<(UInt32, NotificationMessage)>,
+}
+
+impl PublishRequests {
+    pub fn new() -> PublishRequests {
+        PublishRequests {
+            queue: Vec::with_capacity(MAX_DEFAULT_PUBLISH_REQUEST_QUEUE_SIZE),
+            cancelled_publish_responses: Vec::new(),
+            status_changes: Vec::new(),
+        }
+    }
+
+    /// Discards the queued publish requests and anything waiting to be sent with them
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.cancelled_publish_responses.clear();
+        self.status_changes.clear();
+    }
+
+    /// Queues a status change of the subscription to be sent with the next publish request
+    pub fn enqueue_status_change(&mut self, subscription_id: UInt32, notification_message: NotificationMessage) {
+        self.status_changes.push((subscription_id, notification_message));
+    }
+
+    /// Answers the oldest queued publish requests with the waiting status changes, returning a
+    /// response for each status change that there was a publish request for
+    pub fn take_status_change_responses(&mut self) -> Vec<PublishResponseEntry> {
+        let mut publish_responses = Vec::with_capacity(self.status_changes.len());
+        while !self.status_changes.is_empty() && !self.queue.is_empty() {
+            let (subscription_id, notification_message) = self.status_changes.remove(0);
+            // Requests are queued at the front so the oldest is at the back
+            let publish_request = self.queue.pop().unwrap();
+            let now = DateTime::now();
+            publish_responses.push(PublishResponseEntry {
+                request_id: publish_request.request_id,
+                response: SupportedMessage::PublishResponse(PublishResponse {
+                    response_header: ResponseHeader::new_timestamped_service_result(now, &publish_request.request.request_header, GOOD),
+                    subscription_id,
+                    available_sequence_numbers: None,
+                    more_notifications: false,
+                    notification_message,
+                    results: None,
+                    diagnostic_infos: None,
+                }),
+            });
+        }
+        publish_responses
+    }
+
+    /// Iterates through the existing queued publish requests and creates a timeout
+    /// publish response any that have expired.
+    pub fn expire_stale_publish_requests(&mut self, now: &DateTimeUTC) -> Option<Vec<PublishResponseEntry>> {
+        if self.queue.is_empty() {
+            return None;
+        }
+
+        // Look for publish requests that have expired
+        let mut expired_request_handles = HashSet::with_capacity(self.queue.len());
+        let mut expired_requests = Vec::with_capacity(self.queue.len());
+
+        for request in &self.queue {
+            let request_header = &request.request.request_header;
+            let timestamp: DateTimeUTC = request_header.timestamp.as_chrono();
+            let timeout = if request_header.timeout_hint > 0 && (request_header.timeout_hint as i64) < MAX_REQUEST_TIMEOUT {
+                request_header.timeout_hint as i64
+            } else {
+                MAX_REQUEST_TIMEOUT
+            };
+            let timeout_d = time::Duration::milliseconds(timeout);
+            // The request has timed out if the timestamp plus hint exceeds the input time
+            let expiration_time = timestamp + timeout_d;
+            if *now >= expiration_time {
+                debug!("Publish request {} has expired - timestamp = {:?}, expiration hint = {}, expiration time = {:?}, time now = {:?}, ", request_header.request_handle, timestamp, timeout, expiration_time, now);
+                expired_request_handles.insert(request_header.request_handle);
+                expired_requests.push(request.clone());
+            }
+        }
+
+        if expired_request_handles.is_empty() {
+            return None;
+        }
+
+        // Remove the expired requests (if any)
+        self.queue.retain(|ref r| {
+            !expired_request_handles.contains(&r.request.request_header.request_handle)
+        });
+
+        // Make publish responses for each expired request
+        let mut publish_responses = Vec::with_capacity(expired_requests.len());
+        for expired_request in expired_requests {
+            let now = DateTime::from_chrono(now);
+            publish_responses.push(PublishResponseEntry {
+                request_id: expired_request.request_id,
+                response: SupportedMessage::ServiceFault(ServiceFault {
+                    response_header: ResponseHeader::new_timestamped_service_result(now.clone(), &expired_request.request.request_header, BAD_REQUEST_TIMEOUT),
+                }),
+            });
+        }
+        if !publish_responses.is_empty() {
+            Some(publish_responses)
+        } else {
+            None
+        }
+    }
+
+    /// Removes the queued publish requests with the request handle and makes a response for each
+    /// that says it was cancelled. Returns how many were cancelled.
+    pub fn cancel_publish_requests(&mut self, request_handle: UInt32) -> usize {
+        let (cancelled_requests, queue) = self.queue.drain(..).partition::<Vec<PublishRequestEntry>, _>(|r| {
+            r.request.request_header.request_handle == request_handle
+        });
+        self.queue = queue;
+        let now = DateTime::now();
+        for cancelled_request in &cancelled_requests {
+            debug!("Publish request {} is cancelled", request_handle);
+            self.cancelled_publish_responses.push(PublishResponseEntry {
+                request_id: cancelled_request.request_id,
+                response: SupportedMessage::ServiceFault(ServiceFault {
+                    response_header: ResponseHeader::new_timestamped_service_result(now.clone(), &cancelled_request.request.request_header, BAD_REQUEST_CANCELLED_BY_CLIENT),
+                }),
+            });
+        }
+        cancelled_requests.len()
+    }
+
+    /// Takes the responses to cancelled publish requests so they can be sent
+    pub fn take_cancelled_publish_responses(&mut self) -> Option<Vec<PublishResponseEntry>> {
+        if self.cancelled_publish_responses.is_empty() {
+            None
+        } else {
+            Some(mem::replace(&mut self.cancelled_publish_responses, Vec::new()))
+        }
+    }
+}
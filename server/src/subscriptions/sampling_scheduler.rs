@@ -0,0 +1,145 @@
+use std::collections::{HashMap, BTreeMap, BTreeSet};
+use std::mem;
+
+use time;
+
+use opcua_types::*;
+
+use DateTimeUTC;
+use address_space::address_space::AddressSpace;
+use subscriptions::monitored_item::MonitoredItem;
+
+/// The items that share a sampling interval and the time they were last sampled
+#[derive(Debug, Clone, PartialEq)]
+struct SamplingGroup {
+    last_sample_time: DateTimeUTC,
+    monitored_item_ids: BTreeSet<UInt32>,
+}
+
+/// Decides which monitored items of a subscription are due on a tick so that items are sampled
+/// at their own rate rather than the rate of the publishing timer.
+///
+/// * Items with a positive sampling interval are grouped by the interval in milliseconds and
+///   each group is sampled when its interval elapses. Intervals below a millisecond are sampled
+///   on every tick.
+/// * Items with a negative sampling interval are sampled when the publishing timer expires.
+/// * Items with a sampling interval of 0 that monitor the value of a variable held by the
+///   address space are exception-based. They are only sampled when the address space records
+///   a change to the value.
+/// * Event and aggregate items keep their own time and are due on every tick.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplingScheduler {
+    /// Items sampled at a fixed rate, by their sampling interval in milliseconds
+    sampling_groups: BTreeMap<i64, SamplingGroup>,
+    /// Items sampled whenever the publishing timer expires
+    publishing_interval_items: BTreeSet<UInt32>,
+    /// Exception-based items, by the node whose value they monitor
+    exception_items: HashMap<NodeId, BTreeSet<UInt32>>,
+    /// Items that are due on every tick
+    ticked_items: BTreeSet<UInt32>,
+    /// Items that are due on the next tick regardless of their rate
+    pending_items: BTreeSet<UInt32>,
+    /// Index of the last value change examined for exception-based items
+    last_value_change_index: Option<usize>,
+}
+
+impl SamplingScheduler {
+    pub fn new() -> SamplingScheduler {
+        SamplingScheduler {
+            sampling_groups: BTreeMap::new(),
+            publishing_interval_items: BTreeSet::new(),
+            exception_items: HashMap::new(),
+            ticked_items: BTreeSet::new(),
+            pending_items: BTreeSet::new(),
+            last_value_change_index: None,
+        }
+    }
+
+    /// Schedules the monitored item according to its sampling interval, replacing any previous
+    /// schedule for it. The item is due on the next tick so its current value is reported.
+    pub fn add_item(&mut self, address_space: &AddressSpace, monitored_item: &MonitoredItem, now: &DateTimeUTC) {
+        let monitored_item_id = monitored_item.monitored_item_id;
+        self.remove_item(monitored_item_id);
+        if monitored_item.is_event_item() || monitored_item.is_aggregate_item() {
+            self.ticked_items.insert(monitored_item_id);
+        } else if monitored_item.sampling_interval < 0f64 {
+            self.publishing_interval_items.insert(monitored_item_id);
+        } else if SamplingScheduler::is_exception_based(address_space, monitored_item) {
+            if self.last_value_change_index.is_none() {
+                self.last_value_change_index = Some(address_space.last_value_change_index());
+            }
+            let node_id = monitored_item.item_to_monitor.node_id.clone();
+            self.exception_items.entry(node_id).or_insert_with(BTreeSet::new).insert(monitored_item_id);
+        } else {
+            let sampling_interval = monitored_item.sampling_interval as i64;
+            self.sampling_groups.entry(sampling_interval).or_insert_with(|| SamplingGroup {
+                last_sample_time: *now,
+                monitored_item_ids: BTreeSet::new(),
+            }).monitored_item_ids.insert(monitored_item_id);
+        }
+        self.pending_items.insert(monitored_item_id);
+    }
+
+    /// Removes the monitored item from the schedule
+    pub fn remove_item(&mut self, monitored_item_id: UInt32) {
+        self.sampling_groups.retain(|_, group| {
+            group.monitored_item_ids.remove(&monitored_item_id);
+            !group.monitored_item_ids.is_empty()
+        });
+        self.exception_items.retain(|_, monitored_item_ids| {
+            monitored_item_ids.remove(&monitored_item_id);
+            !monitored_item_ids.is_empty()
+        });
+        if self.exception_items.is_empty() {
+            self.last_value_change_index = None;
+        }
+        self.publishing_interval_items.remove(&monitored_item_id);
+        self.ticked_items.remove(&monitored_item_id);
+        self.pending_items.remove(&monitored_item_id);
+    }
+
+    /// Makes the monitored item due on the next tick regardless of its rate
+    pub fn sample_on_next_tick(&mut self, monitored_item_id: UInt32) {
+        self.pending_items.insert(monitored_item_id);
+    }
+
+    /// Returns the ids of the monitored items that are due on this tick
+    pub fn due_items(&mut self, address_space: &AddressSpace, now: &DateTimeUTC, publishing_timer_expired: bool) -> BTreeSet<UInt32> {
+        let mut due_items = mem::replace(&mut self.pending_items, BTreeSet::new());
+        due_items.extend(self.ticked_items.iter().cloned());
+        if publishing_timer_expired {
+            due_items.extend(self.publishing_interval_items.iter().cloned());
+        }
+        for (sampling_interval, group) in &mut self.sampling_groups {
+            let elapsed = (*now).signed_duration_since(group.last_sample_time);
+            if elapsed >= time::Duration::milliseconds(*sampling_interval) {
+                group.last_sample_time = *now;
+                due_items.extend(group.monitored_item_ids.iter().cloned());
+            }
+        }
+        if let Some(last_value_change_index) = self.last_value_change_index {
+            if let Some(node_ids) = address_space.value_changes_since(last_value_change_index) {
+                for node_id in node_ids {
+                    if let Some(monitored_item_ids) = self.exception_items.get(node_id) {
+                        due_items.extend(monitored_item_ids.iter().cloned());
+                    }
+                }
+            } else {
+                // Changes were missed so every exception-based item is sampled
+                for (_, monitored_item_ids) in &self.exception_items {
+                    due_items.extend(monitored_item_ids.iter().cloned());
+                }
+            }
+            self.last_value_change_index = Some(address_space.last_value_change_index());
+        }
+        due_items
+    }
+
+    /// Tests if the item only needs sampling when the address space records a change to the value
+    /// it monitors
+    fn is_exception_based(address_space: &AddressSpace, monitored_item: &MonitoredItem) -> bool {
+        monitored_item.sampling_interval == 0f64 &&
+            monitored_item.item_to_monitor.attribute_id == AttributeId::Value as UInt32 &&
+            address_space.is_value_change_recorded(&monitored_item.item_to_monitor.node_id)
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::{HashMap, BTreeMap};
+use std::collections::{HashMap, BTreeMap, BTreeSet};
 
 use chrono;
 use time;
@@ -9,6 +9,7 @@ use constants;
 
 use DateTimeUTC;
 use subscriptions::monitored_item::*;
+use subscriptions::sampling_scheduler::SamplingScheduler;
 use subscriptions::{PublishRequestEntry, PublishResponseEntry};
 use address_space::address_space::AddressSpace;
 
@@ -67,6 +68,8 @@ pub struct Subscription {
     pub priority: Byte,
     /// Map of monitored items
     pub monitored_items: HashMap<UInt32, MonitoredItem>,
+    /// Decides which monitored items are sampled on each tick
+    sampling_scheduler: SamplingScheduler,
     /// State of the subscription
     pub state: SubscriptionState,
     /// A boolean value that is set to TRUE only by the CreateNotificationMsg() when there were too
@@ -95,6 +98,9 @@ pub struct Subscription {
     pub publishing_req_queued: bool,
     // Notifications waiting to be sent in a map by sequence number. A b-tree is used to ensure ordering is
     // by sequence number.
+    pub notification_queue: BTreeMap<UInt32, NotificationMessage>,
+    // Notifications that have been sent but not acknowledged, by sequence number. They are held
+    // so a client can ask for them to be republished.
     pub retransmission_queue: BTreeMap<UInt32, NotificationMessage>,
     // The last monitored item id
     last_monitored_item_id: UInt32,
@@ -102,6 +108,9 @@ pub struct Subscription {
     last_timer_expired_time: DateTimeUTC,
     /// The value that records the value of the sequence number used in NotificationMessages.
     last_sequence_number: UInt32,
+    /// Counts of what has happened to the subscription. The remaining diagnostics are taken from
+    /// the subscription when they are requested.
+    diagnostics: SubscriptionDiagnosticsDataType,
 }
 
 impl Subscription {
@@ -111,6 +120,7 @@ impl Subscription {
             publishing_interval,
             priority,
             monitored_items: HashMap::with_capacity(constants::DEFAULT_MONITORED_ITEM_CAPACITY),
+            sampling_scheduler: SamplingScheduler::new(),
             max_lifetime_count: lifetime_count,
             max_keep_alive_count: keep_alive_count,
             // State variables
@@ -124,16 +134,119 @@ impl Subscription {
             publishing_enabled,
             publishing_req_queued: false,
             // Outgoing notifications
+            notification_queue: BTreeMap::new(),
             retransmission_queue: BTreeMap::new(),
             // Counters for new items
             last_monitored_item_id: 0,
             last_timer_expired_time: chrono::UTC::now(),
             last_sequence_number: 0,
+            diagnostics: SubscriptionDiagnosticsDataType {
+                session_id: NodeId::null(),
+                subscription_id,
+                priority,
+                publishing_interval,
+                max_keep_alive_count: keep_alive_count,
+                max_lifetime_count: lifetime_count,
+                max_notifications_per_publish: 0,
+                publishing_enabled,
+                modify_count: 0,
+                enable_count: 0,
+                disable_count: 0,
+                republish_request_count: 0,
+                republish_message_request_count: 0,
+                republish_message_count: 0,
+                transfer_request_count: 0,
+                transferred_to_alt_client_count: 0,
+                transferred_to_same_client_count: 0,
+                publish_request_count: 0,
+                data_change_notifications_count: 0,
+                event_notifications_count: 0,
+                notifications_count: 0,
+                late_publish_request_count: 0,
+                current_keep_alive_count: 0,
+                current_lifetime_count: 0,
+                unacknowledged_message_count: 0,
+                discarded_message_count: 0,
+                monitored_item_count: 0,
+                disabled_monitored_item_count: 0,
+                monitoring_queue_overflow_count: 0,
+                next_sequence_number: 0,
+                event_queue_over_flow_count: 0,
+            },
+        }
+    }
+
+    /// Changes the publishing parameters of the subscription
+    pub fn modify(&mut self, publishing_interval: Double, lifetime_count: UInt32, keep_alive_count: UInt32, priority: Byte) {
+        self.publishing_interval = publishing_interval;
+        self.max_lifetime_count = lifetime_count;
+        self.max_keep_alive_count = keep_alive_count;
+        self.priority = priority;
+        self.diagnostics.modify_count = self.diagnostics.modify_count.wrapping_add(1);
+    }
+
+    /// Enables or disables publishing on the subscription
+    pub fn set_publishing_enabled(&mut self, publishing_enabled: bool) {
+        self.publishing_enabled = publishing_enabled;
+        if publishing_enabled {
+            self.diagnostics.enable_count = self.diagnostics.enable_count.wrapping_add(1);
+        } else {
+            self.diagnostics.disable_count = self.diagnostics.disable_count.wrapping_add(1);
+        }
+    }
+
+    /// Called when the subscription is transferred to another session, which may belong to the
+    /// same client or to a different one. Returns the status change notification for the session
+    /// that had the subscription before.
+    pub fn on_transfer(&mut self, same_client: bool) -> NotificationMessage {
+        self.diagnostics.transfer_request_count = self.diagnostics.transfer_request_count.wrapping_add(1);
+        if same_client {
+            self.diagnostics.transferred_to_same_client_count = self.diagnostics.transferred_to_same_client_count.wrapping_add(1);
+        } else {
+            self.diagnostics.transferred_to_alt_client_count = self.diagnostics.transferred_to_alt_client_count.wrapping_add(1);
+        }
+        // Like a keep alive, the notification does not use up the sequence number
+        NotificationMessage::new_status_change(self.next_sequence_number(), &DateTime::now(), GOOD_SUBSCRIPTION_TRANSFERRED)
+    }
+
+    /// Returns the diagnostics of the subscription, which belongs to the session with the id
+    pub fn diagnostics(&self, session_id: &NodeId) -> SubscriptionDiagnosticsDataType {
+        let mut diagnostics = self.diagnostics.clone();
+        diagnostics.session_id = session_id.clone();
+        diagnostics.priority = self.priority;
+        diagnostics.publishing_interval = self.publishing_interval;
+        diagnostics.max_keep_alive_count = self.max_keep_alive_count;
+        diagnostics.max_lifetime_count = self.max_lifetime_count;
+        diagnostics.publishing_enabled = self.publishing_enabled;
+        diagnostics.current_keep_alive_count = self.keep_alive_counter;
+        diagnostics.current_lifetime_count = self.lifetime_counter;
+        diagnostics.unacknowledged_message_count = self.retransmission_queue.len() as UInt32;
+        diagnostics.monitored_item_count = self.monitored_items.len() as UInt32;
+        diagnostics.disabled_monitored_item_count = self.monitored_items.values().filter(|monitored_item| {
+            monitored_item.monitoring_mode == MonitoringMode::Disabled
+        }).count() as UInt32;
+        for monitored_item in self.monitored_items.values() {
+            if monitored_item.is_event_item() {
+                diagnostics.event_queue_over_flow_count = diagnostics.event_queue_over_flow_count.wrapping_add(monitored_item.queue_overflow_count);
+            } else {
+                diagnostics.monitoring_queue_overflow_count = diagnostics.monitoring_queue_overflow_count.wrapping_add(monitored_item.queue_overflow_count);
+            }
+        }
+        diagnostics.next_sequence_number = self.next_sequence_number();
+        diagnostics
+    }
+
+    /// Returns the sequence number the next notification will have
+    fn next_sequence_number(&self) -> UInt32 {
+        if self.last_sequence_number >= constants::SEQUENCE_NUMBER_WRAPAROUND {
+            1
+        } else {
+            self.last_sequence_number + 1
         }
     }
 
     /// Creates monitored items on the specified subscription, returning the creation results
-    pub fn create_monitored_items(&mut self, items_to_create: &[MonitoredItemCreateRequest]) -> Vec<MonitoredItemCreateResult> {
+    pub fn create_monitored_items(&mut self, address_space: &AddressSpace, limits: &MonitoredItemLimits, items_to_create: &[MonitoredItemCreateRequest]) -> Vec<MonitoredItemCreateResult> {
         let mut results = Vec::with_capacity(items_to_create.len());
         // Add items to the subscription if they're not already in its
         for item_to_create in items_to_create {
@@ -141,7 +254,21 @@ impl Subscription {
             // Process items to create here
             let monitored_item_id = self.last_monitored_item_id;
             // Create a monitored item, if possible
-            let monitored_item = MonitoredItem::new(monitored_item_id, item_to_create);
+            let minimum_sampling_interval = Subscription::minimum_sampling_interval(address_space, &item_to_create.item_to_monitor);
+            let exception_based = Subscription::is_exception_based(address_space, &item_to_create.item_to_monitor);
+            let monitored_item = MonitoredItem::new(monitored_item_id, item_to_create, limits, self.publishing_interval, minimum_sampling_interval, exception_based).and_then(|monitored_item| {
+                // Events can only be monitored on nodes which are event notifiers
+                if monitored_item.is_event_item() {
+                    let node_id = &monitored_item.item_to_monitor.node_id;
+                    if !address_space.node_exists(node_id) {
+                        return Err(BAD_NODE_ID_UNKNOWN);
+                    } else if !address_space.is_event_notifier(node_id) {
+                        return Err(BAD_ATTRIBUTE_ID_INVALID);
+                    }
+                }
+                Subscription::check_eu_range(address_space, &monitored_item.item_to_monitor.node_id, &monitored_item.filter)?;
+                Ok(monitored_item)
+            });
             let result = if let Ok(monitored_item) = monitored_item {
                 // Return the status
                 let result = MonitoredItemCreateResult {
@@ -149,9 +276,10 @@ impl Subscription {
                     monitored_item_id: monitored_item_id,
                     revised_sampling_interval: monitored_item.sampling_interval,
                     revised_queue_size: monitored_item.queue_size as UInt32,
-                    filter_result: ExtensionObject::null()
+                    filter_result: monitored_item.filter_result(),
                 };
                 // Register the item with the subscription
+                self.sampling_scheduler.add_item(address_space, &monitored_item, &chrono::UTC::now());
                 self.monitored_items.insert(monitored_item_id, monitored_item);
                 result
             } else {
@@ -170,23 +298,26 @@ impl Subscription {
     }
 
     /// Modify the specified monitored items, returning a result for each
-    pub fn modify_monitored_items(&mut self, items_to_modify: &[MonitoredItemModifyRequest]) -> Vec<MonitoredItemModifyResult> {
+    pub fn modify_monitored_items(&mut self, address_space: &AddressSpace, limits: &MonitoredItemLimits, items_to_modify: &[MonitoredItemModifyRequest]) -> Vec<MonitoredItemModifyResult> {
         let mut result = Vec::with_capacity(items_to_modify.len());
+        let publishing_interval = self.publishing_interval;
         for item_to_modify in items_to_modify {
             let monitored_item = self.monitored_items.get_mut(&item_to_modify.monitored_item_id);
             if let Some(monitored_item) = monitored_item {
-                // Skip items not being reported
-                if monitored_item.monitoring_mode != MonitoringMode::Reporting {
-                    continue;
-                }
                 // Try to change the monitored item according to the modify request
-                let modify_result = monitored_item.modify(item_to_modify);
+                let minimum_sampling_interval = Subscription::minimum_sampling_interval(address_space, &monitored_item.item_to_monitor);
+                let exception_based = Subscription::is_exception_based(address_space, &monitored_item.item_to_monitor);
+                let modify_result = FilterType::from_filter(&item_to_modify.requested_parameters.filter)
+                    .and_then(|filter| Subscription::check_eu_range(address_space, &monitored_item.item_to_monitor.node_id, &filter))
+                    .and_then(|_| monitored_item.modify(item_to_modify, limits, publishing_interval, minimum_sampling_interval, exception_based));
                 result.push(if modify_result.is_ok() {
+                    // The sampling interval may have changed
+                    self.sampling_scheduler.add_item(address_space, monitored_item, &chrono::UTC::now());
                     MonitoredItemModifyResult {
                         status_code: GOOD,
                         revised_sampling_interval: monitored_item.sampling_interval,
                         revised_queue_size: monitored_item.queue_size as UInt32,
-                        filter_result: ExtensionObject::null(),
+                        filter_result: monitored_item.filter_result(),
                     }
                 } else {
                     MonitoredItemModifyResult {
@@ -209,17 +340,97 @@ impl Subscription {
         result
     }
 
+    /// Returns the minimum sampling interval of the node when the item monitors its value
+    fn minimum_sampling_interval(address_space: &AddressSpace, item_to_monitor: &ReadValueId) -> Option<Double> {
+        if item_to_monitor.attribute_id == AttributeId::Value as UInt32 {
+            address_space.minimum_sampling_interval(&item_to_monitor.node_id)
+        } else {
+            None
+        }
+    }
+
+    /// Tests if the item monitors a value held by the address space, which is reported as it changes
+    /// rather than by sampling it
+    fn is_exception_based(address_space: &AddressSpace, item_to_monitor: &ReadValueId) -> bool {
+        item_to_monitor.attribute_id == AttributeId::Value as UInt32 && address_space.is_value_change_recorded(&item_to_monitor.node_id)
+    }
+
+    /// Checks that a node monitored with a percent deadband has the EURange the deadband is a
+    /// percentage of
+    fn check_eu_range(address_space: &AddressSpace, node_id: &NodeId, filter: &FilterType) -> Result<(), StatusCode> {
+        if filter.is_percent_deadband() && address_space.find_eu_range(node_id).is_none() {
+            Err(BAD_MONITORED_ITEM_FILTER_UNSUPPORTED)
+        } else {
+            Ok(())
+        }
+    }
+
     /// Delete the specified monitored items (by item id), returning a status code for each
     pub fn delete_monitored_items(&mut self, items_to_delete: &[UInt32]) -> Vec<StatusCode> {
         let mut result = Vec::with_capacity(items_to_delete.len());
         for item_to_delete in items_to_delete {
             // Remove the item (or report an error with the id)
             let removed = self.monitored_items.remove(item_to_delete);
+            if removed.is_some() {
+                self.sampling_scheduler.remove_item(*item_to_delete);
+                // Links to the deleted item go with it
+                for (_, monitored_item) in &mut self.monitored_items {
+                    monitored_item.remove_triggered_item(*item_to_delete);
+                }
+            }
             result.push(if removed.is_some() { GOOD } else { BAD_MONITORED_ITEM_ID_INVALID });
         }
         result
     }
 
+    /// Sets the monitoring mode of the specified monitored items, returning a status code for each
+    pub fn set_monitoring_mode(&mut self, monitoring_mode: MonitoringMode, monitored_item_ids: &[UInt32]) -> Vec<StatusCode> {
+        monitored_item_ids.iter().map(|monitored_item_id| {
+            if let Some(monitored_item) = self.monitored_items.get_mut(monitored_item_id) {
+                monitored_item.set_monitoring_mode(monitoring_mode);
+                if monitoring_mode != MonitoringMode::Disabled {
+                    // Report the current value rather than wait for it to change
+                    self.sampling_scheduler.sample_on_next_tick(*monitored_item_id);
+                }
+                GOOD
+            } else {
+                BAD_MONITORED_ITEM_ID_INVALID
+            }
+        }).collect()
+    }
+
+    /// Removes and adds links from the triggering item to the items it triggers, returning a status
+    /// code for each link added and each link removed. Links are removed before they are added.
+    /// Fails with BAD_MONITORED_ITEM_ID_INVALID if the triggering item does not exist.
+    pub fn set_triggering(&mut self, triggering_item_id: UInt32, links_to_add: &[UInt32], links_to_remove: &[UInt32]) -> Result<(Vec<StatusCode>, Vec<StatusCode>), StatusCode> {
+        if !self.monitored_items.contains_key(&triggering_item_id) {
+            return Err(BAD_MONITORED_ITEM_ID_INVALID);
+        }
+        // Results of links to add depend on whether the linked items exist
+        let add_results = links_to_add.iter().map(|monitored_item_id| {
+            if self.monitored_items.contains_key(monitored_item_id) { GOOD } else { BAD_MONITORED_ITEM_ID_INVALID }
+        }).collect::<Vec<StatusCode>>();
+        let triggering_item = self.monitored_items.get_mut(&triggering_item_id).unwrap();
+        let remove_results = links_to_remove.iter().map(|monitored_item_id| {
+            if triggering_item.remove_triggered_item(*monitored_item_id) { GOOD } else { BAD_MONITORED_ITEM_ID_INVALID }
+        }).collect();
+        for (monitored_item_id, add_result) in links_to_add.iter().zip(add_results.iter()) {
+            if add_result.is_good() {
+                triggering_item.add_triggered_item(*monitored_item_id);
+            }
+        }
+        Ok((add_results, remove_results))
+    }
+
+    /// Makes the monitored items report their current values on their next sample, regardless of
+    /// whether they have changed, e.g. after the subscription is transferred to another session.
+    pub fn resend_data(&mut self) {
+        for (monitored_item_id, monitored_item) in &mut self.monitored_items {
+            monitored_item.resend_data();
+            self.sampling_scheduler.sample_on_next_tick(*monitored_item_id);
+        }
+    }
+
     /// Checks the subscription and monitored items for state change, messages. If the tick does
     /// nothing, the function returns None. Otherwise it returns one or more messages in an Vec.
     pub fn tick(&mut self, address_space: &AddressSpace, receive_publish_request: bool, publish_request: &Option<PublishRequestEntry>, publishing_req_queued: bool, now: &DateTimeUTC) -> (Option<PublishResponseEntry>, Option<UpdateStateResult>) {
@@ -246,8 +457,8 @@ impl Subscription {
         let items_changed = self.tick_monitored_items(address_space, now, publishing_timer_expired);
 
         self.publishing_req_queued = publishing_req_queued;
-        self.notifications_available = !self.retransmission_queue.is_empty();
-        self.more_notifications = !self.retransmission_queue.is_empty();
+        self.notifications_available = !self.notification_queue.is_empty();
+        self.more_notifications = !self.notification_queue.is_empty();
 
         // If items have changed or subscription interval elapsed then we may have notifications
         // to send or state to update
@@ -259,6 +470,9 @@ impl Subscription {
                 UpdateStateAction::ReturnKeepAlive => Some(self.return_keep_alive(publish_request.as_ref().unwrap(), &update_state_result)),
                 UpdateStateAction::ReturnNotifications => Some(self.return_notifications(publish_request.as_ref().unwrap(), &update_state_result)),
             };
+            if publish_response.is_some() {
+                self.diagnostics.publish_request_count = self.diagnostics.publish_request_count.wrapping_add(1);
+            }
             trace!("Subscription tick - publish_response = {:?}", publish_response);
             (publish_response, Some(update_state_result))
         } else {
@@ -284,25 +498,43 @@ impl Subscription {
         self.last_sequence_number
     }
 
-    /// Iterate through the monitored items belonging to the subscription, calling tick on each in turn.
-    /// The function returns true if any of the monitored items due to the subscription interval
-    /// elapsing, or their own interval elapsing.
+    /// Samples the monitored items that the sampling scheduler says are due and ticks event and
+    /// aggregate items. The function returns true if any of the monitored items produced
+    /// notifications.
     fn tick_monitored_items(&mut self, address_space: &AddressSpace, now: &DateTimeUTC, publishing_timer_expired: bool) -> bool {
         let mut monitored_item_notifications = Vec::new();
-        for (_, monitored_item) in &mut self.monitored_items {
-            if monitored_item.tick(address_space, now, publishing_timer_expired) {
-                // Take the monitored item's first notification
-                if let Some(mut notification_messages) = monitored_item.remove_all_notification_messages() {
-                    monitored_item_notifications.append(&mut notification_messages);
+        let mut event_notifications = Vec::new();
+        let mut triggered_items = BTreeSet::new();
+        for monitored_item_id in self.sampling_scheduler.due_items(address_space, now, publishing_timer_expired) {
+            if let Some(monitored_item) = self.monitored_items.get_mut(&monitored_item_id) {
+                let changed = if monitored_item.is_event_item() || monitored_item.is_aggregate_item() {
+                    monitored_item.tick(address_space, now, publishing_timer_expired)
+                } else {
+                    monitored_item.sample(address_space, now)
+                };
+                // Sampling items queue their changes but only report them when triggered
+                if changed && monitored_item.monitoring_mode == MonitoringMode::Reporting {
+                    triggered_items.extend(monitored_item.triggered_items.iter().cloned());
+                    Subscription::take_notifications(monitored_item, &mut monitored_item_notifications, &mut event_notifications);
+                }
+            }
+        }
+        for monitored_item_id in triggered_items {
+            if let Some(monitored_item) = self.monitored_items.get_mut(&monitored_item_id) {
+                if monitored_item.monitoring_mode == MonitoringMode::Sampling {
+                    Subscription::take_notifications(monitored_item, &mut monitored_item_notifications, &mut event_notifications);
                 }
             }
         }
-        let result = if !monitored_item_notifications.is_empty() {
+        let result = if !monitored_item_notifications.is_empty() || !event_notifications.is_empty() {
             // Create a notification message in the map
             let sequence_number = self.create_sequence_number();
-            trace!("Monitored items, seq nr = {}, nr notifications = {}", sequence_number, monitored_item_notifications.len());
-            let notification = NotificationMessage::new_data_change(sequence_number, &DateTime::now(), monitored_item_notifications);
-            self.retransmission_queue.insert(sequence_number, notification);
+            self.diagnostics.data_change_notifications_count = self.diagnostics.data_change_notifications_count.wrapping_add(monitored_item_notifications.len() as UInt32);
+            self.diagnostics.event_notifications_count = self.diagnostics.event_notifications_count.wrapping_add(event_notifications.len() as UInt32);
+            self.diagnostics.notifications_count = self.diagnostics.notifications_count.wrapping_add((monitored_item_notifications.len() + event_notifications.len()) as UInt32);
+            trace!("Monitored items, seq nr = {}, nr notifications = {}, nr events = {}", sequence_number, monitored_item_notifications.len(), event_notifications.len());
+            let notification = NotificationMessage::new(sequence_number, &DateTime::now(), monitored_item_notifications, event_notifications);
+            self.notification_queue.insert(sequence_number, notification);
             true
         } else {
             false
@@ -310,6 +542,16 @@ impl Subscription {
         result
     }
 
+    /// Moves the queued notifications and events of the monitored item onto the supplied lists
+    fn take_notifications(monitored_item: &mut MonitoredItem, monitored_item_notifications: &mut Vec<MonitoredItemNotification>, event_notifications: &mut Vec<EventFieldList>) {
+        if let Some(mut notification_messages) = monitored_item.remove_all_notification_messages() {
+            monitored_item_notifications.append(&mut notification_messages);
+        }
+        if let Some(mut events) = monitored_item.remove_all_events() {
+            event_notifications.append(&mut events);
+        }
+    }
+
     // See OPC UA Part 4 5.13.1.2 State Table
     //
     // This function implements the main guts of updating the subscription's state according to
@@ -352,7 +594,7 @@ impl Subscription {
     keep_alive_counter: {} lifetime_counter: {}
     message_sent: {}"#,
                        self.subscription_id, self.state, receive_publish_request, publishing_timer_expired, self.publishing_req_queued,
-                       self.publishing_enabled, self.more_notifications, self.notifications_available, self.retransmission_queue.len(),
+                       self.publishing_enabled, self.more_notifications, self.notifications_available, self.notification_queue.len(),
                        self.keep_alive_counter, self.lifetime_counter, self.message_sent);
             }
         }
@@ -412,6 +654,7 @@ impl Subscription {
                         // State #8
                         self.start_publishing_timer();
                         self.state = SubscriptionState::Late;
+                        self.diagnostics.late_publish_request_count = self.diagnostics.late_publish_request_count.wrapping_add(1);
                         return UpdateStateResult::new(8, UpdateStateAction::None);
                     } else if self.message_sent && (!self.publishing_enabled || (self.publishing_enabled && !self.notifications_available)) {
                         // State #9
@@ -467,6 +710,7 @@ impl Subscription {
                         // State #17
                         self.start_publishing_timer();
                         self.state = SubscriptionState::Late;
+                        self.diagnostics.late_publish_request_count = self.diagnostics.late_publish_request_count.wrapping_add(1);
                         return UpdateStateResult::new(17, UpdateStateAction::None);
                     }
                 }
@@ -498,7 +742,6 @@ impl Subscription {
         // Clear notification by sequence number
         if self.retransmission_queue.remove(&subscription_acknowledgement.sequence_number).is_some() {
             trace!("Removed acknowledged notification {}", subscription_acknowledgement.sequence_number);
-            self.more_notifications = !self.retransmission_queue.is_empty();
             GOOD
         } else {
             error!("Can't find acknowledged notification {}", subscription_acknowledgement.sequence_number);
@@ -541,21 +784,24 @@ impl Subscription {
         self.make_publish_response(publish_request, &now, notification_message)
     }
 
-    /// Returns the oldest notification
+    /// Returns the oldest notification and moves it to the retransmission queue until the client
+    /// acknowledges it
     pub fn return_notifications(&mut self, publish_request: &PublishRequestEntry, _: &UpdateStateResult) -> PublishResponseEntry {
-        if self.retransmission_queue.is_empty() {
+        if self.notification_queue.is_empty() {
             panic!("Should not be trying to return notifications if there are none");
         }
 
-        trace!("return notifications, len = {}", self.retransmission_queue.len());
+        trace!("return notifications, len = {}", self.notification_queue.len());
         let now = DateTime::now();
 
         // Find the first notification in the map. The map is ordered so the first item will
         // be the oldest.
         let sequence_number = {
-            *self.retransmission_queue.iter().next().unwrap().0
+            *self.notification_queue.iter().next().unwrap().0
         };
-        let notification_message = self.retransmission_queue.get(&sequence_number).unwrap().clone();
+        let notification_message = self.notification_queue.remove(&sequence_number).unwrap();
+        self.more_notifications = !self.notification_queue.is_empty();
+        self.enqueue_retransmission(notification_message.clone());
 
         // Make the response
         let publish_response = self.make_publish_response(publish_request, &now, notification_message);
@@ -563,8 +809,34 @@ impl Subscription {
         publish_response
     }
 
+    /// Holds a sent notification for republishing, discarding the oldest if the queue is full
+    fn enqueue_retransmission(&mut self, notification_message: NotificationMessage) {
+        while self.retransmission_queue.len() >= constants::MAX_RETRANSMISSION_QUEUE_SIZE {
+            let oldest_sequence_number = *self.retransmission_queue.keys().next().unwrap();
+            trace!("Retransmission queue is full, discarding notification {}", oldest_sequence_number);
+            self.retransmission_queue.remove(&oldest_sequence_number);
+            self.diagnostics.discarded_message_count = self.diagnostics.discarded_message_count.wrapping_add(1);
+        }
+        self.retransmission_queue.insert(notification_message.sequence_number, notification_message);
+    }
+
+    /// Returns the sent notification with the sequence number if it has not been acknowledged or
+    /// discarded from the retransmission queue
+    pub fn republish(&mut self, sequence_number: UInt32) -> Option<NotificationMessage> {
+        // A republish counts as client activity
+        self.reset_lifetime_counter();
+        // Each request asks for one message, which is only counted as republished if it is returned
+        self.diagnostics.republish_request_count = self.diagnostics.republish_request_count.wrapping_add(1);
+        self.diagnostics.republish_message_request_count = self.diagnostics.republish_message_request_count.wrapping_add(1);
+        let notification_message = self.retransmission_queue.get(&sequence_number).cloned();
+        if notification_message.is_some() {
+            self.diagnostics.republish_message_count = self.diagnostics.republish_message_count.wrapping_add(1);
+        }
+        notification_message
+    }
+
     /// Returns the array of available sequence numbers
-    fn available_sequence_numbers(&self) -> Option<Vec<UInt32>> {
+    pub fn available_sequence_numbers(&self) -> Option<Vec<UInt32>> {
         if self.retransmission_queue.is_empty() {
             None
         } else {
@@ -1,149 +1,193 @@
-use std::collections::{HashMap, HashSet};
+use std::cmp::Reverse;
+use std::collections::HashMap;
 
-use time;
 use chrono;
 
 use opcua_types::*;
 
-use DateTimeUTC;
 use address_space::types::AddressSpace;
 use subscriptions::{PublishRequestEntry, PublishResponseEntry};
+use session::UserIdentity;
+use subscriptions::publish_requests::PublishRequests;
 use subscriptions::subscription::{Subscription, SubscriptionState};
 
-const MAX_DEFAULT_PUBLISH_REQUEST_QUEUE_SIZE: usize = 100;
-const MAX_PUBLISH_REQUESTS: usize = 200;
-const MAX_REQUEST_TIMEOUT: i64 = 30000;
+/// The owner of a subscription
+#[derive(Clone, Debug, PartialEq)]
+pub enum SubscriptionOwner {
+    /// The session with the session id
+    Session(NodeId),
+    /// No session. The session that owned the subscription was closed without deleting it and
+    /// these are the user and the client certificate that the session had.
+    Closed(Option<UserIdentity>, ByteString),
+}
 
+/// The subscriptions of the server. Subscriptions are held by the server rather than by the
+/// session that created them so they can be transferred from one session to another.
 pub struct Subscriptions {
-    /// Subscriptions associated with the session
+    /// Subscriptions by subscription id
     subscriptions: HashMap<UInt32, Subscription>,
-    /// The publish requeust queue (requests by the client on the session)
-    pub publish_request_queue: Vec<PublishRequestEntry>,
+    /// The owner of each subscription, by subscription id
+    owners: HashMap<UInt32, SubscriptionOwner>,
 }
 
 impl Subscriptions {
     pub fn new() -> Subscriptions {
         Subscriptions {
             subscriptions: HashMap::new(),
-            publish_request_queue: Vec::with_capacity(MAX_DEFAULT_PUBLISH_REQUEST_QUEUE_SIZE),
+            owners: HashMap::new(),
         }
     }
 
-    pub fn enqueue_publish_request(&mut self, address_space: &AddressSpace, request_id: UInt32, request: PublishRequest) -> Result<Option<Vec<PublishResponseEntry>>, StatusCode> {
-        let max_publish_requests = MAX_PUBLISH_REQUESTS;
-        if self.publish_request_queue.len() >= max_publish_requests {
+    /// Queues a publish request and ticks the subscriptions of the session so it can be used
+    /// straight away. Fails with BAD_TOO_MANY_PUBLISH_REQUESTS if the queue already holds the
+    /// maximum number of requests.
+    pub fn enqueue_publish_request(&mut self, session_id: &NodeId, publish_requests: &mut PublishRequests, address_space: &AddressSpace, max_publish_requests: usize, request_id: UInt32, request: PublishRequest) -> Result<Option<Vec<PublishResponseEntry>>, StatusCode> {
+        if max_publish_requests > 0 && publish_requests.queue.len() >= max_publish_requests {
             error!("Too many publish requests, throwing it away");
             Err(BAD_TOO_MANY_PUBLISH_REQUESTS)
         } else {
             trace!("Sending a tick to subscriptions to deal with the request");
-            self.publish_request_queue.insert(0, PublishRequestEntry {
+            publish_requests.queue.insert(0, PublishRequestEntry {
                 request_id,
                 request,
             });
-            Ok(self.tick(true, address_space))
+            Ok(self.tick(session_id, publish_requests, true, address_space))
         }
     }
 
-    pub fn len(&self) -> usize {
-        self.subscriptions.len()
+    /// Returns the number of subscriptions owned by the session
+    pub fn len(&self, session_id: &NodeId) -> usize {
+        self.subscription_ids(session_id).len()
     }
 
-    pub fn contains(&self, subscription_id: UInt32) -> bool {
-        self.subscriptions.contains_key(&subscription_id)
+    pub fn contains(&self, session_id: &NodeId, subscription_id: UInt32) -> bool {
+        match self.owners.get(&subscription_id) {
+            Some(&SubscriptionOwner::Session(ref owner)) => owner == session_id,
+            _ => false
+        }
     }
 
-    pub fn insert(&mut self, subscription_id: UInt32, subscription: Subscription) {
+    /// Returns the owner of the subscription
+    pub fn owner(&self, subscription_id: UInt32) -> Option<&SubscriptionOwner> {
+        self.owners.get(&subscription_id)
+    }
+
+    pub fn insert(&mut self, session_id: &NodeId, subscription_id: UInt32, subscription: Subscription) {
         self.subscriptions.insert(subscription_id, subscription);
+        self.owners.insert(subscription_id, SubscriptionOwner::Session(session_id.clone()));
     }
 
-    pub fn remove(&mut self, subscription_id: UInt32) -> Option<Subscription> {
-        self.subscriptions.remove(&subscription_id)
+    pub fn remove(&mut self, session_id: &NodeId, subscription_id: UInt32) -> Option<Subscription> {
+        if self.contains(session_id, subscription_id) {
+            self.owners.remove(&subscription_id);
+            self.subscriptions.remove(&subscription_id)
+        } else {
+            None
+        }
     }
 
-    pub fn get_mut(&mut self, subscription_id: UInt32) -> Option<&mut Subscription> {
-        self.subscriptions.get_mut(&subscription_id)
+    pub fn get_mut(&mut self, session_id: &NodeId, subscription_id: UInt32) -> Option<&mut Subscription> {
+        if self.contains(session_id, subscription_id) {
+            self.subscriptions.get_mut(&subscription_id)
+        } else {
+            None
+        }
     }
 
-    /// Iterates through the existing queued publish requests and creates a timeout
-    /// publish response any that have expired.
-    pub fn expire_stale_publish_requests(&mut self, now: &DateTimeUTC) -> Option<Vec<PublishResponseEntry>> {
-        if self.publish_request_queue.is_empty() {
+    /// Makes the session the owner of the subscription. The subscription keeps its sequence
+    /// numbers and unacknowledged notifications. Returns the subscription so the caller can
+    /// finish the transfer, or None if there is no such subscription.
+    pub fn transfer(&mut self, session_id: &NodeId, subscription_id: UInt32) -> Option<&mut Subscription> {
+        if let Some(owner) = self.owners.get_mut(&subscription_id) {
+            *owner = SubscriptionOwner::Session(session_id.clone());
+        } else {
             return None;
         }
+        self.subscriptions.get_mut(&subscription_id)
+    }
 
-        // Look for publish requests that have expired
-        let mut expired_request_handles = HashSet::with_capacity(self.publish_request_queue.len());
-        let mut expired_requests = Vec::with_capacity(self.publish_request_queue.len());
+    /// Deletes the subscriptions owned by the session
+    pub fn remove_session(&mut self, session_id: &NodeId) {
+        let subscription_ids = self.subscription_ids(session_id);
+        for subscription_id in subscription_ids {
+            self.owners.remove(&subscription_id);
+            self.subscriptions.remove(&subscription_id);
+        }
+    }
 
-        for request in &self.publish_request_queue {
-            let request_header = &request.request.request_header;
-            let timestamp: DateTimeUTC = request_header.timestamp.as_chrono();
-            let timeout = if request_header.timeout_hint > 0 && (request_header.timeout_hint as i64) < MAX_REQUEST_TIMEOUT {
-                request_header.timeout_hint as i64
-            } else {
-                MAX_REQUEST_TIMEOUT
-            };
-            let timeout_d = time::Duration::milliseconds(timeout);
-            // The request has timed out if the timestamp plus hint exceeds the input time
-            let expiration_time = timestamp + timeout_d;
-            if *now >= expiration_time {
-                debug!("Publish request {} has expired - timestamp = {:?}, expiration hint = {}, expiration time = {:?}, time now = {:?}, ", request_header.request_handle, timestamp, timeout, expiration_time, now);
-                expired_request_handles.insert(request_header.request_handle);
-                expired_requests.push(request.clone());
-            }
+    /// Keeps the subscriptions owned by the session after the session is closed so another
+    /// session of the same user may transfer them. They are deleted if their lifetime expires
+    /// first.
+    pub fn orphan_session(&mut self, session_id: &NodeId, user_identity: &Option<UserIdentity>, client_certificate: &ByteString) {
+        for subscription_id in self.subscription_ids(session_id) {
+            self.owners.insert(subscription_id, SubscriptionOwner::Closed(user_identity.clone(), client_certificate.clone()));
         }
+    }
 
-        if expired_request_handles.is_empty() {
-            return None;
+    /// Ticks the subscriptions whose session was closed. Having no publish requests, they only
+    /// count down to the expiry of their lifetime.
+    pub fn tick_orphans(&mut self, address_space: &AddressSpace) {
+        let subscription_ids = self.owners.iter().filter(|&(_, owner)| {
+            if let SubscriptionOwner::Closed(..) = *owner { true } else { false }
+        }).map(|(subscription_id, _)| *subscription_id).collect::<Vec<UInt32>>();
+        if !subscription_ids.is_empty() {
+            let _ = self.tick_subscriptions(&NodeId::null(), subscription_ids, &mut PublishRequests::new(), false, address_space);
         }
+    }
 
-        // Remove the expired requests (if any)
-        self.publish_request_queue.retain(|ref r| {
-            !expired_request_handles.contains(&r.request.request_header.request_handle)
-        });
+    /// Returns the number of monitored items across all subscriptions of the session
+    pub fn monitored_item_count(&self, session_id: &NodeId) -> usize {
+        self.subscription_ids(session_id).iter().map(|subscription_id| self.subscriptions[subscription_id].monitored_items.len()).sum()
+    }
 
-        // Make publish responses for each expired request
-        let mut publish_responses = Vec::with_capacity(expired_requests.len());
-        for expired_request in expired_requests {
-            let now = DateTime::from_chrono(now);
-            publish_responses.push(PublishResponseEntry {
-                request_id: expired_request.request_id,
-                response: SupportedMessage::ServiceFault(ServiceFault {
-                    response_header: ResponseHeader::new_timestamped_service_result(now.clone(), &expired_request.request.request_header, BAD_REQUEST_TIMEOUT),
-                }),
-            });
-        }
-        if !publish_responses.is_empty() {
-            Some(publish_responses)
-        } else {
-            None
-        }
+    /// Returns the diagnostics of each subscription of the session with the id
+    pub fn diagnostics(&self, session_id: &NodeId) -> Vec<SubscriptionDiagnosticsDataType> {
+        let mut diagnostics = self.subscription_ids(session_id).iter().map(|subscription_id| self.subscriptions[subscription_id].diagnostics(session_id)).collect::<Vec<SubscriptionDiagnosticsDataType>>();
+        diagnostics.sort_by_key(|diagnostics| diagnostics.subscription_id);
+        diagnostics
     }
 
-    /// Iterate all subscriptions calling tick on each. Note this could potentially be done to run in parallel
+    /// Returns the ids of the subscriptions owned by the session
+    fn subscription_ids(&self, session_id: &NodeId) -> Vec<UInt32> {
+        self.owners.keys().filter(|subscription_id| self.contains(session_id, **subscription_id)).cloned().collect()
+    }
+
+    /// Iterate all subscriptions of the session calling tick on each.
+    pub fn tick(&mut self, session_id: &NodeId, publish_requests: &mut PublishRequests, receive_publish_request: bool, address_space: &AddressSpace) -> Option<Vec<PublishResponseEntry>> {
+        let subscription_ids = self.subscription_ids(session_id);
+        self.tick_subscriptions(session_id, subscription_ids, publish_requests, receive_publish_request, address_space)
+    }
+
+    /// Iterate the subscriptions calling tick on each. Note this could potentially be done to run in parallel
     /// assuming the action to clean dead subscriptions was a join done after all ticks had completed.
-    pub fn tick(&mut self, receive_publish_request: bool, address_space: &AddressSpace) -> Option<Vec<PublishResponseEntry>> {
+    fn tick_subscriptions(&mut self, session_id: &NodeId, subscription_ids: Vec<UInt32>, publish_requests: &mut PublishRequests, receive_publish_request: bool, address_space: &AddressSpace) -> Option<Vec<PublishResponseEntry>> {
         let now = chrono::UTC::now();
 
-        let mut publish_request_queue = self.publish_request_queue.clone();
+        // Status changes of subscriptions transferred away from the session go out first
+        let mut request_response_results = publish_requests.take_status_change_responses();
+
+        let mut publish_request_queue = publish_requests.queue.clone();
 
-        let mut request_response_results = Vec::with_capacity(publish_request_queue.len());
-        let mut dead_subscriptions: Vec<u32> = Vec::with_capacity(self.subscriptions.len());
+        let mut dead_subscriptions: Vec<u32> = Vec::new();
 
         // Iterate through all subscriptions. If there is a publish request it will be used to
         // acknowledge notifications and the response to return new notifications.
 
         let mut acknowledge_results_map = HashMap::new();
         for publish_request in &publish_request_queue {
-            let acknowledge_results = self.process_subscription_acknowledgements(publish_request);
-            acknowledge_results_map.insert(publish_request.request_id, acknowledge_results);
+            // Requests without acknowledgements wait in the queue until a subscription needs them
+            let acknowledge_results = self.process_subscription_acknowledgements(session_id, publish_request);
+            if acknowledge_results.is_some() {
+                acknowledge_results_map.insert(publish_request.request_id, acknowledge_results);
+            }
         }
 
-        // Now tick over the subscriptions
-        for (subscription_id, subscription) in &mut self.subscriptions {
+        // Now tick over the subscriptions in the order they should receive publish requests
+        for subscription_id in self.subscription_ids_by_priority(&subscription_ids) {
+            let subscription = self.subscriptions.get_mut(&subscription_id).unwrap();
             // Dead subscriptions will be removed at the end
             if subscription.state == SubscriptionState::Closed {
-                dead_subscriptions.push(*subscription_id);
+                dead_subscriptions.push(subscription_id);
             } else {
                 let publish_request = publish_request_queue.pop();
                 let publishing_req_queued = !publish_request_queue.is_empty() || publish_request.is_some();
@@ -174,6 +218,7 @@ impl Subscriptions {
 
         // Remove dead subscriptions
         for subscription_id in dead_subscriptions {
+            self.owners.remove(&subscription_id);
             self.subscriptions.remove(&subscription_id);
         }
 
@@ -206,7 +251,7 @@ impl Subscriptions {
         });
 
         // Update modified queue
-        self.publish_request_queue = publish_request_queue;
+        publish_requests.queue = publish_request_queue;
 
         if request_response_results.is_empty() {
             None
@@ -215,6 +260,18 @@ impl Subscriptions {
         }
     }
 
+    /// Returns the ids of the subscriptions in the order that publish requests are given to them.
+    /// Subscriptions with notifications waiting come first, ordered by highest priority and then by
+    /// how long their oldest notification has waited. The rest follow by priority.
+    fn subscription_ids_by_priority(&self, subscription_ids: &[UInt32]) -> Vec<UInt32> {
+        let mut subscriptions = subscription_ids.iter().map(|subscription_id| &self.subscriptions[subscription_id]).collect::<Vec<&Subscription>>();
+        subscriptions.sort_by_key(|s| {
+            let oldest_notification_ticks = s.notification_queue.values().next().map(|n| n.publish_time.checked_ticks());
+            (oldest_notification_ticks.is_none(), Reverse(s.priority), oldest_notification_ticks, s.subscription_id)
+        });
+        subscriptions.iter().map(|s| s.subscription_id).collect()
+    }
+
     /// Deletes the acknowledged notifications, returning a list of status code for each according
     /// to whether it was found or not.
     ///
@@ -222,7 +279,7 @@ impl Subscriptions {
     /// BAD_SUBSCRIPTION_ID_INVALID - Subscription doesn't exist
     /// BAD_SEQUENCE_NUMBER_UNKNOWN - Sequence number doesn't exist
     ///
-    fn process_subscription_acknowledgements(&mut self, request: &PublishRequestEntry) -> Option<Vec<StatusCode>> {
+    fn process_subscription_acknowledgements(&mut self, session_id: &NodeId, request: &PublishRequestEntry) -> Option<Vec<StatusCode>> {
         trace!("Processing subscription acknowledgements");
         let request = &request.request;
         if request.subscription_acknowledgements.is_some() {
@@ -230,7 +287,7 @@ impl Subscriptions {
             let mut results: Vec<StatusCode> = Vec::with_capacity(subscription_acknowledgements.len());
             for subscription_acknowledgement in subscription_acknowledgements {
                 let subscription_id = subscription_acknowledgement.subscription_id;
-                let subscription = self.subscriptions.get_mut(&subscription_id);
+                let subscription = self.get_mut(session_id, subscription_id);
                 let result = if subscription.is_none() {
                     BAD_SUBSCRIPTION_ID_INVALID
                 } else {
@@ -18,6 +18,13 @@ fn make_test_file(filename: &str) -> PathBuf {
     path
 }
 
+/// Returns the path of an empty pki directory for a test server
+fn make_test_pki_dir(dirname: &str) -> String {
+    let path = make_test_file(dirname);
+    let _ = std::fs::remove_dir_all(&path);
+    path.into_os_string().into_string().unwrap()
+}
+
 fn make_sample_address_space() -> AddressSpace {
     let mut address_space = AddressSpace::new();
     add_sample_vars_to_address_space(&mut address_space);
@@ -94,7 +101,7 @@ pub fn expired_publish_requests() {
 
     // Create session with publish requests
     let mut session = Session::new();
-    session.subscriptions.publish_request_queue = vec![pr1, pr2];
+    session.publish_requests.queue = vec![pr1, pr2];
 
     // Expire requests, see which expire
     let expired_responses = session.expire_stale_publish_requests(&now_plus_5s);
@@ -102,8 +109,8 @@ pub fn expired_publish_requests() {
 
     // The > 30s timeout hint request should be expired and the other should remain
     assert_eq!(expired_responses.len(), 1);
-    assert_eq!(session.subscriptions.publish_request_queue.len(), 1);
-    assert_eq!(session.subscriptions.publish_request_queue[0].request.request_header.request_handle, 1000);
+    assert_eq!(session.publish_requests.queue.len(), 1);
+    assert_eq!(session.publish_requests.queue[0].request.request_header.request_handle, 1000);
 
     let r1 = &expired_responses[0];
     if let SupportedMessage::ServiceFault(ref response_header) = r1.response {
@@ -1,4 +1,8 @@
 use chrono;
+use time;
+
+use DateTimeUTC;
+use constants;
 
 use prelude::*;
 
@@ -92,7 +96,7 @@ fn data_change_filter_test() {
 
     // Change timestamps to differ
     let now = DateTime::now();
-    v1.server_timestamp = Some(now.clone());
+    v1.source_timestamp = Some(now.clone());
     assert_eq!(filter.compare(&v1, &v2, None), false);
 }
 
@@ -161,14 +165,77 @@ fn deadband_pct() {
     assert_eq!(DataChangeFilter::pct_compare(101.0001f64, 100f64, 0f64, 100f64, 1.0002f64), true);
 }
 
+#[test]
+fn data_change_deadband_array() {
+    let mut filter = DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: 1,
+        deadband_value: 1f64,
+    };
+    let array = |values: &[f64]| Variant::Array(Box::new(values.iter().map(|v| Variant::Double(*v)).collect()));
+
+    // Every element must be within the deadband
+    assert_eq!(filter.compare_value(&array(&[1f64, 2f64]), &array(&[1.5f64, 3f64]), None), Ok(true));
+    assert_eq!(filter.compare_value(&array(&[1f64, 2f64]), &array(&[1.5f64, 3.5f64]), None), Ok(false));
+
+    // Arrays of different lengths or dimensions have changed
+    assert_eq!(filter.compare_value(&array(&[1f64, 2f64]), &array(&[1f64]), None), Ok(false));
+    let mda1 = Variant::new_multi_dimension_array(vec![Variant::Double(1f64), Variant::Double(2f64)], vec![1, 2]);
+    let mda2 = Variant::new_multi_dimension_array(vec![Variant::Double(1f64), Variant::Double(2f64)], vec![2, 1]);
+    assert_eq!(filter.compare_value(&mda1, &mda1, None), Ok(true));
+    assert_eq!(filter.compare_value(&mda1, &mda2, None), Ok(false));
+
+    // Percent deadband compares each element against the range
+    filter.deadband_type = 2;
+    filter.deadband_value = 10f64;
+    assert_eq!(filter.compare_value(&array(&[10f64, 20f64]), &array(&[15f64, 29f64]), Some((0f64, 100f64))), Ok(true));
+    assert_eq!(filter.compare_value(&array(&[10f64, 20f64]), &array(&[15f64, 31f64]), Some((0f64, 100f64))), Ok(false));
+    assert_eq!(filter.compare_value(&array(&[10f64]), &array(&[15f64]), None), Err(BAD_DEADBAND_FILTER_INVALID));
+}
+
+#[test]
+fn monitored_item_percent_deadband() {
+    let mut address_space = make_address_space();
+    let mut request = make_create_request(0f64, 5);
+    request.requested_parameters.filter = ExtensionObject::from_encodable(ObjectId::DataChangeFilter_Encoding_DefaultBinary.as_node_id(), DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: 2,
+        deadband_value: 1f64,
+    });
+
+    // The filter is unsupported on a node without an EURange
+    let mut s = Subscription::new(1, true, 1000f64, 300, 100, 0);
+    let results = s.create_monitored_items(&address_space, &MonitoredItemLimits::default(), &[request.clone()]);
+    assert_eq!(results[0].status_code, BAD_MONITORED_ITEM_FILTER_UNSUPPORTED);
+
+    // Add an EURange of 0 to 200
+    let eu_range_node_id = NodeId::new_numeric(1, 2);
+    let eu_range = ExtensionObject::from_encodable(ObjectId::Range_Encoding_DefaultBinary.as_node_id(), Range { low: 0f64, high: 200f64 });
+    address_space.insert(NodeType::Variable(Variable::new(&eu_range_node_id, "EURange", "EURange", "", DataTypeId::Range, DataValue::new(Variant::new_extension_object(eu_range)))));
+    address_space.add_has_property(&test_var_node_id(), &eu_range_node_id);
+    assert_eq!(address_space.find_eu_range(&test_var_node_id()), Some(Range { low: 0f64, high: 200f64 }));
+
+    let results = s.create_monitored_items(&address_space, &MonitoredItemLimits::default(), &[request.clone()]);
+    assert_eq!(results[0].status_code, GOOD);
+
+    // Changes of 1% of the range or less are not reported
+    let mut monitored_item = MonitoredItem::new(1, &request, &MonitoredItemLimits::default(), 1000f64, None, true).unwrap();
+    let now = chrono::UTC::now();
+    assert_eq!(monitored_item.tick(&address_space, &now, true), true);
+    let _ = address_space.set_value_by_node_id(&test_var_node_id(), Variant::UInt32(2));
+    assert_eq!(monitored_item.tick(&address_space, &now, true), false);
+    let _ = address_space.set_value_by_node_id(&test_var_node_id(), Variant::UInt32(3));
+    assert_eq!(monitored_item.tick(&address_space, &now, true), true);
+}
+
 #[test]
 fn monitored_item_data_change_filter() {
     // create an address space
     let mut address_space = make_address_space();
 
     // Create request should monitor attribute of variable, e.g. value
-    // Sample interval is negative so it will always test on repeated calls
-    let mut monitored_item = MonitoredItem::new(1, &make_create_request(-1f64, 5)).unwrap();
+    // Sample interval is 0 so it will always test on repeated calls
+    let mut monitored_item = MonitoredItem::new(1, &make_create_request(0f64, 5), &MonitoredItemLimits::default(), 1000f64, None, true).unwrap();
 
     let now = chrono::UTC::now();
 
@@ -197,9 +264,174 @@ fn monitored_item_data_change_filter() {
     assert_eq!(monitored_item.notification_queue.len(), 2);
 }
 
+/// Sets the value, status and source timestamp of the test variable
+fn set_test_var_value(address_space: &mut AddressSpace, value: UInt32, status: StatusCode, source_timestamp: &DateTime) {
+    let variable = address_space.find_variable_by_node_id(&test_var_node_id()).unwrap();
+    variable.set_value(DataValue {
+        value: Some(Variant::UInt32(value)),
+        status: Some(status),
+        source_timestamp: Some(source_timestamp.clone()),
+        source_picoseconds: Some(0),
+        server_timestamp: Some(source_timestamp.clone()),
+        server_picoseconds: Some(0),
+    });
+}
+
+/// Creates an item monitoring the test variable with the trigger, or without a filter if there
+/// is no trigger, and ticks it once to sample the initial value
+fn make_trigger_monitored_item(address_space: &AddressSpace, trigger: Option<DataChangeTrigger>) -> MonitoredItem {
+    let mut request = make_create_request(0f64, 5);
+    request.requested_parameters.filter = if let Some(trigger) = trigger {
+        ExtensionObject::from_encodable(ObjectId::DataChangeFilter_Encoding_DefaultBinary.as_node_id(), DataChangeFilter {
+            trigger,
+            deadband_type: 0,
+            deadband_value: 0f64,
+        })
+    } else {
+        ExtensionObject::null()
+    };
+    let mut monitored_item = MonitoredItem::new(1, &request, &MonitoredItemLimits::default(), 1000f64, None, true).unwrap();
+    assert_eq!(monitored_item.tick(address_space, &chrono::UTC::now(), true), true);
+    monitored_item
+}
+
+#[test]
+fn monitored_item_trigger_status() {
+    let mut address_space = make_address_space();
+    let now = chrono::UTC::now();
+    let t1 = DateTime::now();
+    let t2 = DateTime::from_chrono(&(now + time::Duration::seconds(1)));
+    set_test_var_value(&mut address_space, 1, GOOD, &t1);
+    let mut monitored_item = make_trigger_monitored_item(&address_space, Some(DataChangeTrigger::Status));
+
+    // Value and timestamp changes are ignored
+    set_test_var_value(&mut address_space, 2, GOOD, &t2);
+    assert_eq!(monitored_item.tick(&address_space, &now, true), false);
+
+    // A status change is reported
+    set_test_var_value(&mut address_space, 2, UNCERTAIN_LAST_USABLE_VALUE, &t2);
+    assert_eq!(monitored_item.tick(&address_space, &now, true), true);
+    assert_eq!(monitored_item.notification_queue.len(), 2);
+}
+
+#[test]
+fn monitored_item_trigger_status_value() {
+    let mut address_space = make_address_space();
+    let now = chrono::UTC::now();
+    let t1 = DateTime::now();
+    let t2 = DateTime::from_chrono(&(now + time::Duration::seconds(1)));
+    set_test_var_value(&mut address_space, 1, GOOD, &t1);
+
+    // No filter behaves the same as a StatusValue trigger
+    for trigger in &[Some(DataChangeTrigger::StatusValue), None] {
+        set_test_var_value(&mut address_space, 1, GOOD, &t1);
+        let mut monitored_item = make_trigger_monitored_item(&address_space, *trigger);
+
+        // Timestamp changes are ignored
+        set_test_var_value(&mut address_space, 1, GOOD, &t2);
+        assert_eq!(monitored_item.tick(&address_space, &now, true), false);
+
+        // A value change is reported
+        set_test_var_value(&mut address_space, 2, GOOD, &t2);
+        assert_eq!(monitored_item.tick(&address_space, &now, true), true);
+
+        // A quality drop with an unchanged value is reported
+        set_test_var_value(&mut address_space, 2, BAD_SENSOR_FAILURE, &t2);
+        assert_eq!(monitored_item.tick(&address_space, &now, true), true);
+        assert_eq!(monitored_item.notification_queue.len(), 3);
+    }
+}
+
+#[test]
+fn monitored_item_trigger_status_value_timestamp() {
+    let mut address_space = make_address_space();
+    let now = chrono::UTC::now();
+    let t1 = DateTime::now();
+    let t2 = DateTime::from_chrono(&(now + time::Duration::seconds(1)));
+    set_test_var_value(&mut address_space, 1, GOOD, &t1);
+    let mut monitored_item = make_trigger_monitored_item(&address_space, Some(DataChangeTrigger::StatusValueTimestamp));
+
+    // Nothing changed
+    assert_eq!(monitored_item.tick(&address_space, &now, true), false);
+
+    // A source timestamp change with the same value and status is reported
+    set_test_var_value(&mut address_space, 1, GOOD, &t2);
+    assert_eq!(monitored_item.tick(&address_space, &now, true), true);
+
+    // As are value and status changes
+    set_test_var_value(&mut address_space, 2, GOOD, &t2);
+    assert_eq!(monitored_item.tick(&address_space, &now, true), true);
+    set_test_var_value(&mut address_space, 2, BAD_SENSOR_FAILURE, &t2);
+    assert_eq!(monitored_item.tick(&address_space, &now, true), true);
+    assert_eq!(monitored_item.notification_queue.len(), 4);
+}
+
+#[test]
+fn monitored_item_resend_data() {
+    let address_space = make_address_space();
+    let mut monitored_item = MonitoredItem::new(1, &make_create_request(0f64, 5), &MonitoredItemLimits::default(), 1000f64, None, true).unwrap();
+    let now = chrono::UTC::now();
+
+    assert_eq!(monitored_item.tick(&address_space, &now, true), true);
+    assert_eq!(monitored_item.tick(&address_space, &now, true), false);
+
+    // The unchanged value is reported again after a resend
+    monitored_item.resend_data();
+    assert_eq!(monitored_item.tick(&address_space, &now, true), true);
+    assert_eq!(monitored_item.notification_queue.len(), 2);
+    assert_eq!(monitored_item.tick(&address_space, &now, true), false);
+}
+
+#[test]
+fn monitored_item_revised_parameters() {
+    let mut address_space = make_address_space();
+    let limits = MonitoredItemLimits {
+        min_sampling_interval: 10f64,
+        max_sampling_interval: 5000f64,
+        max_data_change_queue_size: 20,
+        max_event_queue_size: 50,
+    };
+    let create = |address_space: &AddressSpace, sampling_interval: Double, queue_size: UInt32| {
+        let mut s = Subscription::new(1, true, 1000f64, 300, 100, 0);
+        let result = s.create_monitored_items(address_space, &limits, &[make_create_request(sampling_interval, queue_size)]).remove(0);
+        assert_eq!(result.status_code, GOOD);
+        (result.revised_sampling_interval, result.revised_queue_size)
+    };
+
+    // Server limits
+    assert_eq!(create(&address_space, 1f64, 0), (10f64, 1));
+    assert_eq!(create(&address_space, 100f64, 15), (100f64, 15));
+    assert_eq!(create(&address_space, 10000f64, 100), (5000f64, 20));
+    assert_eq!(create(&address_space, 0f64, 5), (0f64, 5));
+    // A negative interval is the publishing interval of the subscription
+    assert_eq!(create(&address_space, -1f64, 5), (1000f64, 5));
+
+    // The node can't be sampled faster than its minimum sampling interval
+    address_space.find_variable_by_node_id(&test_var_node_id()).unwrap().set_minimum_sampling_interval(250);
+    assert_eq!(create(&address_space, 0f64, 5), (250f64, 5));
+    assert_eq!(create(&address_space, 100f64, 5), (250f64, 5));
+    assert_eq!(create(&address_space, 1000f64, 5), (1000f64, 5));
+
+    // Modifying an item revises the values the same way
+    let mut monitored_item = MonitoredItem::new(1, &make_create_request(1000f64, 5), &limits, 1000f64, Some(250f64), true).unwrap();
+    let request = MonitoredItemModifyRequest {
+        monitored_item_id: 1,
+        requested_parameters: make_create_request(100f64, 100).requested_parameters,
+    };
+    assert!(monitored_item.modify(&request, &limits, 1000f64, Some(250f64), true).is_ok());
+    assert_eq!(monitored_item.sampling_interval, 250f64);
+    assert_eq!(monitored_item.queue_size, 20);
+
+    // An interval of 0 is raised to the server minimum unless the item is exception-based
+    let monitored_item = MonitoredItem::new(1, &make_create_request(0f64, 5), &limits, 1000f64, None, false).unwrap();
+    assert_eq!(monitored_item.sampling_interval, 10f64);
+    let monitored_item = MonitoredItem::new(1, &make_create_request(0f64, 5), &limits, 1000f64, None, true).unwrap();
+    assert_eq!(monitored_item.sampling_interval, 0f64);
+}
+
 fn populate_monitored_item(discard_oldest: bool) -> MonitoredItem {
     let client_handle = 999;
-    let mut monitored_item = MonitoredItem::new(1, &make_create_request(-1f64, 5)).unwrap();
+    let mut monitored_item = MonitoredItem::new(1, &make_create_request(0f64, 5), &MonitoredItemLimits::default(), 1000f64, None, true).unwrap();
     monitored_item.discard_oldest = discard_oldest;
     for i in 0..5 {
         monitored_item.enqueue_notification_message(MonitoredItemNotification {
@@ -246,3 +478,271 @@ fn monitored_item_queue_discard_oldest() {
         assert_first_notification_is_i32(&mut monitored_item, 10);
     }
 }
+
+fn test_object_node_id() -> NodeId {
+    NodeId::new_numeric(1, 100)
+}
+
+fn make_event_address_space() -> AddressSpace {
+    let mut address_space = AddressSpace::new();
+    let mut object = Object::new(&test_object_node_id(), "Boiler", "Boiler", "");
+    object.set_event_notifier(EVENT_NOTIFIER_SUBSCRIBE_TO_EVENTS);
+    address_space.insert(NodeType::Object(object));
+    address_space.add_organizes(&AddressSpace::objects_folder_id(), &test_object_node_id());
+    let _ = address_space.add_variable(Variable::new(&test_var_node_id(), "test", "test", "", DataTypeId::UInt32, DataValue::new(Variant::UInt32(0))), &test_object_node_id());
+    address_space.insert_reference(&test_object_node_id(), &test_var_node_id(), ReferenceTypeId::HasEventSource);
+    address_space
+}
+
+fn select_clause(name: &str) -> SimpleAttributeOperand {
+    SimpleAttributeOperand {
+        type_definition_id: ObjectTypeId::BaseEventType.as_node_id(),
+        browse_path: Some(vec![QualifiedName::new(0, name)]),
+        attribute_id: AttributeId::Value as UInt32,
+        index_range: UAString::null(),
+    }
+}
+
+fn make_event_create_request(node_id: &NodeId, elements: Option<Vec<ContentFilterElement>>) -> MonitoredItemCreateRequest {
+    let filter = ExtensionObject::from_encodable(ObjectId::EventFilter_Encoding_DefaultBinary.as_node_id(), EventFilter {
+        select_clauses: Some(vec![select_clause("Message"), select_clause("Severity"), select_clause("Flow")]),
+        where_clause: ContentFilter {
+            elements,
+        },
+    });
+    MonitoredItemCreateRequest {
+        item_to_monitor: ReadValueId {
+            node_id: node_id.clone(),
+            attribute_id: AttributeId::EventNotifier as UInt32,
+            index_range: UAString::null(),
+            data_encoding: QualifiedName::null(),
+        },
+        monitoring_mode: MonitoringMode::Reporting,
+        requested_parameters: MonitoringParameters {
+            client_handle: 999,
+            sampling_interval: 0f64,
+            filter,
+            queue_size: 0,
+            discard_oldest: true,
+        },
+    }
+}
+
+fn make_filter_element(filter_operator: FilterOperator, filter_operands: Vec<ExtensionObject>) -> ContentFilterElement {
+    ContentFilterElement {
+        filter_operator,
+        filter_operands: Some(filter_operands),
+    }
+}
+
+fn simple_attribute_operand(name: &str) -> ExtensionObject {
+    ExtensionObject::from_encodable(ObjectId::SimpleAttributeOperand_Encoding_DefaultBinary.as_node_id(), select_clause(name))
+}
+
+fn literal_operand(value: Variant) -> ExtensionObject {
+    ExtensionObject::from_encodable(ObjectId::LiteralOperand_Encoding_DefaultBinary.as_node_id(), LiteralOperand { value })
+}
+
+fn element_operand(index: UInt32) -> ExtensionObject {
+    ExtensionObject::from_encodable(ObjectId::ElementOperand_Encoding_DefaultBinary.as_node_id(), ElementOperand { index })
+}
+
+fn raise_event(address_space: &mut AddressSpace, source_node: &NodeId, message: &str, severity: UInt16) {
+    let mut event = Event::new(&ObjectTypeId::BaseEventType.as_node_id(), source_node, "test", message, severity);
+    event.set_property(QualifiedName::new(0, "Flow"), Variant::Double(severity as f64 / 10f64));
+    assert!(address_space.raise_event(event).is_ok());
+}
+
+#[test]
+fn monitored_item_event_filter() {
+    let mut address_space = make_event_address_space();
+    let now = chrono::UTC::now();
+
+    // Views hold their EventNotifier as a byte like objects do
+    let view_id = NodeId::new_string(1, "EventView");
+    address_space.insert(NodeType::View(View::new(&view_id, "EventView", "EventView", "", EVENT_NOTIFIER_SUBSCRIBE_TO_EVENTS, false)));
+    assert!(address_space.is_event_notifier(&view_id));
+    assert!(address_space.is_event_notifier(&test_object_node_id()));
+
+    // Event items must use an event filter on the EventNotifier attribute
+    let mut request = make_event_create_request(&test_object_node_id(), None);
+    request.item_to_monitor.attribute_id = AttributeId::Value as UInt32;
+    assert_eq!(MonitoredItem::new(1, &request, &MonitoredItemLimits::default(), 1000f64, None, true).unwrap_err(), BAD_FILTER_NOT_ALLOWED);
+    let mut request = make_create_request(-1f64, 5);
+    request.item_to_monitor.attribute_id = AttributeId::EventNotifier as UInt32;
+    assert_eq!(MonitoredItem::new(1, &request, &MonitoredItemLimits::default(), 1000f64, None, true).unwrap_err(), BAD_MONITORED_ITEM_FILTER_INVALID);
+
+    // Severity >= 500 and message like "Pressure%"
+    let elements = vec![
+        make_filter_element(FilterOperator::And, vec![element_operand(1), element_operand(2)]),
+        make_filter_element(FilterOperator::GreaterThanOrEqual, vec![simple_attribute_operand("Severity"), literal_operand(Variant::UInt16(500))]),
+        make_filter_element(FilterOperator::Like, vec![simple_attribute_operand("Message"), literal_operand(Variant::String(UAString::from_str("Pressure%")))]),
+    ];
+    let mut monitored_item = MonitoredItem::new(1, &make_event_create_request(&test_object_node_id(), Some(elements)), &MonitoredItemLimits::default(), 1000f64, None, true).unwrap();
+    assert!(monitored_item.is_event_item());
+    assert_eq!(monitored_item.sampling_interval, 0f64);
+
+    // Events raised before the first tick are not reported
+    raise_event(&mut address_space, &test_var_node_id(), "Pressure high", 600);
+    assert_eq!(monitored_item.tick(&address_space, &now, true), false);
+
+    raise_event(&mut address_space, &test_var_node_id(), "Pressure high", 800);
+    raise_event(&mut address_space, &test_var_node_id(), "Pressure low", 100);
+    raise_event(&mut address_space, &test_var_node_id(), "Temperature high", 900);
+    // The object is not the notifier of events from the objects folder
+    raise_event(&mut address_space, &AddressSpace::objects_folder_id(), "Pressure high", 800);
+    assert_eq!(monitored_item.tick(&address_space, &now, true), true);
+    assert_eq!(monitored_item.tick(&address_space, &now, true), false);
+
+    let events = monitored_item.remove_all_events().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].client_handle, 999);
+    let event_fields = events[0].event_fields.as_ref().unwrap();
+    assert_eq!(event_fields.len(), 3);
+    assert_eq!(event_fields[0], Variant::new_localized_text(LocalizedText::new("", "Pressure high")));
+    assert_eq!(event_fields[1], Variant::UInt16(800));
+    assert_eq!(event_fields[2], Variant::Double(80f64));
+
+    // The server object is the notifier of every event
+    let mut monitored_item = MonitoredItem::new(2, &make_event_create_request(&ObjectId::Server.as_node_id(), None), &MonitoredItemLimits::default(), 1000f64, None, true).unwrap();
+    assert_eq!(monitored_item.tick(&address_space, &now, true), false);
+    raise_event(&mut address_space, &test_var_node_id(), "Pressure high", 800);
+    raise_event(&mut address_space, &AddressSpace::objects_folder_id(), "Pressure high", 800);
+    assert_eq!(monitored_item.tick(&address_space, &now, true), true);
+    assert_eq!(monitored_item.remove_all_events().unwrap().len(), 2);
+
+    // A sampling item queues events without reporting them
+    monitored_item.set_monitoring_mode(MonitoringMode::Sampling);
+    raise_event(&mut address_space, &test_var_node_id(), "Pressure high", 800);
+    assert_eq!(monitored_item.tick(&address_space, &now, true), false);
+    assert_eq!(monitored_item.remove_all_events().unwrap().len(), 1);
+}
+
+#[test]
+fn event_filter_invalid() {
+    // No select clauses
+    let event_filter = EventFilter {
+        select_clauses: None,
+        where_clause: ContentFilter { elements: None },
+    };
+    assert_eq!(ParsedEventFilter::parse(&event_filter).unwrap_err(), BAD_EVENT_FILTER_INVALID);
+
+    let parse_where_clause = |elements: Vec<ContentFilterElement>| {
+        ParsedEventFilter::parse(&EventFilter {
+            select_clauses: Some(vec![select_clause("Message")]),
+            where_clause: ContentFilter { elements: Some(elements) },
+        })
+    };
+
+    // Wrong number of operands
+    assert_eq!(parse_where_clause(vec![make_filter_element(FilterOperator::Equals, vec![literal_operand(Variant::Int32(1))])]).unwrap_err(), BAD_FILTER_OPERAND_COUNT_MISMATCH);
+    // Element operands can only refer to later elements
+    assert_eq!(parse_where_clause(vec![make_filter_element(FilterOperator::Not, vec![element_operand(0)])]).unwrap_err(), BAD_FILTER_OPERAND_INVALID);
+    assert_eq!(parse_where_clause(vec![make_filter_element(FilterOperator::Not, vec![element_operand(1)])]).unwrap_err(), BAD_FILTER_OPERAND_INVALID);
+    // Unsupported operator
+    assert_eq!(parse_where_clause(vec![make_filter_element(FilterOperator::Cast, vec![literal_operand(Variant::Int32(1)), literal_operand(Variant::Int32(1))])]).unwrap_err(), BAD_FILTER_OPERATOR_UNSUPPORTED);
+    // Valid
+    assert!(parse_where_clause(vec![make_filter_element(FilterOperator::InList, vec![simple_attribute_operand("Severity"), literal_operand(Variant::Int32(1)), literal_operand(Variant::Int32(2))])]).is_ok());
+    // Too many elements
+    let elements = (0..constants::MAX_WHERE_CLAUSE_ELEMENTS + 1).map(|_| make_filter_element(FilterOperator::IsNull, vec![simple_attribute_operand("Message")])).collect();
+    assert_eq!(parse_where_clause(elements).unwrap_err(), BAD_CONTENT_FILTER_INVALID);
+    // Pattern too long
+    let pattern = (0..constants::MAX_LIKE_PATTERN_LENGTH + 1).map(|_| "%").collect::<String>();
+    assert_eq!(parse_where_clause(vec![make_filter_element(FilterOperator::Like, vec![simple_attribute_operand("Message"), literal_operand(Variant::String(UAString::from_str(&pattern)))])]).unwrap_err(), BAD_FILTER_OPERAND_INVALID);
+}
+
+#[test]
+fn event_filter_evaluation() {
+    let address_space = make_event_address_space();
+    let event = Event::new(&ObjectTypeId::BaseEventType.as_node_id(), &test_var_node_id(), "test", "Pressure high in boiler 2", 600);
+    let matches = |elements: Vec<ContentFilterElement>| {
+        ParsedEventFilter::parse(&EventFilter {
+            select_clauses: Some(vec![select_clause("Message")]),
+            where_clause: ContentFilter { elements: Some(elements) },
+        }).unwrap().matches(&address_space, &event)
+    };
+    let like = |pattern: &str| matches(vec![make_filter_element(FilterOperator::Like, vec![simple_attribute_operand("Message"), literal_operand(Variant::String(UAString::from_str(pattern)))])]);
+
+    assert!(like("Pressure%"));
+    assert!(like("%boiler _"));
+    assert!(like("%%Pressure%%%2%"));
+    assert!(like("Pressure high in boiler 2"));
+    assert!(!like("Pressure"));
+    assert!(!like("%boiler __"));
+    assert!(!like("\\%Pressure%"));
+    assert!(like("%"));
+    // Many wildcards before a character that is not there take no time at all
+    let pattern = (0..500).map(|_| "%_").collect::<String>() + "x";
+    assert!(!like(&pattern));
+
+    // Elements shared by the elements before them are evaluated once
+    let mut elements: Vec<ContentFilterElement> = (0..constants::MAX_WHERE_CLAUSE_ELEMENTS - 1).map(|i| {
+        make_filter_element(FilterOperator::And, vec![element_operand(i as UInt32 + 1), element_operand(i as UInt32 + 1)])
+    }).collect();
+    elements.push(make_filter_element(FilterOperator::GreaterThan, vec![simple_attribute_operand("Severity"), literal_operand(Variant::UInt16(500))]));
+    assert!(matches(elements));
+}
+
+fn make_aggregate_create_request(attribute_id: AttributeId, aggregate_type: ObjectId, start_time: &DateTime, processing_interval: Double) -> MonitoredItemCreateRequest {
+    let mut request = make_create_request(0f64, 5);
+    request.item_to_monitor.attribute_id = attribute_id as UInt32;
+    request.requested_parameters.filter = ExtensionObject::from_encodable(ObjectId::AggregateFilter_Encoding_DefaultBinary.as_node_id(), AggregateFilter {
+        start_time: start_time.clone(),
+        aggregate_type: aggregate_type.as_node_id(),
+        processing_interval,
+        aggregate_configuration: AggregateConfiguration {
+            use_server_capabilities_defaults: true,
+            treat_uncertain_as_bad: false,
+            percent_data_bad: 0,
+            percent_data_good: 0,
+            use_sloped_extrapolation: false,
+        },
+    });
+    request
+}
+
+#[test]
+fn monitored_item_aggregate_filter() {
+    let mut address_space = make_address_space();
+
+    let now = chrono::UTC::now();
+    let ms = |ms: i64| now + time::Duration::milliseconds(ms);
+    let set_value = |address_space: &mut AddressSpace, value: UInt32, timestamp: &DateTimeUTC| {
+        if let &mut NodeType::Variable(ref mut node) = address_space.find_node_mut(&test_var_node_id()).unwrap() {
+            let mut data_value = DataValue::new(Variant::UInt32(value));
+            data_value.source_timestamp = Some(DateTime::from_chrono(timestamp));
+            node.set_value(data_value);
+        }
+    };
+
+    // Intervals of 100ms starting on the first tick
+    let start_time = DateTime::from_chrono(&ms(-1000));
+    let mut monitored_item = MonitoredItem::new(1, &make_aggregate_create_request(AttributeId::Value, ObjectId::AggregateFunction_Maximum, &start_time, 100f64), &MonitoredItemLimits::default(), 1000f64, None, true).unwrap();
+    let filter_result = monitored_item.filter_result().decode_inner::<AggregateFilterResult>().unwrap();
+    assert_eq!(filter_result.revised_start_time, start_time);
+    assert_eq!(filter_result.revised_processing_interval, 100f64);
+    assert!(filter_result.revised_aggregate_configuration.treat_uncertain_as_bad);
+
+    // Values sampled during the interval are aggregated when it completes
+    set_value(&mut address_space, 5, &ms(0));
+    assert_eq!(monitored_item.tick(&address_space, &ms(0), true), false);
+    set_value(&mut address_space, 9, &ms(30));
+    assert_eq!(monitored_item.tick(&address_space, &ms(30), true), false);
+    set_value(&mut address_space, 7, &ms(60));
+    assert_eq!(monitored_item.tick(&address_space, &ms(60), true), false);
+    assert_eq!(monitored_item.tick(&address_space, &ms(100), true), true);
+    assert_eq!(monitored_item.notification_queue.len(), 1);
+    let notification = monitored_item.remove_first_notification_message().unwrap();
+    assert_eq!(notification.value.value, Some(Variant::UInt32(9)));
+    assert_eq!(notification.value.status, Some(GOOD));
+    assert_eq!(notification.value.source_timestamp, Some(DateTime::from_chrono(&ms(0))));
+
+    // Intervals without values have no data
+    assert_eq!(monitored_item.tick(&address_space, &ms(300), true), true);
+    assert_eq!(monitored_item.notification_queue.len(), 2);
+    assert_eq!(monitored_item.remove_first_notification_message().unwrap().value.status, Some(BAD_NO_DATA));
+
+    // Unsupported aggregates and aggregates of attributes other than the value are rejected
+    assert_eq!(MonitoredItem::new(2, &make_aggregate_create_request(AttributeId::Value, ObjectId::AggregateFunction_Range, &start_time, 100f64), &MonitoredItemLimits::default(), 1000f64, None, true).unwrap_err(), BAD_AGGREGATE_NOT_SUPPORTED);
+    assert_eq!(MonitoredItem::new(3, &make_aggregate_create_request(AttributeId::DisplayName, ObjectId::AggregateFunction_Maximum, &start_time, 100f64), &MonitoredItemLimits::default(), 1000f64, None, true).unwrap_err(), BAD_FILTER_NOT_ALLOWED);
+}
@@ -1,6 +1,23 @@
+use std;
+use std::sync::{Arc, Mutex};
+
+use chrono;
+use time;
+
 use prelude::*;
+use constants;
 use comms::tcp_transport::*;
+use discovery::registration;
+use server::ServerState;
+use services::attribute::AttributeService;
+use services::method::MethodService;
+use services::node_management::NodeManagementService;
+use services::message_handler::MessageHandler;
+use services::session::SessionService;
 use services::view::ViewService;
+use session::UserIdentity;
+use subscriptions::subscriptions::SubscriptionOwner;
+use address_space::variable::{ACCESS_LEVEL_CURRENT_READ, ACCESS_LEVEL_CURRENT_WRITE, ACCESS_LEVEL_HISTORY_READ, ACCESS_LEVEL_HISTORY_WRITE, USER_ACCESS_LEVEL_CURRENT_READ, USER_ACCESS_LEVEL_CURRENT_WRITE, USER_ACCESS_LEVEL_HISTORY_READ, USER_ACCESS_LEVEL_HISTORY_WRITE};
 
 use tests::*;
 
@@ -39,18 +56,1417 @@ fn make_browse_request(nodes: Vec<NodeId>, browse_direction: BrowseDirection, re
 
 // Attribute service tests
 
+#[test]
+fn read_index_range() {
+    let server = Server::new(ServerConfig::default_anonymous());
+    let tcp_session = TcpTransport::new(server.server_state);
+
+    let attribute = AttributeService::new();
+    {
+        let mut server_state = tcp_session.server_state.lock().unwrap();
+        let mut session = tcp_session.session.lock().unwrap();
+
+        let array_node_id = NodeId::new_string(1, "array");
+        {
+            let mut address_space = server_state.address_space.lock().unwrap();
+            let variable = Variable::new_array(&array_node_id, "array", "array", "", DataTypeId::Int32, DataValue::new(Variant::new_i32_array(&[0, 1, 2, 3, 4, 5])), &[6]);
+            let _ = address_space.add_variable(variable, &AddressSpace::objects_folder_id());
+        }
+
+        let make_read_value_id = |index_range: &str| {
+            let mut read_value_id = ReadValueId::read_value(array_node_id.clone());
+            read_value_id.index_range = UAString::from_str(index_range);
+            read_value_id
+        };
+        let request = ReadRequest {
+            request_header: RequestHeader::new(&NodeId::null(), &DateTime::now(), 1),
+            max_age: 0f64,
+            timestamps_to_return: TimestampsToReturn::Neither,
+            nodes_to_read: Some(vec![make_read_value_id("2:4"), make_read_value_id("4:2"), make_read_value_id("10")]),
+        };
+
+        let result = attribute.read(&mut server_state, &mut session, request);
+        let result = match result.unwrap() {
+            SupportedMessage::ReadResponse(result) => result,
+            _ => {
+                panic!("Wrong response")
+            }
+        };
+        let results = result.results.unwrap();
+        assert_eq!(results[0].value, Some(Variant::new_i32_array(&[2, 3, 4])));
+        assert_eq!(results[1].status, Some(BAD_INDEX_RANGE_INVALID));
+        assert_eq!(results[2].status, Some(BAD_INDEX_RANGE_NO_DATA));
+    }
+}
+
+fn make_write_value(node_id: NodeId, attribute_id: AttributeId, value: Variant) -> WriteValue {
+    WriteValue {
+        node_id,
+        attribute_id: attribute_id as UInt32,
+        index_range: UAString::null(),
+        value: DataValue::new(value),
+    }
+}
+
+#[test]
+fn write() {
+    let server = Server::new(ServerConfig::default_anonymous());
+    let tcp_session = TcpTransport::new(server.server_state);
+
+    let attribute = AttributeService::new();
+    {
+        let mut server_state = tcp_session.server_state.lock().unwrap();
+        let mut session = tcp_session.session.lock().unwrap();
+
+        let writable_node_id = NodeId::new_string(1, "writable");
+        {
+            let mut address_space = server_state.address_space.lock().unwrap();
+            add_sample_vars_to_address_space(&mut address_space);
+            let mut variable = Variable::new_i32(&writable_node_id, "writable", "writable", "", 0);
+            variable.set_access_level(ACCESS_LEVEL_CURRENT_READ | ACCESS_LEVEL_CURRENT_WRITE);
+            variable.set_user_access_level(USER_ACCESS_LEVEL_CURRENT_READ | USER_ACCESS_LEVEL_CURRENT_WRITE);
+            let _ = address_space.add_variable(variable, &AddressSpace::objects_folder_id());
+        }
+
+        let nodes_to_write = vec![
+            // Good
+            make_write_value(writable_node_id.clone(), AttributeId::Value, Variant::Int32(99)),
+            // Wrong type
+            make_write_value(writable_node_id.clone(), AttributeId::Value, Variant::Boolean(true)),
+            // Array to a scalar
+            make_write_value(writable_node_id.clone(), AttributeId::Value, Variant::new_i32_array(&[1, 2, 3])),
+            // Read only value
+            make_write_value(NodeId::new_string(1, "v1"), AttributeId::Value, Variant::Int32(99)),
+            // Write mask does not permit it
+            make_write_value(writable_node_id.clone(), AttributeId::DisplayName, Variant::new_localized_text(LocalizedText::new("", "xyz"))),
+            // Unknown node
+            make_write_value(NodeId::new_string(1, "unknown"), AttributeId::Value, Variant::Int32(99)),
+        ];
+        let request = WriteRequest {
+            request_header: RequestHeader::new(&NodeId::null(), &DateTime::now(), 1),
+            nodes_to_write: Some(nodes_to_write),
+        };
+
+        let result = attribute.write(&mut server_state, &mut session, request);
+        assert!(result.is_ok());
+        let result = match result.unwrap() {
+            SupportedMessage::WriteResponse(result) => result,
+            _ => {
+                panic!("Wrong response")
+            }
+        };
+        let results = result.results.unwrap();
+        assert_eq!(results, vec![GOOD, BAD_TYPE_MISMATCH, BAD_TYPE_MISMATCH, BAD_NOT_WRITABLE, BAD_NOT_WRITABLE, BAD_NODE_ID_UNKNOWN]);
+
+        // Check the value actually changed
+        let mut address_space = server_state.address_space.lock().unwrap();
+        let variable = address_space.find_variable_by_node_id(&writable_node_id).unwrap();
+        assert_eq!(variable.value().value, Some(Variant::Int32(99)));
+    }
+}
+
+
+fn make_history_read_request(details: ExtensionObject, nodes: &[(&NodeId, &ByteString)], release_continuation_points: bool) -> HistoryReadRequest {
+    HistoryReadRequest {
+        request_header: RequestHeader::new(&NodeId::null(), &DateTime::now(), 1),
+        history_read_details: details,
+        timestamps_to_return: TimestampsToReturn::Source,
+        release_continuation_points,
+        nodes_to_read: Some(nodes.iter().map(|&(node_id, continuation_point)| HistoryReadValueId {
+            node_id: node_id.clone(),
+            index_range: UAString::null(),
+            data_encoding: QualifiedName::null(),
+            continuation_point: continuation_point.clone(),
+        }).collect()),
+    }
+}
+
+fn history_read_results(result: Result<SupportedMessage, StatusCode>) -> Vec<HistoryReadResult> {
+    match result.unwrap() {
+        SupportedMessage::HistoryReadResponse(response) => response.results.unwrap(),
+        _ => panic!("Wrong response")
+    }
+}
+
+fn history_data_values(result: &HistoryReadResult) -> Vec<DataValue> {
+    let history_data = result.history_data.decode_inner::<HistoryData>().unwrap();
+    history_data.data_values.unwrap_or(Vec::new())
+}
+
+#[test]
+fn history_read() {
+    let server = Server::new(ServerConfig::default_anonymous());
+    let tcp_session = TcpTransport::new(server.server_state);
+
+    let attribute = AttributeService::new();
+    {
+        let mut server_state = tcp_session.server_state.lock().unwrap();
+        let mut session = tcp_session.session.lock().unwrap();
+
+        let history_node_id = NodeId::new_string(1, "history");
+        let no_history_node_id = NodeId::new_string(1, "no_history");
+        let unknown_node_id = NodeId::new_string(1, "unknown");
+        let no_continuation_point = ByteString::null();
+        {
+            let mut address_space = server_state.address_space.lock().unwrap();
+            let mut variable = Variable::new_i32(&history_node_id, "history", "history", "", 0);
+            variable.set_access_level(ACCESS_LEVEL_CURRENT_READ | ACCESS_LEVEL_HISTORY_READ);
+            variable.set_user_access_level(USER_ACCESS_LEVEL_CURRENT_READ | USER_ACCESS_LEVEL_HISTORY_READ);
+            variable.set_historizing(true);
+            let _ = address_space.add_variable(variable, &AddressSpace::objects_folder_id());
+
+            let variable = Variable::new_i32(&no_history_node_id, "no_history", "no_history", "", 0);
+            let _ = address_space.add_variable(variable, &AddressSpace::objects_folder_id());
+
+            // Values 1 to 10 recorded a minute apart
+            let provider = address_space.historical_data_provider();
+            let mut provider = provider.lock().unwrap();
+            for i in 1..11 {
+                let mut data_value = DataValue::new(Variant::Int32(i));
+                data_value.source_timestamp = Some(DateTime::ymd_hms(2018, 1, 1, 0, i as UInt16, 0));
+                provider.record_value(&history_node_id, &data_value);
+            }
+        }
+
+        let raw_details = |start_time: DateTime, end_time: DateTime, num_values_per_node: UInt32| {
+            let details = ReadRawModifiedDetails {
+                is_read_modified: false,
+                start_time,
+                end_time,
+                num_values_per_node,
+                return_bounds: false,
+            };
+            ExtensionObject::from_encodable(ObjectId::ReadRawModifiedDetails_Encoding_DefaultBinary.as_node_id(), details)
+        };
+        let values = |data_values: Vec<DataValue>| -> Vec<Int32> {
+            data_values.into_iter().map(|v| match v.value {
+                Some(Variant::Int32(v)) => v,
+                _ => panic!("Unexpected value")
+            }).collect()
+        };
+
+        // Read a range, a node without history and an unknown node
+        let details = raw_details(DateTime::ymd_hms(2018, 1, 1, 0, 3, 0), DateTime::ymd_hms(2018, 1, 1, 0, 6, 0), 0);
+        let request = make_history_read_request(details, &[(&history_node_id, &no_continuation_point), (&no_history_node_id, &no_continuation_point), (&unknown_node_id, &no_continuation_point)], false);
+        let results = history_read_results(attribute.history_read(&mut server_state, &mut session, request));
+        assert_eq!(results[0].status_code, GOOD);
+        assert_eq!(values(history_data_values(&results[0])), vec![3, 4, 5, 6]);
+        assert_eq!(results[1].status_code, BAD_NOT_READABLE);
+        assert_eq!(results[2].status_code, BAD_NODE_ID_UNKNOWN);
+
+        // Start time later than the end time returns values newest first
+        let details = raw_details(DateTime::ymd_hms(2018, 1, 1, 0, 6, 0), DateTime::ymd_hms(2018, 1, 1, 0, 3, 0), 0);
+        let request = make_history_read_request(details, &[(&history_node_id, &no_continuation_point)], false);
+        let results = history_read_results(attribute.history_read(&mut server_state, &mut session, request));
+        assert_eq!(values(history_data_values(&results[0])), vec![6, 5, 4, 3]);
+
+        // Limit the values per node and follow the continuation points
+        let details = raw_details(DateTime::ymd_hms(2018, 1, 1, 0, 0, 0), DateTime::ymd_hms(2018, 1, 2, 0, 0, 0), 4);
+        let request = make_history_read_request(details.clone(), &[(&history_node_id, &no_continuation_point)], false);
+        let results = history_read_results(attribute.history_read(&mut server_state, &mut session, request));
+        assert_eq!(values(history_data_values(&results[0])), vec![1, 2, 3, 4]);
+        let continuation_point = results[0].continuation_point.clone();
+        assert!(!continuation_point.is_null());
+
+        let request = make_history_read_request(details.clone(), &[(&history_node_id, &continuation_point)], false);
+        let results = history_read_results(attribute.history_read(&mut server_state, &mut session, request));
+        assert_eq!(values(history_data_values(&results[0])), vec![5, 6, 7, 8]);
+        let continuation_point2 = results[0].continuation_point.clone();
+
+        // The used continuation point is no longer valid
+        let request = make_history_read_request(details.clone(), &[(&history_node_id, &continuation_point)], false);
+        let results = history_read_results(attribute.history_read(&mut server_state, &mut session, request));
+        assert_eq!(results[0].status_code, BAD_CONTINUATION_POINT_INVALID);
+
+        let request = make_history_read_request(details.clone(), &[(&history_node_id, &continuation_point2)], false);
+        let results = history_read_results(attribute.history_read(&mut server_state, &mut session, request));
+        assert_eq!(values(history_data_values(&results[0])), vec![9, 10]);
+        assert!(results[0].continuation_point.is_null());
+        assert!(session.history_continuation_points.is_empty());
+
+        // Releasing a continuation point
+        let request = make_history_read_request(details.clone(), &[(&history_node_id, &no_continuation_point)], false);
+        let results = history_read_results(attribute.history_read(&mut server_state, &mut session, request));
+        let continuation_point = results[0].continuation_point.clone();
+        let request = make_history_read_request(details.clone(), &[(&history_node_id, &continuation_point)], true);
+        let results = history_read_results(attribute.history_read(&mut server_state, &mut session, request));
+        assert_eq!(results[0].status_code, GOOD);
+        assert!(session.history_continuation_points.is_empty());
+
+        // Continuation points do not outlive the session
+        let session_service = SessionService::new();
+        let request = make_history_read_request(details.clone(), &[(&history_node_id, &no_continuation_point)], false);
+        let _ = attribute.history_read(&mut server_state, &mut session, request);
+        assert_eq!(session.history_continuation_points.len(), 1);
+        let endpoint_url = server_state.endpoints[0].endpoint_url.clone();
+        let _ = session_service.create_session(&mut server_state, &mut session, make_create_session_request(&endpoint_url, 5000f64)).unwrap();
+        assert!(session.history_continuation_points.is_empty());
+        let request = make_history_read_request(details.clone(), &[(&history_node_id, &no_continuation_point)], false);
+        let _ = attribute.history_read(&mut server_state, &mut session, request);
+        assert_eq!(session.history_continuation_points.len(), 1);
+        let request = CloseSessionRequest {
+            request_header: RequestHeader::new(&NodeId::null(), &DateTime::now(), 1),
+            delete_subscriptions: true,
+        };
+        let _ = session_service.close_session(&mut server_state, &mut session, request).unwrap();
+        assert!(session.history_continuation_points.is_empty());
+
+        // Read at times, between values the earlier value is used
+        let details = ReadAtTimeDetails {
+            req_times: Some(vec![DateTime::ymd_hms(2017, 1, 1, 0, 0, 0), DateTime::ymd_hms(2018, 1, 1, 0, 2, 0), DateTime::ymd_hms(2018, 1, 1, 0, 2, 30)]),
+            use_simple_bounds: true,
+        };
+        let details = ExtensionObject::from_encodable(ObjectId::ReadAtTimeDetails_Encoding_DefaultBinary.as_node_id(), details);
+        let request = make_history_read_request(details, &[(&history_node_id, &no_continuation_point)], false);
+        let results = history_read_results(attribute.history_read(&mut server_state, &mut session, request));
+        let data_values = history_data_values(&results[0]);
+        assert_eq!(data_values[0].status, Some(BAD_NO_DATA));
+        assert_eq!(data_values[1].value, Some(Variant::Int32(2)));
+        assert_eq!(data_values[2].value, Some(Variant::Int32(2)));
+        assert_eq!(data_values[2].source_timestamp, Some(DateTime::ymd_hms(2018, 1, 1, 0, 2, 30)));
+
+        // Values set on a historizing variable are recorded
+        {
+            let mut address_space = server_state.address_space.lock().unwrap();
+            let _ = address_space.set_value_by_node_id(&history_node_id, Variant::Int32(99));
+        }
+        let details = raw_details(DateTime::ymd_hms(2018, 1, 2, 0, 0, 0), DateTime::now(), 0);
+        let request = make_history_read_request(details, &[(&history_node_id, &no_continuation_point)], false);
+        let results = history_read_results(attribute.history_read(&mut server_state, &mut session, request));
+        assert_eq!(values(history_data_values(&results[0])), vec![99]);
+    }
+}
+
+
+#[test]
+fn history_update() {
+    let server = Server::new(ServerConfig::default_anonymous());
+    let tcp_session = TcpTransport::new(server.server_state);
+
+    let attribute = AttributeService::new();
+    {
+        let mut server_state = tcp_session.server_state.lock().unwrap();
+        let mut session = tcp_session.session.lock().unwrap();
+
+        let history_node_id = NodeId::new_string(1, "history");
+        let read_only_node_id = NodeId::new_string(1, "read_only");
+        {
+            let mut address_space = server_state.address_space.lock().unwrap();
+            let mut variable = Variable::new_i32(&history_node_id, "history", "history", "", 0);
+            variable.set_access_level(ACCESS_LEVEL_CURRENT_READ | ACCESS_LEVEL_HISTORY_READ | ACCESS_LEVEL_HISTORY_WRITE);
+            variable.set_user_access_level(USER_ACCESS_LEVEL_CURRENT_READ | USER_ACCESS_LEVEL_HISTORY_READ | USER_ACCESS_LEVEL_HISTORY_WRITE);
+            let _ = address_space.add_variable(variable, &AddressSpace::objects_folder_id());
+
+            let mut variable = Variable::new_i32(&read_only_node_id, "read_only", "read_only", "", 0);
+            variable.set_access_level(ACCESS_LEVEL_CURRENT_READ | ACCESS_LEVEL_HISTORY_READ);
+            variable.set_user_access_level(USER_ACCESS_LEVEL_CURRENT_READ | USER_ACCESS_LEVEL_HISTORY_READ);
+            let _ = address_space.add_variable(variable, &AddressSpace::objects_folder_id());
+        }
+
+        let make_value = |value: Int32, minute: UInt16| {
+            let mut data_value = DataValue::new(Variant::Int32(value));
+            data_value.source_timestamp = Some(DateTime::ymd_hms(2018, 1, 1, 0, minute, 0));
+            data_value
+        };
+        let update_data = |node_id: &NodeId, perform_insert_replace: PerformUpdateType, update_values: Vec<DataValue>| {
+            let details = UpdateDataDetails {
+                node_id: node_id.clone(),
+                perform_insert_replace,
+                update_values: Some(update_values),
+            };
+            ExtensionObject::from_encodable(ObjectId::UpdateDataDetails_Encoding_DefaultBinary.as_node_id(), details)
+        };
+        let history_update = |server_state: &mut ServerState, session: &mut Session, details: Vec<ExtensionObject>| {
+            let request = HistoryUpdateRequest {
+                request_header: RequestHeader::new(&NodeId::null(), &DateTime::now(), 1),
+                history_update_details: Some(details),
+            };
+            match attribute.history_update(server_state, session, request).unwrap() {
+                SupportedMessage::HistoryUpdateResponse(response) => response.results.unwrap(),
+                _ => panic!("Wrong response")
+            }
+        };
+        let history_values = |server_state: &mut ServerState, session: &mut Session| -> Vec<Int32> {
+            let details = ReadRawModifiedDetails {
+                is_read_modified: false,
+                start_time: DateTime::ymd_hms(2018, 1, 1, 0, 0, 0),
+                end_time: DateTime::ymd_hms(2018, 1, 2, 0, 0, 0),
+                num_values_per_node: 0,
+                return_bounds: false,
+            };
+            let details = ExtensionObject::from_encodable(ObjectId::ReadRawModifiedDetails_Encoding_DefaultBinary.as_node_id(), details);
+            let request = make_history_read_request(details, &[(&history_node_id, &ByteString::null())], false);
+            let results = history_read_results(attribute.history_read(server_state, session, request));
+            history_data_values(&results[0]).into_iter().map(|v| match v.value {
+                Some(Variant::Int32(v)) => v,
+                _ => panic!("Unexpected value")
+            }).collect()
+        };
+
+        // Insert values, one of them twice, and try the node without history write access
+        let results = history_update(&mut server_state, &mut session, vec![
+            update_data(&history_node_id, PerformUpdateType::Insert, vec![make_value(1, 1), make_value(2, 2), make_value(3, 3), make_value(4, 4)]),
+            update_data(&history_node_id, PerformUpdateType::Insert, vec![make_value(22, 2)]),
+            update_data(&read_only_node_id, PerformUpdateType::Insert, vec![make_value(1, 1)]),
+            update_data(&NodeId::new_string(1, "unknown"), PerformUpdateType::Insert, vec![make_value(1, 1)]),
+            update_data(&history_node_id, PerformUpdateType::Remove, vec![make_value(1, 1)]),
+        ]);
+        assert_eq!(results[0].status_code, GOOD);
+        assert_eq!(results[0].operation_results, Some(vec![GOOD_ENTRY_INSERTED; 4]));
+        assert_eq!(results[1].operation_results, Some(vec![BAD_ENTRY_EXISTS]));
+        assert_eq!(results[2].status_code, BAD_NOT_WRITABLE);
+        assert_eq!(results[3].status_code, BAD_NODE_ID_UNKNOWN);
+        assert_eq!(results[4].status_code, BAD_HISTORY_OPERATION_INVALID);
+        assert_eq!(history_values(&mut server_state, &mut session), vec![1, 2, 3, 4]);
+
+        // Replace and update
+        let results = history_update(&mut server_state, &mut session, vec![
+            update_data(&history_node_id, PerformUpdateType::Replace, vec![make_value(22, 2), make_value(5, 5)]),
+            update_data(&history_node_id, PerformUpdateType::Update, vec![make_value(33, 3), make_value(5, 5)]),
+        ]);
+        assert_eq!(results[0].operation_results, Some(vec![GOOD_ENTRY_REPLACED, BAD_NO_ENTRY_EXISTS]));
+        assert_eq!(results[1].operation_results, Some(vec![GOOD_ENTRY_REPLACED, GOOD_ENTRY_INSERTED]));
+        assert_eq!(history_values(&mut server_state, &mut session), vec![1, 22, 33, 4, 5]);
+
+        // Delete at times and a raw range
+        let delete_at_time = DeleteAtTimeDetails {
+            node_id: history_node_id.clone(),
+            req_times: Some(vec![DateTime::ymd_hms(2018, 1, 1, 0, 1, 0), DateTime::ymd_hms(2018, 1, 1, 0, 1, 30)]),
+        };
+        let delete_raw = |start_minute: UInt16, end_minute: UInt16| {
+            let details = DeleteRawModifiedDetails {
+                node_id: history_node_id.clone(),
+                is_delete_modified: false,
+                start_time: DateTime::ymd_hms(2018, 1, 1, 0, start_minute, 0),
+                end_time: DateTime::ymd_hms(2018, 1, 1, 0, end_minute, 0),
+            };
+            ExtensionObject::from_encodable(ObjectId::DeleteRawModifiedDetails_Encoding_DefaultBinary.as_node_id(), details)
+        };
+        let results = history_update(&mut server_state, &mut session, vec![
+            ExtensionObject::from_encodable(ObjectId::DeleteAtTimeDetails_Encoding_DefaultBinary.as_node_id(), delete_at_time),
+            delete_raw(4, 2),
+            delete_raw(30, 40),
+        ]);
+        assert_eq!(results[0].status_code, GOOD);
+        assert_eq!(results[0].operation_results, Some(vec![GOOD, BAD_NO_ENTRY_EXISTS]));
+        assert_eq!(results[1].status_code, GOOD);
+        assert_eq!(results[2].status_code, GOOD_NO_DATA);
+        assert_eq!(history_values(&mut server_state, &mut session), vec![5]);
+    }
+}
+
+/// A provider that only holds modified values
+struct ModifiedValuesProvider {
+    modified_values: Vec<DataValue>,
+}
+
+impl HistoricalDataProvider for ModifiedValuesProvider {
+    fn record_value(&mut self, _: &NodeId, _: &DataValue) {}
+
+    fn read_raw(&self, _: &NodeId, _: &DateTime, _: &DateTime, _: bool) -> Result<Vec<DataValue>, StatusCode> {
+        Ok(Vec::new())
+    }
+
+    fn read_at_time(&self, _: &NodeId, _: &[DateTime], _: bool) -> Result<Vec<DataValue>, StatusCode> {
+        Ok(Vec::new())
+    }
+
+    fn read_modified(&self, _: &NodeId, start_time: &DateTime, end_time: &DateTime) -> Result<Vec<DataValue>, StatusCode> {
+        Ok(self.modified_values.iter().filter(|data_value| {
+            let timestamp = history_timestamp(data_value);
+            timestamp >= start_time.checked_ticks() && timestamp <= end_time.checked_ticks()
+        }).cloned().collect())
+    }
+}
+
+#[test]
+fn history_read_modified() {
+    let server = Server::new(ServerConfig::default_anonymous());
+    let tcp_session = TcpTransport::new(server.server_state);
+
+    let attribute = AttributeService::new();
+    {
+        let mut server_state = tcp_session.server_state.lock().unwrap();
+        let mut session = tcp_session.session.lock().unwrap();
+
+        let history_node_id = NodeId::new_string(1, "history");
+        {
+            let mut address_space = server_state.address_space.lock().unwrap();
+            let mut variable = Variable::new_i32(&history_node_id, "history", "history", "", 0);
+            variable.set_access_level(ACCESS_LEVEL_CURRENT_READ | ACCESS_LEVEL_HISTORY_READ);
+            variable.set_user_access_level(USER_ACCESS_LEVEL_CURRENT_READ | USER_ACCESS_LEVEL_HISTORY_READ);
+            let _ = address_space.add_variable(variable, &AddressSpace::objects_folder_id());
+        }
+
+        let read_modified = |server_state: &mut ServerState, session: &mut Session, start_time: DateTime, end_time: DateTime| {
+            let details = ReadRawModifiedDetails {
+                is_read_modified: true,
+                start_time,
+                end_time,
+                num_values_per_node: 0,
+                return_bounds: false,
+            };
+            let details = ExtensionObject::from_encodable(ObjectId::ReadRawModifiedDetails_Encoding_DefaultBinary.as_node_id(), details);
+            let request = make_history_read_request(details, &[(&history_node_id, &ByteString::null())], false);
+            history_read_results(attribute.history_read(server_state, session, request)).remove(0)
+        };
+
+        // The default provider does not keep modified values
+        let result = read_modified(&mut server_state, &mut session, DateTime::ymd_hms(2018, 1, 1, 0, 0, 0), DateTime::ymd_hms(2018, 1, 2, 0, 0, 0));
+        assert_eq!(result.status_code, BAD_HISTORY_OPERATION_UNSUPPORTED);
+
+        // A provider that does returns them in the requested order
+        let modified_values = (1..6).map(|i| {
+            let mut data_value = DataValue::new(Variant::Int32(i));
+            data_value.source_timestamp = Some(DateTime::ymd_hms(2018, 1, 1, 0, i as UInt16, 0));
+            data_value
+        }).collect();
+        server_state.address_space.lock().unwrap().set_historical_data_provider(Arc::new(Mutex::new(ModifiedValuesProvider { modified_values })));
+        let values = |result: HistoryReadResult| -> Vec<Variant> {
+            history_data_values(&result).into_iter().map(|data_value| data_value.value.unwrap()).collect()
+        };
+        let result = read_modified(&mut server_state, &mut session, DateTime::ymd_hms(2018, 1, 1, 0, 2, 0), DateTime::ymd_hms(2018, 1, 1, 0, 4, 0));
+        assert_eq!(result.status_code, GOOD);
+        assert_eq!(values(result), vec![Variant::Int32(2), Variant::Int32(3), Variant::Int32(4)]);
+        let result = read_modified(&mut server_state, &mut session, DateTime::ymd_hms(2018, 1, 1, 0, 4, 0), DateTime::ymd_hms(2018, 1, 1, 0, 2, 0));
+        assert_eq!(values(result), vec![Variant::Int32(4), Variant::Int32(3), Variant::Int32(2)]);
+    }
+}
+
+
+#[test]
+fn history_read_processed() {
+    let server = Server::new(ServerConfig::default_anonymous());
+    let tcp_session = TcpTransport::new(server.server_state);
+
+    let attribute = AttributeService::new();
+    {
+        let mut server_state = tcp_session.server_state.lock().unwrap();
+        let mut session = tcp_session.session.lock().unwrap();
+
+        let history_node_id = NodeId::new_string(1, "history");
+        let no_continuation_point = ByteString::null();
+        {
+            let mut address_space = server_state.address_space.lock().unwrap();
+            let mut variable = Variable::new_i32(&history_node_id, "history", "history", "", 0);
+            variable.set_access_level(ACCESS_LEVEL_CURRENT_READ | ACCESS_LEVEL_HISTORY_READ);
+            variable.set_user_access_level(USER_ACCESS_LEVEL_CURRENT_READ | USER_ACCESS_LEVEL_HISTORY_READ);
+            let _ = address_space.add_variable(variable, &AddressSpace::objects_folder_id());
+
+            // Values 0, 10, 20... a minute apart, with a bad value at minute 6
+            let provider = address_space.historical_data_provider();
+            let mut provider = provider.lock().unwrap();
+            for i in 0..8 {
+                let mut data_value = DataValue::new(Variant::Int32(i * 10));
+                data_value.source_timestamp = Some(DateTime::ymd_hms(2018, 1, 1, 0, i as UInt16, 0));
+                if i == 6 {
+                    data_value.status = Some(BAD_NO_COMMUNICATION);
+                }
+                provider.record_value(&history_node_id, &data_value);
+            }
+        }
+
+        let processed_details = |start_minute: UInt16, end_minute: UInt16, aggregate_types: Vec<ObjectId>| {
+            let details = ReadProcessedDetails {
+                start_time: DateTime::ymd_hms(2018, 1, 1, 0, start_minute, 0),
+                end_time: DateTime::ymd_hms(2018, 1, 1, 0, end_minute, 0),
+                processing_interval: 120000.0,
+                aggregate_type: Some(aggregate_types.iter().map(|a| a.as_node_id()).collect()),
+                aggregate_configuration: AggregateConfiguration {
+                    use_server_capabilities_defaults: true,
+                    treat_uncertain_as_bad: false,
+                    percent_data_bad: 0,
+                    percent_data_good: 0,
+                    use_sloped_extrapolation: false,
+                },
+            };
+            ExtensionObject::from_encodable(ObjectId::ReadProcessedDetails_Encoding_DefaultBinary.as_node_id(), details)
+        };
+        let values = |result: &HistoryReadResult| -> Vec<Variant> {
+            history_data_values(result).into_iter().map(|v| {
+                assert_eq!(v.status, Some(GOOD));
+                v.value.unwrap()
+            }).collect()
+        };
+
+        // Two intervals of two minutes for each aggregate
+        let aggregate_types = vec![
+            ObjectId::AggregateFunction_Average,
+            ObjectId::AggregateFunction_Minimum,
+            ObjectId::AggregateFunction_Maximum,
+            ObjectId::AggregateFunction_Count,
+            ObjectId::AggregateFunction_Interpolative,
+            ObjectId::AggregateFunction_TimeAverage,
+            ObjectId::AggregateFunction_Total,
+            ObjectId::AggregateFunction_Range,
+        ];
+        let nodes: Vec<(&NodeId, &ByteString)> = aggregate_types.iter().map(|_| (&history_node_id, &no_continuation_point)).collect();
+        let request = make_history_read_request(processed_details(0, 4, aggregate_types), &nodes, false);
+        let results = history_read_results(attribute.history_read(&mut server_state, &mut session, request));
+        assert_eq!(values(&results[0]), vec![Variant::Double(5.0), Variant::Double(25.0)]);
+        assert_eq!(values(&results[1]), vec![Variant::Int32(0), Variant::Int32(20)]);
+        assert_eq!(values(&results[2]), vec![Variant::Int32(10), Variant::Int32(30)]);
+        assert_eq!(values(&results[3]), vec![Variant::Int32(2), Variant::Int32(2)]);
+        assert_eq!(values(&results[4]), vec![Variant::Double(0.0), Variant::Double(20.0)]);
+        assert_eq!(values(&results[5]), vec![Variant::Double(10.0), Variant::Double(30.0)]);
+        assert_eq!(values(&results[6]), vec![Variant::Double(1200.0), Variant::Double(3600.0)]);
+        assert_eq!(results[7].status_code, BAD_AGGREGATE_NOT_SUPPORTED);
+
+        // A bad value in the interval makes the result uncertain
+        let request = make_history_read_request(processed_details(6, 8, vec![ObjectId::AggregateFunction_Average]), &[(&history_node_id, &no_continuation_point)], false);
+        let results = history_read_results(attribute.history_read(&mut server_state, &mut session, request));
+        let data_values = history_data_values(&results[0]);
+        assert_eq!(data_values.len(), 1);
+        assert_eq!(data_values[0].value, Some(Variant::Double(70.0)));
+        assert_eq!(data_values[0].status, Some(UNCERTAIN_DATA_SUB_NORMAL));
+
+        // An aggregate is needed for each node
+        let request = make_history_read_request(processed_details(0, 4, vec![ObjectId::AggregateFunction_Average]), &[(&history_node_id, &no_continuation_point), (&history_node_id, &no_continuation_point)], false);
+        match attribute.history_read(&mut server_state, &mut session, request).unwrap() {
+            SupportedMessage::ServiceFault(fault) => assert_eq!(fault.response_header.service_result, BAD_AGGREGATE_LIST_MISMATCH),
+            _ => panic!("Wrong response")
+        }
+    }
+}
+
 
 // Discovery service tests
 
+fn send_find_servers(message_handler: &mut MessageHandler, server_uris: Option<Vec<UAString>>) -> Result<Vec<ApplicationDescription>, StatusCode> {
+    let request = FindServersRequest {
+        request_header: RequestHeader::new(&NodeId::null(), &DateTime::now(), 1),
+        endpoint_url: UAString::null(),
+        locale_ids: None,
+        server_uris,
+    };
+    match message_handler.handle_message(1, SupportedMessage::FindServersRequest(request)).unwrap() {
+        SupportedMessage::FindServersResponse(response) => Ok(response.servers.unwrap()),
+        SupportedMessage::ServiceFault(fault) => Err(fault.response_header.service_result),
+        _ => panic!("Wrong response")
+    }
+}
+
+fn send_find_servers_on_network(message_handler: &mut MessageHandler, starting_record_id: UInt32, max_records_to_return: UInt32, server_capability_filter: &[&str]) -> Result<Vec<ServerOnNetwork>, StatusCode> {
+    let request = FindServersOnNetworkRequest {
+        request_header: RequestHeader::new(&NodeId::null(), &DateTime::now(), 1),
+        starting_record_id,
+        max_records_to_return,
+        server_capability_filter: Some(server_capability_filter.iter().map(|capability| UAString::from_str(capability)).collect()),
+    };
+    match message_handler.handle_message(1, SupportedMessage::FindServersOnNetworkRequest(request)).unwrap() {
+        SupportedMessage::FindServersOnNetworkResponse(response) => Ok(response.servers.unwrap()),
+        SupportedMessage::ServiceFault(fault) => Err(fault.response_header.service_result),
+        _ => panic!("Wrong response")
+    }
+}
+
+fn make_registered_server(server_uri: &str, discovery_url: &str) -> RegisteredServer {
+    RegisteredServer {
+        server_uri: UAString::from_str(server_uri),
+        product_uri: UAString::null(),
+        server_names: Some(vec![LocalizedText::new("en", server_uri)]),
+        server_type: ApplicationType::Server,
+        gateway_server_uri: UAString::null(),
+        discovery_urls: Some(vec![UAString::from_str(discovery_url)]),
+        semaphore_file_path: UAString::null(),
+        is_online: true,
+    }
+}
+
+fn send_register_server(message_handler: &mut MessageHandler, server: RegisteredServer) -> StatusCode {
+    let request = RegisterServerRequest {
+        request_header: RequestHeader::new(&NodeId::null(), &DateTime::now(), 1),
+        server,
+    };
+    match message_handler.handle_message(1, SupportedMessage::RegisterServerRequest(request)).unwrap() {
+        SupportedMessage::RegisterServerResponse(response) => response.response_header.service_result,
+        SupportedMessage::ServiceFault(fault) => fault.response_header.service_result,
+        _ => panic!("Wrong response")
+    }
+}
+
+/// Makes a certificate that holds the application uri and, if a certificate store is supplied,
+/// puts it in the store's trusted folder
+fn make_application_cert(application_uri: &str, certificate_store: Option<&Arc<Mutex<CertificateStore>>>) -> X509 {
+    let mut args = X509Data::sample_cert();
+    args.application_uri = application_uri.to_string();
+    let (cert, _) = CertificateStore::create_cert_and_pkey(&args).unwrap();
+    if let Some(certificate_store) = certificate_store {
+        let mut path = certificate_store.lock().unwrap().trusted_certs_dir();
+        path.push(CertificateStore::cert_file_name(&cert));
+        std::fs::write(&path, cert.as_byte_string().value.unwrap()).unwrap();
+    }
+    cert
+}
+
+/// Makes the message handler act as if its secure channel was signed with the certificate
+fn sign_secure_channel(message_handler: &mut MessageHandler, cert: &X509) {
+    let mut secure_channel = SecureChannel::new_no_certificate_store();
+    secure_channel.security_policy = SecurityPolicy::Basic256Sha256;
+    secure_channel.security_mode = MessageSecurityMode::Sign;
+    secure_channel.their_cert = Some(cert.clone());
+    message_handler.set_secure_channel(&secure_channel);
+}
+
+#[test]
+fn find_servers() {
+    let server = Server::new(ServerConfig::default_anonymous());
+    let server_state = server.server_state;
+    let endpoint_url = server_state.lock().unwrap().endpoints[0].endpoint_url.clone();
+    let tcp_session = TcpTransport::new(server_state.clone());
+    let mut message_handler = MessageHandler::new(server_state.clone(), tcp_session.session.clone());
+
+    // The server describes itself without a session
+    let servers = send_find_servers(&mut message_handler, None).unwrap();
+    assert_eq!(servers.len(), 1);
+    assert_eq!(servers[0].application_uri, UAString::from_str("urn:OPCUA-Rust"));
+    assert_eq!(servers[0].application_type, ApplicationType::Server);
+    assert_eq!(servers[0].discovery_urls, Some(vec![UAString::from_str(&endpoint_url)]));
+
+    // The servers can be limited by uri
+    assert_eq!(send_find_servers(&mut message_handler, Some(vec![UAString::from_str("urn:OPCUA-Rust")])).unwrap().len(), 1);
+    assert!(send_find_servers(&mut message_handler, Some(vec![UAString::from_str("urn:other")])).unwrap().is_empty());
+
+    // Only a local discovery server accepts registrations and answers FindServersOnNetwork
+    assert_eq!(send_register_server(&mut message_handler, make_registered_server("urn:a", "opc.tcp://127.0.0.1:4855/")), BAD_SERVICE_UNSUPPORTED);
+    assert_eq!(send_find_servers_on_network(&mut message_handler, 0, 0, &[]).unwrap_err(), BAD_SERVICE_UNSUPPORTED);
+
+    // The discovery service can be turned off
+    server_state.lock().unwrap().discovery_service = false;
+    assert_eq!(send_find_servers(&mut message_handler, None).unwrap_err(), BAD_SERVICE_UNSUPPORTED);
+}
+
+#[test]
+fn local_discovery_server() {
+    let mut config = ServerConfig::default_local_discovery_server();
+    config.pki_dir = make_test_pki_dir("local-discovery-server-pki");
+    let server = Server::new(config);
+    let server_state = server.server_state;
+    let certificate_store = server_state.lock().unwrap().certificate_store.clone();
+    let tcp_session = TcpTransport::new(server_state.clone());
+    let mut message_handler = MessageHandler::new(server_state.clone(), tcp_session.session.clone());
+
+    // Registrations must arrive on a secure channel signed with a trusted certificate that holds
+    // the uri of the registering server
+    let mut server_a = make_registered_server("urn:a", "opc.tcp://127.0.0.1:4855/");
+    assert_eq!(send_register_server(&mut message_handler, server_a.clone()), BAD_SECURITY_CHECKS_FAILED);
+    sign_secure_channel(&mut message_handler, &make_application_cert("urn:a", None));
+    assert_eq!(send_register_server(&mut message_handler, server_a.clone()), BAD_CERTIFICATE_UNTRUSTED);
+    sign_secure_channel(&mut message_handler, &make_application_cert("urn:other", Some(&certificate_store)));
+    assert_eq!(send_register_server(&mut message_handler, server_a.clone()), BAD_CERTIFICATE_URI_INVALID);
+    let cert_a = make_application_cert("urn:a", Some(&certificate_store));
+    sign_secure_channel(&mut message_handler, &cert_a);
+
+    // Registrations must be complete
+    server_a.discovery_urls = None;
+    assert_eq!(send_register_server(&mut message_handler, server_a.clone()), BAD_DISCOVERY_URL_MISSING);
+    server_a.discovery_urls = Some(vec![UAString::from_str("opc.tcp://127.0.0.1:4855/")]);
+    server_a.server_names = None;
+    assert_eq!(send_register_server(&mut message_handler, server_a.clone()), BAD_SERVER_NAME_MISSING);
+    server_a.server_names = Some(vec![LocalizedText::new("en", "Server A")]);
+    server_a.semaphore_file_path = UAString::from_str("/no/such/semaphore");
+    assert_eq!(send_register_server(&mut message_handler, server_a.clone()), BAD_SEMPAHORE_FILE_MISSING);
+
+    // Server A registers with a semaphore file
+    let semaphore_file = make_test_file("lds_semaphore");
+    std::fs::File::create(&semaphore_file).unwrap();
+    server_a.semaphore_file_path = UAString::from_str(semaphore_file.to_str().unwrap());
+    assert_eq!(send_register_server(&mut message_handler, server_a.clone()), GOOD);
+
+    // Server B registers with an mDNS configuration
+    let cert_b = make_application_cert("urn:b", Some(&certificate_store));
+    sign_secure_channel(&mut message_handler, &cert_b);
+    let mdns_configuration = MdnsDiscoveryConfiguration {
+        mdns_server_name: UAString::from_str("Server B"),
+        server_capabilities: Some(vec![UAString::from_str("DA")]),
+    };
+    let request = RegisterServer2Request {
+        request_header: RequestHeader::new(&NodeId::null(), &DateTime::now(), 1),
+        server: make_registered_server("urn:b", "opc.tcp://127.0.0.1:4856/"),
+        discovery_configuration: Some(vec![
+            ExtensionObject::from_encodable(ObjectId::MdnsDiscoveryConfiguration_Encoding_DefaultBinary.as_node_id(), mdns_configuration),
+            ExtensionObject::from_encodable(ObjectId::RegisteredServer_Encoding_DefaultBinary.as_node_id(), make_registered_server("urn:c", "opc.tcp://127.0.0.1:4857/")),
+        ]),
+    };
+    match message_handler.handle_message(1, SupportedMessage::RegisterServer2Request(request)).unwrap() {
+        SupportedMessage::RegisterServer2Response(response) => assert_eq!(response.configuration_results, Some(vec![GOOD, BAD_NOT_SUPPORTED])),
+        _ => panic!("Wrong response")
+    }
+
+    // FindServers answers for the discovery server and the registered servers
+    let servers = send_find_servers(&mut message_handler, None).unwrap();
+    let server_uris: Vec<&str> = servers.iter().map(|server| server.application_uri.as_ref()).collect();
+    assert_eq!(server_uris, vec!["urn:OPCUA-Rust-LDS", "urn:a", "urn:b"]);
+    assert_eq!(servers[0].application_type, ApplicationType::DiscoveryServer);
+    assert_eq!(servers[1].application_name, LocalizedText::new("en", "Server A"));
+    assert_eq!(servers[2].discovery_urls, Some(vec![UAString::from_str("opc.tcp://127.0.0.1:4856/")]));
+
+    // FindServersOnNetwork returns records that can be paged and filtered by capability
+    let servers = send_find_servers_on_network(&mut message_handler, 0, 0, &[]).unwrap();
+    let server_names: Vec<&str> = servers.iter().map(|server| server.server_name.as_ref()).collect();
+    assert_eq!(server_names, vec!["OPCUA-Rust-LDS", "Server A", "Server B"]);
+    assert_eq!(servers[0].server_capabilities, Some(vec![UAString::from_str("LDS")]));
+    let record_ids: Vec<UInt32> = servers.iter().map(|server| server.record_id).collect();
+    assert_eq!(send_find_servers_on_network(&mut message_handler, record_ids[1], 1, &[]).unwrap()[0].server_name.as_ref(), "Server A");
+    let servers = send_find_servers_on_network(&mut message_handler, 0, 0, &["DA"]).unwrap();
+    assert_eq!(servers.len(), 1);
+    assert_eq!(servers[0].discovery_url, UAString::from_str("opc.tcp://127.0.0.1:4856/"));
+
+    // A server that goes offline is removed
+    let mut server_b = make_registered_server("urn:b", "opc.tcp://127.0.0.1:4856/");
+    server_b.is_online = false;
+    assert_eq!(send_register_server(&mut message_handler, server_b), GOOD);
+    assert_eq!(send_find_servers(&mut message_handler, None).unwrap().len(), 2);
+
+    // A registration is removed when its semaphore file goes or it is not renewed in time
+    let now = chrono::UTC::now();
+    {
+        let mut server_state = server_state.lock().unwrap();
+        assert_eq!(server_state.registered_servers.as_mut().unwrap().expire(&now), 0);
+        std::fs::remove_file(&semaphore_file).unwrap();
+        assert_eq!(server_state.registered_servers.as_mut().unwrap().expire(&now), 1);
+    }
+    sign_secure_channel(&mut message_handler, &make_application_cert("urn:d", Some(&certificate_store)));
+    assert_eq!(send_register_server(&mut message_handler, make_registered_server("urn:d", "opc.tcp://127.0.0.1:4858/")), GOOD);
+    {
+        let mut server_state = server_state.lock().unwrap();
+        let registered_servers = server_state.registered_servers.as_mut().unwrap();
+        let now = chrono::UTC::now();
+        assert_eq!(registered_servers.expire(&(now + time::Duration::milliseconds(constants::REGISTERED_SERVER_TIMEOUT_MS - 1000))), 0);
+        assert_eq!(registered_servers.expire(&(now + time::Duration::milliseconds(constants::REGISTERED_SERVER_TIMEOUT_MS + 1000))), 1);
+    }
+    assert_eq!(send_find_servers(&mut message_handler, None).unwrap().len(), 1);
+
+    // The number of registrations is limited but registrations can still be renewed
+    let mut registered_servers = RegisteredServers::new(constants::REGISTERED_SERVER_TIMEOUT_MS, 1);
+    let now = chrono::UTC::now();
+    assert_eq!(registered_servers.register(&make_registered_server("urn:a", "opc.tcp://127.0.0.1:4855/"), None, &now), GOOD);
+    assert_eq!(registered_servers.register(&make_registered_server("urn:b", "opc.tcp://127.0.0.1:4856/"), None, &now), BAD_TOO_MANY_OPERATIONS);
+    assert_eq!(registered_servers.register(&make_registered_server("urn:a", "opc.tcp://127.0.0.1:4855/"), None, &now), GOOD);
+}
+
+
+#[test]
+fn register_with_discovery_servers() {
+    // Run a local discovery server on a port of its own. The discovery server and the server each
+    // have a certificate of their own so the server can register over a signed channel.
+    let mut config = ServerConfig::default_local_discovery_server();
+    config.tcp_config.port = 4861;
+    config.pki_dir = make_test_pki_dir("lds-pki");
+    let mut lds = Server::new(config);
+    let lds_state = lds.server_state.clone();
+    std::thread::spawn(move || lds.run());
+
+    let mut config = ServerConfig::default_anonymous();
+    config.tcp_config.port = 4862;
+    config.pki_dir = make_test_pki_dir("server-pki");
+    config.create_sample_keypair = true;
+    config.discovery_server_urls = vec!["opc.tcp://127.0.0.1:4861/".to_string()];
+    let mut server = Server::new(config);
+
+    // The discovery server trusts the server's certificate
+    {
+        let server_certificate = server.server_state.lock().unwrap().server_certificate.clone().unwrap();
+        assert_eq!(server_certificate.application_uri(), Some("urn:OPCUA-Rust".to_string()));
+        let lds_state = lds_state.lock().unwrap();
+        let mut path = lds_state.certificate_store.lock().unwrap().trusted_certs_dir();
+        path.push(CertificateStore::cert_file_name(&server_certificate));
+        std::fs::write(&path, server_certificate.as_byte_string().value.unwrap()).unwrap();
+    }
+    let registered_servers = || {
+        let lds_state = lds_state.lock().unwrap();
+        let registered_servers = lds_state.registered_servers.as_ref().unwrap();
+        registered_servers.registrations().iter().map(|registration| registration.server.clone()).collect::<Vec<RegisteredServer>>()
+    };
+
+    // The server registers through the client stack once the discovery server is listening
+    let (registered_server, certificate_store) = {
+        let server_state = server.server_state.lock().unwrap();
+        (server_state.registered_server(true), server_state.certificate_store.clone())
+    };
+    assert_eq!(registered_server.server_uri, UAString::from_str("urn:OPCUA-Rust"));
+    assert_eq!(registered_server.discovery_urls, Some(vec![UAString::from_str("opc.tcp://127.0.0.1:4862/")]));
+    let mut result = Err(BAD_NOT_CONNECTED);
+    for _ in 0..50 {
+        result = registration::register_with_discovery_server(certificate_store.clone(), "opc.tcp://127.0.0.1:4861/", &registered_server);
+        if result.is_ok() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    assert_eq!(result, Ok(()));
+    assert_eq!(registered_servers(), vec![registered_server]);
+
+    // Aborting the server unregisters it
+    server.abort();
+    assert!(registered_servers().is_empty());
+}
+
+#[test]
+fn multicast_discovery() {
+    use std::net::{Ipv4Addr, SocketAddrV4};
+    use opcua_client::client::Client;
+
+    // The responder listens on a port of its own so it does not need the mDNS port
+    let multicast_address = SocketAddrV4::new(Ipv4Addr::new(224, 0, 0, 251), 15353);
+    let mut config = ServerConfig::default_anonymous();
+    config.tcp_config.port = 4863;
+    let server = Server::new(config);
+    let announcements = server.server_state.lock().unwrap().service_announcements();
+    assert_eq!(announcements.len(), 1);
+    assert_eq!(announcements[0].endpoint_url(), "opc.tcp://127.0.0.1:4863/");
+
+    let mdns_responder = MdnsResponder::new_at(multicast_address, announcements).unwrap();
+    let servers = Client::discover_servers_on_network_at(&multicast_address, 1000).unwrap();
+    assert_eq!(servers, vec![ServerOnNetwork {
+        record_id: 0,
+        server_name: UAString::from_str("OPCUA-Rust"),
+        discovery_url: UAString::from_str("opc.tcp://127.0.0.1:4863/"),
+        server_capabilities: Some(vec![UAString::from_str("NA")]),
+    }]);
+
+    // Nothing is found once the responder has gone
+    drop(mdns_responder);
+    std::thread::sleep(std::time::Duration::from_millis(constants::MDNS_RECEIVE_TIMEOUT_MS + 200));
+    assert!(Client::discover_servers_on_network_at(&multicast_address, 500).unwrap().is_empty());
+}
 
 // Monitored item service tests
 
 
 // Session service tests
 
+fn make_create_session_request(endpoint_url: &str, requested_session_timeout: Double) -> CreateSessionRequest {
+    CreateSessionRequest {
+        request_header: RequestHeader::new(&NodeId::null(), &DateTime::now(), 1),
+        client_description: ApplicationDescription {
+            application_uri: UAString::from_str("urn:test"),
+            product_uri: UAString::null(),
+            application_name: LocalizedText::new("", "test"),
+            application_type: ApplicationType::Client,
+            gateway_server_uri: UAString::null(),
+            discovery_profile_uri: UAString::null(),
+            discovery_urls: None,
+        },
+        server_uri: UAString::null(),
+        endpoint_url: UAString::from_str(endpoint_url),
+        session_name: UAString::from_str("test"),
+        client_nonce: ByteString::null(),
+        client_certificate: ByteString::null(),
+        requested_session_timeout,
+        max_response_message_size: 0,
+    }
+}
+
+#[test]
+fn create_session_limits_and_diagnostics() {
+    let mut config = ServerConfig::default_anonymous();
+    config.max_sessions = 2;
+    let server = Server::new(config);
+    let server_state = server.server_state;
+    let tcp_sessions = [TcpTransport::new(server_state.clone()), TcpTransport::new(server_state.clone()), TcpTransport::new(server_state.clone())];
+
+    let session_service = SessionService::new();
+    let endpoint_url = server_state.lock().unwrap().endpoints[0].endpoint_url.clone();
+
+    let create_session = |tcp_session: &TcpTransport, requested_session_timeout: Double| {
+        let mut server_state = server_state.lock().unwrap();
+        let mut session = tcp_session.session.lock().unwrap();
+        session_service.create_session(&mut server_state, &mut session, make_create_session_request(&endpoint_url, requested_session_timeout)).unwrap()
+    };
+    let revised_session_timeout = |response: SupportedMessage| {
+        match response {
+            SupportedMessage::CreateSessionResponse(response) => response.revised_session_timeout,
+            _ => panic!("Wrong response")
+        }
+    };
+
+    // The requested timeout is revised to fit the server's limits
+    assert_eq!(revised_session_timeout(create_session(&tcp_sessions[0], 0f64)), constants::SESSION_TIMEOUT);
+    assert_eq!(revised_session_timeout(create_session(&tcp_sessions[1], 1f64)), constants::MIN_SESSION_TIMEOUT);
+
+    // A third session is too many
+    match create_session(&tcp_sessions[2], 5000f64) {
+        SupportedMessage::ServiceFault(fault) => assert_eq!(fault.response_header.service_result, BAD_TOO_MANY_SESSIONS),
+        _ => panic!("Wrong response")
+    }
+
+    // Closing a session makes room for another
+    {
+        let mut server_state = server_state.lock().unwrap();
+        let mut session = tcp_sessions[0].session.lock().unwrap();
+        let request = CloseSessionRequest {
+            request_header: RequestHeader::new(&NodeId::null(), &DateTime::now(), 1),
+            delete_subscriptions: true,
+        };
+        let _ = session_service.close_session(&mut server_state, &mut session, request).unwrap();
+        assert!(!session.is_created());
+    }
+    assert_eq!(revised_session_timeout(create_session(&tcp_sessions[2], 5000f64)), 5000f64);
+
+    // A session times out when no request has been made within its timeout
+    {
+        let session = tcp_sessions[2].session.lock().unwrap();
+        let now = session.last_service_request_time.clone();
+        assert!(!session.is_timed_out(&(now + time::Duration::milliseconds(5000))));
+        assert!(session.is_timed_out(&(now + time::Duration::milliseconds(5001))));
+    }
+
+    // The counts are visible in the address space
+    let server_state = server_state.lock().unwrap();
+    let mut address_space = server_state.address_space.lock().unwrap();
+    let mut count = |variable_id: VariableId| {
+        address_space.find_variable_by_variable_id(variable_id).unwrap().value().value.unwrap()
+    };
+    assert_eq!(count(VariableId::Server_ServerDiagnostics_ServerDiagnosticsSummary_CurrentSessionCount), Variant::UInt32(2));
+    assert_eq!(count(VariableId::Server_ServerDiagnostics_ServerDiagnosticsSummary_CumulatedSessionCount), Variant::UInt32(3));
+    assert_eq!(count(VariableId::Server_ServerDiagnostics_ServerDiagnosticsSummary_RejectedSessionCount), Variant::UInt32(1));
+    assert_eq!(count(VariableId::Server_ServerDiagnostics_ServerDiagnosticsSummary_SecurityRejectedSessionCount), Variant::UInt32(0));
+    assert_eq!(count(VariableId::Server_ServerDiagnostics_ServerDiagnosticsSummary_SessionTimeoutCount), Variant::UInt32(0));
+    assert_eq!(count(VariableId::Server_ServerDiagnostics_EnabledFlag), Variant::Boolean(true));
+}
+
+
+fn make_activate_session_request(authentication_token: &NodeId, request_handle: UInt32, user_identity_token: ExtensionObject) -> ActivateSessionRequest {
+    ActivateSessionRequest {
+        request_header: RequestHeader::new(authentication_token, &DateTime::now(), request_handle),
+        client_signature: SignatureData::null(),
+        client_software_certificates: None,
+        locale_ids: None,
+        user_identity_token,
+        user_token_signature: SignatureData::null(),
+    }
+}
+
+#[test]
+fn reactivate_session_on_new_connection() {
+    let server = Server::new(ServerConfig::default_anonymous());
+    let server_state = server.server_state;
+    let endpoint_url = server_state.lock().unwrap().endpoints[0].endpoint_url.clone();
+
+    // Create a session with a subscription on one connection
+    let tcp_session_1 = TcpTransport::new(server_state.clone());
+    let mut message_handler_1 = MessageHandler::new(server_state.clone(), tcp_session_1.session.clone());
+    let authentication_token = match message_handler_1.handle_message(1, SupportedMessage::CreateSessionRequest(make_create_session_request(&endpoint_url, 5000f64))).unwrap() {
+        SupportedMessage::CreateSessionResponse(response) => response.authentication_token,
+        _ => panic!("Wrong response")
+    };
+    let request = CreateSubscriptionRequest {
+        request_header: RequestHeader::new(&authentication_token, &DateTime::now(), 2),
+        requested_publishing_interval: 1000f64,
+        requested_lifetime_count: 100,
+        requested_max_keep_alive_count: 10,
+        max_notifications_per_publish: 0,
+        publishing_enabled: true,
+        priority: 0,
+    };
+    match message_handler_1.handle_message(2, SupportedMessage::CreateSubscriptionRequest(request)).unwrap() {
+        SupportedMessage::CreateSubscriptionResponse(_) => {}
+        _ => panic!("Wrong response")
+    }
+    let request = make_activate_session_request(&authentication_token, 2, ExtensionObject::null());
+    match message_handler_1.handle_message(2, SupportedMessage::ActivateSessionRequest(request)).unwrap() {
+        SupportedMessage::ActivateSessionResponse(_) => {}
+        _ => panic!("Wrong response")
+    }
+    let session_id = tcp_session_1.session.lock().unwrap().session_id.clone();
+
+    // A live session cannot be taken over from another connection
+    let tcp_session_2 = TcpTransport::new(server_state.clone());
+    let mut message_handler_2 = MessageHandler::new(server_state.clone(), tcp_session_2.session.clone());
+    let request = make_activate_session_request(&authentication_token, 3, ExtensionObject::null());
+    match message_handler_2.handle_message(3, SupportedMessage::ActivateSessionRequest(request)).unwrap() {
+        SupportedMessage::ServiceFault(fault) => assert_eq!(fault.response_header.service_result, BAD_IDENTITY_TOKEN_REJECTED),
+        _ => panic!("Wrong response")
+    }
+    assert!(!tcp_session_2.session.lock().unwrap().is_created());
+    assert_eq!(tcp_session_1.session.lock().unwrap().session_id, session_id);
+
+    // The connection drops
+    tcp_session_1.session.lock().unwrap().detached = true;
+
+    // A failed activation leaves the detached session where it was
+    let user_identity_token = ExtensionObject::from_encodable(ObjectId::X509IdentityToken_Encoding_DefaultBinary.as_node_id(), X509IdentityToken {
+        policy_id: UAString::null(),
+        certificate_data: ByteString::null(),
+    });
+    let request = make_activate_session_request(&authentication_token, 3, user_identity_token);
+    match message_handler_2.handle_message(3, SupportedMessage::ActivateSessionRequest(request)).unwrap() {
+        SupportedMessage::ServiceFault(fault) => assert_eq!(fault.response_header.service_result, BAD_IDENTITY_TOKEN_REJECTED),
+        _ => panic!("Wrong response")
+    }
+    assert!(!tcp_session_2.session.lock().unwrap().is_created());
+    {
+        let session = tcp_session_1.session.lock().unwrap();
+        assert_eq!(session.session_id, session_id);
+        assert!(session.detached);
+    }
+
+    // The session cannot be activated as another user than it was activated as
+    let user_identity = tcp_session_1.session.lock().unwrap().user_identity.clone();
+    tcp_session_1.session.lock().unwrap().user_identity = Some(UserIdentity {
+        policy_id: UAString::from_str("user"),
+        user_name: Some("sample".to_string()),
+    });
+    let request = make_activate_session_request(&authentication_token, 3, ExtensionObject::null());
+    match message_handler_2.handle_message(3, SupportedMessage::ActivateSessionRequest(request)).unwrap() {
+        SupportedMessage::ServiceFault(fault) => assert_eq!(fault.response_header.service_result, BAD_IDENTITY_TOKEN_REJECTED),
+        _ => panic!("Wrong response")
+    }
+    assert!(tcp_session_1.session.lock().unwrap().detached);
+    tcp_session_1.session.lock().unwrap().user_identity = user_identity;
+
+    // A secure session cannot be activated on a channel that is not signed by its client
+    let security_policy_uri = {
+        let mut session = tcp_session_1.session.lock().unwrap();
+        let security_policy_uri = session.security_policy_uri.clone();
+        session.security_policy_uri = SecurityPolicy::Basic128Rsa15.to_uri().to_string();
+        session.client_certificate = ByteString::from_bytes(&[1, 2, 3]);
+        security_policy_uri
+    };
+    let request = make_activate_session_request(&authentication_token, 3, ExtensionObject::null());
+    match message_handler_2.handle_message(3, SupportedMessage::ActivateSessionRequest(request)).unwrap() {
+        SupportedMessage::ServiceFault(fault) => assert_eq!(fault.response_header.service_result, BAD_SECURITY_CHECKS_FAILED),
+        _ => panic!("Wrong response")
+    }
+    {
+        let mut session = tcp_session_1.session.lock().unwrap();
+        assert!(session.detached);
+        session.security_policy_uri = security_policy_uri;
+        session.client_certificate = ByteString::null();
+    }
+
+    // The session is activated on a new connection and keeps its subscription
+    let request = make_activate_session_request(&authentication_token, 3, ExtensionObject::null());
+    match message_handler_2.handle_message(3, SupportedMessage::ActivateSessionRequest(request)).unwrap() {
+        SupportedMessage::ActivateSessionResponse(_) => {}
+        _ => panic!("Wrong response")
+    }
+    {
+        let session = tcp_session_2.session.lock().unwrap();
+        assert_eq!(session.session_id, session_id);
+        assert!(!session.detached);
+        assert_eq!(server_state.lock().unwrap().subscriptions.len(&session_id), 1);
+    }
+    assert!(!tcp_session_1.session.lock().unwrap().is_created());
+    assert_eq!(server_state.lock().unwrap().session_manager.len(), 1);
+
+    // An unknown token is still rejected on a new connection
+    let tcp_session_3 = TcpTransport::new(server_state.clone());
+    let mut message_handler_3 = MessageHandler::new(server_state.clone(), tcp_session_3.session.clone());
+    let request = make_activate_session_request(&NodeId::new_numeric(0, 999), 4, ExtensionObject::null());
+    match message_handler_3.handle_message(4, SupportedMessage::ActivateSessionRequest(request)).unwrap() {
+        SupportedMessage::ServiceFault(fault) => assert_eq!(fault.response_header.service_result, BAD_IDENTITY_TOKEN_REJECTED),
+        _ => panic!("Wrong response")
+    }
+
+    // A detached session that is not reactivated in time expires
+    let now = {
+        let mut session = tcp_session_2.session.lock().unwrap();
+        session.detached = true;
+        session.last_service_request_time.clone()
+    };
+    let mut server_state = server_state.lock().unwrap();
+    server_state.expire_detached_sessions(&(now + time::Duration::milliseconds(5000)));
+    assert_eq!(server_state.session_manager.len(), 1);
+    server_state.expire_detached_sessions(&(now + time::Duration::milliseconds(5001)));
+    assert_eq!(server_state.session_manager.len(), 0);
+    let summary = server_state.diagnostics.lock().unwrap().summary();
+    assert_eq!(summary.current_session_count, 0);
+    assert_eq!(summary.session_timeout_count, 1);
+}
+
+#[test]
+fn cancel() {
+    let server = Server::new(ServerConfig::default_anonymous());
+    let server_state = server.server_state;
+    let endpoint_url = server_state.lock().unwrap().endpoints[0].endpoint_url.clone();
+
+    let tcp_session = TcpTransport::new(server_state.clone());
+    let mut message_handler = MessageHandler::new(server_state.clone(), tcp_session.session.clone());
+    let authentication_token = match message_handler.handle_message(1, SupportedMessage::CreateSessionRequest(make_create_session_request(&endpoint_url, 5000f64))).unwrap() {
+        SupportedMessage::CreateSessionResponse(response) => response.authentication_token,
+        _ => panic!("Wrong response")
+    };
+
+    // A publish request waits in the queue because there is no subscription to answer it
+    let request = PublishRequest {
+        request_header: RequestHeader::new(&authentication_token, &DateTime::now(), 2),
+        subscription_acknowledgements: None,
+    };
+    match message_handler.handle_message(2, SupportedMessage::PublishRequest(request)).unwrap() {
+        SupportedMessage::DoNothing => {}
+        _ => panic!("Wrong response")
+    }
+
+    let mut cancel = |request_handle: UInt32| {
+        let request = CancelRequest {
+            request_header: RequestHeader::new(&authentication_token, &DateTime::now(), 3),
+            request_handle,
+        };
+        match message_handler.handle_message(3, SupportedMessage::CancelRequest(request)).unwrap() {
+            SupportedMessage::CancelResponse(response) => response.cancel_count,
+            _ => panic!("Wrong response")
+        }
+    };
+
+    // Cancelling an unknown request does nothing
+    assert_eq!(cancel(99), 0);
+    assert_eq!(tcp_session.session.lock().unwrap().publish_requests.queue.len(), 1);
+
+    // Cancelling the publish request completes it with a fault
+    assert_eq!(cancel(2), 1);
+    let mut session = tcp_session.session.lock().unwrap();
+    assert!(session.publish_requests.queue.is_empty());
+    let publish_responses = session.take_cancelled_publish_responses().unwrap();
+    assert_eq!(publish_responses.len(), 1);
+    assert_eq!(publish_responses[0].request_id, 2);
+    match publish_responses[0].response {
+        SupportedMessage::ServiceFault(ref fault) => {
+            assert_eq!(fault.response_header.request_handle, 2);
+            assert_eq!(fault.response_header.service_result, BAD_REQUEST_CANCELLED_BY_CLIENT);
+        }
+        _ => panic!("Wrong response")
+    }
+    assert!(session.take_cancelled_publish_responses().is_none());
+}
 
 // Subscription service tests
 
+#[test]
+fn transfer_subscriptions() {
+    let server = Server::new(ServerConfig::default_anonymous());
+    let server_state = server.server_state;
+    let endpoint_url = server_state.lock().unwrap().endpoints[0].endpoint_url.clone();
+
+    let create_session = |tcp_session: &TcpTransport| {
+        let mut message_handler = MessageHandler::new(server_state.clone(), tcp_session.session.clone());
+        let (session_id, authentication_token) = match message_handler.handle_message(1, SupportedMessage::CreateSessionRequest(make_create_session_request(&endpoint_url, 5000f64))).unwrap() {
+            SupportedMessage::CreateSessionResponse(response) => (response.session_id, response.authentication_token),
+            _ => panic!("Wrong response")
+        };
+        (message_handler, session_id, authentication_token)
+    };
+
+    let transfer = |message_handler: &mut MessageHandler, authentication_token: &NodeId, subscription_ids: Vec<UInt32>| {
+        let request = TransferSubscriptionsRequest {
+            request_header: RequestHeader::new(authentication_token, &DateTime::now(), 3),
+            subscription_ids: Some(subscription_ids),
+            send_initial_values: true,
+        };
+        match message_handler.handle_message(3, SupportedMessage::TransferSubscriptionsRequest(request)).unwrap() {
+            SupportedMessage::TransferSubscriptionsResponse(response) => response.results.unwrap(),
+            _ => panic!("Wrong response")
+        }
+    };
+
+    // A session with a subscription
+    let tcp_session_1 = TcpTransport::new(server_state.clone());
+    let (mut message_handler_1, session_id_1, authentication_token_1) = create_session(&tcp_session_1);
+    let request = CreateSubscriptionRequest {
+        request_header: RequestHeader::new(&authentication_token_1, &DateTime::now(), 2),
+        requested_publishing_interval: 1000f64,
+        requested_lifetime_count: 100,
+        requested_max_keep_alive_count: 10,
+        max_notifications_per_publish: 0,
+        publishing_enabled: true,
+        priority: 0,
+    };
+    let subscription_id = match message_handler_1.handle_message(2, SupportedMessage::CreateSubscriptionRequest(request)).unwrap() {
+        SupportedMessage::CreateSubscriptionResponse(response) => response.subscription_id,
+        _ => panic!("Wrong response")
+    };
+
+    // Transfer it to another session
+    let tcp_session_2 = TcpTransport::new(server_state.clone());
+    let (mut message_handler_2, session_id_2, authentication_token_2) = create_session(&tcp_session_2);
+    let results = transfer(&mut message_handler_2, &authentication_token_2, vec![subscription_id, subscription_id + 100]);
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].status_code, GOOD);
+    assert_eq!(results[1].status_code, BAD_SUBSCRIPTION_ID_INVALID);
+    {
+        let server_state = server_state.lock().unwrap();
+        assert!(!server_state.subscriptions.contains(&session_id_1, subscription_id));
+        assert!(server_state.subscriptions.contains(&session_id_2, subscription_id));
+    }
+
+    // The first session is told of the transfer by its next publish response
+    let request = PublishRequest {
+        request_header: RequestHeader::new(&authentication_token_1, &DateTime::now(), 4),
+        subscription_acknowledgements: None,
+    };
+    match message_handler_1.handle_message(4, SupportedMessage::PublishRequest(request)).unwrap() {
+        SupportedMessage::PublishResponse(response) => {
+            assert_eq!(response.subscription_id, subscription_id);
+            let notification_data = response.notification_message.notification_data.unwrap();
+            assert_eq!(notification_data.len(), 1);
+            let status_change = notification_data[0].decode_inner::<StatusChangeNotification>().unwrap();
+            assert_eq!(status_change.status, GOOD_SUBSCRIPTION_TRANSFERRED);
+        }
+        _ => panic!("Wrong response")
+    }
+
+    // Transferring a subscription the session already has is fine
+    let results = transfer(&mut message_handler_2, &authentication_token_2, vec![subscription_id]);
+    assert_eq!(results[0].status_code, GOOD);
+
+    // A session of a different user may not take the subscription
+    tcp_session_1.session.lock().unwrap().user_identity = Some(UserIdentity {
+        policy_id: UAString::from_str("username"),
+        user_name: Some("sample".to_string()),
+    });
+    let results = transfer(&mut message_handler_1, &authentication_token_1, vec![subscription_id]);
+    assert_eq!(results[0].status_code, BAD_USER_ACCESS_DENIED);
+    assert!(server_state.lock().unwrap().subscriptions.contains(&session_id_2, subscription_id));
+
+    // The subscription is kept when its session is closed without deleting it
+    let request = CloseSessionRequest {
+        request_header: RequestHeader::new(&authentication_token_2, &DateTime::now(), 5),
+        delete_subscriptions: false,
+    };
+    match message_handler_2.handle_message(5, SupportedMessage::CloseSessionRequest(request)).unwrap() {
+        SupportedMessage::CloseSessionResponse(_) => {}
+        _ => panic!("Wrong response")
+    }
+    match server_state.lock().unwrap().subscriptions.owner(subscription_id) {
+        Some(&SubscriptionOwner::Closed(None, _)) => {}
+        _ => panic!("Subscription should belong to the closed session")
+    }
+
+    // Only a session of the user of the closed session may take it
+    let results = transfer(&mut message_handler_1, &authentication_token_1, vec![subscription_id]);
+    assert_eq!(results[0].status_code, BAD_USER_ACCESS_DENIED);
+    let tcp_session_3 = TcpTransport::new(server_state.clone());
+    let (mut message_handler_3, session_id_3, authentication_token_3) = create_session(&tcp_session_3);
+    let results = transfer(&mut message_handler_3, &authentication_token_3, vec![subscription_id]);
+    assert_eq!(results[0].status_code, GOOD);
+    assert!(server_state.lock().unwrap().subscriptions.contains(&session_id_3, subscription_id));
+}
+
+#[test]
+fn subscription_diagnostics() {
+    let server = Server::new(ServerConfig::default_anonymous());
+    let server_state = server.server_state;
+    let endpoint_url = server_state.lock().unwrap().endpoints[0].endpoint_url.clone();
+
+    // A session with a subscription that is modified, disabled and asked to republish
+    let tcp_session = TcpTransport::new(server_state.clone());
+    let mut message_handler = MessageHandler::new(server_state.clone(), tcp_session.session.clone());
+    let (session_id, authentication_token) = match message_handler.handle_message(1, SupportedMessage::CreateSessionRequest(make_create_session_request(&endpoint_url, 5000f64))).unwrap() {
+        SupportedMessage::CreateSessionResponse(response) => (response.session_id, response.authentication_token),
+        _ => panic!("Wrong response")
+    };
+    let request = CreateSubscriptionRequest {
+        request_header: RequestHeader::new(&authentication_token, &DateTime::now(), 2),
+        requested_publishing_interval: 1000f64,
+        requested_lifetime_count: 100,
+        requested_max_keep_alive_count: 10,
+        max_notifications_per_publish: 0,
+        publishing_enabled: true,
+        priority: 0,
+    };
+    let subscription_id = match message_handler.handle_message(2, SupportedMessage::CreateSubscriptionRequest(request)).unwrap() {
+        SupportedMessage::CreateSubscriptionResponse(response) => response.subscription_id,
+        _ => panic!("Wrong response")
+    };
+    let request = ModifySubscriptionRequest {
+        request_header: RequestHeader::new(&authentication_token, &DateTime::now(), 3),
+        subscription_id,
+        requested_publishing_interval: 2000f64,
+        requested_lifetime_count: 100,
+        requested_max_keep_alive_count: 10,
+        max_notifications_per_publish: 0,
+        priority: 5,
+    };
+    let _ = message_handler.handle_message(3, SupportedMessage::ModifySubscriptionRequest(request)).unwrap();
+    let request = SetPublishingModeRequest {
+        request_header: RequestHeader::new(&authentication_token, &DateTime::now(), 4),
+        publishing_enabled: false,
+        subscription_ids: Some(vec![subscription_id]),
+    };
+    let _ = message_handler.handle_message(4, SupportedMessage::SetPublishingModeRequest(request)).unwrap();
+    let request = RepublishRequest {
+        request_header: RequestHeader::new(&authentication_token, &DateTime::now(), 5),
+        subscription_id,
+        retransmit_sequence_number: 1,
+    };
+    match message_handler.handle_message(5, SupportedMessage::RepublishRequest(request)).unwrap() {
+        SupportedMessage::ServiceFault(fault) => assert_eq!(fault.response_header.service_result, BAD_MESSAGE_NOT_AVAILABLE),
+        _ => panic!("Wrong response")
+    }
+
+    let subscription_diagnostics = |value: DataValue| {
+        match value.value.unwrap() {
+            Variant::Array(values) => values.iter().map(|value| match *value {
+                Variant::ExtensionObject(ref extension_object) => extension_object.decode_inner::<SubscriptionDiagnosticsDataType>().unwrap(),
+                _ => panic!("Wrong value")
+            }).collect::<Vec<SubscriptionDiagnosticsDataType>>(),
+            _ => panic!("Wrong value")
+        }
+    };
+
+    // The counters of the subscription are visible in the server's diagnostics, including
+    // changes made between requests once the diagnostics are refreshed
+    {
+        let mut server_state = server_state.lock().unwrap();
+        let _ = server_state.subscriptions.get_mut(&session_id, subscription_id).unwrap().republish(1);
+        server_state.update_session_diagnostics();
+        server_state.update_session_diagnostics_nodes();
+        let mut address_space = server_state.address_space.lock().unwrap();
+        let value = address_space.find_variable_by_variable_id(VariableId::Server_ServerDiagnostics_SubscriptionDiagnosticsArray).unwrap().value();
+        let diagnostics = subscription_diagnostics(value);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].session_id, session_id);
+        assert_eq!(diagnostics[0].subscription_id, subscription_id);
+        assert_eq!(diagnostics[0].priority, 5);
+        assert_eq!(diagnostics[0].publishing_interval, 2000f64);
+        assert!(!diagnostics[0].publishing_enabled);
+        assert_eq!(diagnostics[0].modify_count, 1);
+        assert_eq!(diagnostics[0].disable_count, 1);
+        assert_eq!(diagnostics[0].republish_request_count, 2);
+        assert_eq!(diagnostics[0].republish_message_count, 0);
+        assert_eq!(diagnostics[0].monitored_item_count, 0);
+        assert_eq!(diagnostics[0].next_sequence_number, 1);
+
+        // And in the diagnostics object of the session
+        let object_id = AddressSpace::session_diagnostics_node_id(&session_id, None);
+        assert_eq!(object_id.namespace, constants::APPLICATION_NAMESPACE);
+        assert!(address_space.has_reference(&ObjectId::Server_ServerDiagnostics_SessionsDiagnosticsSummary.as_node_id(), &object_id, ReferenceTypeId::HasComponent));
+        let value = match address_space.find_node(&AddressSpace::session_diagnostics_node_id(&session_id, Some("SubscriptionDiagnosticsArray"))) {
+            Some(&NodeType::Variable(ref variable)) => variable.value(),
+            _ => panic!("Missing variable")
+        };
+        assert_eq!(subscription_diagnostics(value).len(), 1);
+        let session_diagnostics = match address_space.find_node(&AddressSpace::session_diagnostics_node_id(&session_id, Some("SessionDiagnostics"))) {
+            Some(&NodeType::Variable(ref variable)) => match variable.value().value.unwrap() {
+                Variant::ExtensionObject(extension_object) => extension_object.decode_inner::<SessionDiagnosticsDataType>().unwrap(),
+                _ => panic!("Wrong value")
+            },
+            _ => panic!("Missing variable")
+        };
+        assert_eq!(session_diagnostics.session_name, UAString::from_str("test"));
+        assert_eq!(session_diagnostics.current_subscriptions_count, 1);
+        assert_eq!(session_diagnostics.total_request_count.total_count, 5);
+        assert_eq!(session_diagnostics.total_request_count.error_count, 1);
+        assert_eq!(session_diagnostics.create_subscription_count.total_count, 1);
+        assert_eq!(session_diagnostics.republish_count.error_count, 1);
+    }
+
+    // Closing the session removes its diagnostics
+    let request = CloseSessionRequest {
+        request_header: RequestHeader::new(&authentication_token, &DateTime::now(), 6),
+        delete_subscriptions: true,
+    };
+    let _ = message_handler.handle_message(6, SupportedMessage::CloseSessionRequest(request)).unwrap();
+    let server_state = server_state.lock().unwrap();
+    server_state.update_session_diagnostics_nodes();
+    let mut address_space = server_state.address_space.lock().unwrap();
+    let value = address_space.find_variable_by_variable_id(VariableId::Server_ServerDiagnostics_SubscriptionDiagnosticsArray).unwrap().value();
+    assert!(subscription_diagnostics(value).is_empty());
+    assert!(!address_space.node_exists(&AddressSpace::session_diagnostics_node_id(&session_id, None)));
+    assert!(!address_space.node_exists(&AddressSpace::session_diagnostics_node_id(&session_id, Some("SessionDiagnostics"))));
+}
+
 
 // View service tests
 
@@ -107,10 +1523,505 @@ fn browse() {
 #[test]
 fn browse_next() {
     // Set up a server more more nodes than can fit in a response to test Browse, BrowseNext response
-    // TODO
+    let server = Server::new(ServerConfig::default_anonymous());
+    let tcp_session = TcpTransport::new(server.server_state);
+
+    let view = ViewService::new();
+    {
+        let mut server_state = tcp_session.server_state.lock().unwrap();
+        let mut session = tcp_session.session.lock().unwrap();
+
+        let parent_node_id = {
+            let mut address_space = server_state.address_space.lock().unwrap();
+            let parent_node_id = address_space.add_folder("BigFolder", "BigFolder", &AddressSpace::objects_folder_id()).unwrap();
+            let vars = (0..25).map(|i| {
+                let name = format!("v{}", i);
+                Variable::new(&NodeId::new_string(1, &name), &name, &name, "", DataTypeId::Int32, DataValue::new(Variant::Int32(i)))
+            }).collect();
+            let _ = address_space.add_variables(vars, &parent_node_id);
+            parent_node_id
+        };
+
+        let browse_response = |result: Result<SupportedMessage, StatusCode>| -> Vec<BrowseResult> {
+            match result.unwrap() {
+                SupportedMessage::BrowseResponse(response) => response.results.unwrap(),
+                SupportedMessage::BrowseNextResponse(response) => response.results.unwrap(),
+                _ => panic!("Wrong response")
+            }
+        };
+        let browse_next_request = |continuation_point: &ByteString, release_continuation_points: bool| {
+            BrowseNextRequest {
+                request_header: RequestHeader::new(&NodeId::null(), &DateTime::now(), 1),
+                release_continuation_points,
+                continuation_points: Some(vec![continuation_point.clone()]),
+            }
+        };
+
+        // Browse returns the first 10 references and a continuation point
+        let mut request = make_browse_request(vec![parent_node_id.clone()], BrowseDirection::Forward, ReferenceTypeId::Organizes);
+        request.requested_max_references_per_node = 10;
+        let results = browse_response(view.browse(&mut server_state, &mut session, request));
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].status_code, GOOD);
+        assert_eq!(results[0].references.as_ref().unwrap().len(), 10);
+        let continuation_point = results[0].continuation_point.clone();
+        assert!(!continuation_point.is_null());
+        assert_eq!(session.browse_continuation_points.len(), 1);
+
+        // BrowseNext returns the next 10 and another continuation point
+        let results = browse_response(view.browse_next(&mut server_state, &mut session, browse_next_request(&continuation_point, false)));
+        assert_eq!(results[0].status_code, GOOD);
+        assert_eq!(results[0].references.as_ref().unwrap().len(), 10);
+        let continuation_point2 = results[0].continuation_point.clone();
+        assert!(!continuation_point2.is_null());
+
+        // The old continuation point has been consumed
+        let results = browse_response(view.browse_next(&mut server_state, &mut session, browse_next_request(&continuation_point, false)));
+        assert_eq!(results[0].status_code, BAD_CONTINUATION_POINT_INVALID);
+
+        // The last 5 with no further continuation point
+        let results = browse_response(view.browse_next(&mut server_state, &mut session, browse_next_request(&continuation_point2, false)));
+        assert_eq!(results[0].status_code, GOOD);
+        assert_eq!(results[0].references.as_ref().unwrap().len(), 5);
+        assert!(results[0].continuation_point.is_null());
+        assert!(session.browse_continuation_points.is_empty());
+
+        // Releasing a continuation point returns no references and removes it from the session
+        let mut request = make_browse_request(vec![parent_node_id.clone()], BrowseDirection::Forward, ReferenceTypeId::Organizes);
+        request.requested_max_references_per_node = 10;
+        let results = browse_response(view.browse(&mut server_state, &mut session, request));
+        let continuation_point = results[0].continuation_point.clone();
+        let results = browse_response(view.browse_next(&mut server_state, &mut session, browse_next_request(&continuation_point, true)));
+        assert_eq!(results[0].status_code, GOOD);
+        assert!(results[0].references.is_none());
+        assert!(session.browse_continuation_points.is_empty());
+
+        // Exceeding the max continuation points per session
+        server_state.max_browse_continuation_points = 1;
+        let mut request = make_browse_request(vec![parent_node_id.clone(), parent_node_id.clone()], BrowseDirection::Forward, ReferenceTypeId::Organizes);
+        request.requested_max_references_per_node = 10;
+        let results = browse_response(view.browse(&mut server_state, &mut session, request));
+        assert_eq!(results[0].status_code, GOOD);
+        assert_eq!(results[1].status_code, BAD_NO_CONTINUATION_POINTS);
+        assert!(results[1].references.is_none());
+
+        // Continuation points do not outlive the session
+        let session_service = SessionService::new();
+        let endpoint_url = server_state.endpoints[0].endpoint_url.clone();
+        let _ = session_service.create_session(&mut server_state, &mut session, make_create_session_request(&endpoint_url, 5000f64)).unwrap();
+        assert!(session.browse_continuation_points.is_empty());
+        let mut request = make_browse_request(vec![parent_node_id.clone()], BrowseDirection::Forward, ReferenceTypeId::Organizes);
+        request.requested_max_references_per_node = 10;
+        let _ = view.browse(&mut server_state, &mut session, request);
+        assert_eq!(session.browse_continuation_points.len(), 1);
+        let request = CloseSessionRequest {
+            request_header: RequestHeader::new(&NodeId::null(), &DateTime::now(), 1),
+            delete_subscriptions: true,
+        };
+        let _ = session_service.close_session(&mut server_state, &mut session, request).unwrap();
+        assert!(session.browse_continuation_points.is_empty());
+    }
 }
 
 #[test]
 fn translate_browse_paths_to_node_ids() {
     // TODO
-}
\ No newline at end of file
+}
+
+#[test]
+fn register_nodes() {
+    let server = Server::new(ServerConfig::default_anonymous());
+    let tcp_session = TcpTransport::new(server.server_state);
+
+    let view = ViewService::new();
+    let attribute = AttributeService::new();
+    {
+        let mut server_state = tcp_session.server_state.lock().unwrap();
+        let mut session = tcp_session.session.lock().unwrap();
+
+        {
+            let mut address_space = server_state.address_space.lock().unwrap();
+            add_sample_vars_to_address_space(&mut address_space);
+        }
+
+        // String ids are given numeric aliases, numeric ids and unknown nodes are returned as they are
+        let v1 = NodeId::new_string(1, "v1");
+        let v2 = NodeId::new_numeric(2, 300);
+        let v3 = NodeId::new_string(1, "v3");
+        let unknown = NodeId::new_string(1, "unknown");
+        let request = RegisterNodesRequest {
+            request_header: RequestHeader::new(&NodeId::null(), &DateTime::now(), 1),
+            nodes_to_register: Some(vec![v1.clone(), v2.clone(), v3.clone(), unknown.clone(), v1.clone()]),
+        };
+        let registered_node_ids = match view.register_nodes(&mut server_state, &mut session, request).unwrap() {
+            SupportedMessage::RegisterNodesResponse(response) => response.registered_node_ids.unwrap(),
+            _ => panic!("Wrong response")
+        };
+        assert_eq!(registered_node_ids.len(), 5);
+        assert_eq!(registered_node_ids[0], NodeId::new_numeric(1, constants::REGISTERED_NODE_ID_BASE));
+        assert_eq!(registered_node_ids[1], v2);
+        assert_eq!(registered_node_ids[2], NodeId::new_numeric(1, constants::REGISTERED_NODE_ID_BASE + 1));
+        assert_eq!(registered_node_ids[3], unknown);
+        assert_eq!(registered_node_ids[4], registered_node_ids[0]);
+
+        // Aliases can be read and written
+        let read = |session: &mut Session, server_state: &mut ServerState, node_id: &NodeId| {
+            let request = ReadRequest {
+                request_header: RequestHeader::new(&NodeId::null(), &DateTime::now(), 1),
+                max_age: 0f64,
+                timestamps_to_return: TimestampsToReturn::Neither,
+                nodes_to_read: Some(vec![ReadValueId::read_value(node_id.clone())]),
+            };
+            match attribute.read(server_state, session, request).unwrap() {
+                SupportedMessage::ReadResponse(response) => response.results.unwrap().remove(0),
+                _ => panic!("Wrong response")
+            }
+        };
+        assert_eq!(read(&mut session, &mut server_state, &registered_node_ids[0]).value, Some(Variant::Int32(30)));
+        assert_eq!(read(&mut session, &mut server_state, &registered_node_ids[2]).value, Some(Variant::String(UAString::from_str("Hello world"))));
+        {
+            let mut address_space = server_state.address_space.lock().unwrap();
+            let variable = address_space.find_variable_by_node_id(&v1).unwrap();
+            variable.set_access_level(ACCESS_LEVEL_CURRENT_READ | ACCESS_LEVEL_CURRENT_WRITE);
+            variable.set_user_access_level(USER_ACCESS_LEVEL_CURRENT_READ | USER_ACCESS_LEVEL_CURRENT_WRITE);
+        }
+        let request = WriteRequest {
+            request_header: RequestHeader::new(&NodeId::null(), &DateTime::now(), 1),
+            nodes_to_write: Some(vec![make_write_value(registered_node_ids[0].clone(), AttributeId::Value, Variant::Int32(99))]),
+        };
+        match attribute.write(&mut server_state, &mut session, request).unwrap() {
+            SupportedMessage::WriteResponse(response) => assert_eq!(response.results.unwrap(), vec![GOOD]),
+            _ => panic!("Wrong response")
+        }
+        assert_eq!(read(&mut session, &mut server_state, &v1).value, Some(Variant::Int32(99)));
+
+        // Only ids in the alias namespace are aliases
+        let not_alias = NodeId::new_numeric(2, constants::REGISTERED_NODE_ID_BASE);
+        assert_eq!(session.resolve_node_id(&server_state.address_space.lock().unwrap(), &not_alias), &not_alias);
+
+        // A node with the id of an alias is not shadowed by it
+        {
+            let mut address_space = server_state.address_space.lock().unwrap();
+            address_space.insert(NodeType::Variable(Variable::new(&registered_node_ids[0], "v5", "v5", "", DataTypeId::Int32, DataValue::new(Variant::Int32(7)))));
+        }
+        assert_eq!(read(&mut session, &mut server_state, &registered_node_ids[0]).value, Some(Variant::Int32(7)));
+        assert!(server_state.address_space.lock().unwrap().delete_node(&registered_node_ids[0], true));
+        assert_eq!(read(&mut session, &mut server_state, &registered_node_ids[0]).value, Some(Variant::Int32(99)));
+
+        // An alias does not resolve once it is unregistered, even after another node is registered
+        let request = UnregisterNodesRequest {
+            request_header: RequestHeader::new(&NodeId::null(), &DateTime::now(), 1),
+            nodes_to_unregister: Some(vec![registered_node_ids[0].clone()]),
+        };
+        match view.unregister_nodes(&mut server_state, &mut session, request).unwrap() {
+            SupportedMessage::UnregisterNodesResponse(_) => {}
+            _ => panic!("Wrong response")
+        }
+        assert_eq!(read(&mut session, &mut server_state, &registered_node_ids[0]).status, Some(BAD_NODE_ID_UNKNOWN));
+        assert_eq!(read(&mut session, &mut server_state, &registered_node_ids[2]).value, Some(Variant::String(UAString::from_str("Hello world"))));
+        let alias = session.register_node(&NodeId::new_string(1, "v4"));
+        assert_eq!(alias, NodeId::new_numeric(1, constants::REGISTERED_NODE_ID_BASE + 2));
+        assert_eq!(read(&mut session, &mut server_state, &registered_node_ids[0]).status, Some(BAD_NODE_ID_UNKNOWN));
+
+        // Creating a new session on the connection unregisters the nodes of the old one
+        let endpoint_url = server_state.endpoints[0].endpoint_url.clone();
+        let request = make_create_session_request(&endpoint_url, 5000f64);
+        match SessionService::new().create_session(&mut server_state, &mut session, request).unwrap() {
+            SupportedMessage::CreateSessionResponse(_) => {}
+            _ => panic!("Wrong response")
+        }
+        assert_eq!(read(&mut session, &mut server_state, &alias).status, Some(BAD_NODE_ID_UNKNOWN));
+    }
+}
+// Method service tests
+
+#[test]
+fn call() {
+    let server = Server::new(ServerConfig::default_anonymous());
+    let tcp_session = TcpTransport::new(server.server_state);
+
+    let method_service = MethodService::new();
+    {
+        let mut server_state = tcp_session.server_state.lock().unwrap();
+        let mut session = tcp_session.session.lock().unwrap();
+
+        let object_id = AddressSpace::objects_folder_id();
+        let method_id = NodeId::new_string(1, "add");
+        let not_executable_method_id = NodeId::new_string(1, "not_executable");
+        {
+            let make_argument = |name: &str| Argument {
+                name: UAString::from_str(name),
+                data_type: DataTypeId::Int32.as_node_id(),
+                value_rank: -1,
+                array_dimensions: None,
+                description: LocalizedText::new("", name),
+            };
+
+            let mut address_space = server_state.address_space.lock().unwrap();
+            let mut method = Method::new(&method_id, "Add", "Add", "", false, true, true);
+            method.set_callback(Arc::new(Mutex::new(MethodFnCallback::new(|_: &NodeId, input_arguments: &[Variant]| {
+                match (&input_arguments[0], &input_arguments[1]) {
+                    (&Variant::Int32(a), &Variant::Int32(b)) => Ok(vec![Variant::Int32(a + b)]),
+                    _ => Err(BAD_INVALID_ARGUMENT)
+                }
+            }))));
+            let _ = address_space.add_method(method, &object_id, &[make_argument("a"), make_argument("b")], &[make_argument("sum")]);
+            assert_eq!(address_space.method_input_arguments(&method_id).len(), 2);
+            assert_eq!(address_space.method_output_arguments(&method_id)[0].name, UAString::from_str("sum"));
+
+            let method = Method::new(&not_executable_method_id, "NotExecutable", "NotExecutable", "", false, false, false);
+            let _ = address_space.add_method(method, &object_id, &[], &[]);
+        }
+
+        let make_call = |object_id: &NodeId, method_id: &NodeId, input_arguments: Vec<Variant>| CallMethodRequest {
+            object_id: object_id.clone(),
+            method_id: method_id.clone(),
+            input_arguments: Some(input_arguments),
+        };
+        let request = CallRequest {
+            request_header: RequestHeader::new(&NodeId::null(), &DateTime::now(), 1),
+            methods_to_call: Some(vec![
+                // Good
+                make_call(&object_id, &method_id, vec![Variant::Int32(2), Variant::Int32(3)]),
+                // Too few / many arguments
+                make_call(&object_id, &method_id, vec![Variant::Int32(2)]),
+                make_call(&object_id, &method_id, vec![Variant::Int32(2), Variant::Int32(3), Variant::Int32(4)]),
+                // Wrong type
+                make_call(&object_id, &method_id, vec![Variant::Int32(2), Variant::Boolean(true)]),
+                // Unknown object, unknown method, method that is not a component of the object
+                make_call(&NodeId::new_string(1, "xxx"), &method_id, vec![]),
+                make_call(&object_id, &NodeId::new_string(1, "xxx"), vec![]),
+                make_call(&AddressSpace::root_folder_id(), &method_id, vec![Variant::Int32(2), Variant::Int32(3)]),
+                // Not executable
+                make_call(&object_id, &not_executable_method_id, vec![]),
+            ]),
+        };
+
+        let result = method_service.call(&mut server_state, &mut session, request);
+        let response = match result.unwrap() {
+            SupportedMessage::CallResponse(response) => response,
+            _ => panic!("Wrong response")
+        };
+        let results = response.results.unwrap();
+        let status_codes: Vec<StatusCode> = results.iter().map(|r| r.status_code).collect();
+        assert_eq!(status_codes, vec![GOOD, BAD_ARGUMENTS_MISSING, BAD_TOO_MANY_ARGUMENTS, BAD_INVALID_ARGUMENT, BAD_NODE_ID_UNKNOWN, BAD_METHOD_INVALID, BAD_METHOD_INVALID, BAD_USER_ACCESS_DENIED]);
+        assert_eq!(results[0].output_arguments, Some(vec![Variant::Int32(5)]));
+        assert_eq!(results[3].input_argument_results, Some(vec![GOOD, BAD_TYPE_MISMATCH]));
+    }
+}
+
+// NodeManagement service tests
+
+#[test]
+fn node_management() {
+    // Node management must be explicitly enabled on the endpoint
+    let mut config = ServerConfig::default_anonymous();
+    config.endpoints[0].node_management = Some(true);
+    let server = Server::new(config);
+    let tcp_session = TcpTransport::new(server.server_state);
+
+    let service = NodeManagementService::new();
+    {
+        let mut server_state = tcp_session.server_state.lock().unwrap();
+        let mut session = tcp_session.session.lock().unwrap();
+
+        let make_request_header = || RequestHeader::new(&NodeId::null(), &DateTime::now(), 1);
+
+        // Session is not associated with an endpoint so access is denied
+        let request = DeleteNodesRequest {
+            request_header: make_request_header(),
+            nodes_to_delete: Some(vec![DeleteNodesItem { node_id: AddressSpace::objects_folder_id(), delete_target_references: true }]),
+        };
+        match service.delete_nodes(&mut server_state, &mut session, request).unwrap() {
+            SupportedMessage::ServiceFault(fault) => assert_eq!(fault.response_header.service_result, BAD_USER_ACCESS_DENIED),
+            _ => panic!("Wrong response")
+        }
+        session.endpoint_url = UAString::from_str(&server_state.endpoints[0].endpoint_url);
+
+        // AddNodes
+        let object_id = NodeId::new_string(1, "object");
+        let variable_id = NodeId::new_string(1, "variable");
+        let make_item = |node_id: &NodeId, parent_node_id: &NodeId, reference_type_id: ReferenceTypeId, browse_name: &str, node_class: NodeClass, node_attributes: ExtensionObject, type_definition: NodeId| AddNodesItem {
+            parent_node_id: ExpandedNodeId::new(parent_node_id),
+            reference_type_id: reference_type_id.as_node_id(),
+            requested_new_node_id: ExpandedNodeId::new(node_id),
+            browse_name: QualifiedName::new(1, browse_name),
+            node_class,
+            node_attributes,
+            type_definition: ExpandedNodeId::new(&type_definition),
+        };
+        let object_attributes = ExtensionObject::from_encodable(ObjectId::ObjectAttributes_Encoding_DefaultBinary.as_node_id(), ObjectAttributes {
+            specified_attributes: 1 << 6,
+            display_name: LocalizedText::new("", "Object"),
+            description: LocalizedText::null(),
+            write_mask: 0,
+            user_write_mask: 0,
+            event_notifier: 0,
+        });
+        let variable_attributes = ExtensionObject::from_encodable(ObjectId::VariableAttributes_Encoding_DefaultBinary.as_node_id(), VariableAttributes {
+            specified_attributes: (1 << 4) | (1 << 21),
+            display_name: LocalizedText::null(),
+            description: LocalizedText::null(),
+            write_mask: 0,
+            user_write_mask: 0,
+            value: Variant::Int32(99),
+            data_type: DataTypeId::Int32.as_node_id(),
+            value_rank: -1,
+            array_dimensions: None,
+            access_level: 0,
+            user_access_level: 0,
+            minimum_sampling_interval: 0f64,
+            historizing: false,
+        });
+        let objects_folder_id = AddressSpace::objects_folder_id();
+        let folder_type_id = ObjectTypeId::FolderType.as_node_id();
+        let variable_type_id = VariableTypeId::BaseDataVariableType.as_node_id();
+        let request = AddNodesRequest {
+            request_header: make_request_header(),
+            nodes_to_add: Some(vec![
+                make_item(&object_id, &objects_folder_id, ReferenceTypeId::Organizes, "Object", NodeClass::Object, object_attributes.clone(), folder_type_id.clone()),
+                make_item(&variable_id, &object_id, ReferenceTypeId::HasComponent, "Variable", NodeClass::Variable, variable_attributes.clone(), variable_type_id.clone()),
+                // Node id exists
+                make_item(&object_id, &objects_folder_id, ReferenceTypeId::Organizes, "Object2", NodeClass::Object, object_attributes.clone(), folder_type_id.clone()),
+                // Browse name duplicated
+                make_item(&NodeId::new_string(1, "x1"), &objects_folder_id, ReferenceTypeId::Organizes, "Object", NodeClass::Object, object_attributes.clone(), folder_type_id.clone()),
+                // Parent does not exist
+                make_item(&NodeId::new_string(1, "x2"), &NodeId::new_string(1, "xxx"), ReferenceTypeId::Organizes, "x2", NodeClass::Object, object_attributes.clone(), folder_type_id.clone()),
+                // Non hierarchical reference to parent
+                make_item(&NodeId::new_string(1, "x3"), &objects_folder_id, ReferenceTypeId::HasTypeDefinition, "x3", NodeClass::Object, object_attributes.clone(), folder_type_id.clone()),
+                // Type definition is the wrong kind
+                make_item(&NodeId::new_string(1, "x4"), &objects_folder_id, ReferenceTypeId::Organizes, "x4", NodeClass::Object, object_attributes.clone(), variable_type_id.clone()),
+                make_item(&NodeId::new_string(1, "x5"), &objects_folder_id, ReferenceTypeId::Organizes, "x5", NodeClass::Variable, variable_attributes.clone(), NodeId::null()),
+                // Attributes do not match the node class
+                make_item(&NodeId::new_string(1, "x6"), &objects_folder_id, ReferenceTypeId::Organizes, "x6", NodeClass::Variable, object_attributes.clone(), variable_type_id.clone()),
+                // Node id is in the range of registered node aliases
+                make_item(&NodeId::new_numeric(constants::REGISTERED_NODE_NAMESPACE, constants::REGISTERED_NODE_ID_BASE + 1), &objects_folder_id, ReferenceTypeId::Organizes, "x7", NodeClass::Object, object_attributes.clone(), folder_type_id.clone()),
+            ]),
+        };
+        let results = match service.add_nodes(&mut server_state, &mut session, request).unwrap() {
+            SupportedMessage::AddNodesResponse(response) => response.results.unwrap(),
+            _ => panic!("Wrong response")
+        };
+        let status_codes: Vec<StatusCode> = results.iter().map(|r| r.status_code).collect();
+        assert_eq!(status_codes, vec![GOOD, GOOD, BAD_NODE_ID_EXISTS, BAD_BROWSE_NAME_DUPLICATED, BAD_PARENT_NODE_ID_INVALID, BAD_REFERENCE_NOT_ALLOWED, BAD_TYPE_DEFINITION_INVALID, BAD_TYPE_DEFINITION_INVALID, BAD_NODE_ATTRIBUTES_INVALID, BAD_NODE_ID_REJECTED]);
+        assert_eq!(results[0].added_node_id, object_id);
+        {
+            let address_space = server_state.address_space.lock().unwrap();
+            let node = address_space.find_node(&object_id).unwrap().as_node();
+            assert_eq!(node.browse_name(), QualifiedName::new(1, "Object"));
+            assert_eq!(node.display_name().text, UAString::from_str("Object"));
+            assert!(address_space.has_reference(&objects_folder_id, &object_id, ReferenceTypeId::Organizes));
+            assert!(address_space.has_reference(&object_id, &folder_type_id, ReferenceTypeId::HasTypeDefinition));
+            let node = address_space.find_node(&variable_id).unwrap().as_node();
+            assert_eq!(node.find_attribute(AttributeId::Value).unwrap().value, Some(Variant::Int32(99)));
+        }
+
+        // AddReferences
+        let make_reference = |source_node_id: &NodeId, target_node_id: &NodeId, is_forward: bool| AddReferencesItem {
+            source_node_id: source_node_id.clone(),
+            reference_type_id: ReferenceTypeId::Organizes.as_node_id(),
+            is_forward,
+            target_server_uri: UAString::null(),
+            target_node_id: ExpandedNodeId::new(target_node_id),
+            target_node_class: NodeClass::Unspecified,
+        };
+        let request = AddReferencesRequest {
+            request_header: make_request_header(),
+            references_to_add: Some(vec![
+                make_reference(&variable_id, &objects_folder_id, false),
+                make_reference(&objects_folder_id, &variable_id, true),
+                make_reference(&variable_id, &variable_id, true),
+                make_reference(&variable_id, &NodeId::new_string(1, "xxx"), true),
+            ]),
+        };
+        let results = match service.add_references(&mut server_state, &mut session, request).unwrap() {
+            SupportedMessage::AddReferencesResponse(response) => response.results.unwrap(),
+            _ => panic!("Wrong response")
+        };
+        assert_eq!(results, vec![GOOD, BAD_DUPLICATE_REFERENCE_NOT_ALLOWED, BAD_INVALID_SELF_REFERENCE, BAD_TARGET_NODE_ID_INVALID]);
+        assert!(server_state.address_space.lock().unwrap().has_reference(&objects_folder_id, &variable_id, ReferenceTypeId::Organizes));
+
+        // The type hierarchy may not be made to loop
+        let base_event_type_id = ObjectTypeId::BaseEventType.as_node_id();
+        let system_event_type_id = ObjectTypeId::SystemEventType.as_node_id();
+        let request = AddReferencesRequest {
+            request_header: make_request_header(),
+            references_to_add: Some(vec![AddReferencesItem {
+                source_node_id: system_event_type_id.clone(),
+                reference_type_id: ReferenceTypeId::HasSubtype.as_node_id(),
+                is_forward: true,
+                target_server_uri: UAString::null(),
+                target_node_id: ExpandedNodeId::new(&base_event_type_id),
+                target_node_class: NodeClass::Unspecified,
+            }]),
+        };
+        let results = match service.add_references(&mut server_state, &mut session, request).unwrap() {
+            SupportedMessage::AddReferencesResponse(response) => response.results.unwrap(),
+            _ => panic!("Wrong response")
+        };
+        assert_eq!(results, vec![BAD_REFERENCE_NOT_ALLOWED]);
+        {
+            // A loop that is there anyway does not hang a search of the hierarchy
+            let mut address_space = server_state.address_space.lock().unwrap();
+            address_space.insert_reference(&system_event_type_id, &base_event_type_id, ReferenceTypeId::HasSubtype);
+            assert!(address_space.is_subtype(&system_event_type_id, &base_event_type_id));
+            assert!(!address_space.is_subtype(&system_event_type_id, &ObjectTypeId::FolderType.as_node_id()));
+            assert!(address_space.delete_reference(&system_event_type_id, &base_event_type_id, ReferenceTypeId::HasSubtype));
+        }
+
+        // DeleteReferences
+        let make_reference = |is_forward: bool| DeleteReferencesItem {
+            source_node_id: objects_folder_id.clone(),
+            reference_type_id: ReferenceTypeId::Organizes.as_node_id(),
+            is_forward,
+            target_node_id: ExpandedNodeId::new(&variable_id),
+            delete_bidirectional: true,
+        };
+        let request = DeleteReferencesRequest {
+            request_header: make_request_header(),
+            references_to_delete: Some(vec![make_reference(false), make_reference(true), make_reference(true)]),
+        };
+        let results = match service.delete_references(&mut server_state, &mut session, request).unwrap() {
+            SupportedMessage::DeleteReferencesResponse(response) => response.results.unwrap(),
+            _ => panic!("Wrong response")
+        };
+        assert_eq!(results, vec![BAD_NOT_FOUND, GOOD, BAD_NOT_FOUND]);
+
+        // DeleteNodes. A component that the object shares with another node is not owned by it.
+        let shared_id = NodeId::new_string(1, "shared");
+        {
+            let mut address_space = server_state.address_space.lock().unwrap();
+            address_space.insert(NodeType::Variable(Variable::new(&shared_id, "Shared", "Shared", "", DataTypeId::Int32, DataValue::new(Variant::Int32(1)))));
+            address_space.add_has_component(&object_id, &shared_id);
+            address_space.add_has_component(&objects_folder_id, &shared_id);
+        }
+        let request = DeleteNodesRequest {
+            request_header: make_request_header(),
+            nodes_to_delete: Some(vec![
+                DeleteNodesItem { node_id: object_id.clone(), delete_target_references: true },
+                DeleteNodesItem { node_id: object_id.clone(), delete_target_references: true },
+                DeleteNodesItem { node_id: shared_id.clone(), delete_target_references: false },
+            ]),
+        };
+        let results = match service.delete_nodes(&mut server_state, &mut session, request).unwrap() {
+            SupportedMessage::DeleteNodesResponse(response) => response.results.unwrap(),
+            _ => panic!("Wrong response")
+        };
+        assert_eq!(results, vec![GOOD, BAD_NODE_ID_UNKNOWN, GOOD]);
+        {
+            let address_space = server_state.address_space.lock().unwrap();
+            assert!(!address_space.node_exists(&object_id));
+            assert!(!address_space.has_reference(&objects_folder_id, &object_id, ReferenceTypeId::Organizes));
+            assert!(address_space.find_references_to(&folder_type_id, None).unwrap().iter().all(|r| r.node_id != object_id));
+            // The variable was owned by the object so it went with it, references to it included
+            assert!(!address_space.node_exists(&variable_id));
+            assert!(!address_space.has_reference(&objects_folder_id, &variable_id, ReferenceTypeId::Organizes));
+            assert!(address_space.find_references_to(&variable_type_id, None).unwrap().iter().all(|r| r.node_id != variable_id));
+            // The shared variable outlived the object and was then deleted without the references
+            // to it, which are only forgotten from its side
+            assert!(!address_space.node_exists(&shared_id));
+            assert!(address_space.has_reference(&objects_folder_id, &shared_id, ReferenceTypeId::HasComponent));
+            assert!(address_space.find_references_to(&shared_id, None).is_none());
+        }
+    }
+}
@@ -1,5 +1,12 @@
+use chrono;
+use time;
+
 use prelude::*;
-use subscriptions::PublishRequestEntry;
+use constants;
+
+use subscriptions::{PublishRequestEntry, PublishResponseEntry};
+use subscriptions::publish_requests::PublishRequests;
+use subscriptions::subscriptions::Subscriptions;
 
 const DEFAULT_LIFETIME_COUNT: UInt32 = 300;
 const DEFAULT_KEEPALIVE_COUNT: UInt32 = 100;
@@ -360,3 +367,210 @@ fn update_state_17() {
     assert_eq!(s.state, SubscriptionState::Late);
     assert_eq!(s.keep_alive_counter, 1);
 }
+
+#[test]
+fn retransmission_queue() {
+    let mut s = make_subscription(SubscriptionState::Normal);
+    let publish_request = make_publish_request();
+    let update_state_result = UpdateStateResult::new(5, UpdateStateAction::ReturnNotifications);
+
+    // Queue more notifications than the retransmission queue holds
+    let count = constants::MAX_RETRANSMISSION_QUEUE_SIZE as UInt32 + 2;
+    for sequence_number in 1..(count + 1) {
+        s.notification_queue.insert(sequence_number, NotificationMessage::new_data_change(sequence_number, &DateTime::now(), Vec::new()));
+    }
+
+    // Sent notifications move to the retransmission queue, oldest first
+    let _ = s.return_notifications(&publish_request, &update_state_result);
+    assert_eq!(s.notification_queue.len(), count as usize - 1);
+    assert_eq!(s.available_sequence_numbers(), Some(vec![1]));
+    assert_eq!(s.republish(1).unwrap().sequence_number, 1);
+    assert!(s.republish(2).is_none());
+
+    // The oldest are discarded once the queue is full
+    for _ in 1..count {
+        let _ = s.return_notifications(&publish_request, &update_state_result);
+    }
+    assert!(s.notification_queue.is_empty());
+    assert_eq!(s.retransmission_queue.len(), constants::MAX_RETRANSMISSION_QUEUE_SIZE);
+    assert!(s.republish(1).is_none());
+    assert!(s.republish(2).is_none());
+    assert_eq!(s.republish(3).unwrap().sequence_number, 3);
+
+    // Acknowledged notifications can't be republished
+    let acknowledgement = SubscriptionAcknowledgement {
+        subscription_id: 0,
+        sequence_number: 3,
+    };
+    assert_eq!(s.delete_acked_notification_msg(&acknowledgement), GOOD);
+    assert!(s.republish(3).is_none());
+    assert_eq!(s.delete_acked_notification_msg(&acknowledgement), BAD_SEQUENCE_NUMBER_UNKNOWN);
+
+    // Only the messages that were returned count as republished
+    let diagnostics = s.diagnostics(&NodeId::null());
+    assert_eq!(diagnostics.republish_request_count, 6);
+    assert_eq!(diagnostics.republish_message_request_count, 6);
+    assert_eq!(diagnostics.republish_message_count, 2);
+}
+
+fn make_monitored_item_create_request(node_id: &NodeId, monitoring_mode: MonitoringMode, client_handle: UInt32) -> MonitoredItemCreateRequest {
+    MonitoredItemCreateRequest {
+        item_to_monitor: ReadValueId::read_value(node_id.clone()),
+        monitoring_mode,
+        requested_parameters: MonitoringParameters {
+            client_handle,
+            sampling_interval: -1f64,
+            filter: ExtensionObject::null(),
+            queue_size: 1,
+            discard_oldest: true,
+        },
+    }
+}
+
+#[test]
+fn sampling_items_report_when_triggered() {
+    let trigger_node_id = NodeId::new_numeric(1, 1);
+    let sampled_node_id = NodeId::new_numeric(1, 2);
+    let mut address_space = AddressSpace::new();
+    for node_id in &[&trigger_node_id, &sampled_node_id] {
+        let _ = address_space.add_variable(Variable::new(node_id, "v", "v", "", DataTypeId::UInt32, DataValue::new(Variant::UInt32(0))), &AddressSpace::objects_folder_id());
+    }
+
+    // A publishing interval of zero makes every tick sample the items
+    let mut s = Subscription::new(1, true, 0f64, DEFAULT_LIFETIME_COUNT, DEFAULT_KEEPALIVE_COUNT, 0);
+    let results = s.create_monitored_items(&address_space, &MonitoredItemLimits::default(), &[
+        make_monitored_item_create_request(&trigger_node_id, MonitoringMode::Reporting, 1),
+        make_monitored_item_create_request(&sampled_node_id, MonitoringMode::Sampling, 2)
+    ]);
+    let trigger_id = results[0].monitored_item_id;
+    let sampled_id = results[1].monitored_item_id;
+
+    let tick = |s: &mut Subscription, address_space: &AddressSpace| {
+        let _ = s.tick(address_space, false, &None, false, &chrono::UTC::now());
+        s.notification_queue.len()
+    };
+
+    // Only the reporting item reports its initial value
+    assert_eq!(tick(&mut s, &address_space), 1);
+    assert_eq!(s.monitored_items.get(&sampled_id).unwrap().notification_queue.len(), 1);
+
+    assert_eq!(s.set_triggering(trigger_id, &[sampled_id, 999], &[]).unwrap(), (vec![GOOD, BAD_MONITORED_ITEM_ID_INVALID], vec![]));
+    assert_eq!(s.set_triggering(999, &[sampled_id], &[]).unwrap_err(), BAD_MONITORED_ITEM_ID_INVALID);
+
+    // A change to the sampled item is queued but not reported
+    let _ = address_space.set_value_by_node_id(&sampled_node_id, Variant::UInt32(1));
+    assert_eq!(tick(&mut s, &address_space), 1);
+    assert_eq!(s.monitored_items.get(&sampled_id).unwrap().notification_queue.len(), 1);
+
+    // A change to the triggering item reports the sampled item too
+    let _ = address_space.set_value_by_node_id(&trigger_node_id, Variant::UInt32(1));
+    assert_eq!(tick(&mut s, &address_space), 2);
+    assert!(s.monitored_items.get(&sampled_id).unwrap().notification_queue.is_empty());
+
+    // Disabled items don't sample at all
+    assert_eq!(s.set_monitoring_mode(MonitoringMode::Disabled, &[sampled_id, 999]), vec![GOOD, BAD_MONITORED_ITEM_ID_INVALID]);
+    let _ = address_space.set_value_by_node_id(&sampled_node_id, Variant::UInt32(2));
+    assert_eq!(tick(&mut s, &address_space), 2);
+    assert!(s.monitored_items.get(&sampled_id).unwrap().notification_queue.is_empty());
+
+    // Links are removed, and removed along with deleted items
+    assert_eq!(s.set_triggering(trigger_id, &[], &[sampled_id, 999]).unwrap(), (vec![], vec![GOOD, BAD_MONITORED_ITEM_ID_INVALID]));
+    let _ = s.set_triggering(trigger_id, &[sampled_id], &[]);
+    assert_eq!(s.delete_monitored_items(&[sampled_id]), vec![GOOD]);
+    assert!(s.monitored_items.get(&trigger_id).unwrap().triggered_items.is_empty());
+}
+
+#[test]
+fn items_sampled_at_their_own_rate() {
+    let exception_node_id = NodeId::new_numeric(1, 1);
+    let fast_node_id = NodeId::new_numeric(1, 2);
+    let publishing_node_id = NodeId::new_numeric(1, 3);
+    let mut address_space = AddressSpace::new();
+    for node_id in &[&exception_node_id, &fast_node_id, &publishing_node_id] {
+        let _ = address_space.add_variable(Variable::new(node_id, "v", "v", "", DataTypeId::UInt32, DataValue::new(Variant::UInt32(0))), &AddressSpace::objects_folder_id());
+    }
+
+    // Sampling items keep their notifications so the samples can be counted
+    let make_request = |node_id: &NodeId, sampling_interval: Double| {
+        let mut request = make_monitored_item_create_request(node_id, MonitoringMode::Sampling, 1);
+        request.requested_parameters.sampling_interval = sampling_interval;
+        request.requested_parameters.queue_size = 10;
+        request
+    };
+    let mut s = make_subscription(SubscriptionState::Normal);
+    let results = s.create_monitored_items(&address_space, &MonitoredItemLimits::default(), &[
+        make_request(&exception_node_id, 0f64),
+        make_request(&fast_node_id, 100f64),
+        make_request(&publishing_node_id, -1f64),
+    ]);
+    assert_eq!(results[0].revised_sampling_interval, 0f64);
+    let ids = results.iter().map(|r| r.monitored_item_id).collect::<Vec<UInt32>>();
+
+    let now = chrono::UTC::now();
+    let tick = |s: &mut Subscription, address_space: &AddressSpace, elapsed_ms: i64| {
+        let _ = s.tick(address_space, false, &None, false, &(now + time::Duration::milliseconds(elapsed_ms)));
+        ids.iter().map(|id| s.monitored_items.get(id).unwrap().notification_queue.len()).collect::<Vec<usize>>()
+    };
+
+    // Every item reports its initial value on the first tick
+    assert_eq!(tick(&mut s, &address_space, 0), vec![1, 1, 1]);
+
+    // The exception-based item samples as soon as the value changes, the others at their own rate
+    for node_id in &[&exception_node_id, &fast_node_id, &publishing_node_id] {
+        let _ = address_space.set_value_by_node_id(node_id, Variant::UInt32(1));
+    }
+    assert_eq!(tick(&mut s, &address_space, 50), vec![2, 1, 1]);
+    assert_eq!(tick(&mut s, &address_space, 150), vec![2, 2, 1]);
+    assert_eq!(tick(&mut s, &address_space, 1100), vec![2, 2, 2]);
+
+    // A value set on the node without recording the change is not sampled by the exception-based
+    // item until the change is recorded
+    address_space.find_variable_by_node_id(&exception_node_id).unwrap().set_value_direct(&DateTime::now(), Variant::UInt32(2));
+    assert_eq!(tick(&mut s, &address_space, 1200), vec![2, 2, 2]);
+    address_space.record_value_change(&exception_node_id);
+    assert_eq!(tick(&mut s, &address_space, 1210), vec![3, 2, 2]);
+}
+
+fn publish_response_subscription_id(publish_responses: Option<Vec<PublishResponseEntry>>) -> UInt32 {
+    let mut publish_responses = publish_responses.unwrap();
+    assert_eq!(publish_responses.len(), 1);
+    match publish_responses.remove(0).response {
+        SupportedMessage::PublishResponse(response) => response.subscription_id,
+        _ => panic!("Wrong response")
+    }
+}
+
+#[test]
+fn publish_requests_by_priority() {
+    let address_space = AddressSpace::new();
+    let mut subscriptions = Subscriptions::new();
+    let mut publish_requests = PublishRequests::new();
+    let session_id = NodeId::new_numeric(1, 1);
+
+    // Subscriptions with notifications waiting, created in order of increasing age
+    let now = DateTime::now();
+    let waiting_since = |seconds: i64| DateTime::from_chrono(&(now.as_chrono() - time::Duration::seconds(seconds)));
+    for &(subscription_id, priority, waited) in &[(1, 0, 30), (2, 10, 10), (3, 10, 20), (4, 200, 0)] {
+        let mut s = Subscription::new(subscription_id, true, 1000f64, DEFAULT_LIFETIME_COUNT, DEFAULT_KEEPALIVE_COUNT, priority);
+        s.state = SubscriptionState::Normal;
+        if subscription_id != 4 {
+            s.notification_queue.insert(1, NotificationMessage::new_data_change(1, &waiting_since(waited), Vec::new()));
+        }
+        subscriptions.insert(&session_id, subscription_id, s);
+    }
+
+    // Highest priority with notifications first, then the longest waiting of equal priority
+    let mut publish = |request_id: UInt32| {
+        let mut publish_request = make_publish_request();
+        publish_request.request_id = request_id;
+        subscriptions.enqueue_publish_request(&session_id, &mut publish_requests, &address_space, 2, request_id, publish_request.request)
+    };
+    assert_eq!(publish_response_subscription_id(publish(1).unwrap()), 3);
+    assert_eq!(publish_response_subscription_id(publish(2).unwrap()), 2);
+    assert_eq!(publish_response_subscription_id(publish(3).unwrap()), 1);
+
+    // With nothing to send the requests queue up to the limit
+    assert!(publish(4).unwrap().is_none());
+    assert!(publish(5).unwrap().is_none());
+    assert_eq!(publish(6).unwrap_err(), BAD_TOO_MANY_PUBLISH_REQUESTS);
+}
@@ -53,6 +53,11 @@ The files will be created under the specified under the specified --pkipath valu
             .takes_value(true)
             .multiple(true)
             .required(false))
+        .arg(Arg::with_name("applicationuri")
+            .long("application-uri")
+            .help("The application uri of the server or client that will use the cert")
+            .takes_value(true)
+            .required(false))
         .arg(Arg::with_name("CN")
             .long("CN")
             .help("Specifies the Common Name for the cert")
@@ -85,6 +90,7 @@ The files will be created under the specified under the specified --pkipath valu
     let overwrite = matches.is_present("overwrite");
     let certificate_duration_days = value_t_or_exit!(matches, "duration", u32);
 
+    let application_uri = matches.value_of("applicationuri").unwrap_or("").to_string();
     let common_name = matches.value_of("CN").unwrap().to_string();
     let organization = matches.value_of("O").unwrap().to_string();
     let organizational_unit = matches.value_of("OU").unwrap().to_string();
@@ -128,6 +134,7 @@ The files will be created under the specified under the specified --pkipath valu
         country,
         state,
         alt_host_names,
+        application_uri,
         certificate_duration_days,
     }, overwrite, PathBuf::from(&pki_path))
 }
\ No newline at end of file
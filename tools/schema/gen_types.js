@@ -19,7 +19,7 @@ var ignored_types =
         "Node", "InstanceNode", "TypeNode", "ObjectNode", "ObjectTypeNode", "VariableNode", "VariableTypeNode", "ReferenceTypeNode",
         "MethodNode", "ViewNode", "DataTypeNode", "ReferenceNode",
         // Excluded because they use unimplemented enums
-        "ModificationInfo", "HistoryModifiedData", "UpdateDataDetails", "UpdateEventDetails", "UpdateStructureDataDetails", "RedundantServerDataType",
+        "ModificationInfo", "HistoryModifiedData", "UpdateEventDetails", "UpdateStructureDataDetails", "RedundantServerDataType",
         "ServerStatusDataType", "AxisInformation"
     ];
 
@@ -250,6 +250,8 @@ mod write_response;
 pub use self::write_response::*;
 mod history_update_details;
 pub use self::history_update_details::*;
+mod update_data_details;
+pub use self::update_data_details::*;
 mod delete_raw_modified_details;
 pub use self::delete_raw_modified_details::*;
 mod delete_at_time_details;
@@ -4,9 +4,29 @@
 use std::io::{Read, Write};
 
 #[allow(unused_imports)]
-use types::*;
+use encoding::*;
 #[allow(unused_imports)]
-use services::*;
+use basic_types::*;
+#[allow(unused_imports)]
+use data_types::*;
+#[allow(unused_imports)]
+use data_value::*;
+#[allow(unused_imports)]
+use attribute::*;
+#[allow(unused_imports)]
+use date_time::*;
+#[allow(unused_imports)]
+use node_id::*;
+#[allow(unused_imports)]
+use service_types::*;
+#[allow(unused_imports)]
+use variant::*;
+#[allow(unused_imports)]
+use generated::node_ids::*;
+#[allow(unused_imports)]
+use generated::status_codes::StatusCode;
+#[allow(unused_imports)]
+use generated::status_codes::StatusCode::*;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct UpdateDataDetails {
@@ -39,9 +59,9 @@ impl BinaryEncoder<UpdateDataDetails> for UpdateDataDetails {
         let perform_insert_replace = PerformUpdateType::decode(stream)?;
         let update_values: Option<Vec<DataValue>> = read_array(stream)?;
         Ok(UpdateDataDetails {
-            node_id: node_id,
-            perform_insert_replace: perform_insert_replace,
-            update_values: update_values,
+            node_id,
+            perform_insert_replace,
+            update_values,
         })
     }
 }
@@ -38,6 +38,7 @@ mod data_value;
 mod date_time;
 mod node_id;
 mod variant;
+mod numeric_range;
 mod data_types;
 mod notification_message;
 mod generated;
@@ -51,6 +52,7 @@ pub use self::data_value::*;
 pub use self::date_time::*;
 pub use self::node_id::*;
 pub use self::variant::*;
+pub use self::numeric_range::*;
 pub use self::data_types::*;
 pub use self::generated::*;
 pub use self::attribute::*;
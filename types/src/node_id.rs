@@ -6,7 +6,7 @@ use encoding::*;
 use basic_types::*;
 use generated::StatusCode;
 use generated::StatusCode::*;
-use generated::{ObjectId, ReferenceTypeId};
+use generated::{ObjectId, ReferenceTypeId, DataTypeId};
 
 /// The kind of identifier, numeric, string, guid or byte
 #[derive(Eq, PartialEq, Clone, Debug, Hash)]
@@ -240,6 +240,14 @@ impl NodeId {
         }
     }
 
+    /// Extracts a DataTypeId from a node id, providing the node id holds a data type id
+    pub fn as_data_type_id(&self) -> std::result::Result<DataTypeId, ()> {
+        match self.identifier {
+            Identifier::Numeric(id) if self.namespace == 0 => DataTypeId::from_u64(id),
+            _ => Err(())
+        }
+    }
+
     pub fn to_string(&self) -> String {
         let mut result = String::new();
         if self.namespace != 0 {
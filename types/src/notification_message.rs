@@ -3,23 +3,52 @@
 use date_time::DateTime;
 use basic_types::*;
 use generated::ObjectId;
-use generated::{NotificationMessage, MonitoredItemNotification, DataChangeNotification};
+use generated::StatusCode;
+use generated::{NotificationMessage, MonitoredItemNotification, DataChangeNotification, EventFieldList, EventNotificationList, StatusChangeNotification};
 
 impl NotificationMessage {
-    pub fn new_data_change(sequence_number: UInt32, publish_time: &DateTime, monitored_items: Vec<MonitoredItemNotification>) -> NotificationMessage {
-        let data_change_notification = DataChangeNotification {
-            monitored_items: Some(monitored_items),
-            diagnostic_infos: None,
-        };
+    /// Creates a notification message holding a data change notification and / or an event
+    /// notification list. A notification is omitted when it has nothing in it.
+    pub fn new(sequence_number: UInt32, publish_time: &DateTime, monitored_items: Vec<MonitoredItemNotification>, events: Vec<EventFieldList>) -> NotificationMessage {
+        let mut notification_data = Vec::with_capacity(2);
+        if !monitored_items.is_empty() {
+            let data_change_notification = DataChangeNotification {
+                monitored_items: Some(monitored_items),
+                diagnostic_infos: None,
+            };
+            trace!("data change notification = {:?}", data_change_notification);
+            // Serialize to extension object
+            notification_data.push(ExtensionObject::from_encodable(ObjectId::DataChangeNotification_Encoding_DefaultBinary.as_node_id(), data_change_notification));
+        }
+        if !events.is_empty() {
+            let event_notification_list = EventNotificationList {
+                events: Some(events),
+            };
+            trace!("event notification list = {:?}", event_notification_list);
+            notification_data.push(ExtensionObject::from_encodable(ObjectId::EventNotificationList_Encoding_DefaultBinary.as_node_id(), event_notification_list));
+        }
+        NotificationMessage {
+            sequence_number: sequence_number,
+            publish_time: publish_time.clone(),
+            notification_data: if notification_data.is_empty() { None } else { Some(notification_data) },
+        }
+    }
 
-        trace!("data change notification = {:?}", data_change_notification);
+    pub fn new_data_change(sequence_number: UInt32, publish_time: &DateTime, monitored_items: Vec<MonitoredItemNotification>) -> NotificationMessage {
+        NotificationMessage::new(sequence_number, publish_time, monitored_items, Vec::new())
+    }
 
-        // Serialize to extension object
-        let notification_data = ExtensionObject::from_encodable(ObjectId::DataChangeNotification_Encoding_DefaultBinary.as_node_id(), data_change_notification);
+    /// Creates a notification message holding a status change notification, which tells the
+    /// client of a change to the state of its subscription.
+    pub fn new_status_change(sequence_number: UInt32, publish_time: &DateTime, status: StatusCode) -> NotificationMessage {
+        let status_change_notification = StatusChangeNotification {
+            status,
+            diagnostic_info: DiagnosticInfo::new(),
+        };
         NotificationMessage {
-            sequence_number: sequence_number,
+            sequence_number,
             publish_time: publish_time.clone(),
-            notification_data: Some(vec![notification_data]),
+            notification_data: Some(vec![ExtensionObject::from_encodable(ObjectId::StatusChangeNotification_Encoding_DefaultBinary.as_node_id(), status_change_notification)]),
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,119 @@
+use std::str::FromStr;
+
+use basic_types::*;
+use generated::StatusCode;
+use generated::StatusCode::*;
+
+/// Numeric ranges describe ranges within linear arrays or within multi dimensional arrays as
+/// described in Part 4, 7.22. A range is expressed as a string, e.g. "3" is a single index,
+/// "3:7" is a range of indices and "1:2,0:4" is a range for each dimension of a multi dimensional
+/// array.
+///
+/// The low index of a range must always be lower than the high index. When a range is applied
+/// to a value, the high index may exceed the bounds of the array in which case the result is
+/// truncated to whatever is available.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NumericRange {
+    /// No range was specified, i.e. the entire value
+    None,
+    /// A single index, e.g. "3"
+    Index(UInt32),
+    /// A range of indices, e.g. "3:7"
+    Range(UInt32, UInt32),
+    /// Multiple ranges, one per dimension, e.g. "1:2,0:4"
+    MultipleRanges(Vec<NumericRange>),
+}
+
+impl FromStr for NumericRange {
+    type Err = StatusCode;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            Ok(NumericRange::None)
+        } else {
+            let parts: Vec<&str> = s.split(',').collect();
+            if parts.len() == 1 {
+                NumericRange::parse_range(parts[0])
+            } else {
+                let mut ranges = Vec::with_capacity(parts.len());
+                for part in parts {
+                    ranges.push(NumericRange::parse_range(part)?);
+                }
+                Ok(NumericRange::MultipleRanges(ranges))
+            }
+        }
+    }
+}
+
+impl NumericRange {
+    /// Parses the index range string that comes in a request. A null or empty string is no range.
+    pub fn parse(index_range: &UAString) -> Result<NumericRange, StatusCode> {
+        if index_range.is_null() {
+            Ok(NumericRange::None)
+        } else {
+            NumericRange::from_str(index_range.as_ref())
+        }
+    }
+
+    /// Tests if the range is valid, i.e. the low index is lower than the high index and
+    /// multiple ranges do not contain further multiple ranges.
+    pub fn is_valid(&self) -> bool {
+        match self {
+            &NumericRange::None | &NumericRange::Index(_) => true,
+            &NumericRange::Range(min, max) => min < max,
+            &NumericRange::MultipleRanges(ref ranges) => {
+                !ranges.is_empty() && ranges.iter().all(|r| {
+                    match r {
+                        &NumericRange::Index(_) | &NumericRange::Range(_, _) => r.is_valid(),
+                        _ => false
+                    }
+                })
+            }
+        }
+    }
+
+    /// Returns the low and high index of a single index or range
+    pub fn min_max(&self) -> Option<(usize, usize)> {
+        match self {
+            &NumericRange::Index(idx) => Some((idx as usize, idx as usize)),
+            &NumericRange::Range(min, max) => Some((min as usize, max as usize)),
+            _ => None
+        }
+    }
+
+    /// Parses a single dimension, either an index or a range
+    fn parse_range(s: &str) -> Result<NumericRange, StatusCode> {
+        use regex::Regex;
+
+        lazy_static! {
+            static ref RE: Regex = Regex::new(r"^(?P<min>[0-9]{1,10})(:(?P<max>[0-9]{1,10}))?$").unwrap();
+        }
+
+        let captures = RE.captures(s);
+        if captures.is_none() {
+            return Err(BAD_INDEX_RANGE_INVALID);
+        }
+        let captures = captures.unwrap();
+
+        // Min must exist or the regex wouldn't have matched. Values that overflow are invalid
+        let min = captures.name("min").unwrap().as_str().parse::<UInt32>();
+        if min.is_err() {
+            return Err(BAD_INDEX_RANGE_INVALID);
+        }
+        let min = min.unwrap();
+        if let Some(max) = captures.name("max") {
+            let max = max.as_str().parse::<UInt32>();
+            if max.is_err() {
+                return Err(BAD_INDEX_RANGE_INVALID);
+            }
+            let max = max.unwrap();
+            // Low index must be lower than the high index
+            if min >= max {
+                Err(BAD_INDEX_RANGE_INVALID)
+            } else {
+                Ok(NumericRange::Range(min, max))
+            }
+        } else {
+            Ok(NumericRange::Index(min))
+        }
+    }
+}
@@ -475,6 +475,40 @@ impl BinaryEncoder<DataChangeTrigger> for DataChangeTrigger {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PerformUpdateType {
+    Insert = 1,
+    Replace = 2,
+    Update = 3,
+    Remove = 4,
+}
+
+impl BinaryEncoder<PerformUpdateType> for PerformUpdateType {
+    fn byte_len(&self) -> usize {
+        4
+    }
+
+    fn encode<S: Write>(&self, stream: &mut S) -> EncodingResult<usize> {
+        // All enums are Int32
+        write_i32(stream, *self as Int32)
+    }
+
+    fn decode<S: Read>(stream: &mut S) -> EncodingResult<Self> {
+        // All enums are Int32
+        let value = read_i32(stream)?;
+        match value {
+            1 => Ok(PerformUpdateType::Insert),
+            2 => Ok(PerformUpdateType::Replace),
+            3 => Ok(PerformUpdateType::Update),
+            4 => Ok(PerformUpdateType::Remove),
+            _ => {
+                error!("Don't know what perform update type {} is", value);
+                Err(BAD_UNEXPECTED_ERROR)
+            }
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum FilterOperator {
     Equals = 0,
@@ -602,8 +636,9 @@ impl ValueChangeFilter {
 }
 
 impl DataChangeFilter {
-    /// Compares one data value to another and returns true if they differ, according to their trigger
-    /// type of status, status/value or status/value/timestamp
+    /// Compares one data value to another and returns true if they are the same, according to the
+    /// trigger type of status, status/value or status/value/timestamp. The timestamp compared is the
+    /// source timestamp.
     pub fn compare(&self, v1: &DataValue, v2: &DataValue, eu_range: Option<(f64, f64)>) -> bool {
         match self.trigger {
             DataChangeTrigger::Status => {
@@ -616,7 +651,8 @@ impl DataChangeFilter {
             DataChangeTrigger::StatusValueTimestamp => {
                 v1.status == v2.status &&
                     self.compare_value_option(&v1.value, &v2.value, eu_range) &&
-                    v1.server_timestamp == v2.server_timestamp
+                    v1.source_timestamp == v2.source_timestamp &&
+                    v1.source_picoseconds == v2.source_picoseconds
             }
         }
     }
@@ -645,7 +681,8 @@ impl DataChangeFilter {
     /// Compares two values, either a straight value compare or a numeric comparison against the
     /// deadband settings. If deadband is asked for and the values are not convertible into a numeric
     /// value, the result is false. The value is true if the values are the same within the limits
-    /// set.
+    /// set. Arrays are the same if they have the same dimensions and every element is the same as
+    /// the corresponding element of the other array within the limits set.
     ///
     /// The eu_range is the engineering unit range and represents the range that the value should
     /// typically operate between. It's used for percentage change operations and ignored otherwise.
@@ -655,40 +692,68 @@ impl DataChangeFilter {
     /// BAD_DEADBAND_FILTER_INVALID indicates the deadband settings were invalid, e.g. an invalid
     /// type, or the args were invalid. A (low, high) range must be supplied for a percentage deadband compare.
     pub fn compare_value(&self, v1: &Variant, v2: &Variant, eu_range: Option<(f64, f64)>) -> std::result::Result<bool, StatusCode> {
-        // TODO be able to compare arrays of numbers
-
         if self.deadband_type == 0 {
             // Straight comparison of values
             Ok(v1 == v2)
         } else {
-            // Absolute
-            let v1 = v1.as_f64();
-            let v2 = v2.as_f64();
-            if v1.is_none() || v2.is_none() {
+            match (v1, v2) {
+                (&Variant::Array(ref values1), &Variant::Array(ref values2)) => {
+                    self.compare_array_values(values1, values2, eu_range)
+                }
+                (&Variant::MultiDimensionArray(ref mda1), &Variant::MultiDimensionArray(ref mda2)) => {
+                    if mda1.dimensions != mda2.dimensions {
+                        Ok(false)
+                    } else {
+                        self.compare_array_values(&mda1.values, &mda2.values, eu_range)
+                    }
+                }
+                _ => self.compare_scalar_value(v1, v2, eu_range)
+            }
+        }
+    }
+
+    /// Compares arrays element by element against the deadband settings. Arrays of different
+    /// lengths are never the same.
+    fn compare_array_values(&self, values1: &[Variant], values2: &[Variant], eu_range: Option<(f64, f64)>) -> std::result::Result<bool, StatusCode> {
+        if values1.len() != values2.len() {
+            return Ok(false);
+        }
+        for (v1, v2) in values1.iter().zip(values2.iter()) {
+            if !self.compare_scalar_value(v1, v2, eu_range)? {
                 return Ok(false);
             }
+        }
+        Ok(true)
+    }
+
+    /// Compares two scalar values against the deadband settings
+    fn compare_scalar_value(&self, v1: &Variant, v2: &Variant, eu_range: Option<(f64, f64)>) -> std::result::Result<bool, StatusCode> {
+        let v1 = v1.as_f64();
+        let v2 = v2.as_f64();
+        if v1.is_none() || v2.is_none() {
+            return Ok(false);
+        }
 
-            let v1 = v1.unwrap();
-            let v2 = v2.unwrap();
+        let v1 = v1.unwrap();
+        let v2 = v2.unwrap();
 
-            if self.deadband_value < 0f64 {
+        if self.deadband_value < 0f64 {
+            return Err(BAD_DEADBAND_FILTER_INVALID);
+        }
+        if self.deadband_type == 1 {
+            Ok(DataChangeFilter::abs_compare(v1, v2, self.deadband_value))
+        } else if self.deadband_type == 2 {
+            if eu_range.is_none() {
                 return Err(BAD_DEADBAND_FILTER_INVALID);
             }
-            if self.deadband_type == 1 {
-                Ok(DataChangeFilter::abs_compare(v1, v2, self.deadband_value))
-            } else if self.deadband_type == 2 {
-                if eu_range.is_none() {
-                    return Err(BAD_DEADBAND_FILTER_INVALID);
-                }
-                let (low, high) = eu_range.unwrap();
-                if low >= high {
-                    return Err(BAD_DEADBAND_FILTER_INVALID);
-                }
-                Ok(DataChangeFilter::pct_compare(v1, v2, low, high, self.deadband_value))
-            } else {
-                // Type is not recognized
+            let (low, high) = eu_range.unwrap();
+            if low >= high {
                 return Err(BAD_DEADBAND_FILTER_INVALID);
             }
+            Ok(DataChangeFilter::pct_compare(v1, v2, low, high, self.deadband_value))
+        } else {
+            // Type is not recognized
+            Err(BAD_DEADBAND_FILTER_INVALID)
         }
     }
 
@@ -98,6 +98,30 @@ impl SupportedMessage {
             ObjectId::GetEndpointsResponse_Encoding_DefaultBinary => {
                 SupportedMessage::GetEndpointsResponse(GetEndpointsResponse::decode(stream)?)
             }
+            ObjectId::FindServersRequest_Encoding_DefaultBinary => {
+                SupportedMessage::FindServersRequest(FindServersRequest::decode(stream)?)
+            }
+            ObjectId::FindServersResponse_Encoding_DefaultBinary => {
+                SupportedMessage::FindServersResponse(FindServersResponse::decode(stream)?)
+            }
+            ObjectId::FindServersOnNetworkRequest_Encoding_DefaultBinary => {
+                SupportedMessage::FindServersOnNetworkRequest(FindServersOnNetworkRequest::decode(stream)?)
+            }
+            ObjectId::FindServersOnNetworkResponse_Encoding_DefaultBinary => {
+                SupportedMessage::FindServersOnNetworkResponse(FindServersOnNetworkResponse::decode(stream)?)
+            }
+            ObjectId::RegisterServerRequest_Encoding_DefaultBinary => {
+                SupportedMessage::RegisterServerRequest(RegisterServerRequest::decode(stream)?)
+            }
+            ObjectId::RegisterServerResponse_Encoding_DefaultBinary => {
+                SupportedMessage::RegisterServerResponse(RegisterServerResponse::decode(stream)?)
+            }
+            ObjectId::RegisterServer2Request_Encoding_DefaultBinary => {
+                SupportedMessage::RegisterServer2Request(RegisterServer2Request::decode(stream)?)
+            }
+            ObjectId::RegisterServer2Response_Encoding_DefaultBinary => {
+                SupportedMessage::RegisterServer2Response(RegisterServer2Response::decode(stream)?)
+            }
             ObjectId::CreateSessionRequest_Encoding_DefaultBinary => {
                 SupportedMessage::CreateSessionRequest(CreateSessionRequest::decode(stream)?)
             }
@@ -116,6 +140,12 @@ impl SupportedMessage {
             ObjectId::ActivateSessionResponse_Encoding_DefaultBinary => {
                 SupportedMessage::ActivateSessionResponse(ActivateSessionResponse::decode(stream)?)
             }
+            ObjectId::CancelRequest_Encoding_DefaultBinary => {
+                SupportedMessage::CancelRequest(CancelRequest::decode(stream)?)
+            }
+            ObjectId::CancelResponse_Encoding_DefaultBinary => {
+                SupportedMessage::CancelResponse(CancelResponse::decode(stream)?)
+            }
             ObjectId::BrowseRequest_Encoding_DefaultBinary => {
                 SupportedMessage::BrowseRequest(BrowseRequest::decode(stream)?)
             }
@@ -152,6 +182,12 @@ impl SupportedMessage {
             ObjectId::SetPublishingModeResponse_Encoding_DefaultBinary => {
                 SupportedMessage::SetPublishingModeResponse(SetPublishingModeResponse::decode(stream)?)
             }
+            ObjectId::TransferSubscriptionsRequest_Encoding_DefaultBinary => {
+                SupportedMessage::TransferSubscriptionsRequest(TransferSubscriptionsRequest::decode(stream)?)
+            }
+            ObjectId::TransferSubscriptionsResponse_Encoding_DefaultBinary => {
+                SupportedMessage::TransferSubscriptionsResponse(TransferSubscriptionsResponse::decode(stream)?)
+            }
             ObjectId::PublishRequest_Encoding_DefaultBinary => {
                 SupportedMessage::PublishRequest(PublishRequest::decode(stream)?)
             }
@@ -176,12 +212,36 @@ impl SupportedMessage {
             ObjectId::WriteResponse_Encoding_DefaultBinary => {
                 SupportedMessage::WriteResponse(WriteResponse::decode(stream)?)
             }
+            ObjectId::HistoryReadRequest_Encoding_DefaultBinary => {
+                SupportedMessage::HistoryReadRequest(HistoryReadRequest::decode(stream)?)
+            }
+            ObjectId::HistoryReadResponse_Encoding_DefaultBinary => {
+                SupportedMessage::HistoryReadResponse(HistoryReadResponse::decode(stream)?)
+            }
+            ObjectId::HistoryUpdateRequest_Encoding_DefaultBinary => {
+                SupportedMessage::HistoryUpdateRequest(HistoryUpdateRequest::decode(stream)?)
+            }
+            ObjectId::HistoryUpdateResponse_Encoding_DefaultBinary => {
+                SupportedMessage::HistoryUpdateResponse(HistoryUpdateResponse::decode(stream)?)
+            }
             ObjectId::TranslateBrowsePathsToNodeIdsRequest_Encoding_DefaultBinary => {
                 SupportedMessage::TranslateBrowsePathsToNodeIdsRequest(TranslateBrowsePathsToNodeIdsRequest::decode(stream)?)
             }
             ObjectId::TranslateBrowsePathsToNodeIdsResponse_Encoding_DefaultBinary => {
                 SupportedMessage::TranslateBrowsePathsToNodeIdsResponse(TranslateBrowsePathsToNodeIdsResponse::decode(stream)?)
             }
+            ObjectId::RegisterNodesRequest_Encoding_DefaultBinary => {
+                SupportedMessage::RegisterNodesRequest(RegisterNodesRequest::decode(stream)?)
+            }
+            ObjectId::RegisterNodesResponse_Encoding_DefaultBinary => {
+                SupportedMessage::RegisterNodesResponse(RegisterNodesResponse::decode(stream)?)
+            }
+            ObjectId::UnregisterNodesRequest_Encoding_DefaultBinary => {
+                SupportedMessage::UnregisterNodesRequest(UnregisterNodesRequest::decode(stream)?)
+            }
+            ObjectId::UnregisterNodesResponse_Encoding_DefaultBinary => {
+                SupportedMessage::UnregisterNodesResponse(UnregisterNodesResponse::decode(stream)?)
+            }
             ObjectId::CreateMonitoredItemsRequest_Encoding_DefaultBinary => {
                 SupportedMessage::CreateMonitoredItemsRequest(CreateMonitoredItemsRequest::decode(stream)?)
             }
@@ -200,6 +260,48 @@ impl SupportedMessage {
             ObjectId::DeleteMonitoredItemsResponse_Encoding_DefaultBinary => {
                 SupportedMessage::DeleteMonitoredItemsResponse(DeleteMonitoredItemsResponse::decode(stream)?)
             }
+            ObjectId::SetMonitoringModeRequest_Encoding_DefaultBinary => {
+                SupportedMessage::SetMonitoringModeRequest(SetMonitoringModeRequest::decode(stream)?)
+            }
+            ObjectId::SetMonitoringModeResponse_Encoding_DefaultBinary => {
+                SupportedMessage::SetMonitoringModeResponse(SetMonitoringModeResponse::decode(stream)?)
+            }
+            ObjectId::SetTriggeringRequest_Encoding_DefaultBinary => {
+                SupportedMessage::SetTriggeringRequest(SetTriggeringRequest::decode(stream)?)
+            }
+            ObjectId::SetTriggeringResponse_Encoding_DefaultBinary => {
+                SupportedMessage::SetTriggeringResponse(SetTriggeringResponse::decode(stream)?)
+            }
+            ObjectId::CallRequest_Encoding_DefaultBinary => {
+                SupportedMessage::CallRequest(CallRequest::decode(stream)?)
+            }
+            ObjectId::CallResponse_Encoding_DefaultBinary => {
+                SupportedMessage::CallResponse(CallResponse::decode(stream)?)
+            }
+            ObjectId::AddNodesRequest_Encoding_DefaultBinary => {
+                SupportedMessage::AddNodesRequest(AddNodesRequest::decode(stream)?)
+            }
+            ObjectId::AddNodesResponse_Encoding_DefaultBinary => {
+                SupportedMessage::AddNodesResponse(AddNodesResponse::decode(stream)?)
+            }
+            ObjectId::AddReferencesRequest_Encoding_DefaultBinary => {
+                SupportedMessage::AddReferencesRequest(AddReferencesRequest::decode(stream)?)
+            }
+            ObjectId::AddReferencesResponse_Encoding_DefaultBinary => {
+                SupportedMessage::AddReferencesResponse(AddReferencesResponse::decode(stream)?)
+            }
+            ObjectId::DeleteNodesRequest_Encoding_DefaultBinary => {
+                SupportedMessage::DeleteNodesRequest(DeleteNodesRequest::decode(stream)?)
+            }
+            ObjectId::DeleteNodesResponse_Encoding_DefaultBinary => {
+                SupportedMessage::DeleteNodesResponse(DeleteNodesResponse::decode(stream)?)
+            }
+            ObjectId::DeleteReferencesRequest_Encoding_DefaultBinary => {
+                SupportedMessage::DeleteReferencesRequest(DeleteReferencesRequest::decode(stream)?)
+            }
+            ObjectId::DeleteReferencesResponse_Encoding_DefaultBinary => {
+                SupportedMessage::DeleteReferencesResponse(DeleteReferencesResponse::decode(stream)?)
+            }
             _ => {
                 debug!("decoding unsupported for object id {:?}", object_id);
                 SupportedMessage::Invalid(object_id)
@@ -221,6 +323,14 @@ supported_messages![
     // Discovery service
     GetEndpointsRequest,
     GetEndpointsResponse,
+    FindServersRequest,
+    FindServersResponse,
+    FindServersOnNetworkRequest,
+    FindServersOnNetworkResponse,
+    RegisterServerRequest,
+    RegisterServerResponse,
+    RegisterServer2Request,
+    RegisterServer2Response,
     // Session service
     CreateSessionRequest,
     CreateSessionResponse,
@@ -228,6 +338,8 @@ supported_messages![
     CloseSessionResponse,
     ActivateSessionRequest,
     ActivateSessionResponse,
+    CancelRequest,
+    CancelResponse,
     // MonitoredItem service
     CreateMonitoredItemsRequest,
     CreateMonitoredItemsResponse,
@@ -235,6 +347,10 @@ supported_messages![
     ModifyMonitoredItemsResponse,
     DeleteMonitoredItemsRequest,
     DeleteMonitoredItemsResponse,
+    SetMonitoringModeRequest,
+    SetMonitoringModeResponse,
+    SetTriggeringRequest,
+    SetTriggeringResponse,
     // Subscription service
     CreateSubscriptionRequest,
     CreateSubscriptionResponse,
@@ -244,6 +360,8 @@ supported_messages![
     DeleteSubscriptionsResponse,
     SetPublishingModeRequest,
     SetPublishingModeResponse,
+    TransferSubscriptionsRequest,
+    TransferSubscriptionsResponse,
     // View service
     BrowseRequest,
     BrowseResponse,
@@ -255,9 +373,29 @@ supported_messages![
     RepublishResponse,
     TranslateBrowsePathsToNodeIdsRequest,
     TranslateBrowsePathsToNodeIdsResponse,
+    RegisterNodesRequest,
+    RegisterNodesResponse,
+    UnregisterNodesRequest,
+    UnregisterNodesResponse,
     // Attribute service
     ReadRequest,
     ReadResponse,
     WriteRequest,
     WriteResponse,
+    HistoryReadRequest,
+    HistoryReadResponse,
+    HistoryUpdateRequest,
+    HistoryUpdateResponse,
+    // Method service
+    CallRequest,
+    CallResponse,
+    // NodeManagement service
+    AddNodesRequest,
+    AddNodesResponse,
+    AddReferencesRequest,
+    AddReferencesResponse,
+    DeleteNodesRequest,
+    DeleteNodesResponse,
+    DeleteReferencesRequest,
+    DeleteReferencesResponse,
 ];
@@ -51,4 +51,26 @@ fn parse_node_id() {
     assert_eq!(node_id.identifier, Identifier::Guid(Guid::parse_str("72962B91-FA75-4ae6-8D28-B404DC7DAF63").unwrap()));
 
     // TODO bytestring
-}
\ No newline at end of file
+}
+#[test]
+fn parse_numeric_range() {
+    assert_eq!(NumericRange::from_str("").unwrap(), NumericRange::None);
+    assert_eq!(NumericRange::parse(&UAString::null()).unwrap(), NumericRange::None);
+    assert_eq!(NumericRange::from_str("3").unwrap(), NumericRange::Index(3));
+    assert_eq!(NumericRange::from_str("3:7").unwrap(), NumericRange::Range(3, 7));
+    assert_eq!(NumericRange::from_str("1:2,0:4").unwrap(), NumericRange::MultipleRanges(vec![NumericRange::Range(1, 2), NumericRange::Range(0, 4)]));
+    assert_eq!(NumericRange::from_str("1,0:4,6").unwrap(), NumericRange::MultipleRanges(vec![NumericRange::Index(1), NumericRange::Range(0, 4), NumericRange::Index(6)]));
+    assert!(NumericRange::from_str("1:2,0:4").unwrap().is_valid());
+
+    // Invalid ranges
+    assert_eq!(NumericRange::from_str("7:3").unwrap_err(), BAD_INDEX_RANGE_INVALID);
+    assert_eq!(NumericRange::from_str("3:3").unwrap_err(), BAD_INDEX_RANGE_INVALID);
+    assert_eq!(NumericRange::from_str("-1").unwrap_err(), BAD_INDEX_RANGE_INVALID);
+    assert_eq!(NumericRange::from_str("1:").unwrap_err(), BAD_INDEX_RANGE_INVALID);
+    assert_eq!(NumericRange::from_str(":1").unwrap_err(), BAD_INDEX_RANGE_INVALID);
+    assert_eq!(NumericRange::from_str("1, 2").unwrap_err(), BAD_INDEX_RANGE_INVALID);
+    assert_eq!(NumericRange::from_str("1,").unwrap_err(), BAD_INDEX_RANGE_INVALID);
+    assert_eq!(NumericRange::from_str("a:b").unwrap_err(), BAD_INDEX_RANGE_INVALID);
+    assert_eq!(NumericRange::from_str("4294967296").unwrap_err(), BAD_INDEX_RANGE_INVALID);
+    assert!(!NumericRange::Range(7, 3).is_valid());
+}
@@ -0,0 +1,103 @@
+use std::str::FromStr;
+
+use ::*;
+
+#[test]
+fn variant_is_valid_for() {
+    let scalar = -1;
+    let any = -2;
+    let one_dimension = 1;
+
+    // Scalars
+    assert!(Variant::Int32(1).is_valid_for(&DataTypeId::Int32.as_node_id(), scalar));
+    assert!(!Variant::Int32(1).is_valid_for(&DataTypeId::UInt32.as_node_id(), scalar));
+    assert!(!Variant::Int32(1).is_valid_for(&DataTypeId::Int32.as_node_id(), one_dimension));
+    assert!(Variant::Int32(1).is_valid_for(&DataTypeId::Int32.as_node_id(), any));
+
+    // Abstract types
+    assert!(Variant::Boolean(true).is_valid_for(&DataTypeId::BaseDataType.as_node_id(), scalar));
+    assert!(Variant::Double(1.0).is_valid_for(&DataTypeId::Number.as_node_id(), scalar));
+    assert!(!Variant::Boolean(true).is_valid_for(&DataTypeId::Number.as_node_id(), scalar));
+    assert!(Variant::Int16(1).is_valid_for(&DataTypeId::Integer.as_node_id(), scalar));
+    assert!(!Variant::UInt16(1).is_valid_for(&DataTypeId::Integer.as_node_id(), scalar));
+    assert!(Variant::UInt16(1).is_valid_for(&DataTypeId::UInteger.as_node_id(), scalar));
+
+    // Types derived from builtin types take values of the builtin type
+    assert!(Variant::Double(1.0).is_valid_for(&DataTypeId::Duration.as_node_id(), scalar));
+    assert!(!Variant::Float(1.0).is_valid_for(&DataTypeId::Duration.as_node_id(), scalar));
+    assert!(Variant::DateTime(DateTime::now()).is_valid_for(&DataTypeId::UtcTime.as_node_id(), scalar));
+    assert!(Variant::String(UAString::from_str("en-US")).is_valid_for(&DataTypeId::LocaleId.as_node_id(), scalar));
+    assert!(Variant::String(UAString::from_str("1:2")).is_valid_for(&DataTypeId::NumericRange.as_node_id(), scalar));
+    assert!(Variant::UInt32(1).is_valid_for(&DataTypeId::Counter.as_node_id(), scalar));
+    assert!(Variant::UInt32(1).is_valid_for(&DataTypeId::IntegerId.as_node_id(), scalar));
+    assert!(!Variant::Int32(1).is_valid_for(&DataTypeId::IntegerId.as_node_id(), scalar));
+    assert!(Variant::ByteString(ByteString::from_bytes(&[1, 2])).is_valid_for(&DataTypeId::ImagePNG.as_node_id(), scalar));
+    assert!(Variant::new_u32_array(&[1, 2]).is_valid_for(&DataTypeId::Counter.as_node_id(), one_dimension));
+
+    // Other derived types are structures or enumerations
+    assert!(Variant::Int32(1).is_valid_for(&DataTypeId::IdType.as_node_id(), scalar));
+    assert!(!Variant::Double(1.0).is_valid_for(&DataTypeId::IdType.as_node_id(), scalar));
+
+    // Arrays
+    assert!(Variant::new_i32_array(&[1, 2, 3]).is_valid_for(&DataTypeId::Int32.as_node_id(), one_dimension));
+    assert!(!Variant::new_i32_array(&[1, 2, 3]).is_valid_for(&DataTypeId::Int32.as_node_id(), scalar));
+    assert!(!Variant::new_i32_array(&[1, 2, 3]).is_valid_for(&DataTypeId::Double.as_node_id(), one_dimension));
+    let mda = Variant::new_multi_dimension_array(vec![Variant::Int32(1), Variant::Int32(2), Variant::Int32(3), Variant::Int32(4)], vec![2, 2]);
+    assert!(mda.is_valid_for(&DataTypeId::Int32.as_node_id(), 2));
+    assert!(!mda.is_valid_for(&DataTypeId::Int32.as_node_id(), 3));
+}
+
+#[test]
+fn variant_range_of() {
+    let array = Variant::new_i32_array(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    assert_eq!(array.range_of(&NumericRange::None).unwrap(), array);
+    assert_eq!(array.range_of(&NumericRange::Index(3)).unwrap(), Variant::new_i32_array(&[3]));
+    assert_eq!(array.range_of(&NumericRange::Range(3, 7)).unwrap(), Variant::new_i32_array(&[3, 4, 5, 6, 7]));
+    // Truncated
+    assert_eq!(array.range_of(&NumericRange::Range(8, 20)).unwrap(), Variant::new_i32_array(&[8, 9]));
+    // Outside of the array
+    assert_eq!(array.range_of(&NumericRange::Index(10)).unwrap_err(), BAD_INDEX_RANGE_NO_DATA);
+    // Not an array
+    assert_eq!(Variant::Int32(1).range_of(&NumericRange::Index(0)).unwrap_err(), BAD_INDEX_RANGE_NO_DATA);
+    assert_eq!(array.range_of(&NumericRange::MultipleRanges(vec![NumericRange::Index(0), NumericRange::Index(0)])).unwrap_err(), BAD_INDEX_RANGE_INVALID);
+
+    // 3x4 array, rows 1:2, columns 0:1
+    let values = (0..12).map(|v| Variant::Int32(v)).collect();
+    let mda = Variant::new_multi_dimension_array(values, vec![3, 4]);
+    let range = NumericRange::from_str("1:2,0:1").unwrap();
+    let expected = Variant::new_multi_dimension_array(vec![Variant::Int32(4), Variant::Int32(5), Variant::Int32(8), Variant::Int32(9)], vec![2, 2]);
+    assert_eq!(mda.range_of(&range).unwrap(), expected);
+    let range = NumericRange::from_str("2,3").unwrap();
+    assert_eq!(mda.range_of(&range).unwrap(), Variant::new_multi_dimension_array(vec![Variant::Int32(11)], vec![1, 1]));
+    assert_eq!(mda.range_of(&NumericRange::Index(0)).unwrap_err(), BAD_INDEX_RANGE_INVALID);
+    assert_eq!(mda.range_of(&NumericRange::from_str("3,0").unwrap()).unwrap_err(), BAD_INDEX_RANGE_NO_DATA);
+
+    // Dimensions that claim more values than there are
+    let values = (0..6).map(|v| Variant::Int32(v)).collect();
+    let mda = Variant::new_multi_dimension_array(values, vec![3, 4]);
+    assert_eq!(mda.range_of(&NumericRange::from_str("0,1:3").unwrap()).unwrap(), Variant::new_multi_dimension_array(vec![Variant::Int32(1), Variant::Int32(2), Variant::Int32(3)], vec![1, 3]));
+    assert_eq!(mda.range_of(&NumericRange::from_str("1:2,0:1").unwrap()).unwrap_err(), BAD_INDEX_RANGE_NO_DATA);
+}
+
+#[test]
+fn variant_set_range_of() {
+    let mut array = Variant::new_i32_array(&[0, 1, 2, 3, 4]);
+    assert!(array.set_range_of(&NumericRange::Range(1, 2), &Variant::new_i32_array(&[10, 20])).is_ok());
+    assert_eq!(array, Variant::new_i32_array(&[0, 10, 20, 3, 4]));
+    // Wrong number of elements
+    assert_eq!(array.set_range_of(&NumericRange::Range(1, 2), &Variant::new_i32_array(&[10])).unwrap_err(), BAD_INDEX_RANGE_INVALID);
+    // Extends beyond the array
+    assert_eq!(array.set_range_of(&NumericRange::Range(4, 5), &Variant::new_i32_array(&[10, 20])).unwrap_err(), BAD_INDEX_RANGE_NO_DATA);
+
+    let values = (0..12).map(|v| Variant::Int32(v)).collect();
+    let mut mda = Variant::new_multi_dimension_array(values, vec![3, 4]);
+    let range = NumericRange::from_str("1:2,0:1").unwrap();
+    assert!(mda.set_range_of(&range, &Variant::new_i32_array(&[40, 50, 80, 90])).is_ok());
+    let expected = Variant::new_multi_dimension_array(vec![0, 1, 2, 3, 40, 50, 6, 7, 80, 90, 10, 11].into_iter().map(|v| Variant::Int32(v)).collect(), vec![3, 4]);
+    assert_eq!(mda, expected);
+
+    // Dimensions that claim more values than there are
+    let values = (0..6).map(|v| Variant::Int32(v)).collect();
+    let mut mda = Variant::new_multi_dimension_array(values, vec![3, 4]);
+    assert_eq!(mda.set_range_of(&range, &Variant::new_i32_array(&[40, 50, 80, 90])).unwrap_err(), BAD_INDEX_RANGE_NO_DATA);
+}
@@ -9,6 +9,7 @@ use node_id::{NodeId, ExpandedNodeId};
 use generated::StatusCode;
 use generated::StatusCode::*;
 use generated::DataTypeId;
+use numeric_range::NumericRange;
 
 const ARRAY_DIMENSIONS_BIT: u8 = 1 << 6;
 const ARRAY_VALUES_BIT: u8 = 1 << 7;
@@ -477,6 +478,265 @@ impl Variant {
         }
     }
 
+    /// Returns the data type of the variant. For arrays this is the data type of the elements.
+    /// Returns None for an empty variant, an empty array or a type without a matching data type id.
+    pub fn data_type(&self) -> Option<DataTypeId> {
+        match self {
+            &Variant::Array(ref values) => {
+                if values.is_empty() { None } else { values[0].data_type() }
+            }
+            &Variant::MultiDimensionArray(ref mda) => {
+                if mda.values.is_empty() { None } else { mda.values[0].data_type() }
+            }
+            &Variant::Empty => None,
+            &Variant::ExtensionObject(_) => Some(DataTypeId::Structure),
+            _ => DataTypeId::from_u64(self.get_encoding_mask() as u64).ok()
+        }
+    }
+
+    /// Tests if the variant is compatible with a node or argument declared with the supplied
+    /// data type and value rank. Abstract data types such as BaseDataType, Number, Integer and
+    /// UInteger accept any of their subtypes. Structured types accept any extension object since
+    /// the body cannot be checked here.
+    pub fn is_valid_for(&self, data_type: &NodeId, value_rank: Int32) -> bool {
+        // Test the dimensions of the value against the value rank
+        let rank_ok = match self {
+            &Variant::Array(_) => value_rank == -3 || value_rank == -2 || value_rank == 0 || value_rank == 1,
+            &Variant::MultiDimensionArray(ref mda) => value_rank == -2 || value_rank == 0 || value_rank == mda.dimensions.len() as Int32,
+            _ => value_rank == -3 || value_rank == -2 || value_rank == -1
+        };
+        if !rank_ok {
+            return false;
+        }
+        // Test the value, or every element of an array against the data type
+        match self {
+            &Variant::Array(ref values) => values.iter().all(|v| v.is_valid_data_type(data_type)),
+            &Variant::MultiDimensionArray(ref mda) => mda.values.iter().all(|v| v.is_valid_data_type(data_type)),
+            _ => self.is_valid_data_type(data_type)
+        }
+    }
+
+    /// Returns the builtin type that values of a data type derived from a builtin type, such as
+    /// Duration or UtcTime, are encoded as. Other data types are returned unchanged.
+    fn builtin_data_type(data_type: DataTypeId) -> DataTypeId {
+        match data_type {
+            DataTypeId::Duration => DataTypeId::Double,
+            DataTypeId::UtcTime | DataTypeId::Date => DataTypeId::DateTime,
+            DataTypeId::LocaleId | DataTypeId::NumericRange | DataTypeId::Time | DataTypeId::NormalizedString |
+            DataTypeId::DecimalString | DataTypeId::DurationString | DataTypeId::TimeString | DataTypeId::DateString => DataTypeId::String,
+            DataTypeId::Counter | DataTypeId::IntegerId => DataTypeId::UInt32,
+            DataTypeId::BitFieldMaskDataType => DataTypeId::UInt64,
+            DataTypeId::Image | DataTypeId::ImageBMP | DataTypeId::ImageGIF | DataTypeId::ImageJPG | DataTypeId::ImagePNG |
+            DataTypeId::ApplicationInstanceCertificate | DataTypeId::ContinuationPoint => DataTypeId::ByteString,
+            DataTypeId::SessionAuthenticationToken => DataTypeId::NodeId,
+            data_type => data_type
+        }
+    }
+
+    /// Tests a scalar value against the data type
+    fn is_valid_data_type(&self, data_type: &NodeId) -> bool {
+        if let Ok(data_type) = data_type.as_data_type_id() {
+            match Variant::builtin_data_type(data_type) {
+                DataTypeId::BaseDataType => true,
+                DataTypeId::Number => self.is_numeric(),
+                DataTypeId::Integer => match self {
+                    &Variant::SByte(_) | &Variant::Int16(_) | &Variant::Int32(_) | &Variant::Int64(_) => true,
+                    _ => false
+                },
+                DataTypeId::UInteger => match self {
+                    &Variant::Byte(_) | &Variant::UInt16(_) | &Variant::UInt32(_) | &Variant::UInt64(_) => true,
+                    _ => false
+                },
+                DataTypeId::Enumeration => match self {
+                    &Variant::Int32(_) => true,
+                    _ => false
+                },
+                DataTypeId::Structure => match self {
+                    &Variant::ExtensionObject(_) => true,
+                    _ => false
+                },
+                data_type => {
+                    if data_type as UInt32 > DataTypeId::DiagnosticInfo as UInt32 {
+                        // Derived types are structures or enumerations
+                        match self {
+                            &Variant::ExtensionObject(_) | &Variant::Int32(_) => true,
+                            _ => false
+                        }
+                    } else {
+                        self.data_type() == Some(data_type)
+                    }
+                }
+            }
+        } else {
+            // Data type is not a standard one so it must be a structure or an enumeration
+            match self {
+                &Variant::ExtensionObject(_) | &Variant::Int32(_) => true,
+                _ => false
+            }
+        }
+    }
+
+    /// Returns the elements of an array value that are within the numeric range. A single
+    /// dimension array takes an index or range, a multi dimension array takes a range for each
+    /// of its dimensions. Ranges that extend beyond the end of the array are truncated.
+    pub fn range_of(&self, range: &NumericRange) -> Result<Variant, StatusCode> {
+        match range {
+            &NumericRange::None => Ok(self.clone()),
+            &NumericRange::Index(_) | &NumericRange::Range(_, _) => {
+                match self {
+                    &Variant::Array(ref values) => {
+                        let (min, max) = range.min_max().unwrap();
+                        if min >= values.len() {
+                            Err(BAD_INDEX_RANGE_NO_DATA)
+                        } else {
+                            let max = if max >= values.len() { values.len() - 1 } else { max };
+                            Ok(Variant::Array(Box::new(values[min..(max + 1)].to_vec())))
+                        }
+                    }
+                    &Variant::MultiDimensionArray(_) => Err(BAD_INDEX_RANGE_INVALID),
+                    _ => Err(BAD_INDEX_RANGE_NO_DATA)
+                }
+            }
+            &NumericRange::MultipleRanges(ref ranges) => {
+                match self {
+                    &Variant::MultiDimensionArray(ref mda) => {
+                        let bounds = Variant::multi_dimension_bounds(&mda.dimensions, ranges, true)?;
+                        // The values may not be as many as the dimensions say
+                        let values = Variant::multi_dimension_indices(&mda.dimensions, &bounds).iter()
+                            .map(|idx| mda.values.get(*idx).cloned())
+                            .collect::<Option<Vec<Variant>>>()
+                            .ok_or(BAD_INDEX_RANGE_NO_DATA)?;
+                        let dimensions = bounds.iter().map(|&(min, max)| (max - min + 1) as Int32).collect();
+                        Ok(Variant::new_multi_dimension_array(values, dimensions))
+                    }
+                    &Variant::Array(_) => Err(BAD_INDEX_RANGE_INVALID),
+                    _ => Err(BAD_INDEX_RANGE_NO_DATA)
+                }
+            }
+        }
+    }
+
+    /// Replaces the elements of an array value within the numeric range with the elements of
+    /// the supplied value. The range must lie entirely within the array and the supplied value
+    /// must hold exactly as many elements as the range describes.
+    pub fn set_range_of(&mut self, range: &NumericRange, other: &Variant) -> Result<(), StatusCode> {
+        match range {
+            &NumericRange::None => {
+                *self = other.clone();
+                Ok(())
+            }
+            &NumericRange::Index(_) | &NumericRange::Range(_, _) => {
+                match self {
+                    &mut Variant::Array(ref mut values) => {
+                        let (min, max) = range.min_max().unwrap();
+                        if max >= values.len() {
+                            Err(BAD_INDEX_RANGE_NO_DATA)
+                        } else if let &Variant::Array(ref other_values) = other {
+                            if other_values.len() != max - min + 1 {
+                                Err(BAD_INDEX_RANGE_INVALID)
+                            } else {
+                                for (i, value) in other_values.iter().enumerate() {
+                                    values[min + i] = value.clone();
+                                }
+                                Ok(())
+                            }
+                        } else {
+                            Err(BAD_TYPE_MISMATCH)
+                        }
+                    }
+                    &mut Variant::MultiDimensionArray(_) => Err(BAD_INDEX_RANGE_INVALID),
+                    _ => Err(BAD_INDEX_RANGE_NO_DATA)
+                }
+            }
+            &NumericRange::MultipleRanges(ref ranges) => {
+                match self {
+                    &mut Variant::MultiDimensionArray(ref mut mda) => {
+                        let bounds = Variant::multi_dimension_bounds(&mda.dimensions, ranges, false)?;
+                        let indices = Variant::multi_dimension_indices(&mda.dimensions, &bounds);
+                        // Value can be a multi dimension array of the same shape as the range or
+                        // just a flat array with enough values
+                        let other_values = match other {
+                            &Variant::MultiDimensionArray(ref other) => {
+                                if other.dimensions.len() != bounds.len() || other.dimensions.iter().zip(bounds.iter()).any(|(d, &(min, max))| *d as usize != max - min + 1) {
+                                    return Err(BAD_INDEX_RANGE_INVALID);
+                                }
+                                &other.values
+                            }
+                            &Variant::Array(ref other) => other.as_ref(),
+                            _ => {
+                                return Err(BAD_TYPE_MISMATCH);
+                            }
+                        };
+                        if other_values.len() != indices.len() {
+                            Err(BAD_INDEX_RANGE_INVALID)
+                        } else if indices.iter().any(|idx| *idx >= mda.values.len()) {
+                            Err(BAD_INDEX_RANGE_NO_DATA)
+                        } else {
+                            for (idx, value) in indices.iter().zip(other_values.iter()) {
+                                mda.values[*idx] = value.clone();
+                            }
+                            Ok(())
+                        }
+                    }
+                    &mut Variant::Array(_) => Err(BAD_INDEX_RANGE_INVALID),
+                    _ => Err(BAD_INDEX_RANGE_NO_DATA)
+                }
+            }
+        }
+    }
+
+    /// Turns the ranges for each dimension of a multi dimension array into low and high bounds.
+    /// When truncate is true, high indices beyond the dimension are truncated, otherwise they are
+    /// an error.
+    fn multi_dimension_bounds(dimensions: &[Int32], ranges: &[NumericRange], truncate: bool) -> Result<Vec<(usize, usize)>, StatusCode> {
+        if ranges.len() != dimensions.len() {
+            return Err(BAD_INDEX_RANGE_INVALID);
+        }
+        let mut bounds = Vec::with_capacity(ranges.len());
+        for (range, dimension) in ranges.iter().zip(dimensions.iter()) {
+            let dimension = if *dimension < 0 { 0 } else { *dimension as usize };
+            let (min, max) = if let Some(min_max) = range.min_max() {
+                min_max
+            } else {
+                return Err(BAD_INDEX_RANGE_INVALID);
+            };
+            if min >= dimension || (!truncate && max >= dimension) {
+                return Err(BAD_INDEX_RANGE_NO_DATA);
+            }
+            bounds.push((min, if max >= dimension { dimension - 1 } else { max }));
+        }
+        Ok(bounds)
+    }
+
+    /// Returns the indices into the values of a multi dimension array for every element within
+    /// the bounds. Values are ordered with the last dimension varying fastest.
+    fn multi_dimension_indices(dimensions: &[Int32], bounds: &[(usize, usize)]) -> Vec<usize> {
+        // The stride of each dimension is the product of all the dimensions after it
+        let mut strides = vec![1usize; dimensions.len()];
+        for i in (0..dimensions.len().saturating_sub(1)).rev() {
+            strides[i] = strides[i + 1].saturating_mul(dimensions[i + 1].max(0) as usize);
+        }
+        let mut indices = Vec::new();
+        let mut position: Vec<usize> = bounds.iter().map(|&(min, _)| min).collect();
+        'outer: loop {
+            indices.push(position.iter().zip(strides.iter()).fold(0usize, |sum, (p, s)| sum.saturating_add(p.saturating_mul(*s))));
+            // Increment the position, last dimension first
+            let mut i = position.len();
+            loop {
+                if i == 0 {
+                    break 'outer;
+                }
+                i -= 1;
+                if position[i] < bounds[i].1 {
+                    position[i] += 1;
+                    break;
+                }
+                position[i] = bounds[i].0;
+            }
+        }
+        indices
+    }
+
     // Gets the encoding mask to write the variant to disk
     fn get_encoding_mask(&self) -> u8 {
         let encoding_mask = match self {